@@ -0,0 +1,291 @@
+use crate::{status_error, Client, ClientError, EnclaveKey};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use ciborium::Value;
+use p384::ecdsa::signature::Verifier;
+use p384::ecdsa::{Signature, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use x509_cert::der::{Decode, DecodePem, Encode};
+use x509_cert::Certificate;
+
+/// Where AWS publishes the Nitro Enclaves root certificate (AWS_NitroEnclaves_Root-G1) that
+/// every genuine attestation document chains up to.
+pub const NITRO_ROOT_CERTIFICATE_URL: &str = "https://aws-nitro-enclaves.amazonaws.com/AWS_NitroEnclaves_Root-G1.zip";
+
+/// COSE algorithm identifier of ECDSA with SHA-384, the only one the Nitro Secure Module uses.
+const COSE_ES384: i128 = -35;
+
+/// OID of ecdsa-with-SHA384, the signature algorithm of every certificate in the chain.
+const ECDSA_WITH_SHA384: &str = "1.2.840.10045.4.3.3";
+
+/// What an attestation document has to show before the enclave is trusted.
+#[derive(Debug, Clone)]
+pub struct Expectations {
+    /// DER of the trusted root; the document's CA bundle must start with exactly this certificate
+    pub root_certificate: Vec<u8>,
+    /// PCR values that must match, by index (0-2 identify the enclave image)
+    pub pcrs: BTreeMap<u8, Vec<u8>>,
+    /// Nonce the document must carry; set by the `Client` methods to a fresh one per request
+    pub nonce: Option<Vec<u8>>,
+    /// Oldest document accepted
+    pub max_age: Option<Duration>,
+}
+
+impl Expectations {
+    /// Trusts `root_certificate`, PEM or DER, and nothing else yet.
+    pub fn new(root_certificate: &[u8]) -> Result<Self, ClientError> {
+        let certificate = Certificate::from_pem(root_certificate)
+            .or_else(|_| Certificate::from_der(root_certificate))
+            .map_err(|e| ClientError::Attestation(format!("Invalid root certificate: {}", e)))?;
+        Ok(Self {
+            root_certificate: certificate
+                .to_der()
+                .map_err(|e| ClientError::Attestation(format!("Invalid root certificate: {}", e)))?,
+            pcrs: BTreeMap::new(),
+            nonce: None,
+            max_age: None,
+        })
+    }
+
+    /// Requires PCR `index` to equal `value`, hex as published with the enclave image.
+    pub fn pcr(mut self, index: u8, value: &str) -> Result<Self, ClientError> {
+        let value = hex::decode(value.trim_start_matches("0x"))
+            .map_err(|_| ClientError::Attestation(format!("Expected PCR{} is not valid hex", index)))?;
+        self.pcrs.insert(index, value);
+        Ok(self)
+    }
+
+    pub fn nonce(mut self, nonce: &[u8]) -> Self {
+        self.nonce = Some(nonce.to_vec());
+        self
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+}
+
+/// The contents of an attestation document that passed `verify`.
+#[derive(Debug, Clone)]
+pub struct AttestationDocument {
+    pub module_id: String,
+    /// Milliseconds since the Unix epoch at which the Nitro Secure Module issued the document
+    pub timestamp: u64,
+    pub pcrs: BTreeMap<u8, Vec<u8>>,
+    /// Public key the enclave embedded, if any
+    pub public_key: Option<Vec<u8>>,
+    /// Application data the enclave embedded: the signer address for GET /attestation, the key
+    /// binding for GET /keys
+    pub user_data: Option<Vec<u8>>,
+    pub nonce: Option<Vec<u8>>,
+}
+
+/// Verifies a Nitro attestation document (COSE_Sign1, raw bytes or hex): its certificate chain
+/// up to the trusted root, its ES384 signature, its PCRs, nonce and age.
+pub fn verify(document: &[u8], expectations: &Expectations) -> Result<AttestationDocument, ClientError> {
+    let bytes = match std::str::from_utf8(document) {
+        Ok(text) => hex::decode(text.trim().trim_start_matches("0x")).map_err(|_| fail("Document is not valid hex"))?,
+        Err(_) => document.to_vec(),
+    };
+    let cose = match ciborium::from_reader(bytes.as_slice()).map_err(|_| fail("Document is not CBOR"))? {
+        Value::Tag(_, inner) => *inner,
+        value => value,
+    };
+    let parts = cose.as_array().filter(|parts| parts.len() == 4).ok_or_else(|| fail("Document is not COSE_Sign1"))?;
+    let (Some(protected), Some(payload), Some(signature)) = (parts[0].as_bytes(), parts[2].as_bytes(), parts[3].as_bytes())
+    else {
+        return Err(fail("Document is not COSE_Sign1"));
+    };
+
+    let header: Value = ciborium::from_reader(protected.as_slice()).map_err(|_| fail("Invalid protected header"))?;
+    let algorithm = header
+        .as_map()
+        .and_then(|fields| fields.iter().find(|(key, _)| key.as_integer() == Some(1.into())))
+        .and_then(|(_, algorithm)| algorithm.as_integer());
+    if algorithm.map(i128::from) != Some(COSE_ES384) {
+        return Err(fail("Document is not signed with ES384"));
+    }
+
+    let fields: Value = ciborium::from_reader(payload.as_slice()).map_err(|_| fail("Invalid document payload"))?;
+    let fields = fields.as_map().ok_or_else(|| fail("Invalid document payload"))?;
+    let field = |name: &str| fields.iter().find(|(key, _)| key.as_text() == Some(name)).map(|(_, value)| value);
+    let bytes_field = |name: &str| field(name).and_then(Value::as_bytes).cloned();
+
+    let timestamp = field("timestamp")
+        .and_then(Value::as_integer)
+        .and_then(|timestamp| u64::try_from(timestamp).ok())
+        .ok_or_else(|| fail("Document has no timestamp"))?;
+    let certificate = bytes_field("certificate").ok_or_else(|| fail("Document has no certificate"))?;
+    let cabundle: Vec<Vec<u8>> = field("cabundle")
+        .and_then(Value::as_array)
+        .ok_or_else(|| fail("Document has no CA bundle"))?
+        .iter()
+        .map(|cert| cert.as_bytes().cloned().ok_or_else(|| fail("Invalid CA bundle")))
+        .collect::<Result<_, _>>()?;
+    let mut pcrs = BTreeMap::new();
+    for (index, value) in field("pcrs").and_then(Value::as_map).ok_or_else(|| fail("Document has no PCRs"))? {
+        let index = index.as_integer().and_then(|index| u8::try_from(index).ok()).ok_or_else(|| fail("Invalid PCR index"))?;
+        pcrs.insert(index, value.as_bytes().cloned().ok_or_else(|| fail("Invalid PCR value"))?);
+    }
+
+    // The chain runs from the root (first in the bundle) down to the enclave's certificate
+    if cabundle.first() != Some(&expectations.root_certificate) {
+        return Err(fail("Certificate chain does not start at the trusted root"));
+    }
+    let chain = cabundle
+        .iter()
+        .chain(std::iter::once(&certificate))
+        .map(|der| Certificate::from_der(der).map_err(|e| fail(format!("Invalid certificate in chain: {}", e))))
+        .collect::<Result<Vec<_>, _>>()?;
+    let issued_at = Duration::from_millis(timestamp);
+    for certificate in &chain {
+        let validity = &certificate.tbs_certificate.validity;
+        if issued_at < validity.not_before.to_unix_duration() || issued_at > validity.not_after.to_unix_duration() {
+            return Err(fail("A certificate in the chain was not valid when the document was issued"));
+        }
+    }
+    for pair in chain.windows(2) {
+        check_issued_by(&pair[1], &pair[0])?;
+    }
+
+    // COSE Sig_structure for COSE_Sign1 with no external data
+    let signed = Value::Array(vec![
+        Value::Text("Signature1".into()),
+        Value::Bytes(protected.clone()),
+        Value::Bytes(Vec::new()),
+        Value::Bytes(payload.clone()),
+    ]);
+    let mut message = Vec::new();
+    ciborium::into_writer(&signed, &mut message).map_err(|e| fail(e.to_string()))?;
+    let leaf = public_key(chain.last().expect("the chain holds at least the enclave certificate"))?;
+    let signature = Signature::from_slice(signature).map_err(|_| fail("Invalid document signature"))?;
+    leaf.verify(&message, &signature).map_err(|_| fail("Document signature does not verify"))?;
+
+    for (index, expected) in &expectations.pcrs {
+        if pcrs.get(index) != Some(expected) {
+            return Err(fail(format!("PCR{} does not match the expected image", index)));
+        }
+    }
+    let nonce = bytes_field("nonce");
+    if expectations.nonce.is_some() && nonce != expectations.nonce {
+        return Err(fail("Document does not carry the expected nonce"));
+    }
+    if let Some(max_age) = expectations.max_age {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        if now.saturating_sub(issued_at) > max_age {
+            return Err(fail("Document is too old"));
+        }
+    }
+
+    Ok(AttestationDocument {
+        module_id: field("module_id").and_then(Value::as_text).unwrap_or_default().to_string(),
+        timestamp,
+        pcrs,
+        public_key: bytes_field("public_key"),
+        user_data: bytes_field("user_data"),
+        nonce,
+    })
+}
+
+/// Checks that `certificate` carries a valid ECDSA P-384 signature by `issuer`'s key.
+fn check_issued_by(certificate: &Certificate, issuer: &Certificate) -> Result<(), ClientError> {
+    if certificate.signature_algorithm.oid.to_string() != ECDSA_WITH_SHA384 {
+        return Err(fail("Certificate is not signed with ecdsa-with-SHA384"));
+    }
+    let tbs = certificate.tbs_certificate.to_der().map_err(|e| fail(e.to_string()))?;
+    let signature = Signature::from_der(certificate.signature.raw_bytes()).map_err(|_| fail("Invalid certificate signature"))?;
+    public_key(issuer)?
+        .verify(&tbs, &signature)
+        .map_err(|_| fail("Certificate chain signature does not verify"))
+}
+
+fn public_key(certificate: &Certificate) -> Result<VerifyingKey, ClientError> {
+    VerifyingKey::from_sec1_bytes(certificate.tbs_certificate.subject_public_key_info.subject_public_key.raw_bytes())
+        .map_err(|_| fail("Certificate does not hold a P-384 key"))
+}
+
+fn fail(message: impl Into<String>) -> ClientError {
+    ClientError::Attestation(message.into())
+}
+
+/// The parts of a GET /keys?nonce= reply checked here.
+#[derive(Debug, Deserialize)]
+struct AttestedKeys {
+    signing: EnclaveKey,
+    encryption: EnclaveKey,
+    attestation: Option<AttestedDocument>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AttestedDocument {
+    document: String,
+}
+
+/// A GET /attestation reply.
+#[derive(Debug, Deserialize)]
+struct SignerAttestation {
+    document: String,
+    signer: String,
+}
+
+impl Client {
+    /// The enclave's current encryption key, returned only once an attestation document fetched
+    /// with a fresh nonce verifies against `expectations` and binds the listed keys.
+    pub async fn attested_encryption_key(&self, expectations: &Expectations) -> Result<EnclaveKey, ClientError> {
+        let nonce = fresh_nonce();
+        let reply: AttestedKeys = self.get_json(&format!("keys?nonce={}", hex::encode(nonce))).await?;
+        let attestation = reply.attestation.ok_or_else(|| fail("Server returned no attestation document"))?;
+        let document = verify(attestation.document.as_bytes(), &expectations.clone().nonce(&nonce))?;
+
+        let binding = Sha256::digest(
+            format!("zk-insurance keys\nsigning: {}\nencryption: {}", reply.signing.public_key, reply.encryption.public_key)
+                .as_bytes(),
+        );
+        if document.user_data.as_deref() != Some(&binding[..]) {
+            return Err(fail("Attested key binding does not match the listed keys"));
+        }
+        Ok(reply.encryption)
+    }
+
+    /// The enclave signer address, once an attestation document fetched with a fresh nonce
+    /// verifies against `expectations` and embeds it. Proof signatures by this address come
+    /// from the attested enclave.
+    pub async fn attested_signer(&self, expectations: &Expectations) -> Result<String, ClientError> {
+        let nonce = fresh_nonce();
+        let reply: SignerAttestation = self.get_json(&format!("attestation?nonce={}", hex::encode(nonce))).await?;
+        let document = verify(reply.document.as_bytes(), &expectations.clone().nonce(&nonce))?;
+        let signer = hex::decode(reply.signer.trim_start_matches("0x")).map_err(|_| fail("Invalid signer address"))?;
+        if document.user_data != Some(signer) {
+            return Err(fail("Attested signer does not match the one reported"));
+        }
+        Ok(reply.signer)
+    }
+
+    async fn get_json<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, ClientError> {
+        let response = self
+            .http
+            .get(format!("{}/{}", self.base_url, path))
+            .send()
+            .await
+            .map_err(ClientError::Transport)?;
+        let status = response.status();
+        let body = response.bytes().await.map_err(ClientError::Transport)?;
+        if !status.is_success() {
+            return Err(status_error(status, &body));
+        }
+        serde_json::from_slice(&body).map_err(|e| {
+            let route = path.split('?').next().unwrap_or(path);
+            ClientError::Protocol(format!("Unexpected /{} response: {}", route, e))
+        })
+    }
+}
+
+fn fresh_nonce() -> [u8; 32] {
+    let mut nonce = [0u8; 32];
+    OsRng.fill_bytes(&mut nonce);
+    nonce
+}
@@ -0,0 +1,58 @@
+use crate::{ClientError, ProofRequest};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Must match the server's envelope version.
+const ENVELOPE_INFO: &[u8] = b"zk-insurance envelope v1";
+
+/// An enclave encryption key as listed under `encryption` by GET /keys. Check it against the
+/// attested key binding before sealing anything to it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnclaveKey {
+    pub id: String,
+    /// Hex-encoded X25519 public key
+    pub public_key: String,
+}
+
+/// A ProofRequest only the enclave can decrypt, as accepted by POST /prove/sealed.
+#[derive(Debug, Clone, Serialize)]
+pub struct Envelope {
+    pub key_id: String,
+    pub ephemeral_public_key: String,
+    pub ciphertext: String,
+}
+
+/// Encrypts `request` to `key` with a fresh ephemeral X25519 key pair.
+pub fn seal(request: &ProofRequest, key: &EnclaveKey) -> Result<Envelope, ClientError> {
+    let recipient: [u8; 32] = hex::decode(key.public_key.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ClientError::Protocol(format!("Enclave key {} is not a 32-byte hex X25519 key", key.id)))?;
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    let ephemeral = MontgomeryPoint::mul_base_clamped(secret).to_bytes();
+    let shared = MontgomeryPoint(recipient).mul_clamped(secret).to_bytes();
+
+    let salt = [ephemeral.as_slice(), recipient.as_slice()].concat();
+    let mut aes_key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), &shared)
+        .expand(ENVELOPE_INFO, &mut aes_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&aes_key));
+
+    let plaintext = serde_json::to_vec(request).map_err(|e| ClientError::Protocol(e.to_string()))?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: &plaintext, aad: ENVELOPE_INFO })
+        .map_err(|_| ClientError::Protocol("Encryption failed".into()))?;
+    Ok(Envelope {
+        key_id: key.id.clone(),
+        ephemeral_public_key: format!("0x{}", hex::encode(ephemeral)),
+        ciphertext: format!("0x{}{}", hex::encode(nonce), hex::encode(ciphertext)),
+    })
+}
@@ -0,0 +1,113 @@
+use serde::Deserialize;
+use std::fmt;
+
+/// Machine-readable failure reason reported by the server in `error_code`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Ineligible,
+    BackendError,
+    BackendUnavailable,
+    BudgetExceeded,
+    InvalidRequest,
+    CircuitMismatch,
+    ServerBusy,
+    RateLimited,
+    QuotaExceeded,
+    PaymentRequired,
+    /// A code introduced by a newer server
+    #[serde(other)]
+    Unknown,
+}
+
+/// Why a request did not produce a proof. Only `Ineligible` says anything about the applicant;
+/// every other variant means the request may succeed if retried or fixed.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The server could not be reached or the connection dropped mid-request
+    Transport(reqwest::Error),
+    /// The server answered with something this client does not understand, usually because
+    /// client and server are on different API versions
+    Protocol(String),
+    /// The server wants an API key or bearer token and none or an invalid one was sent
+    Unauthorized { message: String },
+    /// The request was rejected before proving started
+    InvalidRequest { message: String },
+    /// The server runs a different circuit than the request's `expected_circuit_hash`
+    CircuitMismatch { message: String },
+    /// Proving infrastructure failed; `code` tells transient unavailability apart from errors
+    Server { code: ErrorCode, message: String },
+    /// The applicant's inputs do not satisfy the policy's constraints
+    Ineligible { message: String },
+    /// The server is at capacity and asked to be retried after `retry_after_secs`
+    Busy { message: String, retry_after_secs: u64 },
+    /// This client has used up its rate limit and may send more after `retry_after_secs`
+    RateLimited { message: String, retry_after_secs: u64 },
+    /// The API key is over its daily proof quota or its limit on concurrent jobs; the daily one
+    /// resets at 00:00 UTC, which `retry_after_secs` counts down to
+    QuotaExceeded { message: String, retry_after_secs: u64 },
+    /// The server is paid per proof and the request's escrow deposit was missing or does not
+    /// cover it
+    PaymentRequired { message: String },
+    /// An attestation document did not verify, so the server is not the expected enclave
+    Attestation(String),
+}
+
+impl ClientError {
+    /// The server's error code, if the failure was reported by the server.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            ClientError::Transport(_) | ClientError::Protocol(_) | ClientError::Unauthorized { .. } | ClientError::Attestation(_) => None,
+            ClientError::InvalidRequest { .. } => Some(ErrorCode::InvalidRequest),
+            ClientError::CircuitMismatch { .. } => Some(ErrorCode::CircuitMismatch),
+            ClientError::Server { code, .. } => Some(*code),
+            ClientError::Ineligible { .. } => Some(ErrorCode::Ineligible),
+            ClientError::Busy { .. } => Some(ErrorCode::ServerBusy),
+            ClientError::RateLimited { .. } => Some(ErrorCode::RateLimited),
+            ClientError::QuotaExceeded { .. } => Some(ErrorCode::QuotaExceeded),
+            ClientError::PaymentRequired { .. } => Some(ErrorCode::PaymentRequired),
+        }
+    }
+
+    /// Whether retrying the same request later could succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Transport(_)
+            | ClientError::Busy { .. }
+            | ClientError::RateLimited { .. }
+            | ClientError::QuotaExceeded { .. } => true,
+            ClientError::Server { code, .. } => {
+                matches!(code, ErrorCode::BackendError | ErrorCode::BackendUnavailable | ErrorCode::BudgetExceeded)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Transport(e) => write!(f, "Could not reach the proving server: {}", e),
+            ClientError::Protocol(message) => write!(f, "Protocol mismatch: {}", message),
+            ClientError::Unauthorized { message } => write!(f, "Not authorized: {}", message),
+            ClientError::InvalidRequest { message } => write!(f, "Invalid request: {}", message),
+            ClientError::CircuitMismatch { message } => write!(f, "Circuit mismatch: {}", message),
+            ClientError::Server { code, message } => write!(f, "Server error ({:?}): {}", code, message),
+            ClientError::Ineligible { message } => write!(f, "Not eligible: {}", message),
+            ClientError::Busy { message, .. } => write!(f, "Server busy: {}", message),
+            ClientError::RateLimited { message, .. } => write!(f, "Rate limited: {}", message),
+            ClientError::QuotaExceeded { message, .. } => write!(f, "Quota exceeded: {}", message),
+            ClientError::PaymentRequired { message } => write!(f, "Payment required: {}", message),
+            ClientError::Attestation(message) => write!(f, "Attestation failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! HTTP client for the ZK insurance proving server.
+
+#[cfg(feature = "attestation")]
+pub mod attestation;
+mod envelope;
+mod error;
+
+pub use envelope::{seal, EnclaveKey, Envelope};
+pub use error::{ClientError, ErrorCode};
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+/// Barretenberg proving system; `None` in a request uses the server's default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvingScheme {
+    UltraHonk,
+    UltraPlonk,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ProofRequest {
+    pub age: u32,
+    /// BMI * 10
+    pub bmi_multiplied: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<ProvingScheme>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Tier of `policy` to prove against instead of the best one the applicant satisfies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    /// The relying party's session challenge (hex, up to 31 bytes), bound into the proof
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Fail instead of proving if the server's compiled circuit has another sha256
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_circuit_hash: Option<String>,
+}
+
+/// A successfully generated eligibility proof.
+#[derive(Debug, Clone)]
+pub struct Proof {
+    pub proof_hex: String,
+    /// JSON array of field elements
+    pub public_inputs: String,
+    /// sha256 of the compiled circuit the proof was made with, to pin later requests to
+    pub circuit_hash: Option<String>,
+    /// Unix time after which verifiers reject the proof, for circuits that prove one
+    pub expires_at: Option<u64>,
+    /// ID to download the proof again with `Client::proof`, when the server keeps proofs
+    pub job_id: Option<String>,
+    /// CID of the proof's bundle on IPFS, when the server publishes bundles
+    pub ipfs_cid: Option<String>,
+    /// The enclave's receipt for the proof's price, when it was paid for with `prove_paid`
+    pub payment: Option<PaymentReceipt>,
+}
+
+/// An escrow deposit paying for a request, for servers started with --payment-chain.
+#[derive(Debug, Clone)]
+pub struct Payment {
+    /// bytes32 ID of the deposit, in hex
+    pub deposit_id: String,
+    /// The deposit payer's EIP-191 signature over the 32 deposit ID bytes, in hex
+    pub signature: String,
+}
+
+/// The enclave's receipt for a paid proof: EIP-712 `ProofPayment` typed data in the escrow's
+/// domain, signed by `signer`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentReceipt {
+    pub deposit_id: String,
+    pub proof_id: String,
+    /// Price charged, in the escrow token's smallest unit (decimal)
+    pub amount: String,
+    /// The EIP-712 digest that was signed
+    pub digest: String,
+    pub signer: String,
+    pub signature: String,
+}
+
+/// Wire format of a /prove reply.
+#[derive(Debug, Deserialize)]
+struct ProofResponse {
+    proof_hex: String,
+    public_inputs: String,
+    success: bool,
+    #[serde(default)]
+    circuit_hash: Option<String>,
+    #[serde(default)]
+    expires_at: Option<u64>,
+    #[serde(default)]
+    job_id: Option<String>,
+    #[serde(default)]
+    ipfs_cid: Option<String>,
+    #[serde(default)]
+    payment: Option<PaymentReceipt>,
+    #[serde(default)]
+    error_code: Option<ErrorCode>,
+    #[serde(default)]
+    retry_after_secs: Option<u64>,
+    message: String,
+}
+
+/// The part of a GET /proofs/{job_id} reply this client reads.
+#[derive(Debug, Deserialize)]
+struct StoredProof {
+    job_id: String,
+    proof_hex: String,
+    public_inputs: String,
+    #[serde(default)]
+    circuit_hash: Option<String>,
+    #[serde(default)]
+    expires_at: Option<u64>,
+    #[serde(default)]
+    ipfs_cid: Option<String>,
+    #[serde(default)]
+    payment: Option<PaymentReceipt>,
+}
+
+/// The part of a GET /keys reply this client reads.
+#[derive(Debug, Deserialize)]
+struct KeysResponse {
+    encryption: EnclaveKey,
+}
+
+/// Body the server sends alongside non-2xx statuses.
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    error: String,
+    #[serde(default)]
+    error_code: Option<ErrorCode>,
+    /// Set on 503s from a server at capacity and on 429s
+    #[serde(default)]
+    retry_after_secs: Option<u64>,
+}
+
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    /// Header and value proof requests are authenticated with
+    credential: Option<(&'static str, String)>,
+}
+
+impl Client {
+    /// `base_url` is the server's HTTP API root, e.g. `http://localhost:8081`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            credential: None,
+        }
+    }
+
+    /// Sends `key` as X-API-Key with proof requests and proof downloads, for servers started with
+    /// --api-keys-file.
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.credential = Some(("x-api-key", key.into()));
+        self
+    }
+
+    /// Sends `token`, issued by the insurer's identity provider, as a bearer token with proof
+    /// requests and proof downloads, for servers started with --jwt-jwks-url.
+    pub fn with_bearer_token(mut self, token: impl AsRef<str>) -> Self {
+        self.credential = Some(("authorization", format!("Bearer {}", token.as_ref())));
+        self
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.authorized(self.http.post(format!("{}{}", self.base_url, path)))
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.authorized(self.http.get(format!("{}{}", self.base_url, path)))
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.credential {
+            Some((name, value)) => request.header(*name, value),
+            None => request,
+        }
+    }
+
+    /// Requests an eligibility proof. An applicant outside the policy's ranges is reported as
+    /// `ClientError::Ineligible`, never as a server failure.
+    pub async fn prove(&self, request: &ProofRequest) -> Result<Proof, ClientError> {
+        let response = self
+            .post("/prove")
+            .json(request)
+            .send()
+            .await
+            .map_err(ClientError::Transport)?;
+        proof(response).await
+    }
+
+    /// Like `prove`, paying for the proof from an escrow deposit. The deposit is only charged if
+    /// the proof succeeds.
+    pub async fn prove_paid(&self, request: &ProofRequest, payment: &Payment) -> Result<Proof, ClientError> {
+        let response = self
+            .post("/prove")
+            .header("x-payment-deposit", &payment.deposit_id)
+            .header("x-payment-signature", &payment.signature)
+            .json(request)
+            .send()
+            .await
+            .map_err(ClientError::Transport)?;
+        proof(response).await
+    }
+
+    /// Like `prove`, with the request sealed to `key` so only the enclave can read it.
+    pub async fn prove_sealed(&self, request: &ProofRequest, key: &EnclaveKey) -> Result<Proof, ClientError> {
+        let response = self
+            .post("/prove/sealed")
+            .json(&seal(request, key)?)
+            .send()
+            .await
+            .map_err(ClientError::Transport)?;
+        proof(response).await
+    }
+
+    /// Downloads a proof generated earlier by its `job_id`; None if the server has no proof
+    /// with that ID.
+    pub async fn proof(&self, job_id: &str) -> Result<Option<Proof>, ClientError> {
+        let response = self
+            .get(&format!("/proofs/{}", job_id))
+            .send()
+            .await
+            .map_err(ClientError::Transport)?;
+        let status = response.status();
+        let body = response.bytes().await.map_err(ClientError::Transport)?;
+        // A server that predates the route answers 404 without an error body
+        if status == StatusCode::NOT_FOUND && serde_json::from_slice::<ErrorBody>(&body).is_ok() {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(status_error(status, &body));
+        }
+        let reply: StoredProof = serde_json::from_slice(&body)
+            .map_err(|e| ClientError::Protocol(format!("Unexpected /proofs response: {}", e)))?;
+        Ok(Some(Proof {
+            proof_hex: reply.proof_hex,
+            public_inputs: reply.public_inputs,
+            circuit_hash: reply.circuit_hash,
+            expires_at: reply.expires_at,
+            job_id: Some(reply.job_id),
+            ipfs_cid: reply.ipfs_cid,
+            payment: reply.payment,
+        }))
+    }
+
+    /// The enclave's current encryption key. It comes from the server, so check it against an
+    /// attestation document (GET /keys?nonce=) before sealing to it.
+    pub async fn encryption_key(&self) -> Result<EnclaveKey, ClientError> {
+        let response = self
+            .http
+            .get(format!("{}/keys", self.base_url))
+            .send()
+            .await
+            .map_err(ClientError::Transport)?;
+        let status = response.status();
+        let body = response.bytes().await.map_err(ClientError::Transport)?;
+        if !status.is_success() {
+            return Err(status_error(status, &body));
+        }
+        let reply: KeysResponse = serde_json::from_slice(&body)
+            .map_err(|e| ClientError::Protocol(format!("Unexpected /keys response: {}", e)))?;
+        Ok(reply.encryption)
+    }
+}
+
+/// Interprets a /prove or /prove/sealed reply.
+async fn proof(response: reqwest::Response) -> Result<Proof, ClientError> {
+    let status = response.status();
+    let body = response.bytes().await.map_err(ClientError::Transport)?;
+    if !status.is_success() {
+        return Err(status_error(status, &body));
+    }
+
+    let reply: ProofResponse = serde_json::from_slice(&body)
+        .map_err(|e| ClientError::Protocol(format!("Unexpected /prove response: {}", e)))?;
+    if reply.success {
+        return Ok(Proof {
+            proof_hex: reply.proof_hex,
+            public_inputs: reply.public_inputs,
+            circuit_hash: reply.circuit_hash,
+            expires_at: reply.expires_at,
+            job_id: reply.job_id,
+            ipfs_cid: reply.ipfs_cid,
+            payment: reply.payment,
+        });
+    }
+
+    Err(match reply.error_code {
+        Some(ErrorCode::Ineligible) => ClientError::Ineligible { message: reply.message },
+        Some(ErrorCode::InvalidRequest) => ClientError::InvalidRequest { message: reply.message },
+        Some(ErrorCode::CircuitMismatch) => ClientError::CircuitMismatch { message: reply.message },
+        Some(ErrorCode::ServerBusy) => ClientError::Busy {
+            message: reply.message,
+            retry_after_secs: reply.retry_after_secs.unwrap_or_default(),
+        },
+        Some(ErrorCode::RateLimited) => ClientError::RateLimited {
+            message: reply.message,
+            retry_after_secs: reply.retry_after_secs.unwrap_or_default(),
+        },
+        Some(code) => ClientError::Server { code, message: reply.message },
+        None => ClientError::Protocol(format!(
+            "Server reported a failure without an error code (it may predate typed errors): {}",
+            reply.message
+        )),
+    })
+}
+
+/// Maps a non-2xx reply to the failure it stands for.
+fn status_error(status: StatusCode, body: &[u8]) -> ClientError {
+    let (message, error_code, retry_after_secs) = match serde_json::from_slice::<ErrorBody>(body) {
+        Ok(body) => (body.error, body.error_code, body.retry_after_secs),
+        Err(_) => (String::from_utf8_lossy(body).trim().to_string(), None, None),
+    };
+
+    match status {
+        // Routes this client relies on are missing: the server speaks a different API version
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => {
+            ClientError::Protocol(format!("Server does not support this request ({}): {}", status, message))
+        }
+        StatusCode::UNAUTHORIZED => ClientError::Unauthorized { message },
+        StatusCode::PAYMENT_REQUIRED => ClientError::PaymentRequired { message },
+        StatusCode::TOO_MANY_REQUESTS if error_code == Some(ErrorCode::QuotaExceeded) => ClientError::QuotaExceeded {
+            message,
+            retry_after_secs: retry_after_secs.unwrap_or_default(),
+        },
+        StatusCode::TOO_MANY_REQUESTS => ClientError::RateLimited {
+            message,
+            retry_after_secs: retry_after_secs.unwrap_or_default(),
+        },
+        s if s.is_client_error() => ClientError::InvalidRequest { message },
+        StatusCode::SERVICE_UNAVAILABLE if retry_after_secs.is_some() => ClientError::Busy {
+            message,
+            retry_after_secs: retry_after_secs.unwrap_or_default(),
+        },
+        StatusCode::SERVICE_UNAVAILABLE => ClientError::Server {
+            code: ErrorCode::BackendUnavailable,
+            message,
+        },
+        _ => ClientError::Server {
+            code: ErrorCode::BackendError,
+            message,
+        },
+    }
+}
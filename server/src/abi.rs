@@ -0,0 +1,302 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Named circuit inputs as sent in a request's `inputs` or written to Prover.toml.
+pub type Inputs = BTreeMap<String, Value>;
+
+/// The `abi` section of a compiled Noir circuit (target/<name>.json): what `main` takes and
+/// which of it is public.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CircuitAbi {
+    pub parameters: Vec<AbiParameter>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AbiParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub typ: AbiType,
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    Public,
+    Private,
+    DataBus,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AbiType {
+    Field,
+    Boolean,
+    Integer { sign: Sign, width: u32 },
+    Array { length: usize, #[serde(rename = "type")] element: Box<AbiType> },
+    String { length: usize },
+    Struct { fields: Vec<AbiField> },
+    Tuple { fields: Vec<AbiType> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sign {
+    Signed,
+    Unsigned,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AbiField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub typ: AbiType,
+}
+
+#[derive(Deserialize)]
+struct Artifact {
+    abi: CircuitAbi,
+}
+
+impl CircuitAbi {
+    /// Reads the ABI from a compiled circuit artifact.
+    pub fn load(artifact: &Path) -> Result<Self> {
+        let content = fs::read(artifact).with_context(|| format!("Failed to read {}", artifact.display()))?;
+        let artifact: Artifact =
+            serde_json::from_slice(&content).with_context(|| format!("{} has no readable circuit ABI", artifact.display()))?;
+        Ok(artifact.abi)
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&AbiParameter> {
+        self.parameters.iter().find(|parameter| parameter.name == name)
+    }
+
+    /// Position of the public parameter `name` among the proof's public inputs, which follow
+    /// the public parameters in order with arrays, strings, structs and tuples flattened.
+    pub fn public_input_offset(&self, name: &str) -> Option<usize> {
+        let mut offset = 0;
+        for parameter in self.parameters.iter().filter(|parameter| parameter.visibility == Visibility::Public) {
+            if parameter.name == name {
+                return Some(offset);
+            }
+            offset += parameter.typ.field_count();
+        }
+        None
+    }
+
+    /// Number of public inputs a proof of this circuit carries.
+    pub fn public_input_count(&self) -> usize {
+        self.parameters
+            .iter()
+            .filter(|parameter| parameter.visibility == Visibility::Public)
+            .map(|parameter| parameter.typ.field_count())
+            .sum()
+    }
+
+    /// Checks that `inputs` names every parameter exactly once with a value of its type. The
+    /// message is meant for the client.
+    pub fn check(&self, inputs: &Inputs) -> std::result::Result<(), String> {
+        self.check_values(inputs)?;
+        match self.parameters.iter().find(|parameter| !inputs.contains_key(&parameter.name)) {
+            Some(parameter) if parameter.visibility == Visibility::Public => Err(format!("Missing public input {:?}", parameter.name)),
+            Some(parameter) => Err(format!("Missing input {:?}", parameter.name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that every entry of `inputs` names a parameter and has a value of its type,
+    /// without requiring all parameters.
+    pub fn check_values(&self, inputs: &Inputs) -> std::result::Result<(), String> {
+        for (name, value) in inputs {
+            let parameter = self.parameter(name).ok_or_else(|| format!("The circuit has no input named {:?}", name))?;
+            parameter.typ.check(value).map_err(|e| format!("Input {:?}: {}", name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Prover.toml for checked `inputs`, in the form nargo reads (integers and fields as
+    /// decimal or hex strings, structs as tables).
+    pub fn prover_toml(&self, inputs: &Inputs) -> Result<String> {
+        let mut table = toml::Table::new();
+        for parameter in &self.parameters {
+            let value = inputs.get(&parameter.name).with_context(|| format!("Missing input {}", parameter.name))?;
+            table.insert(parameter.name.clone(), parameter.typ.to_toml(value));
+        }
+        toml::to_string(&table).context("Failed to render Prover.toml")
+    }
+}
+
+impl AbiType {
+    /// Number of field elements a value of this type takes up.
+    fn field_count(&self) -> usize {
+        match self {
+            AbiType::Field | AbiType::Boolean | AbiType::Integer { .. } => 1,
+            AbiType::Array { length, element } => length * element.field_count(),
+            AbiType::String { length } => *length,
+            AbiType::Struct { fields } => fields.iter().map(|field| field.typ.field_count()).sum(),
+            AbiType::Tuple { fields } => fields.iter().map(AbiType::field_count).sum(),
+        }
+    }
+
+    /// JSON Schema of the values `check` accepts for this type.
+    pub fn json_schema(&self) -> Value {
+        match self {
+            AbiType::Field => json!({
+                "anyOf": [
+                    { "type": "integer", "minimum": 0 },
+                    { "type": "string", "pattern": "^(0x[0-9a-fA-F]+|[0-9]+)$" },
+                ],
+            }),
+            AbiType::Boolean => json!({ "type": "boolean" }),
+            AbiType::Integer { sign, width } => {
+                let (min, max) = sign.range(*width);
+                let mut number = json!({ "type": "integer" });
+                if let Ok(min) = i64::try_from(min) {
+                    number["minimum"] = min.into();
+                }
+                if let Ok(max) = u64::try_from(max) {
+                    number["maximum"] = max.into();
+                }
+                json!({ "anyOf": [number, { "type": "string", "pattern": "^(0x[0-9a-fA-F]+|-?[0-9]+)$" }] })
+            }
+            AbiType::Array { length, element } => json!({
+                "type": "array",
+                "items": element.json_schema(),
+                "minItems": length,
+                "maxItems": length,
+            }),
+            AbiType::String { length } => json!({ "type": "string", "minLength": length, "maxLength": length }),
+            AbiType::Struct { fields } => json!({
+                "type": "object",
+                "properties": fields.iter().map(|field| (field.name.clone(), field.typ.json_schema())).collect::<serde_json::Map<_, _>>(),
+                "required": fields.iter().map(|field| field.name.as_str()).collect::<Vec<_>>(),
+                "additionalProperties": false,
+            }),
+            AbiType::Tuple { fields } => json!({
+                "type": "array",
+                "prefixItems": fields.iter().map(AbiType::json_schema).collect::<Vec<_>>(),
+                "minItems": fields.len(),
+                "maxItems": fields.len(),
+            }),
+        }
+    }
+
+    fn check(&self, value: &Value) -> std::result::Result<(), String> {
+        match self {
+            AbiType::Field => match value {
+                Value::String(text) if is_digits(text) => Ok(()),
+                _ => parse_number(value).filter(|number| *number >= 0).map(|_| ()).ok_or_else(|| "expected a field element".to_string()),
+            },
+            AbiType::Boolean => value.as_bool().map(|_| ()).ok_or_else(|| "expected true or false".to_string()),
+            AbiType::Integer { sign, width } => {
+                let number = parse_number(value).ok_or_else(|| "expected an integer".to_string())?;
+                let (min, max) = sign.range(*width);
+                if number < min || number > max {
+                    return Err(format!("{} is out of range for a {}-bit {} integer", number, width, sign.name()));
+                }
+                Ok(())
+            }
+            AbiType::Array { length, element } => {
+                let items = value.as_array().ok_or_else(|| format!("expected an array of {}", length))?;
+                if items.len() != *length {
+                    return Err(format!("expected {} elements, got {}", length, items.len()));
+                }
+                items.iter().enumerate().try_for_each(|(index, item)| element.check(item).map_err(|e| format!("[{}]: {}", index, e)))
+            }
+            AbiType::String { length } => {
+                let text = value.as_str().ok_or_else(|| "expected a string".to_string())?;
+                if text.len() != *length {
+                    return Err(format!("expected a string of exactly {} bytes", length));
+                }
+                Ok(())
+            }
+            AbiType::Struct { fields } => {
+                let object = value.as_object().ok_or_else(|| "expected an object".to_string())?;
+                if let Some(unknown) = object.keys().find(|key| !fields.iter().any(|field| &field.name == *key)) {
+                    return Err(format!("unexpected field {:?}", unknown));
+                }
+                fields.iter().try_for_each(|field| {
+                    let value = object.get(&field.name).ok_or_else(|| format!("missing field {:?}", field.name))?;
+                    field.typ.check(value).map_err(|e| format!(".{}: {}", field.name, e))
+                })
+            }
+            AbiType::Tuple { fields } => {
+                let items = value.as_array().ok_or_else(|| format!("expected a tuple of {}", fields.len()))?;
+                if items.len() != fields.len() {
+                    return Err(format!("expected {} elements, got {}", fields.len(), items.len()));
+                }
+                fields.iter().zip(items).enumerate().try_for_each(|(index, (typ, item))| typ.check(item).map_err(|e| format!(".{}: {}", index, e)))
+            }
+        }
+    }
+
+    fn to_toml(&self, value: &Value) -> toml::Value {
+        match (self, value) {
+            (AbiType::Boolean, Value::Bool(flag)) => toml::Value::Boolean(*flag),
+            (AbiType::Field | AbiType::Integer { .. }, Value::Number(number)) => toml::Value::String(number.to_string()),
+            (AbiType::Array { element, .. }, Value::Array(items)) => {
+                toml::Value::Array(items.iter().map(|item| element.to_toml(item)).collect())
+            }
+            (AbiType::Tuple { fields }, Value::Array(items)) => {
+                toml::Value::Array(fields.iter().zip(items).map(|(typ, item)| typ.to_toml(item)).collect())
+            }
+            (AbiType::Struct { fields }, Value::Object(object)) => toml::Value::Table(
+                fields
+                    .iter()
+                    .filter_map(|field| Some((field.name.clone(), field.typ.to_toml(object.get(&field.name)?))))
+                    .collect(),
+            ),
+            (_, Value::String(text)) => toml::Value::String(text.clone()),
+            (_, other) => toml::Value::String(other.to_string()),
+        }
+    }
+}
+
+impl Sign {
+    fn range(&self, width: u32) -> (i128, i128) {
+        let width = width.min(127);
+        match self {
+            Sign::Unsigned => (0, (1i128 << width) - 1),
+            Sign::Signed => {
+                let width = width.max(1);
+                (-(1i128 << (width - 1)), (1i128 << (width - 1)) - 1)
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Sign::Signed => "signed",
+            Sign::Unsigned => "unsigned",
+        }
+    }
+}
+
+/// A non-negative decimal or 0x-prefixed hex string of any size; nargo reduces field elements
+/// modulo the field.
+fn is_digits(text: &str) -> bool {
+    match text.strip_prefix("0x") {
+        Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()),
+    }
+}
+
+/// A JSON number, or a decimal or 0x-prefixed hex string. Hex beyond i128 saturates, so it
+/// fails any integer range check.
+fn parse_number(value: &Value) -> Option<i128> {
+    match value {
+        Value::Number(number) => number.as_i64().map(i128::from).or_else(|| number.as_u64().map(i128::from)),
+        Value::String(text) => match text.strip_prefix("0x") {
+            Some(hex) if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                Some(i128::from_str_radix(hex, 16).unwrap_or(i128::MAX))
+            }
+            Some(_) => None,
+            None => text.parse().ok(),
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,397 @@
+use crate::api::ApiError;
+use crate::audit::AuditLog;
+use crate::compliance::{self, Report, ReportFormat, ReportRange};
+use crate::config::RuntimeConfig;
+use crate::jobs::{JobFilter, JobRecord, JobStore};
+use crate::metrics::{Activity, MetricsSnapshot, TenantStats};
+use crate::registry::RegisteredCircuit;
+use crate::reload::Reloader;
+use crate::state::AppState;
+use crate::upload::{CircuitCompiler, CircuitPackage};
+use crate::tenants::Tenant;
+use crate::usage::UsageReport;
+use anyhow::Result;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+/// Longest a drain request may wait for the node to go idle.
+const MAX_DRAIN_WAIT_SECS: u64 = 3600;
+
+/// How often a waiting drain request checks whether the node is idle.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Privileged operations: stats, draining, config and circuit reloads, cache flushes. They are
+/// served on their own listener (--admin-port), bound to loopback by default, so they never
+/// share the public proof-request surface or its connection and rate limits.
+pub struct Admin {
+    state: Arc<AppState>,
+    audit: Arc<AuditLog>,
+    reloader: Arc<Reloader>,
+    compiler: CircuitCompiler,
+    /// Bearer token callers must present, when set
+    token: Option<String>,
+    started: Instant,
+}
+
+/// Point-in-time view of the node, as served by GET /admin/stats.
+#[derive(Debug, Serialize)]
+pub struct AdminStats {
+    pub uptime_secs: u64,
+    pub warming_up: bool,
+    pub draining: bool,
+    pub integrity_intact: bool,
+    pub queued_jobs: usize,
+    pub active_jobs: usize,
+    pub workers: usize,
+    pub aggregating: bool,
+    pub tcp_connections: i64,
+    pub http_requests_in_flight: i64,
+    pub metrics: MetricsSnapshot,
+    pub config: RuntimeConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportQuery {
+    #[serde(default)]
+    format: ReportFormat,
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DrainQuery {
+    /// Seconds to wait for queued and running jobs to finish before answering (default 0)
+    #[serde(default)]
+    wait_secs: u64,
+}
+
+impl Admin {
+    pub fn new(
+        state: Arc<AppState>,
+        audit: Arc<AuditLog>,
+        reloader: Arc<Reloader>,
+        compiler: CircuitCompiler,
+        token: Option<String>,
+    ) -> Self {
+        Self {
+            state,
+            audit,
+            reloader,
+            compiler,
+            token,
+            started: Instant::now(),
+        }
+    }
+
+    fn stats(&self) -> AdminStats {
+        let state = &self.state;
+        AdminStats {
+            uptime_secs: self.started.elapsed().as_secs(),
+            warming_up: state.pool.is_warming_up(),
+            draining: state.pool.is_draining(),
+            integrity_intact: state.integrity.is_intact(),
+            queued_jobs: state.pool.queued_jobs(),
+            active_jobs: state.pool.active_jobs(),
+            workers: state.pool.worker_count(),
+            aggregating: state.aggregator.is_busy(),
+            tcp_connections: state.metrics.active(Activity::TcpConnection),
+            http_requests_in_flight: state.metrics.active(Activity::HttpRequest),
+            metrics: state.metrics.snapshot(),
+            config: state.config.read().unwrap().clone(),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.state.pool.is_idle() && !self.state.aggregator.is_busy()
+    }
+}
+
+pub async fn serve(admin: Arc<Admin>, listener: TcpListener) -> Result<()> {
+    let router = Router::new()
+        .route("/admin/stats", get(stats))
+        .route("/admin/drain", post(drain))
+        .route("/admin/resume", post(resume))
+        .route("/admin/reload-config", post(reload_config))
+        .route("/admin/flush-caches", post(flush_caches))
+        .route("/admin/reload-circuit", post(reload_circuit))
+        .route("/admin/circuits", post(upload_circuit))
+        .route("/admin/jobs", get(list_jobs))
+        .route("/admin/jobs/:job_id", get(get_job))
+        .route("/admin/reports/:report", get(export_report))
+        .route("/admin/usage", get(usage))
+        .route("/admin/tenants", get(tenants))
+        .layer(middleware::from_fn_with_state(admin.clone(), authorize))
+        .with_state(admin);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+/// Rejects requests without the admin token when one is configured.
+async fn authorize(State(admin): State<Arc<Admin>>, request: Request, next: Next) -> Response {
+    if let Some(token) = &admin.token {
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        // Compared by digest, so the time taken says nothing about the token
+        if Sha256::digest(presented.as_bytes()) != Sha256::digest(token.as_bytes()) {
+            warn!(path = %request.uri().path(), "Admin request without a valid token");
+            return ApiError::new(StatusCode::UNAUTHORIZED, "Missing or invalid admin token").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn stats(State(admin): State<Arc<Admin>>) -> Json<AdminStats> {
+    Json(admin.stats())
+}
+
+/// Stops admitting proof jobs; /readyz and /status report the node as draining so load
+/// balancers move traffic away. With `wait_secs`, answers once queued and running jobs are
+/// done or the wait is over, whichever comes first.
+async fn drain(State(admin): State<Arc<Admin>>, Query(query): Query<DrainQuery>) -> Json<serde_json::Value> {
+    if !admin.state.pool.is_draining() {
+        admin.state.pool.drain();
+        info!("Draining: new proof jobs are turned away");
+        admin.audit.record("admin_drain", json!({ "queued_jobs": admin.state.pool.queued_jobs() }));
+    }
+    let deadline = Instant::now() + Duration::from_secs(query.wait_secs.min(MAX_DRAIN_WAIT_SECS));
+    while !admin.is_idle() && Instant::now() < deadline {
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+    Json(json!({
+        "draining": true,
+        "idle": admin.is_idle(),
+        "queued_jobs": admin.state.pool.queued_jobs(),
+        "active_jobs": admin.state.pool.active_jobs(),
+    }))
+}
+
+async fn resume(State(admin): State<Arc<Admin>>) -> Json<serde_json::Value> {
+    if admin.state.pool.is_draining() {
+        admin.state.pool.resume();
+        info!("Drain ended: accepting proof jobs again");
+        admin.audit.record("admin_resume", json!({}));
+    }
+    Json(json!({ "draining": false }))
+}
+
+/// Re-reads the flags' config file, the policies and chains files and the active release's
+/// configuration, and swaps the result in, as SIGHUP does. Returns the settings that changed;
+/// on any error the running configuration is kept.
+async fn reload_config(State(admin): State<Arc<Admin>>) -> Result<Json<serde_json::Value>, ApiError> {
+    let changed = admin
+        .reloader
+        .reload("admin")
+        .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("Configuration not reloaded: {:#}", e)))?;
+    Ok(Json(json!({ "reloaded": true, "changed": changed })))
+}
+
+/// Forgets cached readiness results and unwrapped tenant data keys.
+async fn flush_caches(State(admin): State<Arc<Admin>>) -> Json<serde_json::Value> {
+    admin.state.preflight.flush().await;
+    let data_keys = admin.state.encryption.flush_keys();
+    info!("Caches flushed ({} data keys dropped)", data_keys);
+    admin.audit.record("admin_caches_flushed", json!({ "data_keys": data_keys }));
+    Json(json!({ "preflight": true, "data_keys": data_keys }))
+}
+
+/// Picks up a circuit recompiled in place: regenerates its verification keys and pins the new
+/// artifacts. Jobs proving meanwhile may fail, so drain first. With circuit snapshots, the
+/// recompiled circuit is swapped in instead and running jobs finish on the old one.
+async fn reload_circuit(State(admin): State<Arc<Admin>>) -> Result<Json<serde_json::Value>, ApiError> {
+    let state = &admin.state;
+    if let Some(snapshots) = &state.snapshots {
+        let swap = snapshots
+            .reload(state, &admin.audit, "admin")
+            .await
+            .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("Circuit not swapped in: {:#}", e)))?;
+        return Ok(Json(match swap {
+            Some(swap) => json!({
+                "circuit": swap.snapshot,
+                "vk_hash": swap.vk_hash,
+                "circuit_hash": format!("0x{}", swap.circuit_hash),
+                "previous_hash": format!("0x{}", swap.previous_hash),
+                "swapped": true,
+            }),
+            None => json!({
+                "circuit": state.verifier.circuit_path(),
+                "vk_hash": state.verifier.verification_key().await?.vk_hash,
+                "swapped": false,
+            }),
+        }));
+    }
+    if !admin.is_idle() {
+        warn!("Reloading the circuit while jobs are running");
+    }
+    let vk = state.verifier.reload().await?;
+    state.aggregator.reload().await;
+    state.integrity.repin();
+    state.preflight.flush().await;
+    let circuit = state.verifier.circuit_path();
+    info!("Circuit reloaded from {} (vk {})", circuit.display(), vk.vk_hash);
+    admin.audit.record("admin_circuit_reloaded", json!({ "circuit": circuit, "vk_hash": vk.vk_hash }));
+    Ok(Json(json!({ "circuit": circuit, "vk_hash": vk.vk_hash })))
+}
+
+/// Proofs metered per API key against its quotas, for billing. Counters are kept in memory and
+/// start over when the server restarts; bill from the issuance log for a durable record.
+async fn usage(State(admin): State<Arc<Admin>>) -> Json<UsageReport> {
+    Json(admin.state.usage.report())
+}
+
+/// One tenant as served by GET /admin/tenants.
+#[derive(Serialize)]
+struct TenantSummary {
+    /// Whether the tenant is in --tenants-file; others are named by X-Tenant-Id alone
+    configured: bool,
+    #[serde(flatten)]
+    settings: Tenant,
+    /// Names of the API keys tied to the tenant
+    api_keys: Vec<String>,
+    #[serde(flatten)]
+    stats: TenantStats,
+}
+
+/// Every configured tenant and every tenant that has requested a proof since the server
+/// started, with its settings, keys and proof counts.
+async fn tenants(State(admin): State<Arc<Admin>>) -> Json<BTreeMap<String, TenantSummary>> {
+    let mut stats = admin.state.metrics.tenant_stats();
+    let config = admin.state.config.read().unwrap();
+    let names: BTreeSet<String> = config.tenants.keys().chain(stats.keys()).cloned().collect();
+    let summaries = names
+        .into_iter()
+        .map(|name| {
+            let summary = TenantSummary {
+                configured: config.tenants.contains_key(&name),
+                settings: config.tenants.get(&name).cloned().unwrap_or_default(),
+                api_keys: config
+                    .api_keys
+                    .iter()
+                    .filter(|(_, key)| key.tenant.as_deref() == Some(name.as_str()))
+                    .map(|(key_name, _)| key_name.clone())
+                    .collect(),
+                stats: stats.remove(&name).unwrap_or_default(),
+            };
+            (name, summary)
+        })
+        .collect();
+    Json(summaries)
+}
+
+/// Proof jobs recorded in --database-url, newest first, without their proofs.
+async fn list_jobs(State(admin): State<Arc<Admin>>, Query(filter): Query<JobFilter>) -> Result<Json<Vec<JobRecord>>, ApiError> {
+    Ok(Json(job_store(&admin)?.list(&filter).await?))
+}
+
+/// One recorded proof job, with its proof and public inputs if it succeeded.
+async fn get_job(State(admin): State<Arc<Admin>>, Path(job_id): Path<String>) -> Result<Json<JobRecord>, ApiError> {
+    job_store(&admin)?
+        .get(&job_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("No proof job {}", job_id)))
+}
+
+/// The audit log or the issuance log as a CSV or JSONL download, for regulators. Applicants'
+/// inputs are never part of a report. Each export is itself recorded in the audit log.
+async fn export_report(
+    State(admin): State<Arc<Admin>>,
+    Path(report): Path<Report>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response, ApiError> {
+    let range = ReportRange { from: query.from, to: query.to };
+    let (body, rows) = match report {
+        Report::Audit => compliance::render(report, query.format, &range, &admin.audit.entries()?)?,
+        Report::Proofs => compliance::render(report, query.format, &range, &admin.state.issuance.entries(0, usize::MAX)?)?,
+    };
+    admin.audit.record(
+        "compliance_report_exported",
+        json!({ "report": report.name(), "format": query.format.extension(), "from": query.from, "to": query.to, "rows": rows, "via": "admin" }),
+    );
+    let file_name = format!("{}-{}.{}", report.name(), chrono::Utc::now().format("%Y%m%dT%H%M%SZ"), query.format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn job_store(admin: &Admin) -> Result<&JobStore, ApiError> {
+    admin
+        .state
+        .jobs
+        .as_deref()
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Proof jobs are not recorded; set --database-url"))
+}
+
+/// Compiles an uploaded Noir package, generates its verification key and registers it under
+/// its `circuit_id`, so a new insurance product needs no new enclave image. Only allowed with an
+/// admin token, since the server runs the compiler on what it is sent.
+async fn upload_circuit(
+    State(admin): State<Arc<Admin>>,
+    Json(package): Json<CircuitPackage>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if admin.token.is_none() {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Circuit uploads require --admin-token-file"));
+    }
+    let verifier = &admin.state.verifier;
+    let registry = verifier.registry();
+    if !registry.is_writable() {
+        return Err(ApiError::new(StatusCode::NOT_IMPLEMENTED, "Circuit uploads need a --circuits-file to record them in"));
+    }
+    let id = &package.circuit_id;
+    if registry.contains(id) {
+        return Err(ApiError::new(StatusCode::CONFLICT, format!("Circuit {} is already registered", id)));
+    }
+
+    let path = admin
+        .compiler
+        .compile(&package)
+        .await?
+        .map_err(|message| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("Circuit {} not registered: {}", id, message)))?;
+    let registered = async {
+        let vk = verifier.key_at(&path).await?;
+        let circuit = RegisteredCircuit {
+            path: path.clone(),
+            parameters: package.parameters.clone(),
+            description: package.description.clone(),
+        };
+        registry.register(id, circuit)?;
+        anyhow::Ok(vk)
+    }
+    .await;
+    let vk = match registered {
+        Ok(vk) => vk,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&path);
+            return Err(e.into());
+        }
+    };
+    admin.state.integrity.watch(path.clone());
+
+    let info = registry.describe(id, Some(vk.vk_hash))?;
+    info!("Circuit {} compiled and registered from {}", id, path.display());
+    admin.audit.record(
+        "admin_circuit_registered",
+        json!({ "circuit_id": id, "circuit": path, "circuit_hash": info.circuit_hash, "vk_hash": info.vk_hash }),
+    );
+    Ok(Json(json!({ "circuit_id": id, "circuit": info })))
+}
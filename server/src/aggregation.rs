@@ -0,0 +1,291 @@
+use crate::eip712::ProofPayment;
+use crate::prover::{read_fields, ErrorCode, NoirProver, ProofRequest, ProofResponse, RecursiveProof};
+use crate::scratch;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+/// Must match MAX_COHORT_SIZE in aggregation-circuit/src/main.nr
+pub const MAX_COHORT_SIZE: usize = 8;
+
+#[derive(Debug, Serialize)]
+pub struct MemberFailure {
+    pub index: usize,
+    pub error_code: Option<ErrorCode>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AggregateResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    pub message: String,
+    pub cohort_size: usize,
+    /// EVM-verifiable (keccak) proof of the aggregation circuit
+    pub proof_hex: String,
+    pub public_inputs: String,
+    /// Cohort members that could not be proven; the aggregate is only produced if this is empty
+    pub failed_members: Vec<MemberFailure>,
+    /// Enclave-signed receipt for the aggregation's price, charged to the request's escrow deposit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment: Option<ProofPayment>,
+}
+
+impl AggregateResponse {
+    fn failed(cohort_size: usize, code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error_code: Some(code),
+            message: message.into(),
+            cohort_size,
+            proof_hex: String::new(),
+            public_inputs: String::new(),
+            failed_members: Vec::new(),
+            payment: None,
+        }
+    }
+}
+
+/// Folds up to MAX_COHORT_SIZE eligibility proofs into one recursive proof, so an insurer can
+/// settle a whole cohort with a single on-chain verification.
+pub struct Aggregator {
+    prover: NoirProver,
+    circuit_path: PathBuf,
+    inner_vk: Mutex<Option<Vec<String>>>,
+    // Aggregation proofs are large; running two at once would starve the worker pool
+    lock: Mutex<()>,
+}
+
+/// Where the aggregation circuit is looked for without --aggregation-circuit-path.
+pub const DEFAULT_CIRCUIT_PATHS: [&str; 2] = ["/app/aggregation-circuit", "../aggregation-circuit"];
+
+impl Aggregator {
+    pub fn new(prover: NoirProver, circuit_path: PathBuf) -> Self {
+        Self {
+            prover,
+            circuit_path,
+            inner_vk: Mutex::new(None),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn circuit_path(&self) -> &Path {
+        &self.circuit_path
+    }
+
+    pub async fn aggregate(&self, cohort: Vec<ProofRequest>) -> Result<AggregateResponse> {
+        let _guard = self.lock.lock().await;
+        let cohort_size = cohort.len();
+
+        let mut members = Vec::with_capacity(cohort_size);
+        let mut failed_members = Vec::new();
+        for (index, request) in cohort.iter().enumerate() {
+            match self.prove_member(request).await? {
+                Ok(proof) => members.push(proof),
+                Err(response) => failed_members.push(MemberFailure {
+                    index,
+                    error_code: response.error_code,
+                    message: response.message,
+                }),
+            }
+        }
+
+        if !failed_members.is_empty() {
+            let code = if failed_members.iter().all(|m| m.error_code == Some(ErrorCode::Ineligible)) {
+                ErrorCode::Ineligible
+            } else {
+                ErrorCode::BackendError
+            };
+            let mut response = AggregateResponse::failed(
+                cohort_size,
+                code,
+                format!("{} of {} cohort members could not be proven", failed_members.len(), cohort_size),
+            );
+            response.failed_members = failed_members;
+            return Ok(response);
+        }
+
+        let vk = self.inner_vk().await?;
+        self.prove_aggregate(&vk, members, cohort_size).await
+    }
+
+    /// Folds recursive proofs produced earlier (`"recursive": true` on /prove) without proving
+    /// the members again. The aggregation circuit verifies each of them.
+    pub async fn aggregate_proofs(&self, proofs: Vec<RecursiveProof>) -> Result<AggregateResponse> {
+        let _guard = self.lock.lock().await;
+        let cohort_size = proofs.len();
+        let vk = self.inner_vk().await?;
+        self.prove_aggregate(&vk, proofs, cohort_size).await
+    }
+
+    /// Whether an aggregation is running.
+    pub fn is_busy(&self) -> bool {
+        self.lock.try_lock().is_err()
+    }
+
+    /// Forgets the eligibility circuit's verification key once the running aggregation is done,
+    /// so the next one regenerates it from a recompiled circuit.
+    pub async fn reload(&self) {
+        let _guard = self.lock.lock().await;
+        *self.inner_vk.lock().await = None;
+    }
+
+    /// Verification key of the eligibility circuit in field form, generated once per circuit.
+    async fn inner_vk(&self) -> Result<Vec<String>> {
+        let mut cached = self.inner_vk.lock().await;
+        if let Some(vk) = cached.as_ref() {
+            return Ok(vk.clone());
+        }
+        let circuit_path = self.prover.circuit_path();
+        let output = self
+            .prover
+            .run_tool(|| {
+                let mut command = Command::new("bb");
+                command
+                    .args([
+                        "write_vk",
+                        "--scheme", "ultra_honk",
+                        "-b", "./target/insurance_verifier.json",
+                        "-o", "./target/recursive_vk",
+                        "--oracle_hash", "poseidon2",
+                        "--output_format", "fields",
+                    ])
+                    .current_dir(&circuit_path);
+                command
+            })
+            .await
+            .context("Failed to run bb write_vk")?;
+        if !output.status.success() {
+            anyhow::bail!("bb write_vk failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        let vk = read_fields(&circuit_path.join("target/recursive_vk/vk_fields.json"))?;
+        *cached = Some(vk.clone());
+        Ok(vk)
+    }
+
+    async fn prove_member(&self, request: &ProofRequest) -> Result<std::result::Result<RecursiveProof, ProofResponse>> {
+        let job_id = uuid::Uuid::new_v4().simple().to_string();
+        let workspace = self.prover.workspace(&self.prover.circuit_path(), &job_id)?;
+        let work_dir = workspace.path();
+        let witness_file = match self.prover.generate_witness(work_dir, request, &job_id).await? {
+            Ok(witness_file) => witness_file,
+            Err(response) => return Ok(Err(response)),
+        };
+
+        // Member proofs are verified inside the aggregation circuit, so only the recursive
+        // variant is needed
+        let proof = self.prover.prove_recursive(work_dir, &witness_file, &job_id).await;
+        scratch::wipe_file(&work_dir.join(&witness_file));
+        proof
+    }
+
+    async fn prove_aggregate(
+        &self,
+        vk: &[String],
+        mut members: Vec<RecursiveProof>,
+        cohort_size: usize,
+    ) -> Result<AggregateResponse> {
+        let circuit_path = &self.circuit_path;
+        let job_id = uuid::Uuid::new_v4().simple().to_string();
+        let prover_name = format!("Prover_{}", job_id);
+        let witness_name = format!("witness_{}", job_id);
+
+        // Pad to the circuit's fixed size by repeating the last member; `cohort_size` is a
+        // public input so verifiers can tell how many distinct applicants are covered
+        while members.len() < MAX_COHORT_SIZE {
+            let last = members.last().expect("cohort is never empty");
+            members.push(RecursiveProof {
+                proof: last.proof.clone(),
+                public_inputs: last.public_inputs.clone(),
+            });
+        }
+
+        let proofs: Vec<&Vec<String>> = members.iter().map(|m| &m.proof).collect();
+        let public_inputs: Vec<&Vec<String>> = members.iter().map(|m| &m.public_inputs).collect();
+        // JSON arrays of strings are valid TOML inline arrays
+        let prover_toml_content = format!(
+            "verification_key = {}\nproofs = {}\npublic_inputs = {}\nkey_hash = \"0x0\"\ncohort_size = \"{}\"\n",
+            serde_json::to_string(vk)?,
+            serde_json::to_string(&proofs)?,
+            serde_json::to_string(&public_inputs)?,
+            cohort_size,
+        );
+        let prover_path = circuit_path.join(format!("{}.toml", prover_name));
+        fs::write(&prover_path, prover_toml_content)?;
+
+        let execute_output = self
+            .prover
+            .run_tool(|| {
+                let mut command = Command::new("nargo");
+                command
+                    .args(["execute", &witness_name, "--prover-name", &prover_name])
+                    .current_dir(circuit_path);
+                command
+            })
+            .await;
+        let _ = fs::remove_file(&prover_path);
+        let execute_output = execute_output.context("Failed to execute aggregation circuit")?;
+        if !execute_output.status.success() {
+            return Ok(AggregateResponse::failed(
+                cohort_size,
+                ErrorCode::BackendError,
+                format!(
+                    "Aggregation circuit rejected the member proofs: {}",
+                    String::from_utf8_lossy(&execute_output.stderr)
+                ),
+            ));
+        }
+
+        let witness_file = format!("./target/{}.gz", witness_name);
+        let output_dir = format!("./target/aggregate_{}", job_id);
+        let prove_output = self
+            .prover
+            .run_tool(|| {
+                let mut command = Command::new("bb");
+                command
+                    .args([
+                        "prove",
+                        "--scheme", "ultra_honk",
+                        "-b", "./target/insurance_aggregator.json",
+                        "-w", &witness_file,
+                        "-o", &output_dir,
+                        "--oracle_hash", "keccak",
+                        "--output_format", "bytes_and_fields",
+                    ])
+                    .current_dir(circuit_path);
+                command
+            })
+            .await
+            .context("Failed to generate aggregate proof with bb")?;
+        let _ = fs::remove_file(circuit_path.join(&witness_file));
+
+        if !prove_output.status.success() {
+            return Ok(AggregateResponse::failed(
+                cohort_size,
+                ErrorCode::BackendError,
+                format!("Aggregate proof generation failed: {}", String::from_utf8_lossy(&prove_output.stderr)),
+            ));
+        }
+
+        let output_dir = circuit_path.join(&output_dir);
+        let proof = fs::read(output_dir.join("proof")).context("Aggregate proof was not generated")?;
+        let public_inputs = fs::read_to_string(output_dir.join("public_inputs_fields.json"))
+            .context("Aggregate public inputs were not generated")?;
+        let _ = fs::remove_dir_all(&output_dir);
+
+        Ok(AggregateResponse {
+            success: true,
+            error_code: None,
+            message: format!("Aggregated {} eligibility proofs into one recursive proof", cohort_size),
+            cohort_size,
+            proof_hex: format!("0x{}", hex::encode(proof)),
+            public_inputs: public_inputs.trim().to_string(),
+            failed_members: Vec::new(),
+            payment: None,
+        })
+    }
+}
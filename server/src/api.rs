@@ -0,0 +1,1350 @@
+use crate::aggregation::{AggregateResponse, MAX_COHORT_SIZE};
+use crate::apikeys;
+use crate::attestation::{Attestation, MAX_NONCE_BYTES};
+use crate::backpressure;
+use crate::bundles;
+use crate::config::{ChainConfig, EligibilityParameters};
+use crate::encryption;
+use crate::evm;
+use crate::envelope::Envelope;
+use crate::health::{self, Check, Preflight};
+use crate::honk;
+use crate::indexer::OnchainStatus;
+use crate::issuance::{IssuanceEntry, SignedHead};
+use crate::jobs::{self, JobFilter, JobState, JobStatus, JobStore, ProofSummary, StoredProof};
+use crate::keys::{KeyKind, PublicKeyInfo};
+use crate::logging;
+use crate::measurements::Measurements;
+use crate::metrics::{Activity, Metrics, ProvingStats};
+use crate::payments::{Payment, PaymentError, PaymentPermit};
+use crate::prover::{ErrorCode, ProofRequest, ProofResponse, RecursiveProof};
+use crate::registry::CircuitInfo;
+use crate::schema::FieldError;
+use crate::sessions::{self, SessionError, SessionGrant};
+use crate::signing::EnclaveSignature;
+use crate::state::{AppState, Credential};
+use crate::submitter::{self, Simulation, SubmitRequest, Submission};
+use crate::telemetry::{self, Span, TraceContext};
+use crate::usage::{QuotaExceeded, UsagePermit};
+use crate::verifier::{CircuitSize, SolidityVerifier, Verifier, VerificationKey, VerifyRequest, VerifyResponse};
+use crate::version::{self, VersionInfo};
+use crate::worker::Validation;
+use anyhow::Result;
+use axum::body::Body;
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequest, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tracing::{debug, warn, Instrument};
+
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+    /// Set when the server is at capacity or the client over its rate limit; sent as Retry-After
+    retry_after_secs: Option<u64>,
+    error_code: Option<ErrorCode>,
+    /// Fields of the body that failed the request schema
+    errors: Vec<FieldError>,
+    /// The quota an API key is over
+    quota: Option<QuotaExceeded>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            retry_after_secs: None,
+            error_code: None,
+            errors: Vec::new(),
+            quota: None,
+        }
+    }
+
+    /// 400 listing every field of the body that does not match the request schema.
+    pub fn invalid_fields(errors: Vec<FieldError>) -> Self {
+        let summary = errors
+            .iter()
+            .map(|error| match error.field.as_str() {
+                "" => error.message.clone(),
+                field => format!("{}: {}", field, error.message),
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        Self {
+            error_code: Some(ErrorCode::InvalidRequest),
+            errors,
+            ..Self::new(StatusCode::BAD_REQUEST, format!("Invalid request: {}", summary))
+        }
+    }
+
+    /// 503 telling the client when to come back.
+    pub fn busy(retry_after_secs: u64) -> Self {
+        Self {
+            retry_after_secs: Some(retry_after_secs),
+            error_code: Some(ErrorCode::ServerBusy),
+            ..Self::new(StatusCode::SERVICE_UNAVAILABLE, backpressure::busy_message(retry_after_secs))
+        }
+    }
+
+    /// 429 telling a client over its rate limit when to come back.
+    pub fn rate_limited(retry_after_secs: u64) -> Self {
+        Self {
+            retry_after_secs: Some(retry_after_secs),
+            error_code: Some(ErrorCode::RateLimited),
+            ..Self::new(StatusCode::TOO_MANY_REQUESTS, backpressure::rate_limited_message(retry_after_secs))
+        }
+    }
+
+    /// 429 naming the quota the caller's API key is over, and when to come back.
+    pub fn quota_exceeded(exceeded: QuotaExceeded) -> Self {
+        let message = exceeded.message();
+        Self {
+            retry_after_secs: Some(exceeded.retry_after_secs),
+            error_code: Some(ErrorCode::QuotaExceeded),
+            quota: Some(exceeded),
+            ..Self::new(StatusCode::TOO_MANY_REQUESTS, message)
+        }
+    }
+
+    /// 402 saying why the request's escrow deposit does not pay for it.
+    pub fn payment_required(message: impl Into<String>) -> Self {
+        Self {
+            error_code: Some(ErrorCode::PaymentRequired),
+            ..Self::new(StatusCode::PAYMENT_REQUIRED, message)
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self.retry_after_secs {
+            Some(secs) => {
+                let mut body = json!({ "error": self.message, "error_code": self.error_code, "retry_after_secs": secs });
+                if let Some(quota) = self.quota {
+                    body["quota"] = json!(quota);
+                }
+                (self.status, [(header::RETRY_AFTER, secs.to_string())], Json(body)).into_response()
+            }
+            None if !self.errors.is_empty() => (
+                self.status,
+                Json(json!({ "error": self.message, "error_code": self.error_code, "errors": self.errors })),
+            )
+                .into_response(),
+            None => {
+                let mut body = json!({ "error": self.message });
+                if let Some(error_code) = self.error_code {
+                    body["error_code"] = json!(error_code);
+                }
+                (self.status, Json(body)).into_response()
+            }
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    }
+}
+
+/// A JSON request body. Unlike `Json`, malformed bodies are rejected with the API's JSON error
+/// instead of plain text.
+struct JsonBody(Value);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for JsonBody {
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, ApiError> {
+        match Json::<Value>::from_request(request, state).await {
+            Ok(Json(body)) => Ok(Self(body)),
+            Err(rejection) => Err(ApiError::new(rejection.status(), rejection.body_text())),
+        }
+    }
+}
+
+/// A proof request body, checked against the request schema of the circuit it names before
+/// it is deserialized, so clients get every bad field at once.
+fn proof_request(state: &AppState, body: Value) -> Result<ProofRequest, ApiError> {
+    state.schemas.check(&state.verifier, &body).map_err(ApiError::invalid_fields)?;
+    serde_json::from_value(body).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))
+}
+
+/// A JSON array of proof requests, each checked like `proof_request`. Fields are reported
+/// under the index of their request, e.g. `/2/age`.
+fn proof_requests(state: &AppState, body: Value) -> Result<Vec<ProofRequest>, ApiError> {
+    let Value::Array(bodies) = body else {
+        return Err(ApiError::invalid_fields(vec![FieldError {
+            field: String::new(),
+            message: "expected a JSON array of requests".to_string(),
+        }]));
+    };
+    let mut errors = Vec::new();
+    for (index, body) in bodies.iter().enumerate() {
+        if let Err(invalid) = state.schemas.check(&state.verifier, body) {
+            errors.extend(invalid.into_iter().map(|error| FieldError {
+                field: format!("/{}{}", index, error.field),
+                ..error
+            }));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::invalid_fields(errors));
+    }
+    serde_json::from_value(Value::Array(bodies)).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/prove", post(prove))
+        .route("/prove/sealed", post(prove_sealed))
+        .route("/prove/async", post(prove_async))
+        .route("/jobs/:job_id", get(job_status))
+        .route("/validate", post(validate))
+        .route("/schema", get(request_schema))
+        .route("/circuits/stats", get(circuit_stats))
+        .route("/prove/batch", post(prove_batch))
+        .route("/prove/batch/stream", post(prove_batch_stream))
+        .route("/aggregate", post(aggregate))
+        .route("/aggregate/proofs", post(aggregate_proofs))
+        .route("/submit", post(submit))
+        .route("/simulate", post(simulate))
+        .route("/proofs", get(proof_history))
+        .route("/proofs/:id", get(stored_proof))
+        .route("/proofs/:id/bundle", get(proof_bundle))
+        .route("/proofs/:id/onchain-status", get(onchain_status))
+        .route("/attestation", get(attestation))
+        .route("/attestation/session", post(open_session))
+        .route("/keys", get(keys))
+        .route("/measurements", get(measurements))
+        .route("/status", get(status))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/audit/issuance", get(issuance_log))
+        .route("/audit/issuance/verify", get(verify_issuance_log))
+        .with_state(state.clone())
+        .merge(verification_routes(state.verifier.clone()))
+        .layer(middleware::from_fn_with_state(state.metrics.clone(), track_request))
+        .layer(middleware::from_fn_with_state(state.clone(), limit_connections))
+        .layer(middleware::from_fn(assign_request_id))
+}
+
+/// Probe and scrape endpoints, which stay reachable when the server is at its connection limit.
+const UNLIMITED_PATHS: [&str; 4] = ["/healthz", "/readyz", "/status", "/metrics"];
+
+/// Turns requests beyond --max-connections away with a 503 before they reach a handler.
+async fn limit_connections(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if UNLIMITED_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    match state.connections.admit() {
+        Ok(_permit) => next.run(request).await,
+        Err(retry_after_secs) => ApiError::busy(retry_after_secs).into_response(),
+    }
+}
+
+/// Sends a proof response, or the same 503 as the connection limit when the job queue was full.
+fn proof_reply(response: ProofResponse) -> Response {
+    match response.retry_after_secs {
+        Some(secs) if response.error_code == Some(ErrorCode::ServerBusy) => ApiError::busy(secs).into_response(),
+        _ => Json(response).into_response(),
+    }
+}
+
+/// ID of an HTTP request, as echoed in its X-Request-Id response header.
+#[derive(Debug, Clone)]
+struct RequestId(String);
+
+/// Tags every request with an ID: the caller's X-Request-Id when usable, a fresh one otherwise.
+/// It is stamped on the request's log lines, handed to handlers and echoed in the response.
+async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(logging::REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| logging::is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(logging::request_id);
+    let span = tracing::info_span!("http", request_id = %id, method = %request.method(), path = %request.uri().path());
+    request.extensions_mut().insert(RequestId(id.clone()));
+    async move {
+        let started = Instant::now();
+        let mut response = next.run(request).await;
+        debug!(status = response.status().as_u16(), elapsed = ?started.elapsed(), "HTTP request served");
+        if let Ok(value) = HeaderValue::from_str(&id) {
+            response.headers_mut().insert(logging::REQUEST_ID_HEADER, value);
+        }
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+/// Counts the request in the in-flight gauge while it is served.
+async fn track_request(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let _in_flight = metrics.track(Activity::HttpRequest);
+    next.run(request).await
+}
+
+/// Routes that only need the verifier; shared by the full server and `--mode verify`.
+fn verification_routes(verifier: Arc<Verifier>) -> Router {
+    Router::new()
+        .route("/verify", post(verify))
+        .route("/vk", get(verification_key))
+        .route("/circuits", get(circuits))
+        .route("/verifier/solidity", get(solidity_verifier))
+        .route("/parameters", get(parameters))
+        .route("/version", get(version))
+        .with_state(verifier)
+}
+
+pub async fn serve(state: Arc<AppState>, listener: TcpListener) -> Result<()> {
+    // Peer addresses are kept for the rate limiter
+    axum::serve(listener, router(state).into_make_service_with_connect_info::<SocketAddr>()).await?;
+    Ok(())
+}
+
+/// Serves the verification routes alone, plus a /status that is always `ready` and the probes.
+pub async fn serve_verify_only(verifier: Arc<Verifier>, listener: TcpListener) -> Result<()> {
+    let preflight = Arc::new(Preflight::new(verifier.clone(), health::VERIFIER_TOOLS));
+    let router = verification_routes(verifier)
+        .route(
+            "/status",
+            get(|| async { Json(json!({ "status": "ready", "mode": "verify" })) }),
+        )
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(move || async move { readiness(preflight.run().await) }))
+        .layer(middleware::from_fn(assign_request_id));
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+async fn prove(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Extension(RequestId(request_id)): Extension<RequestId>,
+    JsonBody(body): JsonBody,
+) -> Result<Response, ApiError> {
+    let mut span = server_span("POST /prove", &headers);
+    let caller = check_caller(&state, &headers).await?;
+    check_session(&state, &headers)?;
+    let mut request = proof_request(&state, body)?;
+    check_rate(&state, peer, &headers, 1)?;
+    let payment = check_payment(&state, &headers, 1).await?;
+    attach_caller(&state, &caller, std::slice::from_mut(&mut request), payment.as_ref())?;
+    request.traceparent = Some(span.context().traceparent());
+    request.request_id = Some(request_id);
+    let mut response = state.pool.submit(request).await?;
+    span.set("success", response.success);
+    state.watermark(caller.watermark_tenant(), &mut response)?;
+    Ok(proof_reply(response))
+}
+
+/// Queues a proof job and answers at once with its `job_id`, to be polled at GET /jobs/{job_id}.
+/// The job is recorded before the reply, so a restart resumes it rather than losing it.
+async fn prove_async(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Extension(RequestId(request_id)): Extension<RequestId>,
+    JsonBody(body): JsonBody,
+) -> Result<Response, ApiError> {
+    let span = server_span("POST /prove/async", &headers);
+    let caller = check_caller(&state, &headers).await?;
+    check_session(&state, &headers)?;
+    job_store(&state)?;
+    let mut request = proof_request(&state, body)?;
+    check_rate(&state, peer, &headers, 1)?;
+    let payment = check_payment(&state, &headers, 1).await?;
+    attach_caller(&state, &caller, std::slice::from_mut(&mut request), payment.as_ref())?;
+    request.traceparent = Some(span.context().traceparent());
+    request.request_id = Some(request_id.clone());
+    let Some(job_id) = state.pool.submit_detached(request).await? else {
+        return Err(ApiError::busy(state.config.read().unwrap().retry_after_secs));
+    };
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/jobs/{}", job_id))],
+        Json(json!({ "job_id": job_id, "request_id": request_id, "status": JobState::Queued })),
+    )
+        .into_response())
+}
+
+/// Where a recorded job stands; once it has succeeded, its proof is at GET /proofs/{job_id}.
+async fn job_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobStatus>, ApiError> {
+    let tenant = check_caller(&state, &headers).await?.tenant;
+    job_store(&state)?
+        .get(&job_id)
+        .await?
+        .filter(|job| job.tenant == tenant)
+        .map(|job| Json(JobStatus::from(job)))
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("No job {}", job_id)))
+}
+
+/// Checks a request as /prove would and compares it with the eligibility ranges, without proving.
+async fn validate(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonBody(body): JsonBody,
+) -> Result<Json<Validation>, ApiError> {
+    check_session(&state, &headers)?;
+    let mut request = proof_request(&state, body)?;
+    request.tenant = reading_tenant(&state, &headers).await?;
+    Ok(Json(state.pool.validate(request)))
+}
+
+/// JSON Schema of /prove bodies for the eligibility circuit or `circuit_id`, as requests are
+/// checked against it.
+async fn request_schema(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CircuitQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let document = state
+        .schemas
+        .document(&state.verifier, query.circuit_id.as_deref())
+        .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, format!("{:#}", e)))?;
+    Ok(Json(document))
+}
+
+#[derive(Debug, Serialize)]
+struct CircuitStats {
+    #[serde(flatten)]
+    size: CircuitSize,
+    /// Measured on this server; absent until the circuit has made a proof
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proving: Option<ProvingStats>,
+}
+
+/// Size of the eligibility circuit or `circuit_id` and what proving with it has cost so far, so
+/// integrators can estimate latency and hardware before sending traffic.
+async fn circuit_stats(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CircuitQuery>,
+) -> Result<Json<CircuitStats>, ApiError> {
+    let size = state
+        .verifier
+        .circuit_size(query.circuit_id.as_deref())
+        .await?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("Unknown circuit_id {:?}", query.circuit_id.unwrap_or_default())))?;
+    let proving = state.metrics.circuit_stats(&size.circuit_hash);
+    Ok(Json(CircuitStats { size, proving }))
+}
+
+/// Like /prove, for a request sealed to the enclave's encryption key (see GET /keys), so the
+/// applicant's data is only ever decrypted inside the enclave.
+async fn prove_sealed(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Extension(RequestId(request_id)): Extension<RequestId>,
+    Json(envelope): Json<Envelope>,
+) -> Result<Response, ApiError> {
+    let mut span = server_span("POST /prove/sealed", &headers);
+    let caller = check_caller(&state, &headers).await?;
+    check_session(&state, &headers)?;
+    let plaintext = state
+        .keys
+        .open_envelope(&envelope)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+    let body: Value = serde_json::from_slice(&plaintext)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("Sealed request is not JSON: {}", e)))?;
+    let mut request = proof_request(&state, body)?;
+    check_rate(&state, peer, &headers, 1)?;
+    let payment = check_payment(&state, &headers, 1).await?;
+    attach_caller(&state, &caller, std::slice::from_mut(&mut request), payment.as_ref())?;
+    request.traceparent = Some(span.context().traceparent());
+    request.request_id = Some(request_id);
+    let mut response = state.pool.submit(request).await?;
+    span.set("success", response.success);
+    state.watermark(caller.watermark_tenant(), &mut response)?;
+    Ok(proof_reply(response))
+}
+
+/// Proves a whole batch in one round trip; results are returned in request order.
+async fn prove_batch(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Extension(RequestId(request_id)): Extension<RequestId>,
+    JsonBody(body): JsonBody,
+) -> Result<Json<Vec<ProofResponse>>, ApiError> {
+    let mut span = server_span("POST /prove/batch", &headers);
+    let caller = check_caller(&state, &headers).await?;
+    check_session(&state, &headers)?;
+    let mut requests = proof_requests(&state, body)?;
+    check_batch_size(&state, &requests)?;
+    check_rate(&state, peer, &headers, requests.len())?;
+    let payment = check_payment(&state, &headers, requests.len()).await?;
+    attach_caller(&state, &caller, &mut requests, payment.as_ref())?;
+    span.set("batch_size", requests.len() as i64);
+    tag_batch(&mut requests, &span, &request_id);
+    let mut responses = state.pool.submit_batch(requests).await;
+    for response in &mut responses {
+        state.watermark(caller.watermark_tenant(), response)?;
+    }
+    Ok(Json(responses))
+}
+
+/// Streams batch results as NDJSON in completion order, one `{"index": .., ...response}` line
+/// per request, so callers can start on early results while the rest are still proving.
+async fn prove_batch_stream(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Extension(RequestId(request_id)): Extension<RequestId>,
+    JsonBody(body): JsonBody,
+) -> Result<Response, ApiError> {
+    let mut span = server_span("POST /prove/batch/stream", &headers);
+    let caller = check_caller(&state, &headers).await?;
+    check_session(&state, &headers)?;
+    let mut requests = proof_requests(&state, body)?;
+    check_batch_size(&state, &requests)?;
+    check_rate(&state, peer, &headers, requests.len())?;
+    let payment = check_payment(&state, &headers, requests.len()).await?;
+    attach_caller(&state, &caller, &mut requests, payment.as_ref())?;
+    span.set("batch_size", requests.len() as i64);
+    tag_batch(&mut requests, &span, &request_id);
+    // The stream owns the span, so it covers the whole response rather than just this handler
+    let lines = state.pool.clone().submit_batch_unordered(requests).map(move |mut item| {
+        let _span = &span;
+        state.watermark(caller.watermark_tenant(), &mut item.response)?;
+        let mut line = serde_json::to_vec(&item)?;
+        line.push(b'\n');
+        Ok::<_, anyhow::Error>(line)
+    });
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response())
+}
+
+/// Server span for a proving route, continuing the caller's trace when it sent a `traceparent`.
+fn server_span(name: &str, headers: &HeaderMap) -> Span {
+    let parent = headers
+        .get(telemetry::TRACEPARENT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceContext::parse);
+    Span::server(name, parent)
+}
+
+/// Parents every item's spans to the batch request's span and gives each item its own ID,
+/// `<request id>-<index>`.
+fn tag_batch(requests: &mut [ProofRequest], span: &Span, request_id: &str) {
+    for (index, request) in requests.iter_mut().enumerate() {
+        request.traceparent = Some(span.context().traceparent());
+        request.request_id = Some(format!("{}-{}", request_id, index));
+    }
+}
+
+/// Who sent a proof request.
+struct Caller {
+    /// Name of the API key it came with, which quotas are kept by
+    api_key: Option<String>,
+    /// None for the default tenant
+    tenant: Option<String>,
+}
+
+impl Caller {
+    fn watermark_tenant(&self) -> &str {
+        self.tenant.as_deref().unwrap_or(crate::DEFAULT_TENANT)
+    }
+}
+
+/// Tenant named by the X-Tenant-Id header, if any.
+fn requested_tenant(headers: &HeaderMap) -> Result<Option<&str>, ApiError> {
+    let Some(value) = headers.get("x-tenant-id") else {
+        return Ok(None);
+    };
+    let tenant = value
+        .to_str()
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "X-Tenant-Id is not valid text"))?;
+    encryption::validate_tenant_id(tenant).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Some(tenant))
+}
+
+fn api_key(headers: &HeaderMap) -> Result<Option<&str>, ApiError> {
+    headers
+        .get(apikeys::API_KEY_HEADER)
+        .map(|value| value.to_str())
+        .transpose()
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "X-API-Key is not valid text"))
+}
+
+/// Rejects proof requests, and reads of stored proofs and jobs, without a known X-API-Key or a
+/// valid bearer token once either is configured, so the enclave's proving capacity and the
+/// proofs it issued are not open to anyone who can reach it, and resolves the caller's tenant.
+async fn check_caller(state: &AppState, headers: &HeaderMap) -> Result<Caller, ApiError> {
+    let credential = state.authenticate(api_key(headers)?, bearer(headers)).await.map_err(|e| {
+        warn!("Request not authenticated: {:#}", e);
+        ApiError::new(StatusCode::UNAUTHORIZED, format!("{:#}", e))
+    })?;
+    let tenant = state
+        .tenant(&credential, requested_tenant(headers)?)
+        .map_err(|e| ApiError::new(StatusCode::FORBIDDEN, e.to_string()))?;
+    Ok(Caller {
+        api_key: credential.api_key().map(str::to_string),
+        tenant,
+    })
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Tenant a /validate caller checks inputs for. The route does not require credentials, but
+/// ones that are sent must be valid, and they are what grants access to a tenant.
+async fn reading_tenant(state: &AppState, headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    let credential = if api_key(headers)?.is_some() || bearer(headers).is_some() {
+        state
+            .authenticate(api_key(headers)?, bearer(headers))
+            .await
+            .map_err(|e| ApiError::new(StatusCode::UNAUTHORIZED, format!("{:#}", e)))?
+    } else {
+        Credential::Anonymous
+    };
+    state
+        .tenant(&credential, requested_tenant(headers)?)
+        .map_err(|e| ApiError::new(StatusCode::FORBIDDEN, e.to_string()))
+}
+
+/// Admits `proofs` proofs under the quotas of `api_key`. Callers without a key (no keys
+/// configured, or a bearer token) are not metered.
+fn check_quota(state: &AppState, api_key: Option<&str>, proofs: usize) -> Result<Vec<UsagePermit>, ApiError> {
+    let Some(api_key) = api_key else {
+        return Ok(Vec::new());
+    };
+    state.usage.admit(api_key, proofs).map_err(|exceeded| {
+        warn!(api_key, quota = ?exceeded.quota, "Quota exceeded");
+        ApiError::quota_exceeded(exceeded)
+    })
+}
+
+/// Ties requests to the caller's tenant, replacing whatever the client sent, and admits them as
+/// a whole under the quotas of its API key, handing each its permit and a share of `payment`.
+fn attach_caller(state: &AppState, caller: &Caller, requests: &mut [ProofRequest], payment: Option<&Payment>) -> Result<(), ApiError> {
+    let mut permits = check_quota(state, caller.api_key.as_deref(), requests.len())?.into_iter();
+    for request in requests {
+        request.tenant = caller.tenant.clone();
+        request.usage = permits.next();
+        request.payment = payment.map(|payment| payment.permit(1));
+    }
+    Ok(())
+}
+
+/// Holds the escrow deposit named by X-Payment-Deposit to pay for `proofs` proofs, when proofs
+/// are paid for (--payment-chain). X-Payment-Signature is the deposit's payer signing for it.
+async fn check_payment(state: &AppState, headers: &HeaderMap, proofs: usize) -> Result<Option<Payment>, ApiError> {
+    let Some(payments) = &state.payments else {
+        return Ok(None);
+    };
+    let header = |name: &'static str| {
+        headers
+            .get(name)
+            .map(|value| value.to_str())
+            .transpose()
+            .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, format!("{} is not valid text", name)))
+    };
+    let (Some(deposit_id), Some(signature)) = (header("x-payment-deposit")?, header("x-payment-signature")?) else {
+        return Err(ApiError::payment_required(format!(
+            "Proofs cost {} each; name an escrow deposit in X-Payment-Deposit and sign for it in X-Payment-Signature",
+            payments.price()
+        )));
+    };
+    match payments.hold(state, deposit_id, signature, proofs as u64).await {
+        Ok(payment) => Ok(Some(payment)),
+        Err(PaymentError::Refused(message)) => {
+            warn!(deposit_id, "Payment refused: {}", message);
+            Err(ApiError::payment_required(message))
+        }
+        Err(PaymentError::Unavailable(e)) => Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", e))),
+    }
+}
+
+/// Rejects requests referencing an unknown or expired attestation session, and requests with no
+/// session at all when sessions are required.
+fn check_session(state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let session_id = headers
+        .get("x-attestation-session")
+        .map(|value| value.to_str())
+        .transpose()
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "X-Attestation-Session is not valid text"))?;
+    state
+        .sessions
+        .check(session_id)
+        .map_err(|e| ApiError::new(StatusCode::UNAUTHORIZED, e.to_string()))
+}
+
+fn check_batch_size(state: &AppState, requests: &[ProofRequest]) -> Result<(), ApiError> {
+    if requests.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "Batch must contain at least one request"));
+    }
+    let max_batch_size = state.config.read().unwrap().max_batch_size;
+    if requests.len() > max_batch_size {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Batch of {} exceeds the maximum of {} requests", requests.len(), max_batch_size),
+        ));
+    }
+    // Turn the whole batch away rather than proving whichever part happens to fit
+    if !state.pool.has_room(requests.len()) {
+        return Err(ApiError::busy(state.config.read().unwrap().retry_after_secs));
+    }
+    Ok(())
+}
+
+/// Aggregations bypass the worker pool, so they are turned away here while it drains.
+fn check_draining(state: &AppState) -> Result<(), ApiError> {
+    if state.pool.is_draining() {
+        return Err(ApiError::busy(state.config.read().unwrap().retry_after_secs));
+    }
+    Ok(())
+}
+
+/// Address a request is rate limited under: the peer's, or with --trust-forwarded-for the
+/// client the proxy appended last to X-Forwarded-For.
+fn client_address(state: &AppState, peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+    let forwarded = state
+        .trust_forwarded_for
+        .then(|| headers.get("x-forwarded-for"))
+        .flatten()
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|client| client.trim().parse().ok());
+    forwarded.unwrap_or(peer.ip())
+}
+
+/// Charges `cost` proofs to the client's rate limit.
+fn check_rate(state: &AppState, peer: SocketAddr, headers: &HeaderMap, cost: usize) -> Result<(), ApiError> {
+    let Some(burst) = state.rate_limit.burst() else {
+        return Ok(());
+    };
+    if cost > burst as usize {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("{} proofs exceed the per-client burst of {}", cost, burst),
+        ));
+    }
+    let client = client_address(state, peer, headers);
+    state.rate_limit.take(client, cost as u32).map_err(|retry_after_secs| {
+        state.metrics.record_rate_limited();
+        warn!(client = %client, "Rate limit exceeded");
+        ApiError::rate_limited(retry_after_secs)
+    })
+}
+
+/// Proves every cohort member and folds the proofs into a single recursive proof.
+async fn aggregate(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    JsonBody(body): JsonBody,
+) -> Result<Json<AggregateResponse>, ApiError> {
+    let caller = check_caller(&state, &headers).await?;
+    check_session(&state, &headers)?;
+    let mut cohort = proof_requests(&state, body)?;
+    check_draining(&state)?;
+    if cohort.is_empty() || cohort.len() > MAX_COHORT_SIZE {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("A cohort must contain between 1 and {} requests", MAX_COHORT_SIZE),
+        ));
+    }
+    for (index, request) in cohort.iter_mut().enumerate() {
+        if request.circuit_id.is_some() {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("Cohort member {}: only eligibility circuit proofs can be aggregated", index),
+            ));
+        }
+        state
+            .pool
+            .intake()
+            .validate(request)
+            .map_err(|message| ApiError::new(StatusCode::BAD_REQUEST, format!("Cohort member {}: {}", index, message)))?;
+        let config = state.config.read().unwrap();
+        request.tenant = caller.tenant.clone();
+        config
+            .check_tenant(request.tenant.as_deref(), None, request.policy.as_deref())
+            .and_then(|()| config.check_tier(request.policy.as_deref(), request.tier.as_deref()))
+            .map_err(|message| ApiError::new(StatusCode::BAD_REQUEST, format!("Cohort member {}: {}", index, message)))?;
+        request.eligibility = Some(config.eligibility(
+            request.tenant.as_deref(),
+            request.policy.as_deref(),
+            request.tier.as_deref(),
+            request.age,
+            request.bmi_multiplied,
+        ));
+        request.expires_at = Some(config.proof_expiry());
+    }
+    check_rate(&state, peer, &headers, cohort.len())?;
+    // Members are proven by the aggregator rather than the pool, so their permits are kept here
+    let mut permits = check_quota(&state, caller.api_key.as_deref(), cohort.len())?;
+    let payment = check_payment(&state, &headers, cohort.len()).await?;
+    let payment = payment.map(|payment| payment.permit(cohort.len() as u64));
+
+    let mut response = state.aggregator.aggregate(cohort).await?;
+    settle(&mut permits, payment, &mut response).await;
+    Ok(Json(response))
+}
+
+/// Folds previously produced recursive proofs into a single proof.
+async fn aggregate_proofs(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(proofs): Json<Vec<RecursiveProof>>,
+) -> Result<Json<AggregateResponse>, ApiError> {
+    let caller = check_caller(&state, &headers).await?;
+    check_draining(&state)?;
+    if proofs.is_empty() || proofs.len() > MAX_COHORT_SIZE {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("A cohort must contain between 1 and {} proofs", MAX_COHORT_SIZE),
+        ));
+    }
+    // Folding yields one proof, so it costs one token however many proofs go in
+    check_rate(&state, peer, &headers, 1)?;
+    let mut permits = check_quota(&state, caller.api_key.as_deref(), 1)?;
+    let payment = check_payment(&state, &headers, 1).await?.map(|payment| payment.permit(1));
+
+    let mut response = state.aggregator.aggregate_proofs(proofs).await?;
+    settle(&mut permits, payment, &mut response).await;
+    Ok(Json(response))
+}
+
+/// Charges an aggregation's proofs to the caller's API key, unless it was turned away as busy,
+/// and a successful one to the request's deposit.
+async fn settle(permits: &mut [UsagePermit], payment: Option<PaymentPermit>, response: &mut AggregateResponse) {
+    if response.error_code != Some(ErrorCode::ServerBusy) {
+        for permit in permits {
+            permit.settle(response.success);
+        }
+    }
+    if let (Some(payment), true) = (payment, response.success) {
+        let receipt = match hex::decode(response.proof_hex.trim_start_matches("0x")) {
+            Ok(proof) => payment.settle(&evm::proof_id(&proof)).await,
+            Err(e) => Err(e.into()),
+        };
+        match receipt {
+            Ok(receipt) => response.payment = Some(receipt),
+            Err(e) => warn!("Failed to charge the aggregation to its deposit: {:#}", e),
+        }
+    }
+}
+
+/// Sends the proof to the verifier contract on the selected chain and returns the transaction
+/// hash and receipt. The submitter key pays for it, so callers are authenticated and rate
+/// limited like proof requests, and a proof the verifier would reject is never sent.
+async fn submit(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<SubmitRequest>,
+) -> Result<Json<Submission>, ApiError> {
+    check_caller(&state, &headers).await?;
+    let submitter = state.submitter.as_ref().ok_or_else(|| {
+        ApiError::new(StatusCode::NOT_IMPLEMENTED, "On-chain submission is not configured (no submitter key)")
+    })?;
+    let (name, chain) = selected_chain(&state, request.chain.as_deref())?;
+    let (proof, public_inputs) = request
+        .proof
+        .decode()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+    check_rate(&state, peer, &headers, 1)?;
+    // A configured gas limit skips estimation, so a reverting call would otherwise still be paid for
+    let simulation = submitter::simulate(&state.http, &name, &chain, Some(submitter.address()), &proof, &public_inputs).await?;
+    if !simulation.would_pass {
+        return Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, simulation.message));
+    }
+    Ok(Json(submitter.submit(&state.http, &name, &chain, &proof, &public_inputs).await?))
+}
+
+/// Dry-runs the verifier call on the selected chain: whether it would pass and its gas cost.
+async fn simulate(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<SubmitRequest>,
+) -> Result<Json<Simulation>, ApiError> {
+    check_caller(&state, &headers).await?;
+    check_rate(&state, peer, &headers, 1)?;
+    let (name, chain) = selected_chain(&state, request.chain.as_deref())?;
+    let (proof, public_inputs) = request
+        .proof
+        .decode()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+    // Simulate as the submitter when there is one, so the gas estimate matches a real submission
+    let from = state.submitter.as_ref().map(|submitter| submitter.address());
+    Ok(Json(submitter::simulate(&state.http, &name, &chain, from, &proof, &public_inputs).await?))
+}
+
+#[derive(Deserialize)]
+struct ProofHistoryQuery {
+    /// Only proofs requested at or after this time
+    from: Option<DateTime<Utc>>,
+    /// Only proofs requested before this time
+    to: Option<DateTime<Utc>>,
+    status: Option<JobState>,
+    circuit_id: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct ProofHistory {
+    proofs: Vec<ProofSummary>,
+    /// `to` for the next page, which carries on from the last proof listed; absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_to: Option<String>,
+}
+
+/// Proof jobs this server has recorded, newest first, a page at a time.
+async fn proof_history(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ProofHistoryQuery>,
+) -> Result<Json<ProofHistory>, ApiError> {
+    let tenant = check_caller(&state, &headers).await?.tenant;
+    let filter = JobFilter {
+        state: query.status,
+        circuit_id: query.circuit_id,
+        after: query.from.map(jobs::timestamp),
+        before: query.to.map(jobs::timestamp),
+        limit: query.limit,
+        tenant: Some(tenant.unwrap_or_else(|| crate::DEFAULT_TENANT.to_string())),
+        ..Default::default()
+    };
+    let proofs: Vec<ProofSummary> = job_store(&state)?.list(&filter).await?.into_iter().map(ProofSummary::from).collect();
+    let next_to = (proofs.len() as i64 == filter.page_size())
+        .then(|| proofs.last().map(|proof| proof.created_at.clone()))
+        .flatten();
+    Ok(Json(ProofHistory { proofs, next_to }))
+}
+
+/// A proof generated earlier, by the `job_id` returned with it, so it can be downloaded again
+/// without re-proving.
+async fn stored_proof(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<Json<StoredProof>, ApiError> {
+    Ok(Json(find_stored_proof(&state, &headers, &job_id).await?))
+}
+
+/// A proof generated earlier, packed with its public inputs, verification key and enclave
+/// signature into a single .tar.gz the applicant can hand to their insurer.
+async fn proof_bundle(State(state): State<Arc<AppState>>, headers: HeaderMap, Path(job_id): Path<String>) -> Result<Response, ApiError> {
+    let proof = find_stored_proof(&state, &headers, &job_id).await?;
+    let vk = match &proof.vk_hash {
+        Some(vk_hash) => Some(
+            bundles::verification_key(&state.verifier, proof.circuit_id.as_deref(), vk_hash).await?.ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::CONFLICT,
+                    format!("The circuit with verification key {} is no longer active, so the key cannot be bundled", vk_hash),
+                )
+            })?,
+        ),
+        None => None,
+    };
+    let archive = bundles::archive(&proof, vk.as_deref())?;
+    let disposition = format!("attachment; filename=\"proof-{}.tar.gz\"", proof.job_id);
+    Ok((
+        [(header::CONTENT_TYPE, "application/gzip".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        archive,
+    )
+        .into_response())
+}
+
+/// The proof of `job_id`, if it belongs to the caller's tenant; other tenants' proofs are not
+/// found, rather than forbidden, so job IDs cannot be probed across tenants.
+async fn find_stored_proof(state: &AppState, headers: &HeaderMap, job_id: &str) -> Result<StoredProof, ApiError> {
+    let tenant = check_caller(state, headers).await?.tenant;
+    job_store(state)?
+        .proof(job_id, tenant.as_deref())
+        .await?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("No proof with job_id {}", job_id)))
+}
+
+fn job_store(state: &AppState) -> Result<&JobStore, ApiError> {
+    state
+        .jobs
+        .as_deref()
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_IMPLEMENTED, "Proofs are not kept (--database-url)"))
+}
+
+/// On-chain verifications seen for a proof this node issued to the caller's tenant, by its
+/// `proof_id`.
+async fn onchain_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(proof_id): Path<String>,
+) -> Result<Json<OnchainStatus>, ApiError> {
+    let tenant = check_caller(&state, &headers).await?.tenant;
+    let indexer = state.indexer.as_ref().ok_or_else(|| {
+        ApiError::new(StatusCode::NOT_IMPLEMENTED, "On-chain indexing is not enabled (--onchain-index-secs)")
+    })?;
+    let status = indexer
+        .issued()
+        .status(&proof_id, tenant.as_deref())
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("No proof {} was issued by this node", proof_id)))?;
+    Ok(Json(status))
+}
+
+/// The chain a submission or simulation targets.
+fn selected_chain(state: &AppState, name: Option<&str>) -> Result<(String, ChainConfig), ApiError> {
+    state
+        .config
+        .read()
+        .unwrap()
+        .chain(name)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+/// Checks a proof produced by this server (UltraHonk, keccak) against the circuit's verification key.
+async fn verify(
+    State(verifier): State<Arc<Verifier>>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, ApiError> {
+    let (proof, public_inputs) = request
+        .decode()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+    if let Some(nonce) = &request.nonce {
+        let nonce = honk::nonce_word(nonce).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+        if let Err(e) = verifier.check_nonce(request.circuit_id.as_deref(), &public_inputs, &nonce) {
+            return Ok(Json(VerifyResponse {
+                valid: false,
+                message: format!("Proof verification failed: {:#}", e),
+            }));
+        }
+    }
+    Ok(Json(verifier.verify(request.circuit_id.as_deref(), &proof, &public_inputs).await?))
+}
+
+#[derive(Deserialize)]
+struct CircuitQuery {
+    /// Registered circuit; the eligibility circuit when absent
+    circuit_id: Option<String>,
+}
+
+/// Verification key of the active circuit, or of a registered one, so verifiers can pin exactly
+/// the key the server proves against.
+async fn verification_key(
+    State(verifier): State<Arc<Verifier>>,
+    Query(query): Query<CircuitQuery>,
+) -> Result<Json<VerificationKey>, ApiError> {
+    match query.circuit_id {
+        Some(id) => verifier
+            .circuit_verification_key(&id)
+            .await?
+            .map(Json)
+            .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("Unknown circuit_id {:?}", id))),
+        None => Ok(Json(verifier.verification_key().await?)),
+    }
+}
+
+/// Circuits registered next to the eligibility circuit, selectable with `circuit_id`.
+async fn circuits(State(verifier): State<Arc<Verifier>>) -> Result<Json<BTreeMap<String, CircuitInfo>>, ApiError> {
+    Ok(Json(verifier.circuits().await?))
+}
+
+/// Eligibility ranges applicants are proven against, so front-ends and verifiers need not hardcode them.
+async fn parameters(State(verifier): State<Arc<Verifier>>) -> Json<EligibilityParameters> {
+    Json(verifier.parameters())
+}
+
+/// Server, toolchain and circuit versions, for spotting mismatches between nodes.
+async fn version(State(verifier): State<Arc<Verifier>>) -> Result<Json<VersionInfo>, ApiError> {
+    Ok(Json(version::report(&verifier).await?))
+}
+
+/// Solidity verifier contract for the circuit the server is proving against.
+async fn solidity_verifier(State(verifier): State<Arc<Verifier>>) -> Result<Json<SolidityVerifier>, ApiError> {
+    Ok(Json(verifier.solidity_verifier().await?))
+}
+
+#[derive(Debug, Serialize)]
+struct KeysResponse {
+    signing: PublicKeyInfo,
+    encryption: PublicKeyInfo,
+    /// Every key ever issued, newest first, so signatures by retired keys can still be checked
+    history: Vec<PublicKeyInfo>,
+    /// sha256 over the current public keys; the attestation document's `user_data`
+    binding: String,
+    /// Attestation document binding `binding`, when a nonce was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attestation: Option<KeysAttestation>,
+}
+
+#[derive(Debug, Serialize)]
+struct KeysAttestation {
+    document: String,
+    nonce: String,
+}
+
+/// Current enclave public keys. With `?nonce=`, also an attestation document whose `user_data`
+/// is their `binding`, so clients know the keys live in the attested enclave.
+async fn keys(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AttestationQuery>,
+) -> Result<Json<KeysResponse>, ApiError> {
+    let keys = &state.keys;
+    let binding = keys.binding();
+    let attestation = match query.nonce {
+        Some(nonce) => {
+            let nonce = parse_nonce(&nonce)?;
+            let user_data = hex::decode(binding.trim_start_matches("0x")).map_err(anyhow::Error::from)?;
+            let document = state
+                .attestation
+                .document(&nonce, &user_data)
+                .await
+                .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", e)))?;
+            Some(KeysAttestation {
+                document,
+                nonce: format!("0x{}", hex::encode(nonce)),
+            })
+        }
+        None => None,
+    };
+    Ok(Json(KeysResponse {
+        signing: keys.current(KeyKind::Signing),
+        encryption: keys.current(KeyKind::Encryption),
+        history: keys.public_keys(),
+        binding,
+        attestation,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct MeasurementsResponse {
+    measurements: Measurements,
+    /// Enclave signature over the measurements' `signing_message`
+    enclave_signature: EnclaveSignature,
+    /// Attestation document whose `user_data` is the sha256 of the signed message, when a
+    /// nonce was given; its PCRs must equal `measurements.pcrs`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attestation: Option<KeysAttestation>,
+}
+
+/// Signed manifest of the enclave image PCRs, the server binary and version, and the active
+/// circuit, so auditors can pin exactly what produced a proof.
+async fn measurements(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AttestationQuery>,
+) -> Result<Json<MeasurementsResponse>, ApiError> {
+    let nonce = query.nonce.as_deref().map(parse_nonce).transpose()?;
+    let vk_hash = state.verifier.verification_key().await.ok().map(|vk| vk.vk_hash);
+    let measurements = state
+        .measurements
+        .measure(&state.attestation, &state.verifier.circuit_path(), vk_hash, state.keys.binding())
+        .await?;
+    let enclave_signature = state.signer.sign_at(|signed_at| measurements.signing_message(signed_at))?;
+    let message = measurements.signing_message(enclave_signature.signed_at);
+    let attestation = match nonce {
+        Some(nonce) => {
+            let document = state
+                .attestation
+                .document(&nonce, &Sha256::digest(message.as_bytes()))
+                .await
+                .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", e)))?;
+            Some(KeysAttestation {
+                document,
+                nonce: format!("0x{}", hex::encode(nonce)),
+            })
+        }
+        None => None,
+    };
+    Ok(Json(MeasurementsResponse {
+        measurements,
+        enclave_signature,
+        attestation,
+    }))
+}
+
+#[derive(Deserialize)]
+struct AttestationQuery {
+    /// Hex-encoded, chosen fresh by the client so an old document cannot be replayed
+    nonce: Option<String>,
+}
+
+/// Fresh Nitro attestation document for this enclave, bound to the caller's nonce and to the
+/// enclave signer. Clients check its PCRs before trusting the prover with health data.
+async fn attestation(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AttestationQuery>,
+) -> Result<Json<Attestation>, ApiError> {
+    let nonce = query
+        .nonce
+        .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "A nonce query parameter is required"))?;
+    let nonce = parse_nonce(&nonce)?;
+    let attestation = state
+        .attestation
+        .attest(&nonce, &state.signer.address())
+        .await
+        .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", e)))?;
+    Ok(Json(attestation))
+}
+
+#[derive(Deserialize)]
+struct SessionRequest {
+    /// Hex-encoded random challenge chosen by the client
+    challenge: String,
+}
+
+/// Opens an attestation session: answers the client's challenge with an attestation document
+/// binding the challenge, a new session ID and the enclave's public keys. Requests that send data
+/// then carry the session ID in X-Attestation-Session.
+async fn open_session(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<SessionRequest>,
+) -> Result<Json<SessionGrant>, ApiError> {
+    let challenge = parse_nonce(&request.challenge)?;
+    // Anyone may open a session, and each costs an attestation document, so it takes a token
+    check_rate(&state, peer, &headers, 1)?;
+    let (session_id, expires_at) = state.sessions.open(&challenge).map_err(|e| match e {
+        SessionError::Reused => ApiError::new(StatusCode::CONFLICT, "Challenge was already used; pick a fresh random one"),
+        SessionError::Full => ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many attestation sessions are open; retry later",
+        ),
+    })?;
+    let keys_binding = state.keys.binding();
+    let user_data = sessions::session_user_data(&session_id, &challenge, &keys_binding);
+    let document = match state.attestation.document(&challenge, &user_data).await {
+        Ok(document) => document,
+        Err(e) => {
+            state.sessions.close(&session_id);
+            return Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", e)));
+        }
+    };
+    Ok(Json(SessionGrant {
+        session_id,
+        challenge: format!("0x{}", hex::encode(&challenge)),
+        expires_at,
+        keys_binding,
+        user_data: format!("0x{}", hex::encode(user_data)),
+        document,
+    }))
+}
+
+fn parse_nonce(nonce: &str) -> Result<Vec<u8>, ApiError> {
+    let nonce = hex::decode(nonce.trim_start_matches("0x"))
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "nonce is not valid hex"))?;
+    if nonce.is_empty() || nonce.len() > MAX_NONCE_BYTES {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("nonce must be between 1 and {} bytes", MAX_NONCE_BYTES),
+        ));
+    }
+    Ok(nonce)
+}
+
+/// Most issuance entries returned by one export call.
+const MAX_ISSUANCE_PAGE: usize = 1000;
+
+#[derive(Deserialize)]
+struct IssuanceQuery {
+    /// First `seq` to return
+    #[serde(default)]
+    from: u64,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct IssuanceExport {
+    entries: Vec<IssuanceEntry>,
+    chain: SignedHead,
+}
+
+/// Exports the issuance log a page at a time, along with the whole chain's verification and an
+/// enclave signature over its head.
+async fn issuance_log(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<IssuanceQuery>,
+) -> Result<Json<IssuanceExport>, ApiError> {
+    let limit = query.limit.unwrap_or(MAX_ISSUANCE_PAGE).min(MAX_ISSUANCE_PAGE);
+    Ok(Json(IssuanceExport {
+        entries: state.issuance.entries(query.from, limit)?,
+        chain: state.issuance.signed_head(&state.signer)?,
+    }))
+}
+
+/// Checks the issuance log's hash chain.
+async fn verify_issuance_log(State(state): State<Arc<AppState>>) -> Result<Json<SignedHead>, ApiError> {
+    Ok(Json(state.issuance.signed_head(&state.signer)?))
+}
+
+/// Counters, queue depth, step durations and open connections in the Prometheus text format.
+async fn prometheus_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render_prometheus(state.pool.queued_jobs()),
+    )
+}
+
+/// Liveness: the process is up and serving requests.
+async fn healthz() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Readiness: the toolchain and circuit pass the preflight, the warm-up is done, the circuit
+/// artifacts are intact and the node is not draining. 503 with the failing checks otherwise, so
+/// traffic is routed elsewhere.
+async fn readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<serde_json::Value>) {
+    let mut checks = state.preflight.run().await;
+    checks.push(Check::new(
+        "warm_up",
+        if state.pool.is_warming_up() { Err("warming up".to_string()) } else { Ok("done".to_string()) },
+    ));
+    checks.push(Check::new(
+        "integrity",
+        if state.integrity.is_intact() { Ok("intact".to_string()) } else { Err("circuit artifacts changed".to_string()) },
+    ));
+    checks.push(Check::new(
+        "draining",
+        if state.pool.is_draining() { Err("draining for maintenance".to_string()) } else { Ok("accepting jobs".to_string()) },
+    ));
+    readiness(checks)
+}
+
+fn readiness(checks: Vec<Check>) -> (StatusCode, Json<serde_json::Value>) {
+    let ready = checks.iter().all(|check| check.ok);
+    let code = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (code, Json(json!({ "ready": ready, "checks": checks })))
+}
+
+/// `warming_up` while the backend is still loading; jobs submitted meanwhile are queued.
+/// `integrity_failed` (with 503) once circuit artifacts no longer match their pinned hashes.
+/// `draining` (with 503) while the admin API has new jobs turned away.
+async fn status(State(state): State<Arc<AppState>>) -> (StatusCode, Json<serde_json::Value>) {
+    let (code, status) = if !state.integrity.is_intact() {
+        (StatusCode::SERVICE_UNAVAILABLE, "integrity_failed")
+    } else if state.pool.is_draining() {
+        (StatusCode::SERVICE_UNAVAILABLE, "draining")
+    } else if state.pool.is_warming_up() {
+        (StatusCode::OK, "warming_up")
+    } else {
+        (StatusCode::OK, "ready")
+    };
+    (code, Json(json!({ "status": status, "queued_jobs": state.pool.queued_jobs() })))
+}
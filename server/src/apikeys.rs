@@ -0,0 +1,110 @@
+use anyhow::{bail, Context, Result};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Header carrying the API key on HTTP proof requests.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Prefix of generated keys, so a leaked one is easy to recognise in logs and secret scanners.
+const KEY_PREFIX: &str = "zki_";
+
+/// An entry of the API keys table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ApiKeyEntry")]
+pub struct ApiKey {
+    /// sha256 of the key, in hex
+    pub sha256: String,
+    /// Proofs the key may be charged per UTC day (0 = no limit)
+    pub proofs_per_day: u64,
+    /// Proof jobs the key may have queued or running at once (0 = no limit)
+    pub max_concurrent: u64,
+    /// Tenant (see --tenants-file) the key's requests are proven for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+/// A key is listed either by its hash alone or with its quotas and tenant.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ApiKeyEntry {
+    Hash(String),
+    Settings {
+        sha256: String,
+        #[serde(default)]
+        proofs_per_day: u64,
+        #[serde(default)]
+        max_concurrent: u64,
+        #[serde(default)]
+        tenant: Option<String>,
+    },
+}
+
+impl From<ApiKeyEntry> for ApiKey {
+    fn from(entry: ApiKeyEntry) -> Self {
+        match entry {
+            ApiKeyEntry::Hash(sha256) => ApiKey {
+                sha256,
+                ..Default::default()
+            },
+            ApiKeyEntry::Settings {
+                sha256,
+                proofs_per_day,
+                max_concurrent,
+                tenant,
+            } => ApiKey {
+                sha256,
+                proofs_per_day,
+                max_concurrent,
+                tenant,
+            },
+        }
+    }
+}
+
+/// Loads the API keys table: a JSON object mapping each key's name to the sha256 of the key, in
+/// hex, or to an object with the hash (`sha256`), the key's quotas and its tenant. Only hashes
+/// are kept, so the file does not hand out working keys.
+pub fn load(path: &Path) -> Result<BTreeMap<String, ApiKey>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let keys: BTreeMap<String, ApiKey> =
+        serde_json::from_str(&content).with_context(|| format!("{} is not a valid API keys table", path.display()))?;
+    keys.into_iter()
+        .map(|(name, mut key)| {
+            key.sha256 = key.sha256.trim_start_matches("0x").to_ascii_lowercase();
+            if key.sha256.len() != 64 || !key.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("API key '{}' in {} is not a sha256 hash in hex", name, path.display());
+            }
+            Ok((name, key))
+        })
+        .collect()
+}
+
+/// Name of the key `presented` is, among `keys`. With no keys configured, anyone may prove and
+/// the name is None.
+pub fn authenticate(keys: &BTreeMap<String, ApiKey>, presented: Option<&str>) -> Result<Option<String>> {
+    if keys.is_empty() {
+        return Ok(None);
+    }
+    let Some(presented) = presented.map(str::trim).filter(|key| !key.is_empty()) else {
+        bail!("An API key is required");
+    };
+    // Compared by digest, so the time taken says nothing about the key
+    let hash = hex::encode(Sha256::digest(presented.as_bytes()));
+    match keys.iter().find(|(_, known)| known.sha256 == hash) {
+        Some((name, _)) => Ok(Some(name.clone())),
+        None => bail!("Unknown API key"),
+    }
+}
+
+/// A fresh random key and the hash to list it under.
+pub fn generate() -> (String, String) {
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    let key = format!("{}{}", KEY_PREFIX, hex::encode(secret));
+    let hash = hex::encode(Sha256::digest(key.as_bytes()));
+    (key, hash)
+}
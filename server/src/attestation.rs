@@ -0,0 +1,94 @@
+use anyhow::{bail, Context, Result};
+use ciborium::Value;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Oyster's attestation server inside the CVM, which asks the Nitro Secure Module for a fresh
+/// document embedding the given nonce and user data.
+pub const DEFAULT_ATTESTATION_URL: &str = "http://127.0.0.1:1350/attestation/raw";
+
+/// Largest nonce the Nitro Secure Module accepts.
+pub const MAX_NONCE_BYTES: usize = 512;
+
+/// A Nitro attestation document as served by GET /attestation.
+#[derive(Debug, Serialize)]
+pub struct Attestation {
+    /// COSE_Sign1 attestation document (CBOR), hex-encoded. Its PCRs identify the enclave image;
+    /// verify the AWS certificate chain and the PCRs before sending the prover any data.
+    pub document: String,
+    /// The caller's nonce, echoed; the document's `nonce` field must equal it
+    pub nonce: String,
+    /// Enclave signer address, embedded as the document's `user_data` so signatures by this key
+    /// are known to come from the attested enclave
+    pub signer: String,
+}
+
+pub struct AttestationClient {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl AttestationClient {
+    pub fn new(http: reqwest::Client, url: String) -> Self {
+        Self { http, url }
+    }
+
+    /// Fetches a fresh attestation document binding `nonce` and `signer`.
+    pub async fn attest(&self, nonce: &[u8], signer: &str) -> Result<Attestation> {
+        let user_data = hex::decode(signer.trim_start_matches("0x")).context("Invalid signer address")?;
+        Ok(Attestation {
+            document: self.document(nonce, &user_data).await?,
+            nonce: format!("0x{}", hex::encode(nonce)),
+            signer: signer.to_string(),
+        })
+    }
+
+    /// Hex-encoded attestation document embedding `nonce` and `user_data`.
+    pub async fn document(&self, nonce: &[u8], user_data: &[u8]) -> Result<String> {
+        let response = self
+            .http
+            .get(&self.url)
+            .query(&[("nonce", hex::encode(nonce)), ("user_data", hex::encode(user_data))])
+            .send()
+            .await
+            .with_context(|| format!("Attestation server at {} is unreachable (not running in an enclave?)", self.url))?;
+        let status = response.status();
+        let body = response.bytes().await.context("Failed to read the attestation document")?;
+        if !status.is_success() {
+            bail!("Attestation server returned {}: {}", status, String::from_utf8_lossy(&body).trim());
+        }
+        if body.is_empty() {
+            bail!("Attestation server returned an empty document");
+        }
+        Ok(format!("0x{}", hex::encode(&body)))
+    }
+}
+
+/// The non-zero PCRs in a hex-encoded attestation document, by index. The document is a
+/// COSE_Sign1 structure whose payload is a CBOR map with a `pcrs` map of index to digest.
+pub fn pcrs(document: &str) -> Result<BTreeMap<u8, String>> {
+    let bytes = hex::decode(document.trim_start_matches("0x")).context("Attestation document is not valid hex")?;
+    let cose = match ciborium::from_reader(bytes.as_slice()).context("Attestation document is not CBOR")? {
+        Value::Tag(_, inner) => *inner,
+        value => value,
+    };
+    let Some(Value::Bytes(payload)) = cose.as_array().and_then(|parts| parts.get(2)) else {
+        bail!("Attestation document is not a COSE_Sign1 structure");
+    };
+    let payload: Value = ciborium::from_reader(payload.as_slice()).context("Attestation payload is not CBOR")?;
+    let pcrs = payload
+        .as_map()
+        .and_then(|fields| fields.iter().find(|(key, _)| key.as_text() == Some("pcrs")))
+        .and_then(|(_, pcrs)| pcrs.as_map())
+        .context("Attestation document has no PCRs")?;
+
+    let mut result = BTreeMap::new();
+    for (index, value) in pcrs {
+        let index = index.as_integer().and_then(|index| u8::try_from(index).ok()).context("Invalid PCR index")?;
+        let value = value.as_bytes().context("Invalid PCR value")?;
+        if value.iter().any(|byte| *byte != 0) {
+            result.insert(index, format!("0x{}", hex::encode(value)));
+        }
+    }
+    Ok(result)
+}
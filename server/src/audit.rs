@@ -0,0 +1,66 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::error;
+
+/// Append-only JSON-lines log of security- and compliance-relevant events. Entries never contain
+/// private inputs.
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, event: &str, details: Value) {
+        let entry = json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "event": event,
+            "details": details,
+        });
+        let mut line = entry.to_string();
+        line.push('\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            error!("Failed to write audit log entry: {}", e);
+        }
+    }
+
+    /// Every entry recorded so far, oldest first.
+    pub fn entries(&self) -> Result<Vec<Value>> {
+        let _file = self.file.lock().unwrap();
+        read_entries(&self.path)
+    }
+}
+
+pub fn read_entries(path: &Path) -> Result<Vec<Value>> {
+    let file = File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => bail!("line {} is not an audit entry: {}", index + 1, e),
+        }
+    }
+    Ok(entries)
+}
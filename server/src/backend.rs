@@ -0,0 +1,18 @@
+use crate::isolation::IsolatedProver;
+use crate::prover::{NoirProver, ProofRequest, ProofResponse};
+use anyhow::Result;
+
+/// Where the proving stage of a job actually runs.
+pub enum ProverBackend {
+    InProcess(NoirProver),
+    Isolated(IsolatedProver),
+}
+
+impl ProverBackend {
+    pub async fn generate_proof(&self, request: ProofRequest) -> Result<ProofResponse> {
+        match self {
+            ProverBackend::InProcess(prover) => prover.generate_proof(request).await,
+            ProverBackend::Isolated(prover) => prover.generate_proof(request).await,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::SharedConfig;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// What a busy client is told, over HTTP and TCP alike.
+pub fn busy_message(retry_after_secs: u64) -> String {
+    format!("Server busy, retry after {} seconds", retry_after_secs)
+}
+
+/// What a client over its rate limit is told.
+pub fn rate_limited_message(retry_after_secs: u64) -> String {
+    format!("Rate limit exceeded, retry after {} seconds", retry_after_secs)
+}
+
+/// Clients tracked before idle ones are forgotten; an idle client's bucket is full anyway.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Caps the TCP sessions and HTTP requests served at once. Clients over the cap are answered
+/// right away with a retry hint rather than getting a task each and piling up on the pool.
+pub struct ConnectionLimit {
+    /// None when unlimited
+    permits: Option<Arc<Semaphore>>,
+    config: SharedConfig,
+}
+
+/// A slot taken from a `ConnectionLimit`, freed when dropped.
+pub struct ConnectionPermit {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl ConnectionLimit {
+    /// `max_connections` of 0 means no limit.
+    pub fn new(max_connections: usize, config: SharedConfig) -> Self {
+        Self {
+            permits: (max_connections > 0).then(|| Arc::new(Semaphore::new(max_connections))),
+            config,
+        }
+    }
+
+    /// Takes a slot, or returns the seconds the client should wait before retrying.
+    pub fn admit(&self) -> Result<ConnectionPermit, u64> {
+        match &self.permits {
+            None => Ok(ConnectionPermit { _permit: None }),
+            Some(permits) => match permits.clone().try_acquire_owned() {
+                Ok(permit) => Ok(ConnectionPermit { _permit: Some(permit) }),
+                Err(_) => Err(self.config.read().unwrap().retry_after_secs),
+            },
+        }
+    }
+}
+
+/// Token bucket per client address, so one client cannot take all of the proving capacity.
+/// Each proof costs a token; a client may burst up to `rate_limit_burst` proofs and then gets
+/// `rate_limit` more each minute. Both are read from the runtime configuration on every call,
+/// so a reload applies to clients already tracked.
+pub struct RateLimiter {
+    config: SharedConfig,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, per_sec: f64, burst: f64, now: Instant) {
+        self.tokens = (self.tokens + now.duration_since(self.updated).as_secs_f64() * per_sec).min(burst);
+        self.updated = now;
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: SharedConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Tokens per second and the burst (at least 1); None when there is no limit.
+    fn limits(&self) -> Option<(f64, u32)> {
+        let config = self.config.read().unwrap();
+        (config.rate_limit > 0).then(|| (config.rate_limit as f64 / 60.0, config.rate_limit_burst.max(1)))
+    }
+
+    /// Most proofs a client can ask for at once; larger batches can never be admitted. None
+    /// when there is no limit.
+    pub fn burst(&self) -> Option<u32> {
+        self.limits().map(|(_, burst)| burst)
+    }
+
+    /// Takes `cost` tokens from `client`'s bucket, or returns the seconds until it holds enough.
+    pub fn take(&self, client: IpAddr, cost: u32) -> Result<(), u64> {
+        let Some((per_sec, burst)) = self.limits() else {
+            return Ok(());
+        };
+        let (burst, cost) = (burst as f64, cost as f64);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            buckets.retain(|_, bucket| {
+                bucket.refill(per_sec, burst, now);
+                bucket.tokens < burst
+            });
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: burst, updated: now });
+        bucket.refill(per_sec, burst, now);
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(((cost - bucket.tokens) / per_sec).ceil() as u64)
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::config::SharedConfig;
+use crate::prover::{ErrorCode, ProofResponse};
+use anyhow::Result;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+enum State {
+    Closed { consecutive_failures: u32 },
+    Open { until: Instant },
+    /// Cooldown elapsed and a canary job is in flight; real jobs keep failing fast until it reports
+    Probing,
+}
+
+/// What the breaker allows a newly submitted job to do.
+pub enum Admission {
+    Allow,
+    Reject,
+    /// Reject, and the caller should launch a canary job to probe recovery
+    Probe,
+}
+
+/// Stops invoking nargo/bb after `breaker_threshold` consecutive backend failures, so that clients
+/// get an immediate `backend_unavailable` instead of queueing behind jobs that are bound to fail.
+pub struct CircuitBreaker {
+    config: SharedConfig,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    /// A `breaker_threshold` of 0 disables the breaker.
+    pub fn new(config: SharedConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::Closed { consecutive_failures: 0 }),
+        }
+    }
+
+    fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.config.read().unwrap().breaker_cooldown_secs)
+    }
+
+    pub fn admit(&self) -> Admission {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => Admission::Allow,
+            State::Probing => Admission::Reject,
+            State::Open { until } if Instant::now() < until => Admission::Reject,
+            State::Open { .. } => {
+                *state = State::Probing;
+                Admission::Probe
+            }
+        }
+    }
+
+    /// Records the outcome of a regular job. Ineligible inputs are a healthy backend doing its job.
+    pub fn record(&self, result: &Result<ProofResponse>) {
+        let threshold = self.config.read().unwrap().breaker_threshold;
+        if threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if let State::Closed { consecutive_failures } = *state {
+            if is_backend_failure(result) {
+                let failures = consecutive_failures + 1;
+                *state = if failures >= threshold {
+                    let cooldown = self.cooldown();
+                    warn!(
+                        "Proving backend failed {} times in a row; pausing it for {:?}",
+                        failures, cooldown
+                    );
+                    State::Open { until: Instant::now() + cooldown }
+                } else {
+                    State::Closed { consecutive_failures: failures }
+                };
+            } else {
+                *state = State::Closed { consecutive_failures: 0 };
+            }
+        }
+    }
+
+    /// Records the outcome of a canary job, closing the breaker or starting another cooldown.
+    pub fn record_probe(&self, result: &Result<ProofResponse>) {
+        let mut state = self.state.lock().unwrap();
+        if is_backend_failure(result) {
+            let cooldown = self.cooldown();
+            warn!("Canary proof failed; proving backend stays paused for {:?}", cooldown);
+            *state = State::Open { until: Instant::now() + cooldown };
+        } else {
+            info!("Canary proof succeeded; proving backend resumed");
+            *state = State::Closed { consecutive_failures: 0 };
+        }
+    }
+
+    pub fn rejection(&self) -> ProofResponse {
+        ProofResponse::failed(
+            ErrorCode::BackendUnavailable,
+            "The proving backend is temporarily unavailable after repeated failures. Please retry later.",
+        )
+    }
+}
+
+fn is_backend_failure(result: &Result<ProofResponse>) -> bool {
+    match result {
+        Ok(response) => matches!(
+            response.error_code,
+            Some(ErrorCode::BackendError) | Some(ErrorCode::BackendUnavailable)
+        ),
+        Err(_) => true,
+    }
+}
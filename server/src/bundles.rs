@@ -0,0 +1,306 @@
+use crate::ipfs::IpfsClient;
+use crate::jobs::StoredProof;
+use crate::prover;
+use crate::signing::EnclaveSigner;
+use crate::storage::Storage;
+use crate::verifier::Verifier;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// `format` of the manifest in a downloadable proof bundle.
+pub const ARCHIVE_FORMAT: &str = "zk-insurance-proof-bundle/1";
+
+/// Signatures over one saved bundle, kept next to it as `signatures_<id>.json`. Signatures are
+/// only ever appended, so a bundle stays verifiable under every key that has vouched for it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleSignatures {
+    /// sha256 of the proof and public inputs files exactly as stored (sealed, if encrypted),
+    /// so signatures can be checked without the tenant's data key
+    pub proof_sha256: String,
+    pub public_inputs_sha256: String,
+    pub signatures: Vec<BundleSignature>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleSignature {
+    pub signer: String,
+    /// EIP-191 signature over `bundle_message`
+    pub signature: String,
+    pub signed_at: DateTime<Utc>,
+}
+
+/// Outcome of a re-signing run, published as the signed `payload` of a manifest.
+#[derive(Debug, Serialize)]
+pub struct ResignReport {
+    pub signer: String,
+    pub started_at: DateTime<Utc>,
+    pub bundles: Vec<ResignedBundle>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResignedBundle {
+    pub id: String,
+    pub proof_sha256: String,
+    pub public_inputs_sha256: String,
+    /// Every key that has signed the bundle, oldest first
+    pub signers: Vec<String>,
+    /// `resigned`, `already_signed` or `failed`
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Re-signing manifest as written to disk: the report as the exact string that was signed.
+#[derive(Serialize)]
+struct SignedManifest {
+    payload: String,
+    signature: String,
+    signer: String,
+}
+
+/// A proof/public inputs pair saved by the TCP interface, identified by its timestamp.
+struct Bundle {
+    id: String,
+    proof: String,
+    public_inputs: String,
+}
+
+impl Bundle {
+    fn signatures_key(&self) -> String {
+        format!("signatures_{}.json", self.id)
+    }
+}
+
+/// Adds `signer`'s signature to a saved bundle, creating its signature file if needed. Returns
+/// false if the key had already signed it. Bundles whose files no longer match the recorded
+/// digests are refused rather than signed again.
+pub async fn sign(storage: &dyn Storage, id: &str, file_suffix: &str, signer: &EnclaveSigner) -> Result<bool> {
+    let bundle = Bundle {
+        id: id.to_string(),
+        proof: format!("proof_{}.hex{}", id, file_suffix),
+        public_inputs: format!("public_inputs_{}.txt{}", id, file_suffix),
+    };
+    Ok(sign_bundle(storage, &bundle, signer).await?.0)
+}
+
+async fn sign_bundle(storage: &dyn Storage, bundle: &Bundle, signer: &EnclaveSigner) -> Result<(bool, BundleSignatures)> {
+    let proof_sha256 = object_sha256(storage, &bundle.proof).await?;
+    let public_inputs_sha256 = object_sha256(storage, &bundle.public_inputs).await?;
+    let key = bundle.signatures_key();
+    let mut signatures = match storage.get(&key).await? {
+        Some(content) => {
+            let signatures: BundleSignatures = serde_json::from_slice(&content)
+                .with_context(|| format!("{} is not a valid signature file", storage.location(&key)))?;
+            if signatures.proof_sha256 != proof_sha256 || signatures.public_inputs_sha256 != public_inputs_sha256 {
+                bail!("Bundle files changed since they were signed");
+            }
+            signatures
+        }
+        None => BundleSignatures {
+            proof_sha256,
+            public_inputs_sha256,
+            signatures: Vec::new(),
+        },
+    };
+
+    let address = signer.address();
+    if signatures.signatures.iter().any(|signature| signature.signer == address) {
+        return Ok((false, signatures));
+    }
+    let message = bundle_message(&bundle.id, &signatures.proof_sha256, &signatures.public_inputs_sha256);
+    signatures.signatures.push(BundleSignature {
+        signature: signer.sign_message(message.as_bytes())?,
+        signer: address,
+        signed_at: Utc::now(),
+    });
+    storage.put(&key, serde_json::to_vec_pretty(&signatures)?).await?;
+    Ok((true, signatures))
+}
+
+/// Signs every bundle saved in `storage` with `signer`, keeping earlier signatures, and writes a
+/// manifest of the run signed by the same key. Returns the report and where the manifest is.
+pub async fn resign_all(storage: &dyn Storage, signer: &EnclaveSigner) -> Result<(ResignReport, String)> {
+    let started_at = Utc::now();
+    let mut report = ResignReport {
+        signer: signer.address(),
+        started_at,
+        bundles: Vec::new(),
+    };
+    for bundle in saved_bundles(storage).await? {
+        let entry = match sign_bundle(storage, &bundle, signer).await {
+            Ok((resigned, signatures)) => ResignedBundle {
+                id: bundle.id,
+                proof_sha256: signatures.proof_sha256,
+                public_inputs_sha256: signatures.public_inputs_sha256,
+                signers: signatures.signatures.into_iter().map(|signature| signature.signer).collect(),
+                status: if resigned { "resigned" } else { "already_signed" },
+                error: None,
+            },
+            Err(e) => ResignedBundle {
+                proof_sha256: object_sha256(storage, &bundle.proof).await.unwrap_or_default(),
+                public_inputs_sha256: object_sha256(storage, &bundle.public_inputs).await.unwrap_or_default(),
+                id: bundle.id,
+                signers: Vec::new(),
+                status: "failed",
+                error: Some(format!("{:#}", e)),
+            },
+        };
+        report.bundles.push(entry);
+    }
+
+    let payload = serde_json::to_string(&report)?;
+    let manifest = SignedManifest {
+        signature: signer.sign_message(payload.as_bytes())?,
+        signer: report.signer.clone(),
+        payload,
+    };
+    let key = format!("resign_manifest_{}.json", started_at.timestamp());
+    storage.put(&key, serde_json::to_vec_pretty(&manifest)?).await?;
+    Ok((report, storage.location(&key)))
+}
+
+/// `manifest.json` of a downloadable proof bundle.
+#[derive(Debug, Serialize)]
+pub struct ArchiveManifest {
+    pub format: &'static str,
+    pub job_id: String,
+    pub proof_id: Option<String>,
+    pub circuit_hash: Option<String>,
+    /// sha256 of `vk`; absent for ultra_plonk proofs, which are checked with the circuit
+    pub vk_hash: Option<String>,
+    pub scheme: Option<String>,
+    pub expires_at: Option<i64>,
+    pub created_at: String,
+    /// Enclave signer address that `enclave_signature.json` recovers to
+    pub signer: String,
+    /// The EIP-191 message `enclave_signature.json` signs, rebuilt from the bundled files
+    pub signed_message: Option<String>,
+    /// sha256 of every other file in the bundle
+    pub files: BTreeMap<String, String>,
+}
+
+/// Packs a stored proof with everything an insurer needs to check it into one gzipped tarball
+/// under `proof-<job_id>/`: the raw proof, its public inputs, the verification key (`vk`, as
+/// `bb verify -k` takes it), the enclave signature and a manifest listing their digests.
+pub fn archive(proof: &StoredProof, vk: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut files = vec![
+        ("proof", hex::decode(proof.proof_hex.trim_start_matches("0x")).context("Stored proof is not hex")?),
+        ("public_inputs.json", proof.public_inputs.clone().into_bytes()),
+    ];
+    if let Some(vk) = vk {
+        files.push(("vk", vk.to_vec()));
+    }
+    if let Some(signature) = &proof.enclave_signature {
+        files.push(("enclave_signature.json", serde_json::to_vec_pretty(signature)?));
+    }
+    let manifest = ArchiveManifest {
+        format: ARCHIVE_FORMAT,
+        job_id: proof.job_id.clone(),
+        proof_id: proof.proof_id.clone(),
+        circuit_hash: proof.circuit_hash.clone(),
+        vk_hash: vk.map(|vk| format!("0x{}", hex::encode(Sha256::digest(vk)))),
+        scheme: proof.scheme.clone(),
+        expires_at: proof.expires_at,
+        created_at: proof.created_at.clone(),
+        signer: proof.node.clone(),
+        signed_message: proof.enclave_signature.as_ref().map(|signature| {
+            prover::signing_message(proof.proof_id.as_deref(), &proof.public_inputs, proof.circuit_hash.as_deref(), signature.signed_at)
+        }),
+        files: files
+            .iter()
+            .map(|(name, bytes)| (name.to_string(), format!("0x{}", hex::encode(Sha256::digest(bytes)))))
+            .collect(),
+    };
+    files.push(("manifest.json", serde_json::to_vec_pretty(&manifest)?));
+
+    // Dated by the proof rather than the download, so the same proof always packs the same way
+    let mtime = DateTime::parse_from_rfc3339(&proof.created_at).map_or(0, |created_at| created_at.timestamp().max(0) as u64);
+    let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, bytes) in &files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        tar.append_data(&mut header, format!("proof-{}/{}", proof.job_id, name), bytes.as_slice())?;
+    }
+    Ok(tar.into_inner()?.finish()?)
+}
+
+/// The verification key with `vk_hash` for a proof by `circuit_id` (the eligibility circuit if
+/// None), as bundled; None once that circuit has been swapped for another.
+pub async fn verification_key(verifier: &Verifier, circuit_id: Option<&str>, vk_hash: &str) -> Result<Option<Vec<u8>>> {
+    let vk = match circuit_id {
+        Some(id) => verifier.circuit_verification_key(id).await?,
+        None => Some(verifier.verification_key().await?),
+    };
+    vk.filter(|vk| vk.vk_hash == vk_hash)
+        .map(|vk| hex::decode(vk.vk_hex.trim_start_matches("0x")).context("Verification key is not hex"))
+        .transpose()
+}
+
+/// Adds the bundle of each successful proof to IPFS (--ipfs-publish-api), giving relying parties
+/// a content-addressed reference they can cite on-chain. The bundle is the one
+/// GET /proofs/{job_id}/bundle serves, byte for byte, so both have the same CID.
+pub struct BundlePublisher {
+    ipfs: IpfsClient,
+    verifier: Arc<Verifier>,
+}
+
+impl BundlePublisher {
+    pub fn new(ipfs: IpfsClient, verifier: Arc<Verifier>) -> Self {
+        Self { ipfs, verifier }
+    }
+
+    /// Adds and pins the bundle of `proof`, returning its CID.
+    pub async fn publish(&self, proof: &StoredProof) -> Result<String> {
+        let vk = match &proof.vk_hash {
+            Some(vk_hash) => Some(
+                verification_key(&self.verifier, proof.circuit_id.as_deref(), vk_hash)
+                    .await?
+                    .with_context(|| format!("Verification key {} is no longer active", vk_hash))?,
+            ),
+            None => None,
+        };
+        let archive = archive(proof, vk.as_deref())?;
+        self.ipfs.add(&format!("proof-{}.tar.gz", proof.job_id), archive).await
+    }
+}
+
+/// Bundles in `storage` with both of their files present, sealed or not.
+async fn saved_bundles(storage: &dyn Storage) -> Result<Vec<Bundle>> {
+    let public_inputs: HashSet<String> = storage.list("public_inputs_").await?.into_iter().collect();
+    let mut bundles = Vec::new();
+    for name in storage.list("proof_").await? {
+        let Some((id, suffix)) = name.strip_prefix("proof_").and_then(|rest| rest.split_once(".hex")) else {
+            continue;
+        };
+        let inputs = format!("public_inputs_{}.txt{}", id, suffix);
+        if public_inputs.contains(&inputs) {
+            bundles.push(Bundle {
+                id: id.to_string(),
+                proof: name.clone(),
+                public_inputs: inputs,
+            });
+        }
+    }
+    bundles.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(bundles)
+}
+
+fn bundle_message(id: &str, proof_sha256: &str, public_inputs_sha256: &str) -> String {
+    format!(
+        "zk-insurance bundle\nid: {}\nproof_sha256: {}\npublic_inputs_sha256: {}",
+        id, proof_sha256, public_inputs_sha256
+    )
+}
+
+async fn object_sha256(storage: &dyn Storage, key: &str) -> Result<String> {
+    let bytes = storage.get(key).await?.with_context(|| format!("{} is missing", storage.location(key)))?;
+    Ok(format!("0x{}", hex::encode(Sha256::digest(&bytes))))
+}
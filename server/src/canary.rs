@@ -0,0 +1,120 @@
+use crate::metrics::Metrics;
+use crate::verifier::{Verifier, VerifyRequest};
+use crate::worker::WorkerPool;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+/// How long an alert webhook may take to answer.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Proves and verifies a synthetic applicant on an interval, so toolchain breakage (a bad bb
+/// upgrade, a missing SRS, a corrupted circuit) shows up in metrics and alerts before a
+/// customer's request hits it.
+pub struct CanaryMonitor {
+    pool: Arc<WorkerPool>,
+    verifier: Arc<Verifier>,
+    metrics: Arc<Metrics>,
+    http: reqwest::Client,
+    /// Set when alerts are posted
+    webhook_url: Option<String>,
+    /// Consecutive failures before an alert goes out
+    alert_after: u32,
+    /// Identifies this node in alerts
+    node: String,
+}
+
+impl CanaryMonitor {
+    pub fn new(
+        pool: Arc<WorkerPool>,
+        verifier: Arc<Verifier>,
+        metrics: Arc<Metrics>,
+        http: reqwest::Client,
+        webhook_url: Option<String>,
+        alert_after: u32,
+        node: String,
+    ) -> Self {
+        Self {
+            pool,
+            verifier,
+            metrics,
+            http,
+            webhook_url,
+            alert_after: alert_after.max(1),
+            node,
+        }
+    }
+
+    pub async fn run(self, interval: Duration) {
+        let mut failures = 0;
+        let mut alerted = false;
+        loop {
+            tokio::time::sleep(interval).await;
+            let started = Instant::now();
+            let result = self.probe().await;
+            let seconds = started.elapsed().as_secs_f64();
+            self.metrics.record_canary(result.is_ok(), seconds);
+            match result {
+                Ok(()) => {
+                    debug!("Canary proof passed in {:.2}s", seconds);
+                    if alerted {
+                        info!("Canary proof passing again after {} failures", failures);
+                        self.alert("canary_recovered", "Canary proof is passing again", failures, seconds).await;
+                    }
+                    failures = 0;
+                    alerted = false;
+                }
+                Err(reason) => {
+                    failures += 1;
+                    warn!("Canary proof failed ({} in a row): {}", failures, reason);
+                    if !alerted && failures >= self.alert_after {
+                        self.alert("canary_failed", &reason, failures, seconds).await;
+                        alerted = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Proves the canary applicant and checks the proof verifies.
+    async fn probe(&self) -> Result<(), String> {
+        let response = self.pool.prove_canary().await.map_err(|e| format!("{:#}", e))?;
+        if !response.success {
+            return Err(format!("proving failed: {}", response.message));
+        }
+        let (proof, public_inputs) = VerifyRequest {
+            proof_hex: response.proof_hex,
+            public_inputs: response.public_inputs,
+            circuit_id: None,
+            nonce: None,
+        }
+        .decode()
+        .map_err(|e| format!("the proof is malformed: {:#}", e))?;
+        let verified = self.verifier.verify(None, &proof, &public_inputs).await.map_err(|e| format!("{:#}", e))?;
+        if !verified.valid {
+            return Err(format!("the proof does not verify: {}", verified.message));
+        }
+        Ok(())
+    }
+
+    /// Posts an alert to the webhook. `text` makes the body usable as a Slack incoming webhook
+    /// message as-is.
+    async fn alert(&self, event: &str, message: &str, consecutive_failures: u32, seconds: f64) {
+        let Some(url) = &self.webhook_url else { return };
+        let body = json!({
+            "event": event,
+            "node": self.node,
+            "message": message,
+            "consecutive_failures": consecutive_failures,
+            "duration_secs": seconds,
+            "at": chrono::Utc::now().to_rfc3339(),
+            "text": format!("[{}] {}: {}", self.node, event, message),
+        });
+        match self.http.post(url).timeout(WEBHOOK_TIMEOUT).json(&body).send().await {
+            Ok(response) if response.status().is_success() => info!("Sent {} alert", event),
+            Ok(response) => error!("Alert webhook rejected {}: {}", event, response.status()),
+            Err(e) => error!("Alert webhook unreachable for {}: {}", event, e),
+        }
+    }
+}
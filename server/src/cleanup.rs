@@ -0,0 +1,202 @@
+use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// Files a job may still be using are never touched before this age, whatever the thresholds
+const IN_FLIGHT_GRACE: Duration = Duration::from_secs(3600);
+
+/// Prefixes of per-job artifacts under a circuit's target/ directory. Compiled circuits and
+/// keys live next to them and are never matched.
+const JOB_ARTIFACT_PREFIXES: &[&str] = &["witness_", "proof_", "recursive_", "aggregate_", "verify_", "vk_"];
+
+/// Prefixes of bundles saved by the TCP interface, and of their signature files.
+const SAVED_OUTPUT_PREFIXES: &[&str] = &["proof_", "public_inputs_", "signatures_"];
+
+/// How long artifacts are kept, how much disk they may use and how many saved bundles are kept.
+/// A `None` threshold is not enforced.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_bytes: Option<u64>,
+    pub max_bundles: Option<usize>,
+}
+
+impl RetentionPolicy {
+    /// Builds a policy from CLI values where 0 disables a threshold.
+    pub fn new(max_age_hours: u64, max_mb: u64, max_bundles: usize) -> Self {
+        Self {
+            max_age: (max_age_hours > 0).then(|| Duration::from_secs(max_age_hours * 3600)),
+            max_bytes: (max_mb > 0).then(|| max_mb * 1024 * 1024),
+            max_bundles: (max_bundles > 0).then_some(max_bundles),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_bytes.is_some() || self.max_bundles.is_some()
+    }
+}
+
+struct Candidate {
+    path: PathBuf,
+    modified: SystemTime,
+    bytes: u64,
+}
+
+/// Periodically prunes leftover job artifacts from circuit target/ directories and saved proof
+/// bundles, oldest first, until the age, disk usage and bundle count thresholds are all met.
+pub struct Janitor {
+    policy: RetentionPolicy,
+    /// Circuit packages whose target/ holds per-job files; resolved on every sweep since the
+    /// active release can change
+    circuit_dirs: Vec<PathBuf>,
+    /// Where saved bundles are kept, when that is local disk
+    output_dir: Option<PathBuf>,
+}
+
+impl Janitor {
+    pub fn new(policy: RetentionPolicy, circuit_dirs: Vec<PathBuf>, output_dir: Option<PathBuf>) -> Self {
+        Self {
+            policy,
+            circuit_dirs,
+            output_dir,
+        }
+    }
+
+    pub async fn run(self, interval: Duration) {
+        loop {
+            match self.sweep() {
+                Ok((0, _)) => {}
+                Ok((removed, bytes)) => info!("Cleanup removed {} artifacts ({} bytes)", removed, bytes),
+                Err(e) => warn!("Artifact cleanup failed: {:#}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Returns how many artifacts were removed and how many bytes that freed.
+    fn sweep(&self) -> Result<(usize, u64)> {
+        let mut candidates = Vec::new();
+        for circuit_dir in &self.circuit_dirs {
+            let circuit_dir = fs::canonicalize(circuit_dir).unwrap_or_else(|_| circuit_dir.clone());
+            collect(&circuit_dir.join("target"), JOB_ARTIFACT_PREFIXES, &mut candidates)?;
+            collect(&circuit_dir, &["Prover_"], &mut candidates)?;
+        }
+        let (mut removed, mut freed) = (0, 0);
+        if let Some(output_dir) = &self.output_dir {
+            let mut saved = Vec::new();
+            collect(output_dir, SAVED_OUTPUT_PREFIXES, &mut saved)?;
+            if let Some(max_bundles) = self.policy.max_bundles {
+                (removed, freed) = prune_bundles(&mut saved, max_bundles);
+            }
+            candidates.extend(saved);
+        }
+        candidates.sort_by_key(|candidate| candidate.modified);
+
+        let now = SystemTime::now();
+        let mut total: u64 = candidates.iter().map(|candidate| candidate.bytes).sum();
+        for candidate in candidates {
+            let age = now.duration_since(candidate.modified).unwrap_or_default();
+            if age < IN_FLIGHT_GRACE {
+                // Sorted oldest first, so everything after this is newer still
+                break;
+            }
+            let expired = self.policy.max_age.is_some_and(|max_age| age > max_age);
+            let over_quota = self.policy.max_bytes.is_some_and(|max_bytes| total > max_bytes);
+            if !expired && !over_quota {
+                continue;
+            }
+
+            let result = if candidate.path.is_dir() {
+                fs::remove_dir_all(&candidate.path)
+            } else {
+                fs::remove_file(&candidate.path)
+            };
+            match result {
+                Ok(()) => {
+                    total -= candidate.bytes;
+                    removed += 1;
+                    freed += candidate.bytes;
+                }
+                Err(e) => warn!("Failed to remove {}: {}", candidate.path.display(), e),
+            }
+        }
+        Ok((removed, freed))
+    }
+}
+
+/// Removes the files of every saved bundle but the newest `max_bundles`, leaving the files of
+/// those kept in `saved`. Returns how many files were removed and how many bytes that freed.
+fn prune_bundles(saved: &mut Vec<Candidate>, max_bundles: usize) -> (usize, u64) {
+    let mut newest: HashMap<String, SystemTime> = HashMap::new();
+    for candidate in saved.iter() {
+        let modified = newest.entry(bundle_id(&candidate.path)).or_insert(candidate.modified);
+        *modified = (*modified).max(candidate.modified);
+    }
+    let mut bundles: Vec<_> = newest.into_iter().collect();
+    bundles.sort_by_key(|(_, modified)| Reverse(*modified));
+    let excess: HashSet<String> = bundles.into_iter().skip(max_bundles).map(|(id, _)| id).collect();
+
+    let (mut removed, mut freed) = (0, 0);
+    let mut kept = Vec::new();
+    for candidate in saved.drain(..) {
+        if !excess.contains(&bundle_id(&candidate.path)) {
+            kept.push(candidate);
+            continue;
+        }
+        match fs::remove_file(&candidate.path) {
+            Ok(()) => {
+                removed += 1;
+                freed += candidate.bytes;
+            }
+            Err(e) => warn!("Failed to remove {}: {}", candidate.path.display(), e),
+        }
+    }
+    *saved = kept;
+    (removed, freed)
+}
+
+/// The timestamp a saved bundle's files are named by, e.g. `1718000000` for `proof_1718000000.hex`.
+fn bundle_id(path: &Path) -> String {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let rest = SAVED_OUTPUT_PREFIXES.iter().find_map(|prefix| name.strip_prefix(prefix)).unwrap_or(&name);
+    rest.split('.').next().unwrap_or_default().to_string()
+}
+
+fn collect(dir: &Path, prefixes: &[&str], candidates: &mut Vec<Candidate>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !prefixes.iter().any(|prefix| name.starts_with(prefix)) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        candidates.push(Candidate {
+            path: entry.path(),
+            modified: metadata.modified()?,
+            bytes: disk_usage(&entry.path())?,
+        });
+    }
+    Ok(())
+}
+
+fn disk_usage(path: &Path) -> Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += disk_usage(&entry?.path())?;
+    }
+    Ok(total)
+}
@@ -0,0 +1,167 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Keys that hold an applicant's inputs. Neither log records them; a report drops them wherever
+/// they appear all the same, so no event added later can carry them into an export.
+const PRIVATE_FIELDS: &[&str] = &[
+    "age",
+    "bmi_multiplied",
+    "height_cm",
+    "weight_kg",
+    "date_of_birth",
+    "smoker",
+    "systolic_bp",
+    "diastolic_bp",
+    "conditions",
+    "inputs",
+    "request",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Report {
+    /// Security and compliance events from the audit log
+    Audit,
+    /// Every proof request and its outcome, from the issuance log
+    Proofs,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// Comma-separated values with a header row; nested values are written as JSON
+    #[default]
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+/// Window of a report, by entry timestamp; `from` is inclusive and `to` exclusive.
+#[derive(Debug, Default, Deserialize)]
+pub struct ReportRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl Report {
+    /// The fields a row holds, in column order. Rows are built from these alone, so a field added
+    /// to a log reaches reports only once it is listed here.
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            Report::Audit => &["timestamp", "event", "details"],
+            Report::Proofs => &[
+                "seq",
+                "timestamp",
+                "request_id",
+                "request_hash",
+                "circuit_hash",
+                "proof_hash",
+                "success",
+                "error_code",
+                "hash",
+                "prev_hash",
+            ],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Report::Audit => "audit",
+            Report::Proofs => "proofs",
+        }
+    }
+}
+
+impl ReportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "text/csv; charset=utf-8",
+            ReportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl ReportRange {
+    /// Entries whose timestamp cannot be read are only in unbounded reports.
+    fn contains(&self, entry: &Value) -> bool {
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        let Some(timestamp) = entry
+            .get("timestamp")
+            .and_then(Value::as_str)
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        else {
+            return false;
+        };
+        self.from.is_none_or(|from| timestamp >= from) && self.to.is_none_or(|to| timestamp < to)
+    }
+}
+
+/// Renders the log `entries` in `range` as `report`, oldest first. Returns the report and how
+/// many rows it holds.
+pub fn render<T: Serialize>(report: Report, format: ReportFormat, range: &ReportRange, entries: &[T]) -> Result<(String, usize)> {
+    let columns = report.columns();
+    let mut out = String::new();
+    if format == ReportFormat::Csv {
+        out.push_str(&columns.join(","));
+        out.push('\n');
+    }
+    let mut rows = 0;
+    for entry in entries {
+        let entry = serde_json::to_value(entry)?;
+        if !range.contains(&entry) {
+            continue;
+        }
+        let mut row = Map::new();
+        for column in columns {
+            let mut value = entry.get(*column).cloned().unwrap_or(Value::Null);
+            strip_private(&mut value);
+            row.insert(column.to_string(), value);
+        }
+        match format {
+            ReportFormat::Csv => out.push_str(&columns.iter().map(|column| csv_cell(&row[*column])).collect::<Vec<_>>().join(",")),
+            ReportFormat::Jsonl => out.push_str(&Value::Object(row).to_string()),
+        }
+        out.push('\n');
+        rows += 1;
+    }
+    Ok((out, rows))
+}
+
+fn strip_private(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|key, _| !PRIVATE_FIELDS.contains(&key.as_str()));
+            fields.values_mut().for_each(strip_private);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_private),
+        _ => {}
+    }
+}
+
+/// One CSV field, quoted as RFC 4180 requires. Text that a spreadsheet would read as a formula
+/// is prefixed with `'`, since these files are opened by people outside the team.
+fn csv_cell(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(text) if text.starts_with(['=', '+', '-', '@']) => format!("'{}", text),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
@@ -0,0 +1,266 @@
+use crate::apikeys::ApiKey;
+use crate::policy::{Bounds, Eligibility, Policy};
+use crate::prover::{self, ProvingScheme};
+use crate::tenants::Tenant;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Settings that can change while the server is running, e.g. when the fleet control channel
+/// activates a new release. Components read them per job instead of copying them at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeConfig {
+    pub default_scheme: ProvingScheme,
+    pub max_batch_size: usize,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown_secs: u64,
+    /// Proving-time ceiling for requests without a policy-specific one (0 = none)
+    #[serde(default)]
+    pub max_proving_secs: u64,
+    /// Proving-time ceilings by policy id
+    #[serde(default)]
+    pub policy_max_proving_secs: BTreeMap<String, u64>,
+    /// Chains proofs can be submitted to, by name
+    #[serde(default)]
+    pub chains: BTreeMap<String, ChainConfig>,
+    /// Eligibility rules by policy id; requests for other policies use the default bounds
+    #[serde(default)]
+    pub policies: BTreeMap<String, Policy>,
+    /// Ranges proven for requests without a configured policy
+    #[serde(default)]
+    pub default_bounds: Bounds,
+    /// Jobs that may wait for a worker before new ones are turned away as server_busy (0 = no limit)
+    #[serde(default)]
+    pub max_queue: usize,
+    /// Seconds busy clients are told to wait before retrying
+    #[serde(default = "default_retry_after_secs")]
+    pub retry_after_secs: u64,
+    /// Proofs generated concurrently
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    /// Proofs each client address may request per minute (0 = no limit)
+    #[serde(default)]
+    pub rate_limit: u32,
+    /// Proofs a client may request at once before `rate_limit` applies
+    #[serde(default = "default_rate_limit_burst")]
+    pub rate_limit_burst: u32,
+    /// Seconds a TCP client has to answer each prompt (0 = no limit)
+    #[serde(default = "default_read_timeout_secs")]
+    pub read_timeout_secs: u64,
+    /// Seconds a TCP client may send nothing while a prompt is waiting (0 = no limit)
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// Seconds a proof stays valid for, proven as its `expires_at` public input
+    #[serde(default = "default_proof_validity_secs")]
+    pub proof_validity_secs: u64,
+    /// Each API key allowed to request proofs, with its quotas, by key name; empty lets anyone
+    #[serde(default)]
+    pub api_keys: BTreeMap<String, ApiKey>,
+    /// Partners sharing the deployment, by tenant ID, with what each may prove
+    #[serde(default)]
+    pub tenants: BTreeMap<String, Tenant>,
+}
+
+fn default_retry_after_secs() -> u64 {
+    5
+}
+
+fn default_workers() -> usize {
+    2
+}
+
+fn default_rate_limit_burst() -> u32 {
+    10
+}
+
+fn default_read_timeout_secs() -> u64 {
+    120
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    30
+}
+
+fn default_proof_validity_secs() -> u64 {
+    prover::DEFAULT_PROOF_VALIDITY_SECS
+}
+
+/// The ranges proofs from this server are checked against, as served by GET /parameters.
+#[derive(Debug, Clone, Serialize)]
+pub struct EligibilityParameters {
+    /// Ranges for requests without a policy
+    pub default_bounds: Bounds,
+    /// Alternatives each policy may be proven with, by policy id
+    pub policies: BTreeMap<String, Vec<Bounds>>,
+    /// Tiers a request may pick with `tier`, best first, by policy id
+    pub tiers: BTreeMap<String, Vec<TierParameters>>,
+    /// Every set of bounds a proof may carry as public inputs
+    pub accepted_bounds: Vec<Bounds>,
+}
+
+/// One tier of a tiered policy and the alternatives a proof in it may carry.
+#[derive(Debug, Clone, Serialize)]
+pub struct TierParameters {
+    pub name: String,
+    pub bounds: Vec<Bounds>,
+}
+
+/// A deployed verifier contract and how to reach it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    pub rpc_url: String,
+    /// Empty until a verifier has been deployed (see `deploy-verifier`)
+    #[serde(default)]
+    pub verifier_address: String,
+    /// Fixed gas limit instead of estimating it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_limit: Option<u64>,
+    /// Fee caps instead of the node's suggestions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas_gwei: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas_gwei: Option<u64>,
+}
+
+pub type SharedConfig = Arc<RwLock<RuntimeConfig>>;
+
+impl RuntimeConfig {
+    /// How long a job for `policy` may spend proving before it is cancelled.
+    pub fn proving_ceiling(&self, policy: Option<&str>) -> Option<Duration> {
+        let secs = policy
+            .and_then(|policy| self.policy_max_proving_secs.get(policy))
+            .copied()
+            .unwrap_or(self.max_proving_secs);
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    /// Unix time a proof made now expires at.
+    pub fn proof_expiry(&self) -> u64 {
+        prover::expiry_from_now(self.proof_validity_secs)
+    }
+
+    /// Bounds and tier an applicant of `tenant` is proven against under `policy`, limited to
+    /// `tier` if given.
+    pub fn eligibility(&self, tenant: Option<&str>, policy: Option<&str>, tier: Option<&str>, age: u32, bmi_multiplied: u32) -> Eligibility {
+        match policy.and_then(|policy| self.policies.get(policy)) {
+            Some(policy) => policy.resolve(tier, age, bmi_multiplied),
+            None => Eligibility {
+                tier: None,
+                bounds: self.tenant_bounds(tenant),
+            },
+        }
+    }
+
+    /// Ranges proven for `tenant`'s requests without a configured policy.
+    pub fn tenant_bounds(&self, tenant: Option<&str>) -> Bounds {
+        tenant
+            .and_then(|tenant| self.tenants.get(tenant))
+            .and_then(|tenant| tenant.default_bounds)
+            .unwrap_or(self.default_bounds)
+    }
+
+    /// Rejects a circuit or policy that `tenant` may not prove with. Tenants missing from the
+    /// table (and the default tenant) may use all of them.
+    pub fn check_tenant(&self, tenant: Option<&str>, circuit_id: Option<&str>, policy: Option<&str>) -> Result<(), String> {
+        match tenant.and_then(|tenant| self.tenants.get(tenant)) {
+            Some(settings) => settings.check(circuit_id, policy),
+            None => Ok(()),
+        }
+    }
+
+    /// Rejects a requested tier that `policy` does not define. A tier without a policy is left to
+    /// intake validation.
+    pub fn check_tier(&self, policy: Option<&str>, tier: Option<&str>) -> Result<(), String> {
+        let (Some(policy), Some(tier)) = (policy, tier) else {
+            return Ok(());
+        };
+        let tiers: Vec<_> = self
+            .policies
+            .get(policy)
+            .map(|policy| policy.tiers().map(|(name, _)| name).collect())
+            .unwrap_or_default();
+        if tiers.contains(&tier) {
+            Ok(())
+        } else if tiers.is_empty() {
+            Err(format!("Policy '{}' has no tiers to choose from", policy))
+        } else {
+            Err(format!("Unknown tier '{}' (policy tiers: {})", tier, tiers.join(", ")))
+        }
+    }
+
+    /// Every set of bounds a proof from this server may carry.
+    pub fn accepted_bounds(&self) -> Vec<Bounds> {
+        let mut accepted = vec![self.default_bounds];
+        let tenant_bounds = self.tenants.values().filter_map(|tenant| tenant.default_bounds.as_ref());
+        for bounds in self.policies.values().flat_map(|policy| policy.variants()).chain(tenant_bounds) {
+            if !accepted.contains(bounds) {
+                accepted.push(*bounds);
+            }
+        }
+        accepted
+    }
+
+    pub fn parameters(&self) -> EligibilityParameters {
+        EligibilityParameters {
+            default_bounds: self.default_bounds,
+            policies: self
+                .policies
+                .iter()
+                .map(|(id, policy)| (id.clone(), policy.variants().copied().collect()))
+                .collect(),
+            tiers: self
+                .policies
+                .iter()
+                .filter(|(_, policy)| policy.tiers().next().is_some())
+                .map(|(id, policy)| {
+                    let tiers = policy
+                        .tiers()
+                        .map(|(name, variants)| TierParameters {
+                            name: name.to_string(),
+                            bounds: variants.to_vec(),
+                        })
+                        .collect();
+                    (id.clone(), tiers)
+                })
+                .collect(),
+            accepted_bounds: self.accepted_bounds(),
+        }
+    }
+
+    /// The chain named `name`, or the only configured chain if no name is given.
+    pub fn chain(&self, name: Option<&str>) -> anyhow::Result<(String, ChainConfig)> {
+        let names = || self.chains.keys().cloned().collect::<Vec<_>>().join(", ");
+        match name {
+            Some(name) => match self.chains.get(name) {
+                Some(chain) => Ok((name.to_string(), chain.clone())),
+                None => anyhow::bail!("Unknown chain '{}' (configured: {})", name, names()),
+            },
+            None if self.chains.is_empty() => anyhow::bail!("No chains are configured"),
+            None if self.chains.len() == 1 => {
+                let (name, chain) = self.chains.iter().next().expect("one chain");
+                Ok((name.clone(), chain.clone()))
+            }
+            None => anyhow::bail!("Select a chain with \"chain\" (configured: {})", names()),
+        }
+    }
+
+    /// Returns a copy with the keys present in `overlay` replaced; unknown keys and a proof
+    /// validity out of range are an error.
+    pub fn merged(&self, overlay: &serde_json::Map<String, serde_json::Value>) -> anyhow::Result<Self> {
+        let mut value = serde_json::to_value(self)?;
+        if let serde_json::Value::Object(fields) = &mut value {
+            for (key, overlay_value) in overlay {
+                fields.insert(key.clone(), overlay_value.clone());
+            }
+        }
+        let merged: Self = serde_json::from_value(value)?;
+        if !(1..=prover::MAX_PROOF_VALIDITY_SECS).contains(&merged.proof_validity_secs) {
+            anyhow::bail!("proof_validity_secs must be between 1 and {}", prover::MAX_PROOF_VALIDITY_SECS);
+        }
+        Ok(merged)
+    }
+}
@@ -0,0 +1,399 @@
+use crate::config::SharedConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const SIGNATURE_FILE: &str = "manifest.json.sig";
+const HISTORY_FILE: &str = "history.jsonl";
+/// History entry of the circuit shipped with the image, active until the first release
+const BUNDLED_VERSION: &str = "bundled";
+
+/// A release published by the fleet registry. The manifest bytes are signed as a whole, so the
+/// artifact hashes it lists are covered by the signature too.
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    version: String,
+    /// Partial RuntimeConfig applied when the release is activated
+    #[serde(default)]
+    config: serde_json::Map<String, serde_json::Value>,
+    /// Files making up the circuit package, laid out relative to the package root
+    #[serde(default)]
+    circuit: Vec<Artifact>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    path: String,
+    url: String,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Activation {
+    version: String,
+    path: PathBuf,
+    activated_at: DateTime<Utc>,
+}
+
+/// Which circuit package was active when. Release directories are kept after they are replaced,
+/// so eligibility disputes can be re-proven under the rules in force at the time.
+#[derive(Debug, Clone)]
+pub struct ReleaseHistory {
+    state_dir: PathBuf,
+}
+
+impl ReleaseHistory {
+    pub fn new(state_dir: PathBuf) -> Self {
+        Self { state_dir }
+    }
+
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    fn record(&self, version: &str, path: &Path) -> Result<()> {
+        let entry = Activation {
+            version: version.to_string(),
+            path: path.to_path_buf(),
+            activated_at: Utc::now(),
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.state_dir.join(HISTORY_FILE))?;
+        std::io::Write::write_all(&mut file, line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Highest release version ever activated from the registry (see `compare_versions`).
+    fn highest_version(&self) -> Result<Option<String>> {
+        let content = match fs::read_to_string(self.state_dir.join(HISTORY_FILE)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Failed to read the release history"),
+        };
+        let mut highest: Option<String> = None;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let entry: Activation = serde_json::from_str(line).context("Corrupt release history")?;
+            // The bundled circuit is not a registry release
+            if entry.version.is_empty() || entry.version == BUNDLED_VERSION {
+                continue;
+            }
+            if highest.as_deref().is_none_or(|highest| compare_versions(&entry.version, highest) == Ordering::Greater) {
+                highest = Some(entry.version);
+            }
+        }
+        Ok(highest)
+    }
+
+    /// The release that was active at `as_of`, as (version, circuit directory).
+    pub fn resolve(&self, as_of: DateTime<Utc>) -> Result<(String, PathBuf)> {
+        let content = fs::read_to_string(self.state_dir.join(HISTORY_FILE)).context("No release history recorded")?;
+        let mut active = None;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let entry: Activation = serde_json::from_str(line).context("Corrupt release history")?;
+            if entry.activated_at > as_of {
+                break;
+            }
+            active = Some(entry);
+        }
+        let entry = active.ok_or_else(|| anyhow!("No circuit release was active at {}", as_of.to_rfc3339()))?;
+        if !entry.path.exists() {
+            bail!("Circuit release {} active at {} is no longer available", entry.version, as_of.to_rfc3339());
+        }
+        Ok((entry.version, entry.path))
+    }
+}
+
+/// Pulls signed releases (configuration plus circuit package) from a registry and activates them
+/// atomically, so an operator can manage a fleet of enclaves without shell access to any of them.
+///
+/// Releases are staged under `<state_dir>/releases/<version>` and activated by swapping the
+/// `<state_dir>/current` symlink, which the prover uses as its circuit directory.
+pub struct ControlClient {
+    registry_url: String,
+    pinned_keys: Vec<VerifyingKey>,
+    state_dir: PathBuf,
+    config: SharedConfig,
+    http: reqwest::Client,
+    /// Signalled after a new release has been activated
+    activated: Arc<Notify>,
+    history: ReleaseHistory,
+}
+
+impl ControlClient {
+    pub fn new(
+        http: reqwest::Client,
+        registry_url: String,
+        pinned_keys: &[String],
+        state_dir: PathBuf,
+        config: SharedConfig,
+    ) -> Result<Self> {
+        if pinned_keys.is_empty() {
+            bail!("The control channel needs at least one pinned signing key (--control-pinned-key)");
+        }
+        let pinned_keys = pinned_keys
+            .iter()
+            .map(|key| {
+                let bytes: [u8; 32] = hex::decode(key.trim_start_matches("0x"))
+                    .context("Pinned key is not valid hex")?
+                    .try_into()
+                    .map_err(|_| anyhow!("Pinned key must be a 32-byte ed25519 public key"))?;
+                VerifyingKey::from_bytes(&bytes).context("Pinned key is not a valid ed25519 public key")
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            registry_url: registry_url.trim_end_matches('/').to_string(),
+            pinned_keys,
+            history: ReleaseHistory::new(state_dir.clone()),
+            state_dir,
+            config,
+            http,
+            activated: Arc::new(Notify::new()),
+        })
+    }
+
+    pub fn history(&self) -> ReleaseHistory {
+        self.history.clone()
+    }
+
+    /// Notified each time a new release becomes active, e.g. to regenerate derived artifacts.
+    pub fn activations(&self) -> Arc<Notify> {
+        self.activated.clone()
+    }
+
+    /// Directory the prover should use as its circuit package: the active release.
+    pub fn current_dir(&self) -> PathBuf {
+        self.state_dir.join("current")
+    }
+
+    /// Points `current` at the bundled circuit on first boot, and re-applies the configuration
+    /// of the already active release (re-verifying its signature) on restarts.
+    pub fn init(&self, bundled_circuit: &Path) -> Result<()> {
+        fs::create_dir_all(self.state_dir.join("releases"))?;
+        let current = self.current_dir();
+
+        if fs::symlink_metadata(&current).is_err() {
+            let target = fs::canonicalize(bundled_circuit)
+                .with_context(|| format!("Bundled circuit not found at {}", bundled_circuit.display()))?;
+            swap_symlink(&target, &current)?;
+            self.history.record(BUNDLED_VERSION, &target)?;
+            return Ok(());
+        }
+
+        // State directories from before history was kept: start it from the active release
+        if !self.state_dir.join(HISTORY_FILE).exists() {
+            let target = fs::canonicalize(&current)?;
+            self.history.record(&self.active_version().unwrap_or_default(), &target)?;
+        }
+
+        if let Some(version) = self.reapply()? {
+            // stderr, since --once keeps stdout for the proof response
+            info!("Control channel: resumed release {}", version);
+        }
+        Ok(())
+    }
+
+    /// Applies the configuration of the active release again, re-verifying its signature, and
+    /// returns its version. Nothing is applied while the bundled circuit is active.
+    pub fn reapply(&self) -> Result<Option<String>> {
+        let current = self.current_dir();
+        let manifest_path = current.join(MANIFEST_FILE);
+        if !manifest_path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&manifest_path)?;
+        let signature = fs::read_to_string(current.join(SIGNATURE_FILE))?;
+        self.verify(&bytes, &signature)?;
+        let manifest: Manifest = serde_json::from_slice(&bytes)?;
+        self.apply_config(&manifest)?;
+        Ok(Some(manifest.version))
+    }
+
+    pub async fn run(self, poll_interval: Duration) {
+        loop {
+            if let Err(e) = self.sync().await {
+                warn!("Control channel sync failed: {:#}", e);
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    async fn sync(&self) -> Result<()> {
+        let manifest_bytes = self.fetch(&format!("{}/{}", self.registry_url, MANIFEST_FILE)).await?;
+        let signature = self.fetch(&format!("{}/{}", self.registry_url, SIGNATURE_FILE)).await?;
+        let signature = String::from_utf8(signature).context("Manifest signature is not text")?;
+        self.verify(&manifest_bytes, &signature)?;
+
+        let manifest: Manifest = serde_json::from_slice(&manifest_bytes).context("Malformed release manifest")?;
+        validate_version(&manifest.version)?;
+        if self.active_version().as_deref() == Some(manifest.version.as_str()) {
+            return Ok(());
+        }
+        // A registry, or anyone replaying its old signed manifests, must not roll the fleet back
+        if let Some(highest) = self.history.highest_version()? {
+            if compare_versions(&manifest.version, &highest) == Ordering::Less {
+                bail!(
+                    "Release {} is older than release {}, which was already activated; publish a fix under a higher version",
+                    manifest.version,
+                    highest
+                );
+            }
+        }
+
+        // Validate the config before staging anything, so a bad release never half-applies
+        self.config.read().unwrap().merged(&manifest.config)?;
+
+        let release_dir = self.state_dir.join("releases").join(&manifest.version);
+        if !release_dir.exists() {
+            self.stage(&manifest, &manifest_bytes, &signature, &release_dir).await?;
+        }
+
+        let release_dir = fs::canonicalize(&release_dir)?;
+        swap_symlink(&release_dir, &self.current_dir())?;
+        self.history.record(&manifest.version, &release_dir)?;
+        self.apply_config(&manifest)?;
+        info!("Control channel: activated release {}", manifest.version);
+        self.activated.notify_waiters();
+        Ok(())
+    }
+
+    async fn stage(&self, manifest: &Manifest, manifest_bytes: &[u8], signature: &str, release_dir: &Path) -> Result<()> {
+        let staging_dir = release_dir.with_extension("staging");
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir)?;
+        }
+        fs::create_dir_all(&staging_dir)?;
+
+        for artifact in &manifest.circuit {
+            let relative = safe_relative_path(&artifact.path)?;
+            let bytes = self.fetch(&artifact.url).await?;
+            let digest = hex::encode(Sha256::digest(&bytes));
+            if !digest.eq_ignore_ascii_case(artifact.sha256.trim_start_matches("0x")) {
+                bail!("Artifact {} hash mismatch: expected {}, got {}", artifact.path, artifact.sha256, digest);
+            }
+
+            let destination = staging_dir.join(relative);
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&destination, bytes)?;
+        }
+
+        // Keep the signed manifest with the release so restarts can re-verify what is active
+        fs::write(staging_dir.join(MANIFEST_FILE), manifest_bytes)?;
+        fs::write(staging_dir.join(SIGNATURE_FILE), signature)?;
+        fs::rename(&staging_dir, release_dir)?;
+        Ok(())
+    }
+
+    fn apply_config(&self, manifest: &Manifest) -> Result<()> {
+        let mut config = self.config.write().unwrap();
+        *config = config.merged(&manifest.config)?;
+        Ok(())
+    }
+
+    fn active_version(&self) -> Option<String> {
+        let target = fs::read_link(self.current_dir()).ok()?;
+        target.file_name().map(|name| name.to_string_lossy().into_owned())
+    }
+
+    fn verify(&self, message: &[u8], signature_hex: &str) -> Result<()> {
+        let bytes: [u8; 64] = hex::decode(signature_hex.trim().trim_start_matches("0x"))
+            .context("Manifest signature is not valid hex")?
+            .try_into()
+            .map_err(|_| anyhow!("Manifest signature must be 64 bytes"))?;
+        let signature = Signature::from_bytes(&bytes);
+
+        if self.pinned_keys.iter().any(|key| key.verify(message, &signature).is_ok()) {
+            Ok(())
+        } else {
+            bail!("Release manifest is not signed by any pinned key")
+        }
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to fetch {}", url))?
+            .error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+fn validate_version(version: &str) -> Result<()> {
+    let valid = !version.is_empty()
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !version.starts_with('.');
+    if !valid {
+        bail!("Invalid release version '{}'", version);
+    }
+    Ok(())
+}
+
+/// Orders release versions with runs of digits compared as numbers, so 2025-06-01.10 follows
+/// 2025-06-01.9 and v10 follows v2.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn chunks(version: &str) -> Vec<&str> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for (i, c) in version.char_indices().skip(1) {
+            let previous = version[..i].chars().next_back().unwrap_or(c);
+            if previous.is_ascii_digit() != c.is_ascii_digit() {
+                chunks.push(&version[start..i]);
+                start = i;
+            }
+        }
+        chunks.push(&version[start..]);
+        chunks
+    }
+    for (a, b) in chunks(a).into_iter().zip(chunks(b)) {
+        let order = if a.starts_with(|c: char| c.is_ascii_digit()) && b.starts_with(|c: char| c.is_ascii_digit()) {
+            let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+            a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+        } else {
+            a.cmp(b)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Rejects absolute paths and `..` so a manifest can only write inside its release directory.
+fn safe_relative_path(path: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(path);
+    if path.components().all(|c| matches!(c, Component::Normal(_))) {
+        Ok(path)
+    } else {
+        bail!("Artifact path '{}' must be relative and stay inside the release", path.display())
+    }
+}
+
+/// Replaces `link` with a symlink to `target` in one rename, so readers never see it missing.
+pub fn swap_symlink(target: &Path, link: &Path) -> Result<()> {
+    let temporary = link.with_extension("next");
+    let _ = fs::remove_file(&temporary);
+    std::os::unix::fs::symlink(target, &temporary)?;
+    fs::rename(&temporary, link)?;
+    Ok(())
+}
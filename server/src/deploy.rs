@@ -0,0 +1,92 @@
+use crate::config::ChainConfig;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tokio::process::Command;
+
+/// Verifier contracts deployed by `deploy-verifier`, by chain name. Kept apart from the chains
+/// table so the operator's configuration is never rewritten.
+pub type Deployments = BTreeMap<String, Deployment>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deployment {
+    pub chain_id: u64,
+    pub verifier_address: String,
+    pub tx_hash: String,
+    /// keccak256 of the deployed source, as printed by write-verifier
+    pub source_keccak256: String,
+    pub deployed_at: DateTime<Utc>,
+}
+
+/// Compiles `source` with solc and returns the creation bytecode of `contract`.
+pub async fn compile(solc: &Path, source: &str, contract: &str) -> Result<Vec<u8>> {
+    let dir = std::env::temp_dir().join(format!("zk-verifier-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let source_path = dir.join("Verifier.sol");
+    fs::write(&source_path, source).with_context(|| format!("Failed to write {}", source_path.display()))?;
+
+    // The generated verifier is close to the contract size limit, so optimise for size
+    let output = Command::new(solc)
+        .args(["--optimize", "--optimize-runs", "1", "--combined-json", "bin"])
+        .arg(&source_path)
+        .output()
+        .await;
+    let _ = fs::remove_dir_all(&dir);
+    let output = output.with_context(|| format!("Failed to run {}", solc.display()))?;
+    if !output.status.success() {
+        bail!("solc failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    #[derive(Deserialize)]
+    struct Compiled {
+        bin: String,
+    }
+    #[derive(Deserialize)]
+    struct CombinedJson {
+        contracts: BTreeMap<String, Compiled>,
+    }
+    let combined: CombinedJson = serde_json::from_slice(&output.stdout).context("Unexpected solc output")?;
+    // Contracts are keyed by "<source path>:<name>"
+    let suffix = format!(":{}", contract);
+    let compiled = combined
+        .contracts
+        .iter()
+        .find(|(name, _)| name.ends_with(&suffix))
+        .map(|(_, compiled)| compiled)
+        .with_context(|| {
+            let names: Vec<&str> = combined.contracts.keys().filter_map(|name| name.rsplit(':').next()).collect();
+            format!("No contract '{}' in the verifier source (found: {})", contract, names.join(", "))
+        })?;
+    if compiled.bin.is_empty() {
+        bail!("Contract '{}' is abstract and cannot be deployed", contract);
+    }
+    hex::decode(&compiled.bin).context("solc returned invalid bytecode")
+}
+
+/// Reads the deployments file; a missing file means nothing has been deployed yet.
+pub fn load(path: &Path) -> Result<Deployments> {
+    if !path.exists() {
+        return Ok(Deployments::new());
+    }
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("{} is not a valid deployments file", path.display()))
+}
+
+pub fn save(path: &Path, deployments: &Deployments) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(deployments)?).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Points each chain at the verifier deployed on it, if any. Deployments made for a different
+/// chain id (the table entry was repointed since) are ignored.
+pub fn apply(chains: &mut BTreeMap<String, ChainConfig>, deployments: &Deployments) {
+    for (name, chain) in chains.iter_mut() {
+        if let Some(deployment) = deployments.get(name).filter(|deployment| deployment.chain_id == chain.chain_id) {
+            chain.verifier_address = deployment.verifier_address.clone();
+        }
+    }
+}
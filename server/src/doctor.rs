@@ -0,0 +1,143 @@
+use crate::config::SharedConfig;
+use crate::health::{self, Check, Preflight};
+use crate::prover::{NoirProver, ProofRequest};
+use crate::verifier::{Verifier, VerifyRequest};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+/// Files bb needs from its structured reference string; without them it downloads the SRS on
+/// the first proof, which fails in an enclave with no network.
+const SRS_FILES: [&str; 2] = ["bn254_g1.dat", "bn254_g2.dat"];
+
+/// Everything the `doctor` subcommand looks at.
+pub struct Doctor {
+    pub prover: NoirProver,
+    pub verifier: Arc<Verifier>,
+    pub config: SharedConfig,
+    /// Directories the server writes to
+    pub writable_dirs: Vec<PathBuf>,
+}
+
+impl Doctor {
+    /// Runs every check in order, ending with a smoke proof. Nothing stops early, so one run
+    /// lists every problem.
+    pub async fn run(&self) -> Vec<Check> {
+        let mut checks = Preflight::new(self.verifier.clone(), health::PROVER_TOOLS).run().await;
+        checks.push(Check::new("noir_version", self.noir_version().await));
+        checks.push(Check::new("circuit_freshness", self.circuit_freshness()));
+        checks.push(Check::new("srs", srs()));
+        for dir in &self.writable_dirs {
+            checks.push(Check::new("writable_dir", writable(dir)));
+        }
+        checks.push(Check::new("smoke_proof", self.smoke_proof().await));
+        checks
+    }
+
+    /// The circuit was compiled by the nargo that will execute it.
+    async fn noir_version(&self) -> Result<String, String> {
+        let nargo = health::tool_version("nargo").await?;
+        let artifact = self.verifier.circuit_path().join("target/insurance_verifier.json");
+        let compiled_with = fs::read(&artifact)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<serde_json::Value>(&content).ok())
+            .and_then(|artifact| artifact.get("noir_version")?.as_str().map(str::to_string))
+            .ok_or_else(|| format!("{} does not record a noir_version", artifact.display()))?;
+        // The artifact carries a `+<commit>` suffix that `nargo --version` prints separately
+        let release = compiled_with.split('+').next().unwrap_or_default();
+        if nargo.contains(release) {
+            Ok(format!("circuit compiled with {}", compiled_with))
+        } else {
+            Err(format!("circuit compiled with Noir {} but {} is installed; run nargo compile", compiled_with, nargo))
+        }
+    }
+
+    /// No circuit source is newer than the compiled artifact.
+    fn circuit_freshness(&self) -> Result<String, String> {
+        let circuit = self.verifier.circuit_path();
+        let artifact = circuit.join("target/insurance_verifier.json");
+        let compiled_at = modified(&artifact).ok_or_else(|| format!("{} is missing", artifact.display()))?;
+        let mut sources = vec![circuit.join("Nargo.toml")];
+        if let Ok(entries) = fs::read_dir(circuit.join("src")) {
+            sources.extend(entries.flatten().map(|entry| entry.path()));
+        }
+        let stale: Vec<String> = sources
+            .iter()
+            .filter(|source| modified(source).is_some_and(|changed| changed > compiled_at))
+            .map(|source| source.display().to_string())
+            .collect();
+        if stale.is_empty() {
+            Ok(format!("{} is up to date", artifact.display()))
+        } else {
+            Err(format!("changed since the last compile: {}; run nargo compile", stale.join(", ")))
+        }
+    }
+
+    /// Proves the canary applicant and verifies the result, as the first client request would.
+    async fn smoke_proof(&self) -> Result<String, String> {
+        let request = ProofRequest::canary(self.config.read().unwrap().default_bounds);
+        let started = Instant::now();
+        let response = self.prover.generate_proof(request).await.map_err(|e| format!("{:#}", e))?;
+        if !response.success {
+            return Err(format!("proving failed: {}", response.message));
+        }
+        let proved_in = started.elapsed();
+        let (proof, public_inputs) = VerifyRequest {
+            proof_hex: response.proof_hex,
+            public_inputs: response.public_inputs,
+            circuit_id: None,
+            nonce: None,
+        }
+        .decode()
+        .map_err(|e| format!("the proof is malformed: {:#}", e))?;
+        let verified = self.verifier.verify(None, &proof, &public_inputs).await.map_err(|e| format!("{:#}", e))?;
+        if !verified.valid {
+            return Err(format!("the proof does not verify: {}", verified.message));
+        }
+        Ok(format!("proved in {:.1?} and verified", proved_in))
+    }
+}
+
+/// Prints one line per check and a summary; returns whether every check passed.
+pub fn print_report(checks: &[Check]) -> bool {
+    for check in checks {
+        println!("[{}] {}: {}", if check.ok { "PASS" } else { "FAIL" }, check.name, check.detail);
+    }
+    let failed = checks.iter().filter(|check| !check.ok).count();
+    if failed == 0 {
+        println!("\nAll {} checks passed", checks.len());
+    } else {
+        println!("\n{} of {} checks failed", failed, checks.len());
+    }
+    failed == 0
+}
+
+/// bb's SRS is present under ~/.bb-crs.
+fn srs() -> Result<String, String> {
+    let home = std::env::var_os("HOME").ok_or("HOME is not set, so bb cannot locate ~/.bb-crs")?;
+    let dir = Path::new(&home).join(".bb-crs");
+    let missing: Vec<&str> = SRS_FILES.iter().copied().filter(|file| !dir.join(file).is_file()).collect();
+    if missing.is_empty() {
+        Ok(dir.display().to_string())
+    } else {
+        Err(format!("{} lacks {}; bb would download it on the first proof", dir.display(), missing.join(", ")))
+    }
+}
+
+/// `dir` accepts new files, or can be created when it does not exist yet.
+fn writable(dir: &Path) -> Result<String, String> {
+    let existing = dir.ancestors().find(|ancestor| ancestor.is_dir()).unwrap_or(Path::new("."));
+    let probe = existing.join(format!(".doctor-{}", uuid::Uuid::new_v4().simple()));
+    fs::write(&probe, b"").map_err(|e| format!("{} is not writable: {}", existing.display(), e))?;
+    let _ = fs::remove_file(&probe);
+    if existing == dir {
+        Ok(dir.display().to_string())
+    } else {
+        Ok(format!("{} (will be created)", dir.display()))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,175 @@
+use crate::signing::EnclaveSigner;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::time::Duration;
+
+/// `name` of the EIP-712 domain the enclave signs in.
+pub const DOMAIN_NAME: &str = "ZK Insurance Verifier";
+/// `version` of the EIP-712 domain; bumped whenever a signed struct changes.
+pub const DOMAIN_VERSION: &str = "1";
+
+const ELIGIBILITY_ATTESTATION_TYPE: &str =
+    "EligibilityAttestation(bytes32 applicantCommitment,bytes32 circuitHash,uint256 expiry)";
+
+const PROOF_PAYMENT_TYPE: &str = "ProofPayment(bytes32 depositId,bytes32 proofId,uint256 amount)";
+
+/// EIP-712 domain of the enclave's typed-data signatures. `chainId` and `verifyingContract` are
+/// only part of it when configured, so the domain matches whatever the consuming contract uses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Domain {
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verifying_contract: Option<Address>,
+}
+
+impl Domain {
+    pub fn new(chain_id: Option<u64>, verifying_contract: Option<Address>) -> Self {
+        Self {
+            name: DOMAIN_NAME.to_string(),
+            version: DOMAIN_VERSION.to_string(),
+            chain_id,
+            verifying_contract,
+        }
+    }
+
+    /// `hashStruct(EIP712Domain)`, with the type string listing only the fields present.
+    pub fn separator(&self) -> [u8; 32] {
+        let mut fields = vec!["string name", "string version"];
+        let mut encoded = vec![keccak(self.name.as_bytes()), keccak(self.version.as_bytes())];
+        if let Some(chain_id) = self.chain_id {
+            fields.push("uint256 chainId");
+            encoded.push(uint256(chain_id));
+        }
+        if let Some(contract) = self.verifying_contract {
+            fields.push("address verifyingContract");
+            let mut word = [0u8; 32];
+            word[12..].copy_from_slice(contract.as_bytes());
+            encoded.push(word);
+        }
+        let type_hash = keccak(format!("EIP712Domain({})", fields.join(",")).as_bytes());
+        keccak(&[&type_hash[..], &encoded.concat()].concat())
+    }
+
+    /// The digest that is signed for a struct: keccak256(0x1901 ‖ domainSeparator ‖ structHash).
+    pub fn digest(&self, struct_hash: [u8; 32]) -> [u8; 32] {
+        keccak(&[&[0x19, 0x01][..], &self.separator(), &struct_hash].concat())
+    }
+}
+
+/// The enclave's endorsement that an applicant proved eligibility under a circuit, signed as
+/// EIP-712 typed data so a contract can check it with `ecrecover` next to the ZK proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EligibilityAttestation {
+    /// `bytes32 applicantCommitment`
+    pub applicant_commitment: String,
+    /// `bytes32 circuitHash`: sha256 of the compiled circuit, as in `circuit_hash`
+    pub circuit_hash: String,
+    /// `uint256 expiry`: Unix time after which the endorsement should be refused
+    pub expiry: u64,
+    pub domain: Domain,
+    /// The EIP-712 digest that was signed
+    pub digest: String,
+    /// Ethereum address of the enclave key
+    pub signer: String,
+    /// 65 bytes r||s||v over `digest`
+    pub signature: String,
+}
+
+/// Issues `EligibilityAttestation`s in one domain with one lifetime.
+pub struct Attester {
+    domain: Domain,
+    ttl: Duration,
+}
+
+impl Attester {
+    pub fn new(domain: Domain, ttl: Duration) -> Self {
+        Self { domain, ttl }
+    }
+
+    pub fn attest(&self, signer: &EnclaveSigner, applicant_commitment: &str, circuit_hash: &str) -> Result<EligibilityAttestation> {
+        let commitment = parse_bytes32(applicant_commitment).context("Invalid applicant commitment")?;
+        let circuit = parse_bytes32(circuit_hash).context("Invalid circuit hash")?;
+        let expiry = Utc::now().timestamp() as u64 + self.ttl.as_secs();
+        let struct_hash = keccak(
+            &[
+                &keccak(ELIGIBILITY_ATTESTATION_TYPE.as_bytes())[..],
+                &commitment,
+                &circuit,
+                &uint256(expiry),
+            ]
+            .concat(),
+        );
+        let digest = self.domain.digest(struct_hash);
+        Ok(EligibilityAttestation {
+            applicant_commitment: format!("0x{}", hex::encode(commitment)),
+            circuit_hash: format!("0x{}", hex::encode(circuit)),
+            expiry,
+            domain: self.domain.clone(),
+            digest: format!("0x{}", hex::encode(digest)),
+            signer: signer.address(),
+            signature: signer.sign_digest(digest)?,
+        })
+    }
+}
+
+/// The enclave's receipt for a proof paid for from an escrow deposit, signed as EIP-712 typed
+/// data in the escrow's domain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofPayment {
+    /// `bytes32 depositId`
+    pub deposit_id: String,
+    /// `bytes32 proofId`: keccak256 of the proof bytes, as `proof_id` in the response
+    pub proof_id: String,
+    /// `uint256 amount`: what the proof costs, in the escrow token's smallest unit (decimal)
+    pub amount: String,
+    pub domain: Domain,
+    /// The EIP-712 digest that was signed
+    pub digest: String,
+    /// Ethereum address of the enclave key
+    pub signer: String,
+    /// 65 bytes r||s||v over `digest`
+    pub signature: String,
+}
+
+impl ProofPayment {
+    pub fn sign(domain: &Domain, signer: &EnclaveSigner, deposit_id: [u8; 32], proof_id: [u8; 32], amount: U256) -> Result<Self> {
+        let mut amount_word = [0u8; 32];
+        amount.to_big_endian(&mut amount_word);
+        let struct_hash = keccak(&[&keccak(PROOF_PAYMENT_TYPE.as_bytes())[..], &deposit_id, &proof_id, &amount_word].concat());
+        let digest = domain.digest(struct_hash);
+        Ok(Self {
+            deposit_id: format!("0x{}", hex::encode(deposit_id)),
+            proof_id: format!("0x{}", hex::encode(proof_id)),
+            amount: amount.to_string(),
+            domain: domain.clone(),
+            digest: format!("0x{}", hex::encode(digest)),
+            signer: signer.address(),
+            signature: signer.sign_digest(digest)?,
+        })
+    }
+}
+
+/// A 0x-prefixed (or bare) 32-byte hex value.
+pub fn parse_bytes32(value: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(value.trim().trim_start_matches("0x")).context("Not valid hex")?;
+    match bytes.try_into() {
+        Ok(word) => Ok(word),
+        Err(bytes) => bail!("Expected 32 bytes, found {}", bytes.len()),
+    }
+}
+
+fn keccak(bytes: &[u8]) -> [u8; 32] {
+    Keccak256::digest(bytes).into()
+}
+
+fn uint256(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
@@ -0,0 +1,312 @@
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use aes_gcm::{AeadCore, Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+/// Marks a database field sealed by `FieldCipher`; fields without it are plain text.
+const SEALED_FIELD_PREFIX: &str = "sealed:";
+
+/// How saved proof bundles are protected on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EncryptionMode {
+    /// Write bundles as plaintext (previous behaviour)
+    None,
+    /// Encrypt bundles with per-tenant data keys wrapped by the enclave master key
+    Envelope,
+}
+
+/// Where the enclave master key comes from. It wraps tenant data keys, seals the keyring and
+/// keys watermarks, so whoever holds it can read everything the server stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MasterKeySource {
+    /// Hex key in --master-key-file, created on first start
+    File,
+    /// Derived from the Oyster KMS root at every start and never written anywhere, so stored data
+    /// copied off the host cannot be decrypted outside the enclave
+    Kms,
+}
+
+/// Encryption applied to everything the server persists on behalf of a tenant.
+pub trait StorageEncryption: Send + Sync {
+    fn seal(&self, tenant: &str, plaintext: &[u8]) -> Result<Vec<u8>>;
+    fn open(&self, tenant: &str, sealed: &[u8]) -> Result<Vec<u8>>;
+    /// Destroys the tenant's data key so that everything sealed under it becomes unreadable,
+    /// including copies in backups. Returns false if the tenant had no key.
+    fn shred_tenant(&self, tenant: &str) -> Result<bool>;
+    /// Extension appended to file names written through this scheme.
+    fn file_suffix(&self) -> &'static str;
+    /// Drops the data keys held unwrapped in memory, so a tenant shredded by another process
+    /// (e.g. the `shred-tenant` command) stops being readable here too. Returns how many.
+    fn flush_keys(&self) -> usize;
+}
+
+pub fn build(mode: EncryptionMode, key_dir: &Path, master_key: &[u8]) -> Result<Arc<dyn StorageEncryption>> {
+    match mode {
+        EncryptionMode::None => Ok(Arc::new(NoEncryption)),
+        EncryptionMode::Envelope => Ok(Arc::new(EnvelopeEncryption::new(key_dir, master_key)?)),
+    }
+}
+
+pub struct NoEncryption;
+
+impl StorageEncryption for NoEncryption {
+    fn seal(&self, _tenant: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(plaintext.to_vec())
+    }
+
+    fn open(&self, _tenant: &str, sealed: &[u8]) -> Result<Vec<u8>> {
+        Ok(sealed.to_vec())
+    }
+
+    fn shred_tenant(&self, _tenant: &str) -> Result<bool> {
+        bail!("Crypto-shredding requires --storage-encryption envelope")
+    }
+
+    fn file_suffix(&self) -> &'static str {
+        ""
+    }
+
+    fn flush_keys(&self) -> usize {
+        0
+    }
+}
+
+/// Envelope encryption: every tenant gets a random AES-256-GCM data key, which is stored on disk
+/// only in wrapped form (encrypted under the enclave master key). Deleting the wrapped key is
+/// enough to make all of that tenant's stored bundles unrecoverable.
+pub struct EnvelopeEncryption {
+    master: Aes256Gcm,
+    key_dir: PathBuf,
+    data_keys: Mutex<HashMap<String, Aes256Gcm>>,
+}
+
+impl EnvelopeEncryption {
+    pub fn new(key_dir: &Path, master_key: &[u8]) -> Result<Self> {
+        fs::create_dir_all(key_dir)
+            .with_context(|| format!("Failed to create key directory {}", key_dir.display()))?;
+
+        Ok(Self {
+            master: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(master_key)),
+            key_dir: key_dir.to_path_buf(),
+            data_keys: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn wrapped_key_path(&self, tenant: &str) -> Result<PathBuf> {
+        validate_tenant_id(tenant)?;
+        Ok(self.key_dir.join(format!("{}.dek", tenant)))
+    }
+
+    /// Returns the tenant's data key, unwrapping it from disk or creating it on first use.
+    fn data_key(&self, tenant: &str, create: bool) -> Result<Aes256Gcm> {
+        let mut cache = self.data_keys.lock().unwrap();
+        if let Some(cipher) = cache.get(tenant) {
+            return Ok(cipher.clone());
+        }
+
+        let path = self.wrapped_key_path(tenant)?;
+        let key = if path.exists() {
+            let wrapped = fs::read(&path)
+                .with_context(|| format!("Failed to read wrapped key {}", path.display()))?;
+            decrypt(&self.master, tenant.as_bytes(), &wrapped)
+                .with_context(|| format!("Failed to unwrap data key for tenant '{}'", tenant))?
+        } else if create {
+            let mut key = vec![0u8; KEY_LEN];
+            OsRng.fill_bytes(&mut key);
+            let wrapped = encrypt(&self.master, tenant.as_bytes(), &key)?;
+            write_private(&path, &wrapped)?;
+            key
+        } else {
+            bail!("No data key for tenant '{}' (it may have been shredded)", tenant);
+        };
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        cache.insert(tenant.to_string(), cipher.clone());
+        Ok(cipher)
+    }
+}
+
+impl StorageEncryption for EnvelopeEncryption {
+    fn seal(&self, tenant: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let cipher = self.data_key(tenant, true)?;
+        encrypt(&cipher, tenant.as_bytes(), plaintext)
+    }
+
+    fn open(&self, tenant: &str, sealed: &[u8]) -> Result<Vec<u8>> {
+        let cipher = self.data_key(tenant, false)?;
+        decrypt(&cipher, tenant.as_bytes(), sealed)
+    }
+
+    fn shred_tenant(&self, tenant: &str) -> Result<bool> {
+        let path = self.wrapped_key_path(tenant)?;
+        self.data_keys.lock().unwrap().remove(tenant);
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to delete wrapped key {}", path.display()))?;
+        Ok(true)
+    }
+
+    fn file_suffix(&self) -> &'static str {
+        ".enc"
+    }
+
+    fn flush_keys(&self) -> usize {
+        let mut cache = self.data_keys.lock().unwrap();
+        let flushed = cache.len();
+        cache.clear();
+        flushed
+    }
+}
+
+/// Seals individual database fields, for records that belong to no single tenant such as the
+/// proof job history. The key is derived from the master key, so with `--master-key-source kms`
+/// the fields cannot be read outside the enclave.
+pub struct FieldCipher {
+    cipher: Aes256Gcm,
+}
+
+impl FieldCipher {
+    /// A cipher keyed for `purpose`, e.g. `zk-insurance job store key`.
+    pub fn new(master_key: &[u8], purpose: &str) -> Result<Self> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master_key)?;
+        mac.update(purpose.as_bytes());
+        Ok(Self {
+            cipher: Aes256Gcm::new(&mac.finalize().into_bytes()),
+        })
+    }
+
+    /// Seals `value` for `context` (the row and column it is stored in), so it cannot be moved
+    /// to another field.
+    pub fn seal(&self, context: &str, value: &str) -> Result<String> {
+        let sealed = encrypt(&self.cipher, context.as_bytes(), value.as_bytes())?;
+        Ok(format!("{}{}", SEALED_FIELD_PREFIX, hex::encode(sealed)))
+    }
+
+    /// Opens a field sealed for `context`. Plain-text values, written before sealing was
+    /// enabled, are returned as they are.
+    pub fn open(&self, context: &str, value: String) -> Result<String> {
+        let Some(sealed) = value.strip_prefix(SEALED_FIELD_PREFIX) else {
+            return Ok(value);
+        };
+        let sealed = hex::decode(sealed).context("Sealed field is not hex")?;
+        let plaintext = decrypt(&self.cipher, context.as_bytes(), &sealed)?;
+        String::from_utf8(plaintext).context("Sealed field is not UTF-8")
+    }
+}
+
+/// Whether a database field was sealed by a `FieldCipher`.
+pub fn is_sealed_field(value: &str) -> bool {
+    value.starts_with(SEALED_FIELD_PREFIX)
+}
+
+/// Re-wraps every tenant data key in `key_dir` from `old_master` to `new_master`, so bundles stay
+/// readable after the master key changes. Returns the number of keys re-wrapped.
+pub fn rewrap_data_keys(key_dir: &Path, old_master: &[u8], new_master: &[u8]) -> Result<usize> {
+    let old = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(old_master));
+    let new = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(new_master));
+    let mut rewrapped = 0;
+    if !key_dir.exists() {
+        return Ok(0);
+    }
+    for entry in fs::read_dir(key_dir).with_context(|| format!("Failed to read {}", key_dir.display()))? {
+        let path = entry?.path();
+        let Some(tenant) = path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".dek")) else {
+            continue;
+        };
+        let wrapped = fs::read(&path).with_context(|| format!("Failed to read wrapped key {}", path.display()))?;
+        let key = decrypt(&old, tenant.as_bytes(), &wrapped)
+            .with_context(|| format!("Failed to unwrap data key for tenant '{}'", tenant))?;
+        write_private(&path, &encrypt(&new, tenant.as_bytes(), &key)?)?;
+        rewrapped += 1;
+    }
+    Ok(rewrapped)
+}
+
+/// Wraps `data_key` under `master_key` and seals `plaintext` with it exactly as the envelope
+/// scheme does, returning (wrapped key file contents, sealed bundle). Used for test vectors.
+pub fn envelope_vector(master_key: &[u8], data_key: &[u8], tenant: &str, plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    if master_key.len() != KEY_LEN || data_key.len() != KEY_LEN {
+        bail!("Keys must be {} bytes", KEY_LEN);
+    }
+    let master = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(master_key));
+    let data = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(data_key));
+    Ok((encrypt(&master, tenant.as_bytes(), data_key)?, encrypt(&data, tenant.as_bytes(), plaintext)?))
+}
+
+/// Tenant IDs end up in file names, so only allow a conservative character set.
+pub fn validate_tenant_id(tenant: &str) -> Result<()> {
+    let valid = !tenant.is_empty()
+        && tenant.len() <= 64
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        bail!("Invalid tenant id '{}': use 1-64 characters from [A-Za-z0-9_-]", tenant);
+    }
+    Ok(())
+}
+
+pub fn load_or_create_master_key(path: &Path) -> Result<Vec<u8>> {
+    if path.exists() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read master key {}", path.display()))?;
+        let key = hex::decode(content.trim()).context("Master key file is not valid hex")?;
+        if key.len() != KEY_LEN {
+            bail!("Master key must be {} bytes, found {}", KEY_LEN, key.len());
+        }
+        return Ok(key);
+    }
+
+    let mut key = vec![0u8; KEY_LEN];
+    OsRng.fill_bytes(&mut key);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_private(path, hex::encode(&key).as_bytes())?;
+    Ok(key)
+}
+
+/// Output layout: 12-byte nonce followed by the AES-GCM ciphertext and tag.
+pub fn encrypt(cipher: &Aes256Gcm, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|_| anyhow!("Encryption failed"))?;
+
+    let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+pub fn decrypt(cipher: &Aes256Gcm, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        bail!("Ciphertext is too short");
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| anyhow!("Decryption failed: wrong key or corrupted data"))
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
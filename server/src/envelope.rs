@@ -0,0 +1,58 @@
+use crate::encryption;
+use aes_gcm::aead::KeyInit;
+use aes_gcm::{Aes256Gcm, Key};
+use anyhow::{bail, Context, Result};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Domain separation for envelope keys; also the AES-GCM associated data.
+const ENVELOPE_INFO: &[u8] = b"zk-insurance envelope v1";
+
+/// A ProofRequest encrypted to the enclave's X25519 key (ECIES): the client makes an ephemeral
+/// X25519 key pair and derives an AES-256-GCM key from the shared secret with
+/// HKDF-SHA256(salt = ephemeral public key || enclave public key, info = ENVELOPE_INFO). Only the
+/// enclave can open it; the host and the network see ciphertext.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Enclave encryption key the request was sealed to; the current one when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// Hex-encoded ephemeral X25519 public key
+    pub ephemeral_public_key: String,
+    /// Hex-encoded 12-byte nonce followed by the AES-GCM ciphertext of the request JSON
+    pub ciphertext: String,
+}
+
+impl Envelope {
+    /// Decrypts the envelope with the enclave secret `secret`.
+    pub fn open(&self, secret: &[u8; 32]) -> Result<Vec<u8>> {
+        let ephemeral = parse_key(&self.ephemeral_public_key).context("Invalid ephemeral_public_key")?;
+        let sealed = hex::decode(self.ciphertext.trim_start_matches("0x")).context("ciphertext is not valid hex")?;
+        let recipient = MontgomeryPoint::mul_base_clamped(*secret).to_bytes();
+        let shared = MontgomeryPoint(ephemeral).mul_clamped(*secret).to_bytes();
+        if shared == [0u8; 32] {
+            bail!("ephemeral_public_key is a low-order point");
+        }
+        let cipher = cipher(&shared, &ephemeral, &recipient);
+        encryption::decrypt(&cipher, ENVELOPE_INFO, &sealed).context("Envelope could not be decrypted (sealed to a different key?)")
+    }
+}
+
+fn cipher(shared: &[u8; 32], ephemeral: &[u8; 32], recipient: &[u8; 32]) -> Aes256Gcm {
+    let salt = [ephemeral.as_slice(), recipient.as_slice()].concat();
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(ENVELOPE_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+fn parse_key(key: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(key.trim_start_matches("0x"))?;
+    match bytes.try_into() {
+        Ok(key) => Ok(key),
+        Err(bytes) => bail!("expected 32 bytes, got {}", bytes.len()),
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{bail, Context, Result};
+use sha3::{Digest, Keccak256};
+
+/// Signature of the entry point in the contract generated by `bb write_solidity_verifier`.
+pub const VERIFY_SIGNATURE: &str = "verify(bytes,bytes32[])";
+
+/// First four bytes of the keccak256 of a function signature.
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = Keccak256::digest(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// ABI-encoded call to `verify(bytes,bytes32[])`, ready to be used as transaction data.
+pub fn verify_calldata(proof: &[u8], public_inputs: &[[u8; 32]]) -> Vec<u8> {
+    let proof_words = proof.len().div_ceil(32);
+    let mut out = Vec::with_capacity(4 + 32 * (5 + proof_words + public_inputs.len()));
+    out.extend_from_slice(&selector(VERIFY_SIGNATURE));
+
+    // Head: offsets of the two dynamic arguments, relative to the start of the arguments
+    out.extend_from_slice(&word(64));
+    out.extend_from_slice(&word(64 + 32 + 32 * proof_words as u64));
+
+    out.extend_from_slice(&word(proof.len() as u64));
+    out.extend_from_slice(proof);
+    out.resize(out.len() + 32 * proof_words - proof.len(), 0);
+
+    out.extend_from_slice(&word(public_inputs.len() as u64));
+    for input in public_inputs {
+        out.extend_from_slice(input);
+    }
+    out
+}
+
+/// ID of a proof: the keccak256 of its bytes, which anyone watching the chain can recompute from
+/// a verify call.
+pub fn proof_id(proof: &[u8]) -> String {
+    format!("0x{}", hex::encode(Keccak256::digest(proof)))
+}
+
+/// Extracts the proof from `verify(bytes,bytes32[])` calldata; `None` for any other call.
+pub fn decode_verify_proof(calldata: &[u8]) -> Option<&[u8]> {
+    let args = calldata.strip_prefix(&selector(VERIFY_SIGNATURE)[..])?;
+    let offset = read_word(args, 0)?;
+    let len = read_word(args, offset)?;
+    let start = offset.checked_add(32)?;
+    args.get(start..start.checked_add(len)?)
+}
+
+/// Parses the prover's `public_inputs` (a JSON array of hex field elements) into bytes32 words.
+pub fn parse_public_inputs(public_inputs: &str) -> Result<Vec<[u8; 32]>> {
+    let fields: Vec<String> =
+        serde_json::from_str(public_inputs.trim()).context("public_inputs must be a JSON array of field elements")?;
+    fields
+        .iter()
+        .map(|field| {
+            let bytes = hex::decode(field.trim_start_matches("0x"))
+                .with_context(|| format!("Public input '{}' is not hex", field))?;
+            if bytes.len() > 32 {
+                bail!("Public input '{}' is longer than 32 bytes", field);
+            }
+            let mut word = [0u8; 32];
+            word[32 - bytes.len()..].copy_from_slice(&bytes);
+            Ok(word)
+        })
+        .collect()
+}
+
+/// The word at byte `at` of `data` as an offset or length, if it fits in a usize.
+fn read_word(data: &[u8], at: usize) -> Option<usize> {
+    let word = data.get(at..at.checked_add(32)?)?;
+    if word[..24].iter().any(|b| *b != 0) {
+        return None;
+    }
+    usize::try_from(u64::from_be_bytes(word[24..].try_into().ok()?)).ok()
+}
+
+fn word(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
@@ -0,0 +1,111 @@
+use crate::ipfs::IpfsClient;
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::s3::S3Client;
+use crate::signing::EnclaveSigner;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportTarget {
+    /// No scheduled export
+    None,
+    /// Write snapshots to a local (typically mounted) directory
+    Local,
+    S3,
+    Ipfs,
+}
+
+pub enum Destination {
+    Local(PathBuf),
+    S3 { client: S3Client, prefix: String },
+    Ipfs(IpfsClient),
+}
+
+/// Evidence captured at one point in time.
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    exported_at: String,
+    server_version: &'a str,
+    signer: &'a str,
+    metrics: MetricsSnapshot,
+}
+
+/// What is written to cold storage: the snapshot as the exact string that was signed, so it can
+/// be verified byte-for-byte after retrieval.
+#[derive(Serialize)]
+struct SignedSnapshot {
+    payload: String,
+    /// EIP-191 signature over `payload` by the enclave key
+    signature: String,
+    signer: String,
+}
+
+/// Periodically pushes signed snapshots out of the enclave, so compliance teams keep durable
+/// evidence even after the enclave itself is gone.
+pub struct Exporter {
+    metrics: Arc<Metrics>,
+    signer: Arc<EnclaveSigner>,
+    destination: Destination,
+}
+
+impl Exporter {
+    pub fn new(metrics: Arc<Metrics>, signer: Arc<EnclaveSigner>, destination: Destination) -> Self {
+        Self { metrics, signer, destination }
+    }
+
+    pub async fn run(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick fires immediately; wait a full period so the first snapshot has data
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match self.export().await {
+                Ok(location) => info!("Exported snapshot to {}", location),
+                Err(e) => error!("Snapshot export failed: {:#}", e),
+            }
+        }
+    }
+
+    async fn export(&self) -> Result<String> {
+        let now = chrono::Utc::now();
+        let body = signed_snapshot(&self.signer, &now.to_rfc3339(), self.metrics.snapshot())?;
+        let file_name = format!("snapshot-{}.json", now.format("%Y%m%dT%H%M%SZ"));
+
+        match &self.destination {
+            Destination::Local(dir) => {
+                fs::create_dir_all(dir)?;
+                let path = dir.join(&file_name);
+                fs::write(&path, body).with_context(|| format!("Failed to write {}", path.display()))?;
+                Ok(path.display().to_string())
+            }
+            Destination::S3 { client, prefix } => {
+                let key = format!("{}{}", prefix, file_name);
+                client.put_object(&key, body).await?;
+                Ok(format!("s3 key {}", key))
+            }
+            Destination::Ipfs(client) => {
+                let cid = client.add(&file_name, body).await?;
+                Ok(format!("ipfs://{}", cid))
+            }
+        }
+    }
+}
+
+/// Serialised signed snapshot, as written to cold storage.
+pub fn signed_snapshot(signer: &EnclaveSigner, exported_at: &str, metrics: MetricsSnapshot) -> Result<Vec<u8>> {
+    let address = signer.address();
+    let payload = serde_json::to_string(&Snapshot {
+        exported_at: exported_at.to_string(),
+        server_version: env!("CARGO_PKG_VERSION"),
+        signer: &address,
+        metrics,
+    })?;
+    let signature = signer.sign_message(payload.as_bytes())?;
+    Ok(serde_json::to_vec_pretty(&SignedSnapshot { payload, signature, signer: address })?)
+}
@@ -0,0 +1,108 @@
+use crate::verifier::Verifier;
+use serde::Serialize;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+/// How long preflight results are reused, so frequent orchestrator probes do not spawn nargo
+/// and bb every time.
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// A tool that does not answer `--version` within this long is treated as broken.
+const TOOL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tools a proving node shells out to.
+pub const PROVER_TOOLS: &[&str] = &["nargo", "bb"];
+/// Tools a verify-only node shells out to.
+pub const VERIFIER_TOOLS: &[&str] = &["bb"];
+
+/// Outcome of one readiness check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn new(name: &'static str, result: Result<String, String>) -> Self {
+        let (ok, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        Self { name, ok, detail }
+    }
+}
+
+/// Checks that the node can actually prove: the toolchain runs, the active circuit is compiled
+/// and its verification key loads. Failures here would otherwise only surface at proof time.
+pub struct Preflight {
+    verifier: Arc<Verifier>,
+    /// Tools the node shells out to; a verify-only node needs no nargo
+    tools: &'static [&'static str],
+    cache: Mutex<Option<(Instant, Vec<Check>)>>,
+}
+
+impl Preflight {
+    pub fn new(verifier: Arc<Verifier>, tools: &'static [&'static str]) -> Self {
+        Self {
+            verifier,
+            tools,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Runs the checks, or returns the last results if they are recent enough.
+    pub async fn run(&self) -> Vec<Check> {
+        let mut cache = self.cache.lock().await;
+        if let Some((at, checks)) = cache.as_ref() {
+            if at.elapsed() < CACHE_TTL {
+                return checks.clone();
+            }
+        }
+
+        let mut checks = Vec::new();
+        for tool in self.tools {
+            checks.push(Check::new(tool, tool_version(tool).await));
+        }
+        checks.push(Check::new("circuit", self.compiled_circuit()));
+        let vk = match self.verifier.verification_key().await {
+            Ok(vk) => Ok(vk.vk_hash),
+            Err(e) => Err(format!("{:#}", e)),
+        };
+        checks.push(Check::new("verification_key", vk));
+
+        *cache = Some((Instant::now(), checks.clone()));
+        checks
+    }
+
+    /// Discards the cached results, so the next probe runs every check again.
+    pub async fn flush(&self) {
+        *self.cache.lock().await = None;
+    }
+
+    fn compiled_circuit(&self) -> Result<String, String> {
+        let path = self.verifier.circuit_path().join("target/insurance_verifier.json");
+        let content = fs::read(&path).map_err(|e| format!("{}: {} (run nargo compile)", path.display(), e))?;
+        let artifact: serde_json::Value =
+            serde_json::from_slice(&content).map_err(|e| format!("{} is not a compiled circuit: {}", path.display(), e))?;
+        if artifact.get("bytecode").is_none() {
+            return Err(format!("{} has no bytecode", path.display()));
+        }
+        Ok(path.display().to_string())
+    }
+}
+
+/// The first line `tool --version` prints.
+pub async fn tool_version(tool: &str) -> Result<String, String> {
+    let output = tokio::time::timeout(TOOL_TIMEOUT, Command::new(tool).arg("--version").kill_on_drop(true).output())
+        .await
+        .map_err(|_| format!("{} --version did not finish within {:?}", tool, TOOL_TIMEOUT))?
+        .map_err(|e| format!("{} is not runnable: {}", tool, e))?;
+    if !output.status.success() {
+        return Err(format!("{} --version failed: {}", tool, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or_default().trim().to_string())
+}
@@ -0,0 +1,967 @@
+use crate::policy::Bounds;
+use crate::prover::read_fields;
+use anyhow::{anyhow, bail, Context, Result};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::pairing::Pairing;
+use ark_ec::{AffineRepr, CurveGroup, VariableBaseMSM};
+use ark_ff::{BigInteger, Field, One, PrimeField, Zero};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// Longest verifier nonce in bytes. Anything shorter than the field modulus is a field element,
+/// so the nonce is proven as given.
+pub const MAX_NONCE_BYTES: usize = 31;
+
+/// In-process sanity check of an UltraHonk proof's public inputs: there must be the circuit's
+/// four bounds, followed by the verifier's nonce and the proof's expiry in circuits that take
+/// them, and the bounds must be one of the sets of eligibility bounds in `accepted`. This catches proofs made with other
+/// bounds, truncated or reordered inputs and formatting mistakes before the proof itself is
+/// checked (see `NativeVerifier`).
+pub fn check_public_inputs(public_inputs: &[[u8; 32]], accepted: &[Bounds]) -> Result<()> {
+    const BOUNDS: usize = 4;
+    if !(BOUNDS..=BOUNDS + 2).contains(&public_inputs.len()) {
+        bail!(
+            "Expected {} public inputs, {} with a nonce or {} with a nonce and expiry, found {}",
+            BOUNDS,
+            BOUNDS + 1,
+            BOUNDS + 2,
+            public_inputs.len()
+        );
+    }
+    let matches = |bounds: &Bounds| {
+        public_inputs.iter().zip(bounds.public_inputs()).all(|(field, expected)| {
+            let mut word = [0u8; 32];
+            word[28..].copy_from_slice(&expected.to_be_bytes());
+            *field == word
+        })
+    };
+    if !accepted.iter().any(matches) {
+        let inputs: Vec<String> = public_inputs.iter().map(|field| format!("0x{}", hex::encode(field))).collect();
+        bail!("Public inputs [{}] do not match any accepted eligibility bounds", inputs.join(", "));
+    }
+    Ok(())
+}
+
+/// A verifier nonce as the public input it is proven as: hex of at most `MAX_NONCE_BYTES`,
+/// left-padded to a 32-byte word.
+pub fn nonce_word(nonce: &str) -> Result<[u8; 32]> {
+    let digits = nonce.trim().trim_start_matches("0x");
+    if digits.is_empty() || digits.len() > MAX_NONCE_BYTES * 2 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("nonce must be hex of 1 to {} bytes", MAX_NONCE_BYTES);
+    }
+    let bytes = hex::decode(format!("{:0>64}", digits))?;
+    let mut word = [0u8; 32];
+    word.copy_from_slice(&bytes);
+    Ok(word)
+}
+
+/// The unix time carried in an `expires_at` public input.
+pub fn expiry(word: &[u8; 32]) -> Result<u64> {
+    if word[..24].iter().any(|byte| *byte != 0) {
+        bail!("Expiry 0x{} is not a u64", hex::encode(word));
+    }
+    Ok(u64::from_be_bytes(word[24..].try_into()?))
+}
+
+/// Checks UltraHonk proofs in-process with `VerificationKey::verify`, once it has been shown to
+/// agree with bb for the key in use.
+///
+/// A key starts out uncalibrated: its proofs are still checked with `bb verify`, and the native
+/// result is compared with bb's. The first valid proof both accept is then tampered with (see
+/// `tampered`), and only if the native verifier rejects every copy does the key become trusted;
+/// from then on its proofs never reach bb. If the two ever disagree, the native verifier accepts
+/// a tampered copy, or the key cannot be read natively (a bb release with another proof
+/// layout), the key stays with bb.
+#[derive(Default)]
+pub struct NativeVerifier {
+    /// By sha256 of vk_fields.json, so a regenerated key starts over
+    keys: Mutex<HashMap<String, Native>>,
+}
+
+#[derive(Clone)]
+pub enum Native {
+    Uncalibrated(Arc<VerificationKey>),
+    Trusted(Arc<VerificationKey>),
+    Unavailable,
+}
+
+impl NativeVerifier {
+    /// The native state of the key in `vk_dir`, with the digest it is tracked under.
+    pub fn state(&self, vk_dir: &Path) -> Result<(String, Native)> {
+        let path = vk_dir.join("vk_fields.json");
+        let content = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let digest = hex::encode(Sha256::digest(&content));
+        if let Some(native) = self.keys.lock().unwrap().get(&digest) {
+            return Ok((digest, native.clone()));
+        }
+        let native = match read_fields(&path).and_then(|fields| VerificationKey::from_fields(&fields)) {
+            Ok(key) => Native::Uncalibrated(Arc::new(key)),
+            Err(e) => {
+                warn!("Verification key in {} cannot be checked natively, using bb verify: {:#}", vk_dir.display(), e);
+                Native::Unavailable
+            }
+        };
+        self.keys.lock().unwrap().insert(digest.clone(), native.clone());
+        Ok((digest, native))
+    }
+
+    /// Records bb's verdict on `proof` next to the native one for the key tracked as `digest`.
+    pub fn calibrate(&self, digest: &str, bb_valid: bool, proof: &[u8], public_inputs: &[Word], native: &Result<()>) {
+        let Some(Native::Uncalibrated(key)) = self.keys.lock().unwrap().get(digest).cloned() else {
+            return;
+        };
+        match (bb_valid, native) {
+            (true, Ok(())) => {
+                // Checked outside the lock, as each copy costs a full verification
+                let accepted: Vec<&str> = tampered(proof, public_inputs)
+                    .into_iter()
+                    .filter(|(_, proof, public_inputs)| key.verify(proof, public_inputs).is_ok())
+                    .map(|(part, _, _)| part)
+                    .collect();
+                if accepted.is_empty() {
+                    info!("Native UltraHonk verification agrees with bb for key {}, no longer calling bb verify", digest);
+                    self.settle(digest, Native::Trusted(key));
+                } else {
+                    warn!(
+                        "Native UltraHonk verification accepts a proof with a tampered {} for key {}, using bb verify",
+                        accepted.join(", "),
+                        digest
+                    );
+                    self.settle(digest, Native::Unavailable);
+                }
+            }
+            (false, Err(_)) => {}
+            (_, native) => {
+                warn!(
+                    "Native UltraHonk verification disagrees with bb for key {} (bb: {}, native: {:?}), using bb verify",
+                    digest,
+                    if bb_valid { "valid" } else { "invalid" },
+                    native.as_ref().err().map(|e| format!("{:#}", e))
+                );
+                self.settle(digest, Native::Unavailable);
+            }
+        }
+    }
+
+    /// Ends the calibration of the key tracked as `digest`, unless another proof already found
+    /// it unusable.
+    fn settle(&self, digest: &str, native: Native) {
+        let mut keys = self.keys.lock().unwrap();
+        if !matches!(keys.get(digest), Some(Native::Unavailable)) {
+            keys.insert(digest.to_string(), native);
+        }
+    }
+}
+
+/// Copies of a valid proof and its public inputs with one word changed each, by what was
+/// changed: a sumcheck round univariate, a limb of the first witness commitment and the first
+/// public input. A sound verifier rejects all of them.
+fn tampered(proof: &[u8], public_inputs: &[Word]) -> Vec<(&'static str, Vec<u8>, Vec<Word>)> {
+    let pairing_points = (proof.len() / 32).saturating_sub(PROOF_SIZE);
+    let flip = |word: usize| {
+        let mut proof = proof.to_vec();
+        proof[word * 32 + 31] ^= 1;
+        proof
+    };
+    // The first round univariate follows the eight witness commitments of four limbs each
+    let mut copies = vec![
+        ("sumcheck univariate", flip(pairing_points + 8 * 4), public_inputs.to_vec()),
+        ("commitment limb", flip(pairing_points), public_inputs.to_vec()),
+    ];
+    if !public_inputs.is_empty() {
+        let mut inputs = public_inputs.to_vec();
+        inputs[0][31] ^= 1;
+        copies.push(("public input", proof.to_vec(), inputs));
+    }
+    copies
+}
+
+/// Sumcheck rounds and Gemini evaluations every proof carries, whatever the circuit size; the
+/// rounds past log2 of the circuit size are padding.
+const CONST_PROOF_SIZE_LOG_N: usize = 28;
+/// Evaluations of each sumcheck round univariate
+const BATCHED_RELATION_PARTIAL_LENGTH: usize = 8;
+/// Polynomials opened at the sumcheck point: the precomputed and witness ones, then the shifts
+const NUMBER_OF_ENTITIES: usize = 40;
+const NUMBER_UNSHIFTED: usize = 35;
+const NUMBER_OF_SUBRELATIONS: usize = 26;
+const NUMBER_OF_ALPHAS: usize = NUMBER_OF_SUBRELATIONS - 1;
+/// Commitments in the verification key
+const PRECOMPUTED_COMMITMENTS: usize = 27;
+/// Fields of the pairing point accumulator at the start of the proof, counted as public inputs
+const PAIRING_POINTS_SIZE: usize = 16;
+/// Field elements a proof carries besides the pairing points
+const PROOF_SIZE: usize = 8 * 4
+    + CONST_PROOF_SIZE_LOG_N * BATCHED_RELATION_PARTIAL_LENGTH
+    + NUMBER_OF_ENTITIES
+    + (CONST_PROOF_SIZE_LOG_N - 1) * 4
+    + CONST_PROOF_SIZE_LOG_N
+    + 2 * 4;
+
+/// `[x]₂` of the Aztec Ignition SRS, the G2 point KZG openings are checked against.
+const SRS_G2_X: [&str; 4] = [
+    "0x0118c4d5b837bcc2bc89b5b398b5974e9f5944073b32078b7e231fec938883b0",
+    "0x260e01b251f6f1c7e7ff4e580791dee8ea51d87a358e038b4efe30fac09383c1",
+    "0x22febda3c0c0632a56475b4214e5615e11e6dd3f96e6cea2854a87d4dacc5e55",
+    "0x04fc6369f7110fe3d25156c1bb9a72859cf2a04641f99ba4ee413c80da6a5fe4",
+];
+
+/// Diagonal of Poseidon2's internal matrix, minus one, for a state of four.
+const POSEIDON2_INTERNAL_DIAGONAL: [&str; 4] = [
+    "0x10dc6e9c006ea38b04b1e03b4bd9490c0d03f98929ca1d7fb56821fd19d3b6e7",
+    "0x0c28145b6a44df3e0149b3d0a30b3bb599df9756d4dd9b84a86b38cfb45a740b",
+    "0x00544b8338791518b2c7645a50392798b21f75bb60e3596170067d00141cac15",
+    "0x222c01175718386f2e2e82eb122789e352e105a3b8fa852613bc534433ee428b",
+];
+
+/// Positions of the polynomials in the proof's sumcheck evaluations.
+mod entity {
+    pub const Q_M: usize = 0;
+    pub const Q_C: usize = 1;
+    pub const Q_L: usize = 2;
+    pub const Q_R: usize = 3;
+    pub const Q_O: usize = 4;
+    pub const Q_4: usize = 5;
+    pub const Q_LOOKUP: usize = 6;
+    pub const Q_ARITH: usize = 7;
+    pub const Q_RANGE: usize = 8;
+    pub const Q_ELLIPTIC: usize = 9;
+    pub const Q_AUX: usize = 10;
+    pub const Q_POSEIDON2_EXTERNAL: usize = 11;
+    pub const Q_POSEIDON2_INTERNAL: usize = 12;
+    pub const SIGMA_1: usize = 13;
+    pub const ID_1: usize = 17;
+    pub const TABLE_1: usize = 21;
+    pub const LAGRANGE_FIRST: usize = 25;
+    pub const LAGRANGE_LAST: usize = 26;
+    pub const W_L: usize = 27;
+    pub const W_R: usize = 28;
+    pub const W_O: usize = 29;
+    pub const W_4: usize = 30;
+    pub const Z_PERM: usize = 31;
+    pub const LOOKUP_INVERSES: usize = 32;
+    pub const LOOKUP_READ_COUNTS: usize = 33;
+    pub const LOOKUP_READ_TAGS: usize = 34;
+    pub const W_L_SHIFT: usize = 35;
+    pub const W_R_SHIFT: usize = 36;
+    pub const W_O_SHIFT: usize = 37;
+    pub const W_4_SHIFT: usize = 38;
+    pub const Z_PERM_SHIFT: usize = 39;
+}
+
+type Word = [u8; 32];
+
+/// An UltraHonk verification key, as `bb write_vk --oracle_hash keccak` writes it to
+/// vk_fields.json.
+pub struct VerificationKey {
+    /// The circuit size (or its log, as newer bb writes it), public input count and offset,
+    /// hashed as given
+    header: [Word; 3],
+    circuit_size: u64,
+    log_circuit_size: usize,
+    /// Public inputs including the pairing points
+    public_inputs: usize,
+    pub_inputs_offset: u64,
+    /// q_m, q_c, q_l, q_r, q_o, q_4, q_lookup, q_arith, q_delta_range, q_elliptic, q_aux, the
+    /// two Poseidon2 selectors, sigma_1-4, id_1-4, table_1-4, lagrange_first and lagrange_last
+    commitments: Vec<G1Affine>,
+}
+
+impl VerificationKey {
+    /// Reads a key from its fields: the circuit size, the number of public inputs and their
+    /// offset first, the commitments last, each as four limbs.
+    pub fn from_fields(fields: &[String]) -> Result<Self> {
+        let words = fields
+            .iter()
+            .map(|field| {
+                let bytes = hex::decode(field.trim_start_matches("0x")).with_context(|| format!("Key field '{}' is not hex", field))?;
+                if bytes.len() > 32 {
+                    bail!("Key field '{}' is longer than 32 bytes", field);
+                }
+                let mut word = [0u8; 32];
+                word[32 - bytes.len()..].copy_from_slice(&bytes);
+                Ok(word)
+            })
+            .collect::<Result<Vec<Word>>>()?;
+        let header = words
+            .len()
+            .checked_sub(PRECOMPUTED_COMMITMENTS * 4)
+            .filter(|header| *header >= 3)
+            .with_context(|| format!("A key of {} fields is too short", words.len()))?;
+        let size = small(&words[0]).context("Invalid circuit size")?;
+        // No UltraHonk circuit is as small as 2^28 gates, so a value that small is a log
+        let circuit_size = if size as usize <= CONST_PROOF_SIZE_LOG_N { 1 << size } else { size };
+        if !circuit_size.is_power_of_two() || circuit_size.trailing_zeros() as usize > CONST_PROOF_SIZE_LOG_N {
+            bail!("Circuit size {} is not a power of two up to 2^{}", size, CONST_PROOF_SIZE_LOG_N);
+        }
+        let commitments = words[header..].chunks(4).map(point).collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            header: [words[0], words[1], words[2]],
+            circuit_size,
+            log_circuit_size: circuit_size.trailing_zeros() as usize,
+            public_inputs: small(&words[1]).context("Invalid public input count")? as usize,
+            pub_inputs_offset: small(&words[2]).context("Invalid public input offset")?,
+            commitments,
+        })
+    }
+
+    /// Verifies an UltraHonk (keccak) proof for `public_inputs` in-process: the sumcheck over
+    /// every relation, the Shplemini batch opening and the KZG pairing, as the contract from
+    /// `bb write_solidity_verifier` does.
+    pub fn verify(&self, proof: &[u8], public_inputs: &[Word]) -> Result<()> {
+        if !proof.len().is_multiple_of(32) {
+            bail!("A proof of {} bytes is not a whole number of fields", proof.len());
+        }
+        let words: Vec<Word> = proof.chunks(32).map(|chunk| chunk.try_into().unwrap()).collect();
+        let pairing_points = match words.len().checked_sub(PROOF_SIZE) {
+            Some(0) => 0,
+            Some(PAIRING_POINTS_SIZE) => PAIRING_POINTS_SIZE,
+            _ => bail!("A proof of {} fields does not have the UltraHonk layout", words.len()),
+        };
+        if public_inputs.len() + pairing_points != self.public_inputs {
+            bail!("Expected {} public inputs, found {}", self.public_inputs - pairing_points, public_inputs.len());
+        }
+        let proof = Proof::read(&words, pairing_points)?;
+        let inputs = public_inputs.iter().map(scalar).collect::<Result<Vec<Fr>>>()?;
+        let challenges = Challenges::derive(self, &proof, public_inputs);
+        let parameters = RelationParameters {
+            eta: challenges.eta,
+            eta_two: challenges.eta_two,
+            eta_three: challenges.eta_three,
+            beta: challenges.beta,
+            gamma: challenges.gamma,
+            public_inputs_delta: self.public_inputs_delta(&inputs, &proof.pairing_points, challenges.beta, challenges.gamma)?,
+        };
+        self.check_sumcheck(&proof, &challenges, &parameters)?;
+        self.check_opening(&proof, &challenges)
+    }
+
+    /// The permutation argument's correction for the public inputs, which are copied into the
+    /// first rows of the circuit.
+    fn public_inputs_delta(&self, inputs: &[Fr], pairing_points: &[Fr], beta: Fr, gamma: Fr) -> Result<Fr> {
+        let mut numerator = Fr::one();
+        let mut denominator = Fr::one();
+        let mut numerator_acc = gamma + beta * Fr::from(self.circuit_size + self.pub_inputs_offset);
+        let mut denominator_acc = gamma - beta * Fr::from(self.pub_inputs_offset + 1);
+        for input in inputs.iter().chain(pairing_points) {
+            numerator *= numerator_acc + input;
+            denominator *= denominator_acc + input;
+            numerator_acc += beta;
+            denominator_acc -= beta;
+        }
+        Ok(numerator * denominator.inverse().context("The public input delta is undefined")?)
+    }
+
+    fn check_sumcheck(&self, proof: &Proof, challenges: &Challenges, parameters: &RelationParameters) -> Result<()> {
+        let mut target = Fr::zero();
+        let mut pow_evaluation = Fr::one();
+        for round in 0..self.log_circuit_size {
+            let univariate = &proof.sumcheck_univariates[round];
+            if univariate[0] + univariate[1] != target {
+                bail!("Sumcheck round {} does not add up", round);
+            }
+            let challenge = challenges.sumcheck[round];
+            target = barycentric_evaluation(univariate, challenge)?;
+            pow_evaluation *= Fr::one() + challenge * (challenges.gates[round] - Fr::one());
+        }
+        let evaluations = relation_evaluations(&proof.sumcheck_evaluations, parameters, pow_evaluation);
+        let batched = evaluations[1..]
+            .iter()
+            .zip(&challenges.alphas)
+            .fold(evaluations[0], |acc, (evaluation, alpha)| acc + *evaluation * alpha);
+        if batched != target {
+            bail!("The relations do not hold at the sumcheck point");
+        }
+        Ok(())
+    }
+
+    /// Shplemini: reduces the claimed evaluations to one KZG opening, then checks it with a
+    /// pairing, batched with the proof's pairing point accumulator when it carries one.
+    fn check_opening(&self, proof: &Proof, challenges: &Challenges) -> Result<()> {
+        let mut powers = vec![challenges.gemini_r];
+        for i in 1..CONST_PROOF_SIZE_LOG_N {
+            powers.push(powers[i - 1].square());
+        }
+        let invert = |value: Fr| value.inverse().context("Shplemini denominator is zero");
+        let mut pos_inverted = invert(challenges.shplonk_z - powers[0])?;
+        let mut neg_inverted = invert(challenges.shplonk_z + powers[0])?;
+        let unshifted_scalar = pos_inverted + challenges.shplonk_nu * neg_inverted;
+        let shifted_scalar = invert(challenges.gemini_r)? * (pos_inverted - challenges.shplonk_nu * neg_inverted);
+
+        let mut scalars = vec![Fr::one()];
+        let mut commitments = vec![proof.shplonk_q.point];
+        let mut batching = Fr::one();
+        let mut batched_evaluation = Fr::zero();
+        for (i, evaluation) in proof.sumcheck_evaluations.iter().enumerate() {
+            let scalar = if i < NUMBER_UNSHIFTED { unshifted_scalar } else { shifted_scalar };
+            scalars.push(-scalar * batching);
+            batched_evaluation += *evaluation * batching;
+            batching *= challenges.rho;
+        }
+        commitments.extend(&self.commitments);
+        commitments.extend(proof.witness_commitments.iter().map(|commitment| commitment.point));
+        // The shifted polynomials are opened from the commitments of w_1-w_4 and z_perm
+        commitments.extend(proof.witness_commitments[..5].iter().map(|commitment| commitment.point));
+
+        let fold_evaluations = fold_pos_evaluations(
+            &challenges.sumcheck,
+            batched_evaluation,
+            &proof.gemini_a_evaluations,
+            &powers,
+            self.log_circuit_size,
+        )?;
+        let mut constant_term = fold_evaluations[0] * pos_inverted + proof.gemini_a_evaluations[0] * challenges.shplonk_nu * neg_inverted;
+        batching = challenges.shplonk_nu.square();
+        for i in 0..CONST_PROOF_SIZE_LOG_N - 1 {
+            let mut scalar = Fr::zero();
+            if i < self.log_circuit_size - 1 {
+                pos_inverted = invert(challenges.shplonk_z - powers[i + 1])?;
+                neg_inverted = invert(challenges.shplonk_z + powers[i + 1])?;
+                let scaling_pos = batching * pos_inverted;
+                let scaling_neg = batching * challenges.shplonk_nu * neg_inverted;
+                scalar = -scaling_neg - scaling_pos;
+                constant_term += scaling_neg * proof.gemini_a_evaluations[i + 1] + scaling_pos * fold_evaluations[i + 1];
+                batching *= challenges.shplonk_nu * challenges.shplonk_nu;
+            }
+            scalars.push(scalar);
+            commitments.push(proof.gemini_fold_commitments[i].point);
+        }
+        scalars.push(constant_term);
+        commitments.push(G1Affine::generator());
+        scalars.push(challenges.shplonk_z);
+        commitments.push(proof.kzg_quotient.point);
+
+        let mut lhs = G1Projective::msm(&commitments, &scalars).map_err(|_| anyhow!("Mismatched opening terms"))?;
+        let mut rhs = -G1Projective::from(proof.kzg_quotient.point);
+        if !proof.pairing_points.is_empty() {
+            let limbs: Vec<Word> = proof.pairing_points.iter().map(|limb| word(*limb)).collect();
+            let other_lhs = pairing_point(&limbs[..8])?;
+            let other_rhs = pairing_point(&limbs[8..])?;
+            let (lhs_affine, rhs_affine) = (lhs.into_affine(), rhs.into_affine());
+            let separator = hash(&[other_lhs, other_rhs, lhs_affine, rhs_affine].map(coordinates).concat());
+            lhs = lhs * separator + other_lhs;
+            rhs = rhs * separator + other_rhs;
+        }
+        let pairing = Bn254::multi_pairing([lhs.into_affine(), rhs.into_affine()], [G2Affine::generator(), srs_g2_x()?]);
+        if !pairing.0.is_one() {
+            bail!("The KZG opening does not verify");
+        }
+        Ok(())
+    }
+}
+
+/// A commitment in the proof with the four limbs it is hashed as.
+struct Commitment<'a> {
+    point: G1Affine,
+    limbs: &'a [Word],
+}
+
+/// A proof split into its parts, in the order bb writes them.
+struct Proof<'a> {
+    pairing_points: Vec<Fr>,
+    pairing_point_words: &'a [Word],
+    /// w_1, w_2, w_3, w_4, z_perm, lookup_inverses, lookup_read_counts, lookup_read_tags
+    witness_commitments: Vec<Commitment<'a>>,
+    sumcheck_univariates: Vec<Vec<Fr>>,
+    sumcheck_univariate_words: Vec<&'a [Word]>,
+    sumcheck_evaluations: Vec<Fr>,
+    sumcheck_evaluation_words: &'a [Word],
+    gemini_fold_commitments: Vec<Commitment<'a>>,
+    gemini_a_evaluations: Vec<Fr>,
+    gemini_a_evaluation_words: &'a [Word],
+    shplonk_q: Commitment<'a>,
+    kzg_quotient: Commitment<'a>,
+}
+
+impl<'a> Proof<'a> {
+    fn read(words: &'a [Word], pairing_points: usize) -> Result<Self> {
+        let mut rest = words;
+        let mut take = |n: usize| {
+            let (taken, left) = rest.split_at(n);
+            rest = left;
+            taken
+        };
+        let pairing_point_words = take(pairing_points);
+        let mut commitment = |limbs: &'a [Word]| -> Result<Commitment<'a>> { Ok(Commitment { point: point(limbs)?, limbs }) };
+        // bb sends the lookup read counts and tags before w_4, and the inverses before z_perm
+        let [w1, w2, w3, read_counts, read_tags, w4, inverses, z_perm] = [(); 8].map(|_| take(4));
+        let witness_commitments = [w1, w2, w3, w4, z_perm, inverses, read_counts, read_tags]
+            .into_iter()
+            .map(&mut commitment)
+            .collect::<Result<Vec<_>>>()?;
+        let sumcheck_univariate_words: Vec<&[Word]> = (0..CONST_PROOF_SIZE_LOG_N).map(|_| take(BATCHED_RELATION_PARTIAL_LENGTH)).collect();
+        let sumcheck_evaluation_words = take(NUMBER_OF_ENTITIES);
+        let fold_words: Vec<&[Word]> = (0..CONST_PROOF_SIZE_LOG_N - 1).map(|_| take(4)).collect();
+        let gemini_a_evaluation_words = take(CONST_PROOF_SIZE_LOG_N);
+        let shplonk_q = take(4);
+        let kzg_quotient = take(4);
+        let scalars = |words: &[Word]| words.iter().map(scalar).collect::<Result<Vec<Fr>>>();
+        Ok(Self {
+            pairing_points: scalars(pairing_point_words)?,
+            pairing_point_words,
+            witness_commitments,
+            sumcheck_univariates: sumcheck_univariate_words.iter().map(|words| scalars(words)).collect::<Result<_>>()?,
+            sumcheck_univariate_words,
+            sumcheck_evaluations: scalars(sumcheck_evaluation_words)?,
+            sumcheck_evaluation_words,
+            gemini_fold_commitments: fold_words.into_iter().map(&mut commitment).collect::<Result<_>>()?,
+            gemini_a_evaluations: scalars(gemini_a_evaluation_words)?,
+            gemini_a_evaluation_words,
+            shplonk_q: commitment(shplonk_q)?,
+            kzg_quotient: commitment(kzg_quotient)?,
+        })
+    }
+
+    fn witness(&self, index: usize) -> &[Word] {
+        self.witness_commitments[index].limbs
+    }
+}
+
+struct RelationParameters {
+    eta: Fr,
+    eta_two: Fr,
+    eta_three: Fr,
+    beta: Fr,
+    gamma: Fr,
+    public_inputs_delta: Fr,
+}
+
+/// The Fiat-Shamir challenges of a proof, from a keccak transcript of everything before them.
+struct Challenges {
+    eta: Fr,
+    eta_two: Fr,
+    eta_three: Fr,
+    beta: Fr,
+    gamma: Fr,
+    alphas: Vec<Fr>,
+    gates: Vec<Fr>,
+    sumcheck: Vec<Fr>,
+    rho: Fr,
+    gemini_r: Fr,
+    shplonk_nu: Fr,
+    shplonk_z: Fr,
+}
+
+impl Challenges {
+    fn derive(vk: &VerificationKey, proof: &Proof, public_inputs: &[Word]) -> Self {
+        let mut round = vk.header.to_vec();
+        round.extend_from_slice(public_inputs);
+        round.extend_from_slice(proof.pairing_point_words);
+        for wire in 0..3 {
+            round.extend_from_slice(proof.witness(wire));
+        }
+        let mut previous = hash(&round);
+        let (eta, eta_two) = split(previous);
+        previous = hash(&[word(previous)]);
+        let (eta_three, _) = split(previous);
+
+        // Lookup read counts and tags, then w_4
+        previous = hash(&[&[word(previous)], proof.witness(6), proof.witness(7), proof.witness(3)].concat());
+        let (beta, gamma) = split(previous);
+
+        // Lookup inverses, then z_perm
+        previous = hash(&[&[word(previous)], proof.witness(5), proof.witness(4)].concat());
+        let mut alphas = Vec::with_capacity(NUMBER_OF_ALPHAS);
+        let (first, second) = split(previous);
+        alphas.extend([first, second]);
+        while alphas.len() < NUMBER_OF_ALPHAS {
+            previous = hash(&[word(previous)]);
+            let (first, second) = split(previous);
+            alphas.push(first);
+            if alphas.len() < NUMBER_OF_ALPHAS {
+                alphas.push(second);
+            }
+        }
+
+        let mut gates = Vec::with_capacity(CONST_PROOF_SIZE_LOG_N);
+        for _ in 0..CONST_PROOF_SIZE_LOG_N {
+            previous = hash(&[word(previous)]);
+            gates.push(split(previous).0);
+        }
+
+        let mut sumcheck = Vec::with_capacity(CONST_PROOF_SIZE_LOG_N);
+        for univariate in &proof.sumcheck_univariate_words {
+            previous = hash(&[&[word(previous)], *univariate].concat());
+            sumcheck.push(split(previous).0);
+        }
+
+        previous = hash(&[&[word(previous)], proof.sumcheck_evaluation_words].concat());
+        let rho = split(previous).0;
+        let folds: Vec<Word> = proof.gemini_fold_commitments.iter().flat_map(|fold| fold.limbs.iter().copied()).collect();
+        previous = hash(&[&[word(previous)], folds.as_slice()].concat());
+        let gemini_r = split(previous).0;
+        previous = hash(&[&[word(previous)], proof.gemini_a_evaluation_words].concat());
+        let shplonk_nu = split(previous).0;
+        previous = hash(&[&[word(previous)], proof.shplonk_q.limbs].concat());
+        let shplonk_z = split(previous).0;
+
+        Self {
+            eta,
+            eta_two,
+            eta_three,
+            beta,
+            gamma,
+            alphas,
+            gates,
+            sumcheck,
+            rho,
+            gemini_r,
+            shplonk_nu,
+            shplonk_z,
+        }
+    }
+}
+
+/// Evaluates a round univariate, given by its values at 0..8, at `challenge`.
+fn barycentric_evaluation(univariate: &[Fr], challenge: Fr) -> Result<Fr> {
+    let points: Vec<Fr> = (0..BATCHED_RELATION_PARTIAL_LENGTH as u64).map(Fr::from).collect();
+    if let Some(i) = points.iter().position(|point| *point == challenge) {
+        return Ok(univariate[i]);
+    }
+    let numerator: Fr = points.iter().map(|point| challenge - point).product();
+    let mut sum = Fr::zero();
+    for (point_i, value) in points.iter().zip(univariate) {
+        let denominator: Fr = points.iter().filter(|point_j| *point_j != point_i).map(|point_j| *point_i - point_j).product();
+        sum += *value * (denominator * (challenge - point_i)).inverse().context("Barycentric denominator is zero")?;
+    }
+    Ok(numerator * sum)
+}
+
+/// Evaluations of the Gemini fold polynomials at the positive powers of r, from the batched
+/// evaluation and the prover's evaluations at the negative ones.
+fn fold_pos_evaluations(sumcheck: &[Fr], mut batched: Fr, gemini_evaluations: &[Fr], powers: &[Fr], log_size: usize) -> Result<Vec<Fr>> {
+    let mut evaluations = vec![Fr::zero(); CONST_PROOF_SIZE_LOG_N];
+    for i in (1..=CONST_PROOF_SIZE_LOG_N).rev() {
+        let power = powers[i - 1];
+        let u = sumcheck[i - 1];
+        // Rounds past the circuit size are padding and do not contribute
+        if i <= log_size {
+            let numerator = power * batched * Fr::from(2u64) - gemini_evaluations[i - 1] * (power * (Fr::one() - u) - u);
+            let denominator = (power * (Fr::one() - u) + u).inverse().context("Gemini fold denominator is zero")?;
+            batched = numerator * denominator;
+            evaluations[i - 1] = batched;
+        }
+    }
+    Ok(evaluations)
+}
+
+/// Every UltraHonk subrelation at the sumcheck point, each scaled by the pow polynomial.
+fn relation_evaluations(p: &[Fr], rp: &RelationParameters, domain: Fr) -> [Fr; NUMBER_OF_SUBRELATIONS] {
+    use entity::*;
+    let w = |index: usize| p[index];
+    let one = Fr::one();
+    let mut evals = [Fr::zero(); NUMBER_OF_SUBRELATIONS];
+
+    // Arithmetic
+    let q_arith = w(Q_ARITH);
+    let neg_half = -Fr::from(2u64).inverse().unwrap();
+    let mut accum = (q_arith - Fr::from(3u64)) * w(Q_M) * w(W_R) * w(W_L) * neg_half;
+    accum += w(Q_L) * w(W_L) + w(Q_R) * w(W_R) + w(Q_O) * w(W_O) + w(Q_4) * w(W_4) + w(Q_C);
+    accum += (q_arith - one) * w(W_4_SHIFT);
+    evals[0] = accum * q_arith * domain;
+    let accum = w(W_L) + w(W_4) - w(W_L_SHIFT) + w(Q_M);
+    evals[1] = accum * (q_arith - Fr::from(2u64)) * (q_arith - one) * q_arith * domain;
+
+    // Permutation
+    let numerator: Fr = (0..4).map(|i| w(W_L + i) + w(ID_1 + i) * rp.beta + rp.gamma).product();
+    let denominator: Fr = (0..4).map(|i| w(W_L + i) + w(SIGMA_1 + i) * rp.beta + rp.gamma).product();
+    evals[2] = ((w(Z_PERM) + w(LAGRANGE_FIRST)) * numerator
+        - (w(Z_PERM_SHIFT) + w(LAGRANGE_LAST) * rp.public_inputs_delta) * denominator)
+        * domain;
+    evals[3] = w(LAGRANGE_LAST) * w(Z_PERM_SHIFT) * domain;
+
+    // Log-derivative lookup
+    let write_term = w(TABLE_1) + rp.gamma + w(TABLE_1 + 1) * rp.eta + w(TABLE_1 + 2) * rp.eta_two + w(TABLE_1 + 3) * rp.eta_three;
+    let derived_1 = w(W_L) + rp.gamma + w(Q_R) * w(W_L_SHIFT);
+    let derived_2 = w(W_R) + w(Q_M) * w(W_R_SHIFT);
+    let derived_3 = w(W_O) + w(Q_C) * w(W_O_SHIFT);
+    let read_term = derived_1 + derived_2 * rp.eta + derived_3 * rp.eta_two + w(Q_O) * rp.eta_three;
+    let read_inverse = w(LOOKUP_INVERSES) * write_term;
+    let write_inverse = w(LOOKUP_INVERSES) * read_term;
+    let inverse_exists = w(LOOKUP_READ_TAGS) + w(Q_LOOKUP) - w(LOOKUP_READ_TAGS) * w(Q_LOOKUP);
+    evals[4] = (read_term * write_term * w(LOOKUP_INVERSES) - inverse_exists) * domain;
+    evals[5] = w(Q_LOOKUP) * read_inverse - w(LOOKUP_READ_COUNTS) * write_inverse;
+
+    // Delta range
+    let deltas = [w(W_R) - w(W_L), w(W_O) - w(W_R), w(W_4) - w(W_O), w(W_L_SHIFT) - w(W_4)];
+    for (i, delta) in deltas.into_iter().enumerate() {
+        evals[6 + i] = delta * (delta - one) * (delta - Fr::from(2u64)) * (delta - Fr::from(3u64)) * w(Q_RANGE) * domain;
+    }
+
+    // Elliptic curve addition and doubling on Grumpkin (y² = x³ - 17)
+    let (x_1, y_1, x_2, y_2, x_3, y_3) = (w(W_R), w(W_O), w(W_L_SHIFT), w(W_4_SHIFT), w(W_R_SHIFT), w(W_O_SHIFT));
+    let q_sign = w(Q_L);
+    let q_is_double = w(Q_M);
+    let q_elliptic = w(Q_ELLIPTIC);
+    let x_diff = x_2 - x_1;
+    let y1_sqr = y_1 * y_1;
+    let y1y2 = y_1 * y_2 * q_sign;
+    let x_add_identity = (x_3 + x_2 + x_1) * x_diff * x_diff - y_2 * y_2 - y1_sqr + y1y2 + y1y2;
+    evals[10] = x_add_identity * domain * q_elliptic * (one - q_is_double);
+    let y_add_identity = (y_1 + y_3) * x_diff + (x_3 - x_1) * (y_2 * q_sign - y_1);
+    evals[11] = y_add_identity * domain * q_elliptic * (one - q_is_double);
+    let x_pow_4 = (y1_sqr + Fr::from(17u64)) * x_1;
+    let x_double_identity = (x_3 + x_1 + x_1) * (y1_sqr * Fr::from(4u64)) - x_pow_4 * Fr::from(9u64);
+    evals[10] += x_double_identity * domain * q_elliptic * q_is_double;
+    let y_double_identity = (x_1 + x_1 + x_1) * x_1 * (x_1 - x_3) - (y_1 + y_1) * (y_1 + y_3);
+    evals[11] += y_double_identity * domain * q_elliptic * q_is_double;
+
+    // Auxiliary: non-native field arithmetic, limb accumulation and ROM/RAM memory
+    let limb_size = Fr::from(1u128 << 68);
+    let sublimb_shift = Fr::from(1u64 << 14);
+    let mut limb_subproduct = w(W_L) * w(W_R_SHIFT) + w(W_L_SHIFT) * w(W_R);
+    let non_native_2 = ((w(W_L) * w(W_4) + w(W_R) * w(W_O) - w(W_O_SHIFT)) * limb_size - w(W_4_SHIFT) + limb_subproduct) * w(Q_4);
+    limb_subproduct = limb_subproduct * limb_size + w(W_L_SHIFT) * w(W_R_SHIFT);
+    let non_native_1 = (limb_subproduct - (w(W_O) + w(W_4))) * w(Q_O);
+    let non_native_3 = (limb_subproduct + w(W_4) - (w(W_O_SHIFT) + w(W_4_SHIFT))) * w(Q_M);
+    let non_native_field_identity = (non_native_1 + non_native_2 + non_native_3) * w(Q_R);
+
+    let accumulate = |limbs: [Fr; 5]| limbs[1..].iter().fold(limbs[0], |acc, limb| acc * sublimb_shift + limb);
+    let limb_accumulator_1 = (accumulate([w(W_R_SHIFT), w(W_L_SHIFT), w(W_O), w(W_R), w(W_L)]) - w(W_4)) * w(Q_4);
+    let limb_accumulator_2 = (accumulate([w(W_O_SHIFT), w(W_R_SHIFT), w(W_L_SHIFT), w(W_4), w(W_O)]) - w(W_4_SHIFT)) * w(Q_M);
+    let limb_accumulator_identity = (limb_accumulator_1 + limb_accumulator_2) * w(Q_O);
+
+    let partial_record_check = w(W_O) * rp.eta_three + w(W_R) * rp.eta_two + w(W_L) * rp.eta + w(Q_C);
+    let memory_record_check = partial_record_check - w(W_4);
+    let index_delta = w(W_L_SHIFT) - w(W_L);
+    let record_delta = w(W_4_SHIFT) - w(W_4);
+    let index_is_monotonically_increasing = index_delta * index_delta - index_delta;
+    let indices_differ = one - index_delta;
+    let q_aux = w(Q_AUX) * domain;
+    evals[13] = indices_differ * record_delta * (w(Q_L) * w(Q_R)) * q_aux;
+    evals[14] = index_is_monotonically_increasing * (w(Q_L) * w(Q_R)) * q_aux;
+    let rom_consistency_check_identity = memory_record_check * (w(Q_L) * w(Q_R));
+
+    let access_type = w(W_4) - partial_record_check;
+    let access_check = access_type * access_type - access_type;
+    let next_gate_access_type = w(W_4_SHIFT) - (w(W_O_SHIFT) * rp.eta_three + w(W_R_SHIFT) * rp.eta_two + w(W_L_SHIFT) * rp.eta);
+    let value_delta = w(W_O_SHIFT) - w(W_O);
+    let values_match_on_read = indices_differ * value_delta * (one - next_gate_access_type);
+    let next_gate_access_type_is_boolean = next_gate_access_type * next_gate_access_type - next_gate_access_type;
+    evals[15] = values_match_on_read * w(Q_ARITH) * q_aux;
+    evals[16] = index_is_monotonically_increasing * w(Q_ARITH) * q_aux;
+    evals[17] = next_gate_access_type_is_boolean * w(Q_ARITH) * q_aux;
+    let ram_consistency_check_identity = access_check * w(Q_ARITH);
+    let timestamp_delta = w(W_R_SHIFT) - w(W_R);
+    let ram_timestamp_check_identity = indices_differ * timestamp_delta - w(W_O);
+    let memory_identity = rom_consistency_check_identity
+        + ram_timestamp_check_identity * (w(Q_4) * w(Q_L))
+        + memory_record_check * (w(Q_M) * w(Q_L))
+        + ram_consistency_check_identity;
+    evals[12] = (memory_identity + non_native_field_identity + limb_accumulator_identity) * q_aux;
+
+    // Poseidon2 external round: add round constants, S-box every lane, multiply by M_E
+    let s: Vec<Fr> = (0..4).map(|i| w(W_L + i) + w(Q_L + i)).collect();
+    let u: Vec<Fr> = s.iter().map(|s| s.pow([5u64])).collect();
+    let t0 = u[0] + u[1];
+    let t1 = u[2] + u[3];
+    let t2 = u[1] + u[1] + t1;
+    let t3 = u[3] + u[3] + t0;
+    let v4 = t1 + t1 + t1 + t1 + t3;
+    let v2 = t0 + t0 + t0 + t0 + t2;
+    let v1 = t3 + v2;
+    let v3 = t2 + v4;
+    let q_external = w(Q_POSEIDON2_EXTERNAL) * domain;
+    for (i, v) in [v1, v2, v3, v4].into_iter().enumerate() {
+        evals[18 + i] = q_external * (v - w(W_L_SHIFT + i));
+    }
+
+    // Poseidon2 internal round: the S-box on the first lane only, then M_I
+    let u = [(w(W_L) + w(Q_L)).pow([5u64]), w(W_R), w(W_O), w(W_4)];
+    let u_sum: Fr = u.iter().sum();
+    let q_internal = w(Q_POSEIDON2_INTERNAL) * domain;
+    for (i, diagonal) in POSEIDON2_INTERNAL_DIAGONAL.iter().enumerate() {
+        let v = u[i] * hex_scalar(diagonal) + u_sum;
+        evals[22 + i] = q_internal * (v - w(W_L_SHIFT + i));
+    }
+    evals
+}
+
+fn hash(words: &[Word]) -> Fr {
+    Fr::from_be_bytes_mod_order(&Keccak256::digest(words.concat()))
+}
+
+/// The low and high 128 bits of a challenge, each a challenge of its own.
+fn split(challenge: Fr) -> (Fr, Fr) {
+    let bytes = word(challenge);
+    (Fr::from_be_bytes_mod_order(&bytes[16..]), Fr::from_be_bytes_mod_order(&bytes[..16]))
+}
+
+fn word(value: Fr) -> Word {
+    let mut word = [0u8; 32];
+    word.copy_from_slice(&value.into_bigint().to_bytes_be());
+    word
+}
+
+/// x and y of a point, the point at infinity being (0, 0).
+fn coordinates(point: G1Affine) -> [Word; 2] {
+    let mut words = [[0u8; 32]; 2];
+    if let Some((x, y)) = point.xy() {
+        words[0].copy_from_slice(&x.into_bigint().to_bytes_be());
+        words[1].copy_from_slice(&y.into_bigint().to_bytes_be());
+    }
+    words
+}
+
+/// A word that fits in a u64.
+fn small(word: &Word) -> Option<u64> {
+    word[..24].iter().all(|byte| *byte == 0).then(|| u64::from_be_bytes(word[24..].try_into().unwrap()))
+}
+
+/// A scalar field element, which must be given in canonical form.
+fn scalar(word: &Word) -> Result<Fr> {
+    if word.as_slice() >= Fr::MODULUS.to_bytes_be().as_slice() {
+        bail!("0x{} is not a field element", hex::encode(word));
+    }
+    Ok(Fr::from_be_bytes_mod_order(word))
+}
+
+fn hex_scalar(value: &str) -> Fr {
+    Fr::from_be_bytes_mod_order(&hex::decode(value.trim_start_matches("0x")).unwrap_or_default())
+}
+
+/// A base field element split into `limbs` of `bits` bits each, least significant first.
+fn base_field(limbs: &[Word], bits: usize) -> Result<Fq> {
+    let mut value = [0u8; 32];
+    for (i, limb) in limbs.iter().enumerate() {
+        for bit in 0..256 {
+            if limb[31 - bit / 8] >> (bit % 8) & 1 == 0 {
+                continue;
+            }
+            let position = i * bits + bit;
+            if bit >= bits || position >= 256 {
+                bail!("Coordinate limb 0x{} is out of range", hex::encode(limb));
+            }
+            value[31 - position / 8] |= 1 << (position % 8);
+        }
+    }
+    if value.as_slice() >= Fq::MODULUS.to_bytes_be().as_slice() {
+        bail!("Coordinate 0x{} is not a field element", hex::encode(value));
+    }
+    Ok(Fq::from_be_bytes_mod_order(&value))
+}
+
+fn curve_point(x: Fq, y: Fq) -> Result<G1Affine> {
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1Affine::zero());
+    }
+    let point = G1Affine::new_unchecked(x, y);
+    if !point.is_on_curve() {
+        bail!("({}, {}) is not on the curve", x, y);
+    }
+    Ok(point)
+}
+
+/// A commitment given as x and y, each split into a low 136-bit and a high limb.
+fn point(limbs: &[Word]) -> Result<G1Affine> {
+    curve_point(base_field(&limbs[..2], 136)?, base_field(&limbs[2..], 136)?)
+}
+
+/// A point of the pairing point accumulator: x and y, each in four 68-bit limbs.
+fn pairing_point(limbs: &[Word]) -> Result<G1Affine> {
+    curve_point(base_field(&limbs[..4], 68)?, base_field(&limbs[4..], 68)?)
+}
+
+fn srs_g2_x() -> Result<G2Affine> {
+    let [x_0, x_1, y_0, y_1] = SRS_G2_X.map(|value| Fq::from_be_bytes_mod_order(&hex::decode(&value[2..]).unwrap_or_default()));
+    let point = G2Affine::new_unchecked(Fq2::new(x_0, x_1), Fq2::new(y_0, y_1));
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        bail!("The SRS G2 point is invalid");
+    }
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Known-answer fixtures made with the bb pinned in the Dockerfile (0.87.0) from an
+    /// eligibility proof, as the prover makes them: `bb prove` and `bb write_vk` with
+    /// `--scheme ultra_honk --oracle_hash keccak --output_format bytes_and_fields
+    /// -o tests/fixtures/ultra_honk`, which leaves `proof`, `public_inputs` and `vk_fields.json`
+    /// there.
+    fn fixtures() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ultra_honk")
+    }
+
+    fn known_answer() -> (VerificationKey, Vec<u8>, Vec<Word>) {
+        let dir = fixtures();
+        let key = VerificationKey::from_fields(&read_fields(&dir.join("vk_fields.json")).unwrap()).unwrap();
+        let proof = fs::read(dir.join("proof")).unwrap();
+        let public_inputs = fs::read(dir.join("public_inputs")).unwrap();
+        let public_inputs = public_inputs.chunks(32).map(|chunk| chunk.try_into().unwrap()).collect();
+        (key, proof, public_inputs)
+    }
+
+    /// The round univariate 3 + 4x + 5x² + … + 10x⁷, by its values at 0..8.
+    fn univariate() -> (Vec<Fr>, impl Fn(Fr) -> Fr) {
+        let evaluate = |x: Fr| (3..=10u64).rev().fold(Fr::zero(), |acc, coefficient| acc * x + Fr::from(coefficient));
+        ((0..8u64).map(|x| evaluate(Fr::from(x))).collect(), evaluate)
+    }
+
+    #[test]
+    fn barycentric_evaluation_matches_the_polynomial() {
+        let (values, evaluate) = univariate();
+        for challenge in [Fr::from(8u64), Fr::from(123_456_789u64), -Fr::from(5u64)] {
+            assert_eq!(barycentric_evaluation(&values, challenge).unwrap(), evaluate(challenge));
+        }
+    }
+
+    #[test]
+    fn barycentric_evaluation_at_a_domain_point_is_its_value() {
+        let (values, _) = univariate();
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(barycentric_evaluation(&values, Fr::from(i as u64)).unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn tampered_copies_each_change_one_word() {
+        let proof = vec![0u8; (PAIRING_POINTS_SIZE + PROOF_SIZE) * 32];
+        let public_inputs = vec![[0u8; 32]; 3];
+        let copies = tampered(&proof, &public_inputs);
+        assert_eq!(copies.len(), 3);
+        for (part, copy, inputs) in copies {
+            let changed: Vec<usize> = copy.chunks(32).zip(proof.chunks(32)).enumerate().filter(|(_, (a, b))| a != b).map(|(i, _)| i).collect();
+            let inputs_changed = inputs.iter().zip(&public_inputs).filter(|(a, b)| a != b).count();
+            match part {
+                "sumcheck univariate" => assert_eq!((changed, inputs_changed), (vec![PAIRING_POINTS_SIZE + 32], 0)),
+                "commitment limb" => assert_eq!((changed, inputs_changed), (vec![PAIRING_POINTS_SIZE], 0)),
+                "public input" => assert_eq!((changed.len(), inputs_changed), (0, 1)),
+                _ => panic!("unexpected tampered part {}", part),
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the bb 0.87.0 fixtures in tests/fixtures/ultra_honk"]
+    fn accepts_a_proof_from_bb() {
+        let (key, proof, public_inputs) = known_answer();
+        key.verify(&proof, &public_inputs).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs the bb 0.87.0 fixtures in tests/fixtures/ultra_honk"]
+    fn rejects_tampered_proofs_from_bb() {
+        let (key, proof, public_inputs) = known_answer();
+        for (part, proof, public_inputs) in tampered(&proof, &public_inputs) {
+            assert!(key.verify(&proof, &public_inputs).is_err(), "accepted a tampered {}", part);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the bb 0.87.0 fixtures in tests/fixtures/ultra_honk"]
+    fn calibration_trusts_a_key_that_rejects_tampered_proofs() {
+        let (key, proof, public_inputs) = known_answer();
+        let native = NativeVerifier::default();
+        let (digest, _) = native.state(&fixtures()).unwrap();
+        native.calibrate(&digest, true, &proof, &public_inputs, &key.verify(&proof, &public_inputs));
+        assert!(matches!(native.state(&fixtures()).unwrap().1, Native::Trusted(_)));
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, JsonRpcError, ProviderError, RpcError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How outbound requests reach the network. Enclaves usually have no resolver and no CA store,
+/// so everything is explicit: environment proxy variables and the system trust store are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct OutboundConfig {
+    /// Proxy every request goes through, e.g. the vsock bridge of the enclave host
+    pub proxy: Option<String>,
+    /// Fixed addresses for host names, used instead of DNS
+    pub dns_overrides: Vec<(String, IpAddr)>,
+}
+
+/// Builds the one client used for every outbound request (control registry, chain RPC, S3, IPFS).
+/// TLS roots are the Mozilla set compiled into the binary.
+pub fn client(config: &OutboundConfig) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .use_rustls_tls()
+        .tls_built_in_root_certs(true)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .no_proxy();
+    if let Some(proxy) = &config.proxy {
+        let proxy = reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy URL '{}'", proxy))?;
+        // Local services such as the attestation server are always reached directly
+        builder = builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_string("localhost,127.0.0.1,::1")));
+    }
+    for (host, ip) in &config.dns_overrides {
+        // The port is ignored; the one from the request URL is used
+        builder = builder.resolve(host, SocketAddr::new(*ip, 0));
+    }
+    builder.build().context("Failed to build the outbound HTTP client")
+}
+
+/// Parses a `--dns-override` value of the form HOST=IP.
+pub fn parse_dns_override(value: &str) -> Result<(String, IpAddr), String> {
+    let (host, ip) = value.split_once('=').ok_or("expected HOST=IP")?;
+    let ip = ip.parse().map_err(|_| format!("invalid IP address '{}'", ip))?;
+    Ok((host.to_string(), ip))
+}
+
+/// JSON-RPC transport for ethers on top of the shared client, so chain RPC goes through the same
+/// proxy and DNS overrides as everything else.
+#[derive(Debug, Clone)]
+pub struct RpcTransport {
+    http: reqwest::Client,
+    url: reqwest::Url,
+    next_id: Arc<AtomicU64>,
+}
+
+impl RpcTransport {
+    pub fn new(http: reqwest::Client, url: &str) -> Result<Self> {
+        Ok(Self {
+            http,
+            url: url.parse().with_context(|| format!("Invalid RPC URL '{}'", url))?,
+            next_id: Arc::new(AtomicU64::new(1)),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    // A missing result and an explicit null are the same to callers (e.g. a pending receipt)
+    #[serde(default)]
+    result: Value,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug)]
+pub enum RpcTransportError {
+    Http(reqwest::Error),
+    Json(serde_json::Error),
+    Rpc(JsonRpcError),
+}
+
+impl fmt::Display for RpcTransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcTransportError::Http(e) => write!(f, "RPC request failed: {}", e),
+            RpcTransportError::Json(e) => write!(f, "Invalid RPC response: {}", e),
+            RpcTransportError::Rpc(e) => write!(f, "RPC error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RpcTransportError {}
+
+impl RpcError for RpcTransportError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            RpcTransportError::Rpc(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            RpcTransportError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<RpcTransportError> for ProviderError {
+    fn from(e: RpcTransportError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(e))
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcTransport {
+    type Error = RpcTransportError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, RpcTransportError>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut body = json!({ "jsonrpc": "2.0", "id": id, "method": method });
+        // Methods without arguments are called with `()`; some nodes reject "params": null
+        let params = serde_json::to_value(params).map_err(RpcTransportError::Json)?;
+        if !params.is_null() {
+            body["params"] = params;
+        }
+
+        let response = self
+            .http
+            .post(self.url.clone())
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(RpcTransportError::Http)?;
+        let bytes = response.bytes().await.map_err(RpcTransportError::Http)?;
+        let response: RpcResponse = serde_json::from_slice(&bytes).map_err(RpcTransportError::Json)?;
+        if let Some(error) = response.error {
+            return Err(RpcTransportError::Rpc(error));
+        }
+        serde_json::from_value(response.result).map_err(RpcTransportError::Json)
+    }
+}
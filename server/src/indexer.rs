@@ -0,0 +1,191 @@
+use crate::config::{ChainConfig, SharedConfig};
+use crate::evm;
+use crate::submitter;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// Proofs remembered for correlation; the oldest are forgotten first.
+const MAX_ISSUED_PROOFS: usize = 100_000;
+
+/// Blocks fetched per chain and poll, so a long outage is caught up gradually.
+const MAX_BLOCKS_PER_POLL: u64 = 100;
+
+/// IDs of proofs this node has issued, with everything seen on-chain for them.
+#[derive(Default)]
+pub struct IssuedProofs {
+    inner: Mutex<Issued>,
+}
+
+#[derive(Default)]
+struct Issued {
+    order: VecDeque<String>,
+    proofs: HashMap<String, OnchainStatus>,
+    /// Tenant each proof was issued to, as jobs record it (none for the default tenant)
+    tenants: HashMap<String, Option<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OnchainStatus {
+    pub proof_id: String,
+    pub issued_at: DateTime<Utc>,
+    /// `verified` once a verify call for the proof succeeded, `failed` if every call seen
+    /// reverted, `pending` while none has been seen
+    pub status: &'static str,
+    pub verifications: Vec<OnchainVerification>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OnchainVerification {
+    pub chain: String,
+    pub tx_hash: String,
+    pub block_number: u64,
+    /// Account that sent the verify call
+    pub from: String,
+    pub success: bool,
+}
+
+impl IssuedProofs {
+    pub fn record(&self, proof_id: &str, tenant: Option<&str>) {
+        let mut issued = self.inner.lock().unwrap();
+        if issued.proofs.contains_key(proof_id) {
+            return;
+        }
+        if issued.order.len() >= MAX_ISSUED_PROOFS {
+            if let Some(oldest) = issued.order.pop_front() {
+                issued.proofs.remove(&oldest);
+                issued.tenants.remove(&oldest);
+            }
+        }
+        issued.order.push_back(proof_id.to_string());
+        issued.tenants.insert(proof_id.to_string(), tenant.map(str::to_string));
+        issued.proofs.insert(
+            proof_id.to_string(),
+            OnchainStatus {
+                proof_id: proof_id.to_string(),
+                issued_at: Utc::now(),
+                status: "pending",
+                verifications: Vec::new(),
+            },
+        );
+    }
+
+    fn contains(&self, proof_id: &str) -> bool {
+        self.inner.lock().unwrap().proofs.contains_key(proof_id)
+    }
+
+    /// On-chain status of a proof issued here to `tenant`; `None` for proofs this node does not
+    /// know or issued to another tenant.
+    pub fn status(&self, proof_id: &str, tenant: Option<&str>) -> Option<OnchainStatus> {
+        let issued = self.inner.lock().unwrap();
+        let proof_id = proof_id.to_lowercase();
+        if issued.tenants.get(&proof_id)?.as_deref() != tenant {
+            return None;
+        }
+        issued.proofs.get(&proof_id).cloned()
+    }
+
+    fn add_verification(&self, proof_id: &str, verification: OnchainVerification) {
+        let mut issued = self.inner.lock().unwrap();
+        let Some(status) = issued.proofs.get_mut(proof_id) else {
+            return;
+        };
+        if status.verifications.iter().any(|seen| seen.tx_hash == verification.tx_hash) {
+            return;
+        }
+        status.verifications.push(verification);
+        status.status = if status.verifications.iter().any(|v| v.success) { "verified" } else { "failed" };
+    }
+}
+
+/// Watches the verifier contract on every configured chain and attaches verify calls to the
+/// proofs this node issued.
+///
+/// The bb-generated verifier emits no events and `verify` is a view function, so the indexer
+/// scans blocks for transactions sent directly to the verifier and recovers the proof ID from
+/// their calldata. Calls made from other contracts need traces and are not seen.
+pub struct OnchainIndexer {
+    config: SharedConfig,
+    http: reqwest::Client,
+    issued: Arc<IssuedProofs>,
+    /// Next block to scan, by chain name
+    cursors: Mutex<BTreeMap<String, u64>>,
+}
+
+impl OnchainIndexer {
+    pub fn new(config: SharedConfig, http: reqwest::Client, issued: Arc<IssuedProofs>) -> Self {
+        Self {
+            config,
+            http,
+            issued,
+            cursors: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn issued(&self) -> &IssuedProofs {
+        &self.issued
+    }
+
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        loop {
+            // Read per poll so chains added by a fleet release are picked up
+            let chains = self.config.read().unwrap().chains.clone();
+            for (name, chain) in chains.iter().filter(|(_, chain)| !chain.verifier_address.is_empty()) {
+                if let Err(e) = self.poll(name, chain).await {
+                    warn!("On-chain indexing of chain '{}' failed: {:#}", name, e);
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn poll(&self, name: &str, chain: &ChainConfig) -> Result<()> {
+        let verifier = submitter::verifier_address(name, chain)?;
+        let provider = submitter::provider(&self.http, name, chain)?;
+        let latest = provider.get_block_number().await.context("Failed to fetch the latest block")?.as_u64();
+        // Proofs issued before startup are not tracked, so there is nothing to backfill
+        let from = *self.cursors.lock().unwrap().entry(name.to_string()).or_insert(latest);
+        let to = latest.min(from + MAX_BLOCKS_PER_POLL - 1);
+
+        for number in from..=to {
+            let block = provider
+                .get_block_with_txs(number)
+                .await
+                .with_context(|| format!("Failed to fetch block {}", number))?;
+            for tx in block.map(|block| block.transactions).unwrap_or_default() {
+                if tx.to != Some(verifier) {
+                    continue;
+                }
+                let Some(proof) = evm::decode_verify_proof(&tx.input) else {
+                    continue;
+                };
+                let proof_id = evm::proof_id(proof);
+                if !self.issued.contains(&proof_id) {
+                    continue;
+                }
+                let receipt = provider
+                    .get_transaction_receipt(tx.hash)
+                    .await
+                    .with_context(|| format!("Failed to fetch receipt of {:?}", tx.hash))?;
+                self.issued.add_verification(
+                    &proof_id,
+                    OnchainVerification {
+                        chain: name.to_string(),
+                        tx_hash: format!("{:?}", tx.hash),
+                        block_number: number,
+                        from: format!("{:?}", tx.from),
+                        success: receipt.and_then(|receipt| receipt.status).map(|s| s.as_u64() == 1).unwrap_or(false),
+                    },
+                );
+            }
+            // Advance block by block so a failed fetch is retried from where it stopped
+            self.cursors.lock().unwrap().insert(name.to_string(), number + 1);
+        }
+        Ok(())
+    }
+}
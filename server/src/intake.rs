@@ -0,0 +1,204 @@
+use crate::eip712;
+use crate::encryption;
+use crate::honk;
+use crate::abi::Inputs;
+use crate::prover::{self, BmiDerivation, ErrorCode, ProofRequest, ProofResponse};
+use aes_gcm::aead::{KeyInit, OsRng};
+use aes_gcm::Aes256Gcm;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Anything above these is a data-entry error rather than an applicant to prove
+pub const MAX_PLAUSIBLE_AGE: u32 = 150;
+pub const MAX_PLAUSIBLE_BMI: u32 = 1000;
+pub const MIN_PLAUSIBLE_HEIGHT_CM: f64 = 50.0;
+pub const MAX_PLAUSIBLE_HEIGHT_CM: f64 = 272.0;
+pub const MIN_PLAUSIBLE_WEIGHT_KG: f64 = 2.0;
+pub const MAX_PLAUSIBLE_WEIGHT_KG: f64 = 650.0;
+pub const PLAUSIBLE_SYSTOLIC_BP: std::ops::RangeInclusive<u32> = 50..=300;
+pub const PLAUSIBLE_DIASTOLIC_BP: std::ops::RangeInclusive<u32> = 20..=200;
+
+/// The health fields of a request, the only part that is sealed while a job waits.
+#[derive(Serialize, Deserialize)]
+struct HealthInputs {
+    age: u32,
+    bmi_multiplied: u32,
+    #[serde(default)]
+    height_cm: Option<f64>,
+    #[serde(default)]
+    weight_kg: Option<f64>,
+    #[serde(default)]
+    date_of_birth: Option<chrono::NaiveDate>,
+    #[serde(default)]
+    smoker: Option<bool>,
+    #[serde(default)]
+    systolic_bp: Option<u32>,
+    #[serde(default)]
+    diastolic_bp: Option<u32>,
+    #[serde(default)]
+    conditions: Option<u64>,
+    #[serde(default)]
+    inputs: Inputs,
+}
+
+/// A validated request as it travels from intake to the prover: routing options in the clear,
+/// health inputs encrypted.
+pub struct SealedRequest {
+    /// The request with its health fields zeroed
+    pub request: ProofRequest,
+    inputs: Vec<u8>,
+}
+
+/// Front half of the pipeline: validates requests, checks consent and seals the raw health
+/// inputs under a key that exists only in this process's memory. The proving side opens them
+/// right before witness generation, so plaintext inputs never sit in the queue and are gone
+/// with the process.
+pub struct Intake {
+    cipher: Aes256Gcm,
+    require_consent: bool,
+}
+
+impl Intake {
+    pub fn new(require_consent: bool) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng)),
+            require_consent,
+        }
+    }
+
+    /// Checks a request without sealing it, for paths that prove outside the queue. A BMI sent as
+    /// height and weight is computed into `bmi_multiplied`, a date of birth into `age`.
+    pub fn validate(&self, request: &mut ProofRequest) -> std::result::Result<(), String> {
+        if self.require_consent && !request.consent {
+            return Err("The applicant's consent is required (\"consent\": true)".to_string());
+        }
+        match (request.height_cm, request.weight_kg) {
+            (None, None) => {}
+            (Some(height_cm), Some(weight_kg)) => {
+                if !(MIN_PLAUSIBLE_HEIGHT_CM..=MAX_PLAUSIBLE_HEIGHT_CM).contains(&height_cm)
+                    || !(MIN_PLAUSIBLE_WEIGHT_KG..=MAX_PLAUSIBLE_WEIGHT_KG).contains(&weight_kg)
+                {
+                    return Err(format!(
+                        "Implausible inputs: height_cm must be {}-{} and weight_kg {}-{}",
+                        MIN_PLAUSIBLE_HEIGHT_CM, MAX_PLAUSIBLE_HEIGHT_CM, MIN_PLAUSIBLE_WEIGHT_KG, MAX_PLAUSIBLE_WEIGHT_KG
+                    ));
+                }
+                let derived = BmiDerivation::new(height_cm, weight_kg).bmi_multiplied;
+                if request.bmi_multiplied != prover::UNSET && request.bmi_multiplied != derived {
+                    return Err("Send either bmi_multiplied or height_cm and weight_kg, not both".to_string());
+                }
+                request.bmi_multiplied = derived;
+            }
+            _ => return Err("height_cm and weight_kg must be sent together".to_string()),
+        }
+        if let Some(date_of_birth) = request.date_of_birth {
+            let today = chrono::Utc::now().date_naive();
+            if date_of_birth > today {
+                return Err("date_of_birth cannot be in the future".to_string());
+            }
+            let age = prover::age_on(date_of_birth, today);
+            if request.age != prover::UNSET && request.age != age {
+                return Err("Send either age or date_of_birth, not both".to_string());
+            }
+            request.age = age;
+        }
+        if request.circuit_id.is_none() && (request.age == prover::UNSET || request.bmi_multiplied == prover::UNSET) {
+            return Err("age and bmi_multiplied are required".to_string());
+        }
+        let implausible = |value: u32, max: u32| value > max && value != prover::UNSET;
+        if implausible(request.age, MAX_PLAUSIBLE_AGE) || implausible(request.bmi_multiplied, MAX_PLAUSIBLE_BMI) {
+            return Err(format!(
+                "Implausible inputs: age must be at most {} and bmi_multiplied at most {}",
+                MAX_PLAUSIBLE_AGE, MAX_PLAUSIBLE_BMI
+            ));
+        }
+        match (request.systolic_bp, request.diastolic_bp) {
+            (None, None) => {}
+            (Some(systolic), Some(diastolic)) => {
+                if !PLAUSIBLE_SYSTOLIC_BP.contains(&systolic) || !PLAUSIBLE_DIASTOLIC_BP.contains(&diastolic) || diastolic >= systolic {
+                    return Err(format!(
+                        "Implausible inputs: blood pressure must be {}-{} over {}-{} mmHg",
+                        PLAUSIBLE_SYSTOLIC_BP.start(),
+                        PLAUSIBLE_SYSTOLIC_BP.end(),
+                        PLAUSIBLE_DIASTOLIC_BP.start(),
+                        PLAUSIBLE_DIASTOLIC_BP.end()
+                    ));
+                }
+            }
+            _ => return Err("systolic_bp and diastolic_bp must be sent together".to_string()),
+        }
+        if let Some(name) = prover::SERVER_INPUTS.iter().find(|name| request.inputs.contains_key(**name)) {
+            return Err(format!(
+                "inputs cannot set {:?}; it comes from the request's own fields or the policy",
+                name
+            ));
+        }
+        if request.circuit_id.is_some() && request.policy.is_some() {
+            return Err("policy only applies to the eligibility circuit, not a circuit_id".to_string());
+        }
+        if request.tier.is_some() && request.policy.is_none() {
+            return Err("tier picks a tier of a policy; send the policy too".to_string());
+        }
+        if let Some(as_of) = request.as_of {
+            if as_of > chrono::Utc::now() {
+                return Err("as_of cannot be in the future".to_string());
+            }
+        }
+        if let Some(nonce) = &request.nonce {
+            honk::nonce_word(nonce).map_err(|e| e.to_string())?;
+        }
+        if let Some(hash) = &request.expected_circuit_hash {
+            if eip712::parse_bytes32(hash).is_err() {
+                return Err("expected_circuit_hash must be 32 bytes of hex".to_string());
+            }
+        }
+        if let Some(commitment) = &request.applicant_commitment {
+            if eip712::parse_bytes32(commitment).is_err() {
+                return Err("applicant_commitment must be 32 bytes of hex".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates and seals a request; a rejected request is answered with `invalid_request`.
+    pub fn admit(&self, mut request: ProofRequest) -> Result<std::result::Result<SealedRequest, ProofResponse>> {
+        if let Err(message) = self.validate(&mut request) {
+            return Ok(Err(ProofResponse::failed(ErrorCode::InvalidRequest, message)));
+        }
+
+        let inputs = HealthInputs {
+            age: std::mem::take(&mut request.age),
+            bmi_multiplied: std::mem::take(&mut request.bmi_multiplied),
+            height_cm: request.height_cm.take(),
+            weight_kg: request.weight_kg.take(),
+            date_of_birth: request.date_of_birth.take(),
+            smoker: request.smoker.take(),
+            systolic_bp: request.systolic_bp.take(),
+            diastolic_bp: request.diastolic_bp.take(),
+            conditions: request.conditions.take(),
+            inputs: std::mem::take(&mut request.inputs),
+        };
+        let inputs = encryption::encrypt(&self.cipher, b"intake", &serde_json::to_vec(&inputs)?)
+            .context("Failed to seal inputs")?;
+        Ok(Ok(SealedRequest { request, inputs }))
+    }
+
+    /// Restores the full request for witness generation.
+    pub fn open(&self, sealed: SealedRequest) -> Result<ProofRequest> {
+        let plaintext = encryption::decrypt(&self.cipher, b"intake", &sealed.inputs)?;
+        let inputs: HealthInputs = serde_json::from_slice(&plaintext).context("Sealed inputs are malformed")?;
+        Ok(ProofRequest {
+            age: inputs.age,
+            bmi_multiplied: inputs.bmi_multiplied,
+            height_cm: inputs.height_cm,
+            weight_kg: inputs.weight_kg,
+            date_of_birth: inputs.date_of_birth,
+            smoker: inputs.smoker,
+            systolic_bp: inputs.systolic_bp,
+            diastolic_bp: inputs.diastolic_bp,
+            conditions: inputs.conditions,
+            inputs: inputs.inputs,
+            ..sealed.request
+        })
+    }
+}
@@ -0,0 +1,188 @@
+use crate::audit::AuditLog;
+use anyhow::{Context, Result};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Directories under a circuit package holding verification keys.
+const VK_DIRS: &[&str] = &["target/verifier_vk", "target/recursive_vk"];
+
+/// Files modified more recently than this may still be being written (e.g. a verification key
+/// bb is generating) and are not pinned yet.
+const SETTLE_TIME: Duration = Duration::from_secs(60);
+
+/// Where bb keeps the downloaded SRS, relative to $HOME.
+const SRS_DIR: &str = ".bb-crs";
+
+#[derive(Default)]
+struct Pins {
+    /// Resolved circuit directories the pins were taken from
+    releases: Vec<PathBuf>,
+    hashes: BTreeMap<PathBuf, String>,
+}
+
+/// Periodically re-hashes compiled circuits, verification keys and the SRS, and compares them
+/// with the hashes pinned when they were first seen. A mismatch means the enclave volume was
+/// corrupted or tampered with: it is logged, audited and reported by /status until a new circuit
+/// release is activated.
+pub struct IntegrityMonitor {
+    circuit_dirs: Mutex<Vec<PathBuf>>,
+    srs_dir: Option<PathBuf>,
+    pins: Mutex<Pins>,
+    intact: AtomicBool,
+    audit: Arc<AuditLog>,
+}
+
+impl IntegrityMonitor {
+    pub fn new(circuit_dirs: Vec<PathBuf>, audit: Arc<AuditLog>) -> Self {
+        Self {
+            circuit_dirs: Mutex::new(circuit_dirs),
+            srs_dir: std::env::var_os("HOME").map(|home| PathBuf::from(home).join(SRS_DIR)),
+            pins: Mutex::new(Pins::default()),
+            intact: AtomicBool::new(true),
+            audit,
+        }
+    }
+
+    pub fn is_intact(&self) -> bool {
+        self.intact.load(Ordering::Relaxed)
+    }
+
+    /// Drops every pin and clears a raised alert, after the operator has deliberately replaced
+    /// circuit artifacts in place. The next check pins them afresh.
+    pub fn repin(&self) {
+        *self.pins.lock().unwrap() = Pins::default();
+        self.intact.store(true, Ordering::Relaxed);
+    }
+
+    /// Adds a circuit registered at runtime. Pins are retaken from the new set of directories.
+    pub fn watch(&self, circuit_dir: PathBuf) {
+        self.circuit_dirs.lock().unwrap().push(circuit_dir);
+    }
+
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        loop {
+            let monitor = self.clone();
+            match tokio::task::spawn_blocking(move || monitor.check()).await {
+                Ok(Ok(mismatches)) if !mismatches.is_empty() => self.alert(mismatches),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => error!("Integrity check failed: {:#}", e),
+                Err(e) => error!("Integrity check panicked: {}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Hashes every artifact, pinning files seen for the first time (keys are generated after
+    /// startup), and returns a description of each pinned file that changed or disappeared.
+    fn check(&self) -> Result<Vec<String>> {
+        let releases: Vec<PathBuf> = self
+            .circuit_dirs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()))
+            .collect();
+        let mut current = BTreeMap::new();
+        for path in self.artifacts(&releases)? {
+            current.insert(path.clone(), sha256_file(&path)?);
+        }
+
+        let mut pins = self.pins.lock().unwrap();
+        // An activation replaces the circuit artifacts, so pins are retaken from the new release
+        if pins.releases != releases {
+            if !pins.releases.is_empty() {
+                info!("Circuit release changed; integrity pins retaken");
+            }
+            *pins = Pins {
+                releases,
+                hashes: BTreeMap::new(),
+            };
+            self.intact.store(true, Ordering::Relaxed);
+        }
+
+        let mut mismatches = Vec::new();
+        for (path, expected) in &pins.hashes {
+            match current.get(path) {
+                Some(actual) if actual == expected => {}
+                Some(actual) => mismatches.push(format!("{} changed (pinned {}, found {})", path.display(), expected, actual)),
+                None => mismatches.push(format!("{} is missing", path.display())),
+            }
+        }
+        for (path, hash) in current {
+            if !pins.hashes.contains_key(&path) && is_settled(&path) {
+                pins.hashes.insert(path, hash);
+            }
+        }
+        Ok(mismatches)
+    }
+
+    fn alert(&self, mismatches: Vec<String>) {
+        let was_intact = self.intact.swap(false, Ordering::Relaxed);
+        for mismatch in &mismatches {
+            error!("INTEGRITY ALERT: {}", mismatch);
+        }
+        if was_intact {
+            self.audit.record("integrity_mismatch", json!({ "mismatches": mismatches }));
+        }
+    }
+
+    fn artifacts(&self, circuit_dirs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for circuit_dir in circuit_dirs {
+            collect(&circuit_dir.join("target"), is_compiled_circuit, &mut files)?;
+            for vk_dir in VK_DIRS {
+                collect(&circuit_dir.join(vk_dir), |_| true, &mut files)?;
+            }
+        }
+        if let Some(srs_dir) = &self.srs_dir {
+            collect(srs_dir, |_| true, &mut files)?;
+        }
+        Ok(files)
+    }
+}
+
+/// Regular files directly inside `dir` accepted by `filter`; a missing directory has none.
+fn collect(dir: &Path, filter: impl Fn(&Path) -> bool, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to list {}", dir.display())),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && filter(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Compiled circuit artifacts are the only JSON files in target/ apart from public inputs bb
+/// may write there when proving.
+fn is_compiled_circuit(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.ends_with(".json") && !name.starts_with("public_inputs")
+}
+
+fn is_settled(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| modified.elapsed().unwrap_or_default() >= SETTLE_TIME)
+        .unwrap_or(false)
+}
+
+/// Hex sha256 of a file, streamed.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hex::encode(hasher.finalize()))
+}
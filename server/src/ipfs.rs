@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct AddResponse {
+    #[serde(rename = "Hash")]
+    hash: String,
+}
+
+/// Client for the HTTP RPC API of an IPFS node (Kubo), used to add and pin content.
+pub struct IpfsClient {
+    http: reqwest::Client,
+    api_url: String,
+}
+
+impl IpfsClient {
+    pub fn new(http: reqwest::Client, api_url: String) -> Self {
+        Self {
+            http,
+            api_url: api_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Adds and pins `body`, returning its CID.
+    pub async fn add(&self, file_name: &str, body: Vec<u8>) -> Result<String> {
+        let part = reqwest::multipart::Part::bytes(body).file_name(file_name.to_string());
+        let form = reqwest::multipart::Form::new().part("file", part);
+
+        let response: AddResponse = self
+            .http
+            .post(format!("{}/api/v0/add?pin=true&cid-version=1", self.api_url))
+            .multipart(form)
+            .send()
+            .await
+            .context("Failed to reach IPFS node")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Unexpected response from IPFS node")?;
+        Ok(response.hash)
+    }
+}
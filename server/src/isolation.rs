@@ -0,0 +1,187 @@
+use crate::control::ReleaseHistory;
+use crate::limits::ResourceLimits;
+use crate::logging::LogFormat;
+use crate::prover::{ErrorCode, NoirProver, ProofRequest, ProofResponse, ProvingScheme};
+use crate::retry::RetryPolicy;
+use crate::telemetry::{self, SpanData};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::process::Command;
+use zeroize::Zeroizing;
+
+/// Line-delimited JSON message a worker process sends back over its socket.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WorkerReply {
+    Ok(Box<ProofResponse>),
+    Error(String),
+}
+
+/// A worker's reply with the spans it recorded, which the parent exports.
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkerMessage {
+    reply: WorkerReply,
+    #[serde(default)]
+    spans: Vec<SpanData>,
+}
+
+/// Runs every proof in a freshly exec'd `prover-worker` process. A worker that crashes or gets
+/// OOM-killed only fails its own job; the listener, queue and keys live in the parent.
+pub struct IsolatedProver {
+    circuit_path: String,
+    default_scheme: ProvingScheme,
+    retry_policy: RetryPolicy,
+    limits: ResourceLimits,
+    history: Option<ReleaseHistory>,
+    scratch: Option<PathBuf>,
+    circuits_file: Option<PathBuf>,
+    log_format: LogFormat,
+    log_level: String,
+    log_private_inputs: bool,
+}
+
+impl IsolatedProver {
+    pub fn new(circuit_path: String, default_scheme: ProvingScheme, retry_policy: RetryPolicy, limits: ResourceLimits) -> Self {
+        Self {
+            circuit_path,
+            default_scheme,
+            retry_policy,
+            limits,
+            history: None,
+            scratch: None,
+            circuits_file: None,
+            log_format: LogFormat::Text,
+            log_level: "info".to_string(),
+            log_private_inputs: false,
+        }
+    }
+
+    /// Has workers log like the server, so their lines carry the same request IDs and format.
+    pub fn with_logging(mut self, format: LogFormat, level: String, private_inputs: bool) -> Self {
+        self.log_format = format;
+        self.log_level = level;
+        self.log_private_inputs = private_inputs;
+        self
+    }
+
+    /// Has workers keep private inputs in memory under `scratch`.
+    pub fn with_scratch(mut self, scratch: Option<PathBuf>) -> Self {
+        self.scratch = scratch;
+        self
+    }
+
+    /// Lets workers serve `circuit_id` requests from the circuits listed in `circuits_file`.
+    pub fn with_circuits_file(mut self, circuits_file: Option<PathBuf>) -> Self {
+        self.circuits_file = circuits_file;
+        self
+    }
+
+    /// Lets workers serve `as_of` requests against past circuit releases.
+    pub fn with_history(mut self, history: Option<ReleaseHistory>) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub async fn generate_proof(&self, request: ProofRequest) -> Result<ProofResponse> {
+        let socket_path = std::env::temp_dir().join(format!("zk-prover-{}.sock", uuid::Uuid::new_v4().simple()));
+        let result = self.run_worker(&socket_path, request).await;
+        let _ = std::fs::remove_file(&socket_path);
+        result
+    }
+
+    async fn run_worker(&self, socket_path: &Path, request: ProofRequest) -> Result<ProofResponse> {
+        let listener = UnixListener::bind(socket_path)
+            .with_context(|| format!("Failed to bind worker socket {}", socket_path.display()))?;
+
+        let scheme = self.default_scheme.to_possible_value().expect("scheme has a name");
+        let log_format = self.log_format.to_possible_value().expect("log format has a name");
+        let mut command = Command::new(std::env::current_exe()?);
+        command
+            .args(["--scheme", scheme.get_name()])
+            .args(["--prover-retries", &self.retry_policy.max_retries.to_string()])
+            .args(["--retry-backoff-ms", &self.retry_policy.initial_backoff.as_millis().to_string()])
+            .args(["--job-memory-mb", &(self.limits.memory_bytes.unwrap_or(0) / (1024 * 1024)).to_string()])
+            .args(["--job-cpu-secs", &self.limits.cpu_secs.unwrap_or(0).to_string()])
+            .args(["--log-format", log_format.get_name(), "--log-level", &self.log_level]);
+        if self.log_private_inputs {
+            command.arg("--log-private-inputs");
+        }
+        if let Some(scratch) = &self.scratch {
+            command.args(["--private-inputs", "memory", "--scratch-dir"]).arg(scratch);
+        }
+        if let Some(circuits_file) = &self.circuits_file {
+            command.arg("--circuits-file").arg(circuits_file);
+        }
+        command
+            .args(["prover-worker", "--circuit", &self.circuit_path, "--socket"])
+            .arg(socket_path)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(history) = &self.history {
+            command.arg("--history").arg(history.state_dir());
+        }
+        if telemetry::enabled() {
+            command.arg("--trace");
+        }
+        let mut child = command.spawn().context("Failed to spawn prover worker")?;
+
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            status = child.wait() => bail!("Prover worker exited before connecting ({})", status?),
+        };
+
+        // Inputs travel over the socket rather than argv so they never show up in the process table
+        let (reader, mut writer) = stream.into_split();
+        let mut payload = Zeroizing::new(serde_json::to_vec(&request)?);
+        payload.push(b'\n');
+        writer.write_all(&payload).await?;
+        writer.flush().await?;
+
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await?;
+        let status = child.wait().await?;
+
+        if line.trim().is_empty() {
+            return Ok(ProofResponse::failed(ErrorCode::BackendError, format!("Prover worker crashed ({})", status)));
+        }
+        let message: WorkerMessage = serde_json::from_str(&line).context("Malformed reply from prover worker")?;
+        telemetry::submit(message.spans);
+        match message.reply {
+            WorkerReply::Ok(response) => Ok(*response),
+            WorkerReply::Error(e) => bail!(e),
+        }
+    }
+}
+
+/// Entry point of the `prover-worker` subcommand: proves exactly one request received over
+/// `socket` and exits.
+pub async fn run_worker_process(socket: PathBuf, prover: NoirProver) -> Result<()> {
+    let stream = UnixStream::connect(&socket)
+        .await
+        .with_context(|| format!("Failed to connect to {}", socket.display()))?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = Zeroizing::new(String::new());
+    BufReader::new(reader).read_line(&mut line).await?;
+    let request: ProofRequest = serde_json::from_str(&line).context("Malformed job from parent")?;
+
+    let reply = match prover.generate_proof(request).await {
+        Ok(response) => WorkerReply::Ok(Box::new(response)),
+        Err(e) => WorkerReply::Error(format!("{:#}", e)),
+    };
+
+    let message = WorkerMessage {
+        reply,
+        spans: telemetry::take_finished(),
+    };
+    let mut payload = serde_json::to_vec(&message)?;
+    payload.push(b'\n');
+    writer.write_all(&payload).await?;
+    writer.flush().await?;
+    Ok(())
+}
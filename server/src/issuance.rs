@@ -0,0 +1,309 @@
+use crate::prover::{ErrorCode, ProofRequest, ProofResponse};
+use crate::signing::EnclaveSigner;
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::error;
+
+/// `prev_hash` of the first entry.
+const GENESIS_HASH: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One proof request's outcome, as chained into the log. Never holds private inputs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuanceRecord {
+    /// Position in the chain, from 0
+    pub seq: u64,
+    pub timestamp: String,
+    pub request_id: Option<String>,
+    /// HMAC of the request's inputs under a key derived from the master key, so the operator can
+    /// match a disputed request without the log revealing (or allowing a brute force of) them
+    pub request_hash: String,
+    pub circuit_hash: Option<String>,
+    /// keccak256 of the proof, as `proof_id` in the response
+    pub proof_hash: Option<String>,
+    pub success: bool,
+    pub error_code: Option<ErrorCode>,
+    /// `hash` of the previous entry
+    pub prev_hash: String,
+}
+
+/// A log line: the record and the sha256 of its JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuanceEntry {
+    #[serde(flatten)]
+    pub record: IssuanceRecord,
+    pub hash: String,
+}
+
+impl IssuanceRecord {
+    fn hash(&self) -> String {
+        let json = serde_json::to_vec(self).expect("issuance records serialize");
+        format!("0x{}", hex::encode(Sha256::digest(json)))
+    }
+}
+
+/// Outcome of checking a chain.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainVerification {
+    pub valid: bool,
+    pub entries: u64,
+    /// Hash of the last entry that checked out
+    pub head_hash: String,
+    /// Where and why the chain breaks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A chain's verification, signed by the enclave (EIP-191 over `head_message`). An auditor who
+/// keeps it can later check that the entry at `entries - 1` still has hash `head_hash`.
+#[derive(Debug, Serialize)]
+pub struct SignedHead {
+    #[serde(flatten)]
+    pub verification: ChainVerification,
+    pub signer: String,
+    pub signature: String,
+}
+
+struct Chain {
+    file: File,
+    next_seq: u64,
+    last_hash: String,
+    /// Byte offset of each entry's line, so a page is read without the lines before it
+    offsets: Vec<u64>,
+    /// The chain as verified at startup and extended by every entry written since
+    verification: ChainVerification,
+}
+
+/// Append-only, hash-chained record of every proof request the pool handles. Each entry commits
+/// to the one before it, so editing, dropping or reordering entries breaks every later hash;
+/// exports carry an enclave signature over the head, so auditors holding an earlier export can
+/// also detect the chain being rewritten from scratch.
+pub struct IssuanceLog {
+    path: PathBuf,
+    request_key: [u8; 32],
+    chain: Mutex<Chain>,
+}
+
+impl IssuanceLog {
+    /// Opens the log, continuing the chain it holds. A chain that does not verify is reported
+    /// and appended to anyway, so the break stays visible to auditors; a file that is not an
+    /// issuance log at all stops startup.
+    pub fn open(path: &Path, master_key: &[u8]) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open issuance log {}", path.display()))?;
+        let (entries, offsets) = read_indexed(path)?;
+        let (next_seq, last_hash) = match entries.last() {
+            Some(entry) => (entry.record.seq + 1, entry.hash.clone()),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        let verification = verify_chain(&entries);
+        if !verification.valid {
+            error!("Issuance log {} does not verify: {}", path.display(), verification.error.as_deref().unwrap_or_default());
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(master_key)?;
+        mac.update(b"zk-insurance issuance request key");
+        Ok(Self {
+            path: path.to_path_buf(),
+            request_key: mac.finalize().into_bytes().into(),
+            chain: Mutex::new(Chain {
+                file,
+                next_seq,
+                last_hash,
+                offsets,
+                verification,
+            }),
+        })
+    }
+
+    /// Chains the outcome of the request with `request_hash` (see `request_hash`).
+    pub fn record(&self, request_id: Option<&str>, request_hash: String, result: &Result<ProofResponse>) {
+        let (circuit_hash, proof_hash, success, error_code) = match result {
+            Ok(response) => (response.circuit_hash.clone(), response.proof_id.clone(), response.success, response.error_code),
+            Err(_) => (None, None, false, Some(ErrorCode::BackendError)),
+        };
+        let mut chain = self.chain.lock().unwrap();
+        let record = IssuanceRecord {
+            seq: chain.next_seq,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            request_id: request_id.map(str::to_string),
+            request_hash,
+            circuit_hash,
+            proof_hash,
+            success,
+            error_code,
+            prev_hash: chain.last_hash.clone(),
+        };
+        let entry = IssuanceEntry {
+            hash: record.hash(),
+            record,
+        };
+        let mut line = serde_json::to_string(&entry).expect("issuance entries serialize");
+        line.push('\n');
+        let written = chain.file.metadata().and_then(|metadata| {
+            chain.file.write_all(line.as_bytes())?;
+            Ok(metadata.len())
+        });
+        match written {
+            Ok(offset) => {
+                chain.offsets.push(offset);
+                chain.next_seq += 1;
+                chain.last_hash = entry.hash.clone();
+                // A chain broken before startup stays broken at the same entry
+                if chain.verification.valid {
+                    chain.verification.entries += 1;
+                    chain.verification.head_hash = entry.hash;
+                }
+            }
+            Err(e) => error!("Failed to write issuance log entry: {}", e),
+        }
+    }
+
+    /// HMAC over the inputs that decide a proof, not the per-submission IDs.
+    pub fn request_hash(&self, request: &ProofRequest) -> String {
+        let mut inputs = json!({
+            "age": request.age,
+            "bmi_multiplied": request.bmi_multiplied,
+            "policy": request.policy,
+            "scheme": request.scheme,
+            "as_of": request.as_of,
+            "applicant_commitment": request.applicant_commitment,
+        });
+        // Only present for other circuits or ones with more inputs, so hashes of plain requests
+        // are unchanged
+        if !request.inputs.is_empty() {
+            inputs["inputs"] = json!(request.inputs);
+        }
+        let underwriting = request.underwriting();
+        if let Some(tier) = &request.tier {
+            inputs["tier"] = json!(tier);
+        }
+        if let Some(nonce) = &request.nonce {
+            inputs["nonce"] = json!(nonce);
+        }
+        if !underwriting.is_empty() {
+            inputs["underwriting"] = json!(serde_json::Map::from_iter(underwriting.into_iter().map(|(name, value)| (name.to_string(), value))));
+        }
+        if let Some(circuit_id) = &request.circuit_id {
+            inputs["circuit_id"] = json!(circuit_id);
+        }
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.request_key).expect("HMAC accepts any key length");
+        mac.update(inputs.to_string().as_bytes());
+        format!("0x{}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// Up to `limit` entries starting at the `from`th, read from its offset on.
+    pub fn entries(&self, from: u64, limit: usize) -> Result<Vec<IssuanceEntry>> {
+        let chain = self.chain.lock().unwrap();
+        let Some(&offset) = chain.offsets.get(from as usize) else {
+            return Ok(Vec::new());
+        };
+        let mut file = File::open(&self.path).with_context(|| format!("Failed to read {}", self.path.display()))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let count = limit.min(chain.offsets.len() - from as usize);
+        let mut entries = Vec::with_capacity(count);
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if entries.len() == count {
+                break;
+            }
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line).context("Issuance log entry is not valid")?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The chain's verification, kept up to date as entries are written rather than re-read.
+    /// Entries served by `entries` are read from the file, so an auditor checking them against
+    /// the signed head still sees any edit made to it since.
+    pub fn verify(&self) -> ChainVerification {
+        self.chain.lock().unwrap().verification.clone()
+    }
+
+    /// Verifies the chain and has the enclave sign the head that checked out.
+    pub fn signed_head(&self, signer: &EnclaveSigner) -> Result<SignedHead> {
+        let verification = self.verify();
+        Ok(SignedHead {
+            signature: signer.sign_message(head_message(verification.entries, &verification.head_hash).as_bytes())?,
+            signer: signer.address(),
+            verification,
+        })
+    }
+}
+
+/// What the enclave signs for a chain of `entries` ending in `head_hash`.
+pub fn head_message(entries: u64, head_hash: &str) -> String {
+    format!("zk-insurance issuance log\nentries: {}\nhead_hash: {}", entries, head_hash)
+}
+
+/// Checks that every entry's hash matches its contents and links to the one before.
+pub fn verify_chain(entries: &[IssuanceEntry]) -> ChainVerification {
+    let mut head_hash = GENESIS_HASH.to_string();
+    for (index, entry) in entries.iter().enumerate() {
+        let error = if entry.record.seq != index as u64 {
+            Some(format!("entry {} has seq {}", index, entry.record.seq))
+        } else if entry.record.prev_hash != head_hash {
+            Some(format!("entry {} does not link to the entry before it", index))
+        } else if entry.record.hash() != entry.hash {
+            Some(format!("entry {} does not match its hash", index))
+        } else {
+            None
+        };
+        if error.is_some() {
+            return ChainVerification {
+                valid: false,
+                entries: index as u64,
+                head_hash,
+                error,
+            };
+        }
+        head_hash = entry.hash.clone();
+    }
+    ChainVerification {
+        valid: true,
+        entries: entries.len() as u64,
+        head_hash,
+        error: None,
+    }
+}
+
+pub fn read_entries(path: &Path) -> Result<Vec<IssuanceEntry>> {
+    Ok(read_indexed(path)?.0)
+}
+
+/// The log's entries and the byte offset of each one's line.
+fn read_indexed(path: &Path) -> Result<(Vec<IssuanceEntry>, Vec<u64>)> {
+    let file = File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let (mut entries, mut offsets) = (Vec::new(), Vec::new());
+    let (mut line, mut offset) = (String::new(), 0);
+    for index in 1.. {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+        if !line.trim().is_empty() {
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => bail!("line {} is not an issuance entry: {}", index, e),
+            }
+            offsets.push(offset);
+        }
+        offset += read as u64;
+    }
+    Ok((entries, offsets))
+}
@@ -0,0 +1,706 @@
+use crate::encryption::{self, FieldCipher};
+use crate::metrics::StepDurations;
+use crate::eip712::ProofPayment;
+use crate::prover::{ProofRequest, ProofResponse};
+use crate::signing::EnclaveSignature;
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sqlx::any::{AnyPoolOptions, AnyRow};
+use sqlx::{AnyPool, Row};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+/// Most jobs one page of GET /admin/jobs returns.
+pub const MAX_PAGE: i64 = 500;
+
+/// What the key sealing job fields is derived for, from the master key.
+pub const CIPHER_PURPOSE: &str = "zk-insurance job store key";
+
+/// Connections kept to the database; writes are short and never held across proving.
+const MAX_CONNECTIONS: u32 = 4;
+
+/// Plain SQL both SQLite and Postgres accept. Timestamps are RFC 3339 in UTC with fixed
+/// precision, so they sort as text.
+const SCHEMA: [&str; 4] = [
+    "CREATE TABLE IF NOT EXISTS proof_jobs (
+        job_id TEXT PRIMARY KEY,
+        request_id TEXT,
+        node TEXT NOT NULL,
+        state TEXT NOT NULL,
+        request_hash TEXT NOT NULL,
+        policy TEXT,
+        circuit_id TEXT,
+        scheme TEXT,
+        created_at TEXT NOT NULL,
+        started_at TEXT,
+        finished_at TEXT,
+        error_code TEXT,
+        message TEXT,
+        proof_id TEXT,
+        circuit_hash TEXT,
+        vk_hash TEXT,
+        expires_at BIGINT,
+        proof_hex TEXT,
+        public_inputs TEXT,
+        enclave_signature TEXT,
+        ipfs_cid TEXT,
+        witness_secs DOUBLE PRECISION,
+        prove_secs DOUBLE PRECISION,
+        request TEXT,
+        tenant TEXT,
+        payment TEXT
+    )",
+    "CREATE INDEX IF NOT EXISTS proof_jobs_created_at ON proof_jobs (created_at)",
+    "CREATE INDEX IF NOT EXISTS proof_jobs_request_id ON proof_jobs (request_id)",
+    // Proofs charged to escrow deposits and not yet released, so a restart does not lose them
+    "CREATE TABLE IF NOT EXISTS payment_releases (
+        deposit_id TEXT PRIMARY KEY,
+        node TEXT NOT NULL,
+        proofs BIGINT NOT NULL,
+        price TEXT NOT NULL,
+        charged_at TEXT NOT NULL
+    )",
+];
+
+/// Columns added since the table was first created, for databases made by older versions.
+/// Adding a column that is already there fails, which is expected and ignored.
+const ADDED_COLUMNS: [&str; 3] = ["request TEXT", "tenant TEXT", "payment TEXT"];
+
+/// Columns of a listing; the proof artifacts are only returned for a single job.
+const SUMMARY_COLUMNS: &str = "job_id, request_id, node, state, request_hash, policy, circuit_id, scheme, created_at, \
+    started_at, finished_at, error_code, message, proof_id, circuit_hash, vk_hash, expires_at, ipfs_cid, witness_secs, prove_secs, tenant";
+
+/// Columns that may hold sealed values: `request` always does, the others when fields are sealed.
+const SEALED_COLUMNS: [&str; 7] = ["policy", "error_code", "message", "proof_hex", "public_inputs", "enclave_signature", "request"];
+
+/// Columns only returned for a single job.
+const ARTIFACT_COLUMNS: &str = "proof_hex, public_inputs, enclave_signature, payment";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Admitted and waiting for a proving worker
+    Queued,
+    Proving,
+    Succeeded,
+    /// Finished without a proof; `error_code` says why
+    Failed,
+    /// The server stopped before the job finished
+    Interrupted,
+}
+
+impl JobState {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Proving => "proving",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+            JobState::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// A proof job as stored. Holds the HMAC commitment to the request's inputs (as in the issuance
+/// log), never the inputs themselves.
+#[derive(Debug, Serialize)]
+pub struct JobRecord {
+    pub job_id: String,
+    pub request_id: Option<String>,
+    /// Enclave signer address of the node that ran the job
+    pub node: String,
+    pub state: String,
+    pub request_hash: String,
+    pub policy: Option<String>,
+    pub circuit_id: Option<String>,
+    /// As requested; None for the server's default
+    pub scheme: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub error_code: Option<String>,
+    pub message: Option<String>,
+    pub proof_id: Option<String>,
+    pub circuit_hash: Option<String>,
+    pub vk_hash: Option<String>,
+    pub expires_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof_hex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_inputs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enclave_signature: Option<EnclaveSignature>,
+    pub ipfs_cid: Option<String>,
+    pub witness_secs: Option<f64>,
+    pub prove_secs: Option<f64>,
+    /// Tenant the job was proven for; None for the default tenant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Receipt for the proof's price, when it was paid for from an escrow deposit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment: Option<ProofPayment>,
+}
+
+/// Where a job stands, as served by GET /jobs/{job_id} to clients polling a job submitted with
+/// POST /prove/async.
+#[derive(Debug, Serialize)]
+pub struct JobStatus {
+    pub job_id: String,
+    pub request_id: Option<String>,
+    pub status: String,
+    /// Why the job failed, when it did
+    pub error_code: Option<String>,
+    pub message: Option<String>,
+    /// Set once the job succeeded; the proof is at GET /proofs/{job_id}
+    pub proof_id: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+impl From<JobRecord> for JobStatus {
+    fn from(job: JobRecord) -> Self {
+        Self {
+            job_id: job.job_id,
+            request_id: job.request_id,
+            status: job.state,
+            error_code: job.error_code,
+            message: job.message,
+            proof_id: job.proof_id,
+            created_at: job.created_at,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+        }
+    }
+}
+
+/// A job recorded as queued that no worker has picked up yet, with its request.
+pub struct QueuedJob {
+    pub job_id: String,
+    pub created_at: String,
+    pub request: ProofRequest,
+}
+
+/// A successful job's proof, as served by GET /proofs/{job_id}.
+#[derive(Debug, Serialize)]
+pub struct StoredProof {
+    pub job_id: String,
+    pub proof_hex: String,
+    pub public_inputs: String,
+    /// keccak256 of the proof bytes, as `proof_id` in the original response
+    pub proof_id: Option<String>,
+    pub circuit_hash: Option<String>,
+    /// sha256 of the verification key the proof checks against; ultra_honk proofs only
+    pub vk_hash: Option<String>,
+    /// Unix time the proof expires at, for circuits that prove one
+    pub expires_at: Option<i64>,
+    pub enclave_signature: Option<EnclaveSignature>,
+    /// CID of the bundle published to IPFS, if it was
+    pub ipfs_cid: Option<String>,
+    pub request_id: Option<String>,
+    pub policy: Option<String>,
+    pub circuit_id: Option<String>,
+    pub scheme: Option<String>,
+    /// Enclave signer address of the node that generated the proof
+    pub node: String,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+    /// Receipt for the proof's price, when it was paid for from an escrow deposit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment: Option<ProofPayment>,
+}
+
+impl StoredProof {
+    /// None unless the job produced a proof.
+    fn from_job(job: JobRecord) -> Option<Self> {
+        if job.state != JobState::Succeeded.as_str() {
+            return None;
+        }
+        Some(Self {
+            job_id: job.job_id,
+            proof_hex: job.proof_hex?,
+            public_inputs: job.public_inputs?,
+            proof_id: job.proof_id,
+            circuit_hash: job.circuit_hash,
+            vk_hash: job.vk_hash,
+            expires_at: job.expires_at,
+            enclave_signature: job.enclave_signature,
+            ipfs_cid: job.ipfs_cid,
+            request_id: job.request_id,
+            policy: job.policy,
+            circuit_id: job.circuit_id,
+            scheme: job.scheme,
+            node: job.node,
+            created_at: job.created_at,
+            finished_at: job.finished_at,
+            payment: job.payment,
+        })
+    }
+}
+
+/// An issued proof as listed by GET /proofs, for insurers reconciling proofs against their
+/// application pipeline. Leaves out the `job_id`, which is what a proof is downloaded by, and
+/// the fields sealed at rest; what remains is no more than the issuance log publishes.
+#[derive(Debug, Serialize)]
+pub struct ProofSummary {
+    pub request_id: Option<String>,
+    pub proof_id: Option<String>,
+    pub status: String,
+    pub circuit_id: Option<String>,
+    pub circuit_hash: Option<String>,
+    pub vk_hash: Option<String>,
+    pub scheme: Option<String>,
+    pub expires_at: Option<i64>,
+    pub ipfs_cid: Option<String>,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+}
+
+impl From<JobRecord> for ProofSummary {
+    fn from(job: JobRecord) -> Self {
+        Self {
+            request_id: job.request_id,
+            proof_id: job.proof_id,
+            status: job.state,
+            circuit_id: job.circuit_id,
+            circuit_hash: job.circuit_hash,
+            vk_hash: job.vk_hash,
+            scheme: job.scheme,
+            expires_at: job.expires_at,
+            ipfs_cid: job.ipfs_cid,
+            created_at: job.created_at,
+            finished_at: job.finished_at,
+        }
+    }
+}
+
+/// Filters for a page of job history, newest first.
+#[derive(Debug, Default, Deserialize)]
+pub struct JobFilter {
+    pub state: Option<JobState>,
+    pub request_id: Option<String>,
+    pub circuit_id: Option<String>,
+    /// Only jobs created at or after this RFC 3339 time
+    pub after: Option<String>,
+    /// Only jobs created before this RFC 3339 time, to page back from the last one returned
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+    /// Only jobs of this tenant; `default` for those of the default tenant
+    pub tenant: Option<String>,
+}
+
+impl JobFilter {
+    /// Jobs one page holds: `limit`, within 1 and `MAX_PAGE`, or 100.
+    pub fn page_size(&self) -> i64 {
+        self.limit.unwrap_or(100).clamp(1, MAX_PAGE)
+    }
+}
+
+/// Proof jobs kept in SQLite or Postgres (--database-url), so their state and outcome survive
+/// restarts and operators can look through the history. Recording is best effort: a database
+/// error is logged and never fails the proof.
+///
+/// A queued job keeps its request, sealed, until a worker picks it up, so jobs queued when the
+/// server stops are resumed at the next start. With `seal_fields`, what the applicant applied
+/// for (`policy`), why a job failed (`error_code`, `message`) and the proof itself are sealed
+/// too. The rest stays in plain text so jobs can be filtered and paged.
+pub struct JobStore {
+    pool: AnyPool,
+    node: String,
+    cipher: FieldCipher,
+    seal_fields: bool,
+}
+
+impl JobStore {
+    /// Connects, creates the table if needed and marks the jobs this node was proving when it
+    /// last stopped as `interrupted`. Those it had only queued are left for `queued_jobs`.
+    pub async fn connect(url: &str, node: &str, cipher: FieldCipher, seal_fields: bool) -> Result<Self> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect(url)
+            .await
+            .context("Failed to connect to --database-url")?;
+        create_schema(&pool).await?;
+        // A queued job without its request (recorded by an older version) cannot be resumed either
+        let interrupted = sqlx::query(
+            "UPDATE proof_jobs SET state = $1, finished_at = $2, message = $3, request = NULL \
+             WHERE node = $4 AND (state = $5 OR (state = $6 AND request IS NULL))",
+        )
+        .bind(JobState::Interrupted.as_str())
+        .bind(now())
+        .bind("The server stopped before the job finished")
+        .bind(node)
+        .bind(JobState::Proving.as_str())
+        .bind(JobState::Queued.as_str())
+        .execute(&pool)
+        .await?
+        .rows_affected();
+        if interrupted > 0 {
+            warn!("{} proof jobs were cut short by the last shutdown and are marked interrupted", interrupted);
+        }
+        info!(
+            "Recording proof jobs in {}{}",
+            url.split(':').next().unwrap_or_default(),
+            if seal_fields { ", sealed" } else { "" }
+        );
+        Ok(Self {
+            pool,
+            node: node.to_string(),
+            cipher,
+            seal_fields,
+        })
+    }
+
+    /// Jobs this node queued and had not started when it last stopped, oldest first, to be
+    /// resumed. A job whose request cannot be opened is marked `interrupted` instead.
+    pub async fn queued_jobs(&self) -> Result<Vec<QueuedJob>> {
+        let rows = sqlx::query("SELECT job_id, created_at, request FROM proof_jobs WHERE node = $1 AND state = $2 ORDER BY created_at")
+            .bind(&self.node)
+            .bind(JobState::Queued.as_str())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut jobs = Vec::new();
+        for row in rows {
+            let job_id: String = row.try_get("job_id")?;
+            let request = self
+                .open(&row, &job_id, "request")
+                .and_then(|request| Ok(serde_json::from_str(&Zeroizing::new(request.context("No request was kept")?))?));
+            match request {
+                Ok(request) => jobs.push(QueuedJob {
+                    created_at: row.try_get("created_at")?,
+                    job_id,
+                    request,
+                }),
+                Err(e) => {
+                    warn!("Queued proof job {} cannot be resumed: {:#}", job_id, e);
+                    let result = sqlx::query(
+                        "UPDATE proof_jobs SET state = $1, finished_at = $2, message = $3, request = NULL WHERE job_id = $4",
+                    )
+                    .bind(JobState::Interrupted.as_str())
+                    .bind(now())
+                    .bind("The server stopped before the job started, and its request could not be recovered")
+                    .bind(&job_id)
+                    .execute(&self.pool)
+                    .await;
+                    log_failure(&job_id, result);
+                }
+            }
+        }
+        Ok(jobs)
+    }
+
+    /// Re-seals every sealed field in the database at `url` from `old` to `new`, in one
+    /// transaction, so the job history stays readable after the master key changes. Returns how
+    /// many fields were re-sealed.
+    pub async fn reseal(url: &str, old: &FieldCipher, new: &FieldCipher) -> Result<usize> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect(url)
+            .await
+            .context("Failed to connect to --database-url")?;
+        create_schema(&pool).await?;
+        let mut tx = pool.begin().await?;
+        let rows = sqlx::query(&format!("SELECT job_id, {} FROM proof_jobs", SEALED_COLUMNS.join(", ")))
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut resealed = 0;
+        for row in rows {
+            let job_id: String = row.try_get("job_id")?;
+            for column in SEALED_COLUMNS {
+                let Some(value) = row.try_get::<Option<String>, _>(column)?.filter(|value| encryption::is_sealed_field(value)) else {
+                    continue;
+                };
+                let context = field_context(&job_id, column);
+                let plaintext = old.open(&context, value).with_context(|| format!("Failed to open {} of proof job {}", column, job_id))?;
+                sqlx::query(&format!("UPDATE proof_jobs SET {} = $1 WHERE job_id = $2", column))
+                    .bind(new.seal(&context, &plaintext)?)
+                    .bind(&job_id)
+                    .execute(&mut *tx)
+                    .await?;
+                resealed += 1;
+            }
+        }
+        tx.commit().await?;
+        Ok(resealed)
+    }
+
+    /// Records a job admitted at `created_at` for `request`, whose inputs commit to `request_hash`.
+    /// The request itself is kept sealed until a worker picks the job up.
+    pub async fn queued(&self, job_id: &str, created_at: &str, request: &ProofRequest, request_hash: &str) -> Result<()> {
+        let sealed_request = self.cipher.seal(&field_context(job_id, "request"), &Zeroizing::new(serde_json::to_string(request)?))?;
+        sqlx::query(
+            "INSERT INTO proof_jobs (job_id, request_id, node, state, request_hash, policy, circuit_id, scheme, created_at, request, tenant) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+        )
+        .bind(job_id)
+        .bind(request.request_id.clone())
+        .bind(&self.node)
+        .bind(JobState::Queued.as_str())
+        .bind(request_hash)
+        .bind(self.seal(job_id, "policy", request.policy.clone()))
+        .bind(request.circuit_id.clone())
+        .bind(scheme_name(request))
+        .bind(created_at)
+        .bind(sealed_request)
+        .bind(request.tenant.clone())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Records that a worker picked the job up; from here on it is not resumed after a restart.
+    pub async fn started(&self, job_id: &str) {
+        let result = sqlx::query("UPDATE proof_jobs SET state = $1, started_at = $2, request = NULL WHERE job_id = $3")
+            .bind(JobState::Proving.as_str())
+            .bind(now())
+            .bind(job_id)
+            .execute(&self.pool)
+            .await;
+        log_failure(job_id, result);
+    }
+
+    /// Records how the job ended, with its proof and the time its steps took.
+    pub async fn finished(&self, job_id: &str, result: &Result<ProofResponse>, steps: Option<&StepDurations>) {
+        let (state, error_code, message, response) = match result {
+            Ok(response) => {
+                let state = if response.success { JobState::Succeeded } else { JobState::Failed };
+                let error_code = response
+                    .error_code
+                    .and_then(|code| serde_json::to_value(code).ok())
+                    .and_then(|code| code.as_str().map(str::to_string));
+                (state, error_code, Some(response.message.clone()), Some(response))
+            }
+            Err(e) => (JobState::Failed, Some("backend_error".to_string()), Some(format!("{:#}", e)), None),
+        };
+        let proof = response.filter(|response| response.success);
+        let result = sqlx::query(
+            "UPDATE proof_jobs SET state = $1, finished_at = $2, error_code = $3, message = $4, proof_id = $5, \
+             circuit_hash = $6, vk_hash = $7, expires_at = $8, proof_hex = $9, public_inputs = $10, enclave_signature = $11, \
+             ipfs_cid = $12, witness_secs = $13, prove_secs = $14, payment = $15, request = NULL WHERE job_id = $16",
+        )
+        .bind(state.as_str())
+        .bind(now())
+        .bind(self.seal(job_id, "error_code", error_code))
+        .bind(self.seal(job_id, "message", message))
+        .bind(proof.and_then(|response| response.proof_id.clone()))
+        .bind(proof.and_then(|response| response.circuit_hash.clone()))
+        .bind(proof.and_then(|response| response.vk_hash.clone()))
+        .bind(proof.and_then(|response| response.expires_at).map(|expires_at| expires_at as i64))
+        .bind(self.seal(job_id, "proof_hex", proof.map(|response| response.proof_hex.clone())))
+        .bind(self.seal(job_id, "public_inputs", proof.map(|response| response.public_inputs.clone())))
+        .bind(self.seal(
+            job_id,
+            "enclave_signature",
+            proof.and_then(|response| response.enclave_signature.as_ref()).and_then(|signature| serde_json::to_string(signature).ok()),
+        ))
+        .bind(proof.and_then(|response| response.ipfs_cid.clone()))
+        .bind(steps.and_then(|steps| steps.witness))
+        .bind(steps.and_then(|steps| steps.prove))
+        .bind(proof.and_then(|response| response.payment.as_ref()).and_then(|payment| serde_json::to_string(payment).ok()))
+        .bind(job_id)
+        .execute(&self.pool)
+        .await;
+        log_failure(job_id, result);
+    }
+
+    /// One job with its proof; None if there is no such job.
+    pub async fn get(&self, job_id: &str) -> Result<Option<JobRecord>> {
+        let row = sqlx::query(&format!("SELECT {}, {} FROM proof_jobs WHERE job_id = $1", SUMMARY_COLUMNS, ARTIFACT_COLUMNS))
+            .bind(job_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| self.record(&row, true)).transpose()
+    }
+
+    /// The proof of a successful job of `tenant` (None for the default tenant); None for unknown
+    /// jobs, those of other tenants and those that did not succeed.
+    pub async fn proof(&self, job_id: &str, tenant: Option<&str>) -> Result<Option<StoredProof>> {
+        Ok(self
+            .get(job_id)
+            .await?
+            .filter(|job| job.tenant.as_deref() == tenant)
+            .and_then(StoredProof::from_job))
+    }
+
+    /// A page of jobs matching `filter`, newest first, without their proofs.
+    pub async fn list(&self, filter: &JobFilter) -> Result<Vec<JobRecord>> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        for (column, operator, value) in [
+            ("state", "=", filter.state.map(|state| state.as_str().to_string())),
+            ("request_id", "=", filter.request_id.clone()),
+            ("circuit_id", "=", filter.circuit_id.clone()),
+            ("created_at", ">=", filter.after.clone()),
+            ("created_at", "<", filter.before.clone()),
+            ("tenant", "=", filter.tenant.clone().filter(|tenant| tenant != crate::DEFAULT_TENANT)),
+        ] {
+            if let Some(value) = value {
+                values.push(value);
+                conditions.push(format!("{} {} ${}", column, operator, values.len()));
+            }
+        }
+        // Jobs of the default tenant are stored without one
+        if filter.tenant.as_deref() == Some(crate::DEFAULT_TENANT) {
+            conditions.push("tenant IS NULL".to_string());
+        }
+        let filter_clause = if conditions.is_empty() { String::new() } else { format!(" WHERE {}", conditions.join(" AND ")) };
+        let sql = format!(
+            "SELECT {} FROM proof_jobs{} ORDER BY created_at DESC LIMIT ${}",
+            SUMMARY_COLUMNS,
+            filter_clause,
+            values.len() + 1
+        );
+        let mut query = sqlx::query(&sql);
+        for value in values {
+            query = query.bind(value);
+        }
+        let rows = query
+            .bind(filter.page_size())
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(|row| self.record(row, false)).collect()
+    }
+
+    /// Adds `proofs` proofs at `price` each to the pending release of deposit `deposit_id`.
+    pub async fn charge(&self, deposit_id: &str, proofs: u64, price: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO payment_releases (deposit_id, node, proofs, price, charged_at) VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (deposit_id) DO UPDATE SET proofs = payment_releases.proofs + excluded.proofs, charged_at = excluded.charged_at",
+        )
+        .bind(deposit_id)
+        .bind(&self.node)
+        .bind(proofs as i64)
+        .bind(price)
+        .bind(now())
+        .execute(&self.pool)
+        .await
+        .with_context(|| format!("Failed to record the charge to deposit {}", deposit_id))?;
+        Ok(())
+    }
+
+    /// Deposits this node charged proofs to and has not released, as (deposit ID, proofs, price).
+    pub async fn pending_releases(&self) -> Result<Vec<(String, u64, String)>> {
+        let rows = sqlx::query("SELECT deposit_id, proofs, price FROM payment_releases WHERE node = $1 ORDER BY charged_at")
+            .bind(&self.node)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| Ok((row.try_get("deposit_id")?, row.try_get::<i64, _>("proofs")? as u64, row.try_get("price")?)))
+            .collect()
+    }
+
+    /// Forgets the pending release of deposit `deposit_id` once it has landed.
+    pub async fn released(&self, deposit_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM payment_releases WHERE deposit_id = $1")
+            .bind(deposit_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// `value` as it is stored in `column` of job `job_id`: sealed when fields are.
+    fn seal(&self, job_id: &str, column: &str, value: Option<String>) -> Option<String> {
+        let Some(plaintext) = value.as_ref().filter(|_| self.seal_fields) else {
+            return value;
+        };
+        match self.cipher.seal(&field_context(job_id, column), plaintext) {
+            Ok(sealed) => Some(sealed),
+            Err(e) => {
+                warn!("Failed to seal {} of proof job {}, so it is not stored: {:#}", column, job_id, e);
+                None
+            }
+        }
+    }
+
+    /// `column` of job `job_id` as read from `row`, opened if it was sealed.
+    fn open(&self, row: &AnyRow, job_id: &str, column: &str) -> Result<Option<String>> {
+        let Some(value) = row.try_get::<Option<String>, _>(column)? else {
+            return Ok(None);
+        };
+        self.cipher
+            .open(&field_context(job_id, column), value)
+            .map(Some)
+            .with_context(|| format!("Failed to open {} of proof job {}", column, job_id))
+    }
+
+    fn record(&self, row: &AnyRow, artifacts: bool) -> Result<JobRecord> {
+        let job_id: String = row.try_get("job_id")?;
+        let artifact = |column| if artifacts { self.open(row, &job_id, column) } else { Ok(None) };
+        Ok(JobRecord {
+            request_id: row.try_get("request_id")?,
+            node: row.try_get("node")?,
+            state: row.try_get("state")?,
+            request_hash: row.try_get("request_hash")?,
+            policy: self.open(row, &job_id, "policy")?,
+            circuit_id: row.try_get("circuit_id")?,
+            scheme: row.try_get("scheme")?,
+            created_at: row.try_get("created_at")?,
+            started_at: row.try_get("started_at")?,
+            finished_at: row.try_get("finished_at")?,
+            error_code: self.open(row, &job_id, "error_code")?,
+            message: self.open(row, &job_id, "message")?,
+            proof_id: row.try_get("proof_id")?,
+            circuit_hash: row.try_get("circuit_hash")?,
+            vk_hash: row.try_get("vk_hash")?,
+            expires_at: row.try_get("expires_at")?,
+            proof_hex: artifact("proof_hex")?,
+            public_inputs: artifact("public_inputs")?,
+            enclave_signature: artifact("enclave_signature")?
+                .map(|signature| serde_json::from_str(&signature))
+                .transpose()
+                .context("Stored enclave_signature is not valid")?,
+            ipfs_cid: row.try_get("ipfs_cid")?,
+            witness_secs: row.try_get("witness_secs")?,
+            prove_secs: row.try_get("prove_secs")?,
+            tenant: row.try_get("tenant")?,
+            payment: artifacts
+                .then(|| row.try_get::<Option<String>, _>("payment"))
+                .transpose()?
+                .flatten()
+                .map(|payment| serde_json::from_str(&payment))
+                .transpose()
+                .context("Stored payment is not valid")?,
+            job_id,
+        })
+    }
+}
+
+async fn create_schema(pool: &AnyPool) -> Result<()> {
+    for statement in SCHEMA {
+        sqlx::query(statement).execute(pool).await.context("Failed to create the proof_jobs table")?;
+    }
+    for column in ADDED_COLUMNS {
+        let _ = sqlx::query(&format!("ALTER TABLE proof_jobs ADD COLUMN {}", column)).execute(pool).await;
+    }
+    Ok(())
+}
+
+/// What a sealed field is bound to: the job and column it is stored in.
+fn field_context(job_id: &str, column: &str) -> String {
+    format!("{}/{}", job_id, column)
+}
+
+/// The current time as job timestamps are stored.
+pub fn now() -> String {
+    timestamp(Utc::now())
+}
+
+/// `at` as job timestamps are stored, so the two compare as text.
+pub fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// The scheme `request` asks for, as stored; None for the server's default.
+pub fn scheme_name(request: &ProofRequest) -> Option<String> {
+    request.scheme.and_then(|scheme| scheme.to_possible_value()).map(|value| value.get_name().to_string())
+}
+
+fn log_failure<T>(job_id: &str, result: std::result::Result<T, sqlx::Error>) {
+    if let Err(e) = result {
+        warn!("Failed to record proof job {}: {}", job_id, e);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::encryption;
+use anyhow::{anyhow, bail, Context, Result};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// How long a fetched key set is used before it is fetched again.
+const JWKS_TTL: Duration = Duration::from_secs(600);
+
+/// Least time between fetches of the key set, so tokens with made-up `kid`s or an unreachable
+/// provider do not turn every request into a fetch.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Clock skew tolerated on `exp` and `nbf`, in seconds.
+const LEEWAY_SECS: u64 = 60;
+
+/// Signature algorithms accepted. Symmetric ones are not: their key would have to be shared
+/// with the server, which is what using the insurer's identity provider avoids.
+const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+#[derive(Default)]
+struct KeyCache {
+    keys: Option<JwkSet>,
+    /// When `keys` were fetched
+    fetched: Option<Instant>,
+    /// Last fetch, whether or not it succeeded
+    attempted: Option<Instant>,
+}
+
+impl KeyCache {
+    fn find(&self, kid: &str) -> Option<Jwk> {
+        self.keys.as_ref().and_then(|keys| keys.find(kid)).cloned()
+    }
+}
+
+/// Checks bearer tokens issued by the insurer's identity provider against the keys it publishes
+/// at its JWKS URL, so access is granted and revoked there without the server holding any
+/// credentials. Keys are cached and fetched again when they expire or a token names one the
+/// cache does not have, which is how key rotations at the provider are picked up.
+pub struct JwtVerifier {
+    http: reqwest::Client,
+    jwks_url: String,
+    issuer: String,
+    audience: String,
+    /// Claim naming the tenant a token's holder belongs to
+    tenant_claim: String,
+    cache: RwLock<KeyCache>,
+}
+
+impl JwtVerifier {
+    pub fn new(http: reqwest::Client, jwks_url: String, issuer: String, audience: String, tenant_claim: String) -> Self {
+        Self {
+            http,
+            jwks_url,
+            issuer,
+            audience,
+            tenant_claim,
+            cache: RwLock::default(),
+        }
+    }
+
+    /// Fetches the key set ahead of the first request.
+    pub async fn warm_up(&self) -> Result<()> {
+        self.refresh().await
+    }
+
+    /// Checks `token`'s signature, issuer, audience and validity period, and returns the tenant
+    /// its tenant claim names; None for tokens without one, which are for the default tenant.
+    pub async fn verify(&self, token: &str) -> Result<Option<String>> {
+        let header = jsonwebtoken::decode_header(token).context("Malformed bearer token")?;
+        if !ALGORITHMS.contains(&header.alg) {
+            bail!("Bearer tokens signed with {:?} are not accepted", header.alg);
+        }
+        let kid = header.kid.ok_or_else(|| anyhow!("Bearer token names no signing key (kid)"))?;
+        let key = self.key(&kid).await?;
+        if key.common.algorithm.is_some_and(|algorithm| algorithm != header.alg) {
+            bail!("Bearer token is not signed with the algorithm of key {}", kid);
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        validation.validate_nbf = true;
+        validation.leeway = LEEWAY_SECS;
+        let decoding_key = DecodingKey::from_jwk(&key).with_context(|| format!("Unusable key {} in {}", kid, self.jwks_url))?;
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &decoding_key, &validation).context("Invalid bearer token")?.claims;
+        match claims.get(&self.tenant_claim) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(tenant)) => {
+                encryption::validate_tenant_id(tenant).with_context(|| format!("Invalid {} claim", self.tenant_claim))?;
+                Ok(Some(tenant.clone()))
+            }
+            Some(_) => bail!("The bearer token's {} claim is not a string", self.tenant_claim),
+        }
+    }
+
+    /// The key `kid`, fetching the key set again when it is stale or lacks the key. While the
+    /// provider cannot be reached, the keys fetched last stay in use.
+    async fn key(&self, kid: &str) -> Result<Jwk> {
+        {
+            let cache = self.cache.read().await;
+            let fresh = cache.fetched.is_some_and(|fetched| fetched.elapsed() < JWKS_TTL);
+            let recently_fetched = cache.attempted.is_some_and(|attempted| attempted.elapsed() < MIN_REFRESH_INTERVAL);
+            match cache.find(kid) {
+                Some(key) if fresh || recently_fetched => return Ok(key),
+                None if recently_fetched && cache.keys.is_none() => bail!("The JWKS at {} could not be fetched yet", self.jwks_url),
+                None if recently_fetched => bail!("Unknown signing key {}", kid),
+                _ => {}
+            }
+        }
+        let refreshed = self.refresh().await;
+        match self.cache.read().await.find(kid) {
+            Some(key) => {
+                if let Err(e) = refreshed {
+                    warn!("Using the signing keys fetched earlier: {:#}", e);
+                }
+                Ok(key)
+            }
+            None => {
+                refreshed?;
+                bail!("Unknown signing key {}", kid)
+            }
+        }
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let mut cache = self.cache.write().await;
+        // Another request may have fetched the set while this one waited for the lock
+        if cache.attempted.is_some_and(|attempted| attempted.elapsed() < MIN_REFRESH_INTERVAL) {
+            return Ok(());
+        }
+        cache.attempted = Some(Instant::now());
+        let keys: JwkSet = async {
+            let response = self.http.get(&self.jwks_url).send().await?.error_for_status()?;
+            anyhow::Ok(response.json().await?)
+        }
+        .await
+        .with_context(|| format!("Failed to fetch the JWKS from {}", self.jwks_url))?;
+        info!("Fetched {} signing keys from {}", keys.keys.len(), self.jwks_url);
+        cache.keys = Some(keys);
+        cache.fetched = Some(Instant::now());
+        Ok(())
+    }
+}
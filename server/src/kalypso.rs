@@ -0,0 +1,200 @@
+use crate::audit::AuditLog;
+use crate::config::ChainConfig;
+use crate::envelope::Envelope;
+use crate::evm;
+use crate::prover::{ProofRequest, ProofResponse};
+use crate::state::AppState;
+use crate::submitter::{self, Submission, Submitter};
+use anyhow::{bail, Context, Result};
+use ethers::abi::{self, ParamType, Token};
+use ethers::providers::Middleware;
+use ethers::types::{Address, Filter, H256, U256};
+use serde_json::json;
+use sha3::{Digest, Keccak256};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// GeneratorRegistry: registers the generator with its reward address, declared compute and
+/// generator data (here the enclave encryption key).
+const REGISTER: &str = "register(address,uint256,bytes)";
+/// GeneratorRegistry: offers the generator's compute to one market at a price and proof time.
+const JOIN_MARKETPLACE: &str = "joinMarketPlace(uint256,uint256,uint256,uint256,bool,bytes,bytes)";
+/// ProofMarketplace: answers an assigned ask with a proof.
+const SUBMIT_PROOF: &str = "submitProof(uint256,bytes)";
+/// ProofMarketplace: gives back an assigned ask that cannot be proved.
+const DISCARD_REQUEST: &str = "discardRequest(uint256)";
+/// ProofMarketplace: emitted when the matching engine assigns an ask to a generator. The bytes
+/// carry the ask's private inputs, re-encrypted for that generator.
+const TASK_CREATED: &str = "TaskCreated(uint256,address,bytes)";
+
+/// Blocks scanned per poll, so a long outage is caught up gradually.
+const MAX_BLOCKS_PER_POLL: u64 = 1000;
+
+/// What the generator offers when it joins a market.
+#[derive(Debug, Clone)]
+pub struct Offer {
+    /// Compute units declared to the registry; asks are matched until they are used up
+    pub declared_compute: u64,
+    /// Compute units one proof takes
+    pub compute_per_request: u64,
+    /// Price of one proof in the market's payment token (smallest unit)
+    pub proof_cost: u128,
+    /// Seconds the generator commits to deliver a proof in
+    pub proposed_time: u64,
+}
+
+/// Registers the submitter key as a Kalypso generator and joins market `market_id`. The enclave
+/// encryption key is published as generator data, so the matching engine can re-encrypt ask
+/// inputs to it. Staking is done separately with Kalypso's tooling.
+#[allow(clippy::too_many_arguments)]
+pub async fn register(
+    http: &reqwest::Client,
+    submitter: &Submitter,
+    name: &str,
+    chain: &ChainConfig,
+    registry: Address,
+    market_id: u64,
+    offer: &Offer,
+    encryption_key: &[u8],
+) -> Result<Vec<Submission>> {
+    let register = calldata(
+        REGISTER,
+        &[
+            Token::Address(submitter.address()),
+            Token::Uint(offer.declared_compute.into()),
+            Token::Bytes(encryption_key.to_vec()),
+        ],
+    );
+    let join = calldata(
+        JOIN_MARKETPLACE,
+        &[
+            Token::Uint(market_id.into()),
+            Token::Uint(offer.compute_per_request.into()),
+            Token::Uint(offer.proof_cost.into()),
+            Token::Uint(offer.proposed_time.into()),
+            Token::Bool(false),
+            Token::Bytes(Vec::new()),
+            Token::Bytes(Vec::new()),
+        ],
+    );
+    let mut submissions = Vec::new();
+    for (step, data) in [("register", register), ("joinMarketPlace", join)] {
+        let submission = submitter.transact(http, name, chain, registry, data, None).await?;
+        if !submission.receipt.as_ref().is_some_and(|receipt| receipt.success) {
+            bail!("{} transaction {} did not succeed", step, submission.tx_hash);
+        }
+        submissions.push(submission);
+    }
+    Ok(submissions)
+}
+
+/// Takes part in a Kalypso proof marketplace: watches for asks the matching engine assigned to
+/// this generator (the submitter key), proves them with the worker pool and answers on-chain with
+/// the proof, or discards asks that cannot be proved.
+///
+/// Ask inputs must be a sealed envelope (as POST /prove/sealed takes) for the enclave encryption
+/// key, so they are only decrypted inside the enclave.
+pub struct KalypsoAdapter {
+    chain: String,
+    marketplace: Address,
+    audit: Arc<AuditLog>,
+    /// Next block to scan
+    cursor: Mutex<Option<u64>>,
+}
+
+impl KalypsoAdapter {
+    pub fn new(chain: String, marketplace: Address, audit: Arc<AuditLog>) -> Self {
+        Self {
+            chain,
+            marketplace,
+            audit,
+            cursor: Mutex::new(None),
+        }
+    }
+
+    pub async fn run(self: Arc<Self>, state: Arc<AppState>, interval: Duration) {
+        loop {
+            if let Err(e) = self.poll(&state).await {
+                warn!("Kalypso marketplace poll failed: {:#}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn poll(self: &Arc<Self>, state: &Arc<AppState>) -> Result<()> {
+        let submitter = state.submitter.as_ref().context("Kalypso needs a submitter key")?;
+        let (name, chain) = state.config.read().unwrap().chain(Some(&self.chain))?;
+        let provider = submitter::provider(&state.http, &name, &chain)?;
+        let latest = provider.get_block_number().await.context("Failed to fetch the latest block")?.as_u64();
+        // Asks assigned before startup have most likely expired, so there is nothing to backfill
+        let from = *self.cursor.lock().unwrap().get_or_insert(latest);
+        if from > latest {
+            return Ok(());
+        }
+        let to = latest.min(from + MAX_BLOCKS_PER_POLL - 1);
+
+        let filter = Filter::new()
+            .address(self.marketplace)
+            .topic0(H256::from_slice(&Keccak256::digest(TASK_CREATED.as_bytes())))
+            .topic2(H256::from(submitter.address()))
+            .from_block(from)
+            .to_block(to);
+        let logs = provider.get_logs(&filter).await.context("Failed to fetch TaskCreated events")?;
+        for log in logs {
+            let Some(ask_id) = log.topics.get(1).map(|topic| U256::from_big_endian(topic.as_bytes())) else {
+                continue;
+            };
+            let inputs = match abi::decode(&[ParamType::Bytes], &log.data).ok().and_then(|tokens| tokens.into_iter().next()) {
+                Some(Token::Bytes(inputs)) => inputs,
+                _ => Vec::new(),
+            };
+            let adapter = self.clone();
+            let state = state.clone();
+            let (name, chain) = (name.clone(), chain.clone());
+            tokio::spawn(async move {
+                if let Err(e) = adapter.serve(&state, &name, &chain, ask_id, &inputs).await {
+                    warn!("Kalypso ask {} failed: {:#}", ask_id, e);
+                }
+            });
+        }
+        *self.cursor.lock().unwrap() = Some(to + 1);
+        Ok(())
+    }
+
+    /// Proves ask `ask_id` and answers it: with the proof, or by discarding it when the inputs
+    /// cannot be opened or the applicant is not eligible.
+    async fn serve(&self, state: &AppState, name: &str, chain: &ChainConfig, ask_id: U256, inputs: &[u8]) -> Result<()> {
+        let submitter = state.submitter.as_ref().context("Kalypso needs a submitter key")?;
+        let (data, outcome, message) = match self.prove(state, inputs).await {
+            Ok(response) if response.success => {
+                let proof = hex::decode(response.proof_hex.trim_start_matches("0x")).context("Prover returned invalid proof hex")?;
+                let public_inputs = evm::parse_public_inputs(&response.public_inputs)?;
+                // abi.encode(bytes proof, bytes32[] publicInputs): the verify call's arguments
+                let proof = evm::verify_calldata(&proof, &public_inputs)[4..].to_vec();
+                (calldata(SUBMIT_PROOF, &[Token::Uint(ask_id), Token::Bytes(proof)]), "proof_submitted", response.message)
+            }
+            Ok(response) => (calldata(DISCARD_REQUEST, &[Token::Uint(ask_id)]), "discarded", response.message),
+            Err(e) => (calldata(DISCARD_REQUEST, &[Token::Uint(ask_id)]), "discarded", format!("{:#}", e)),
+        };
+        let submission = submitter.transact(&state.http, name, chain, self.marketplace, data, None).await?;
+        let success = submission.receipt.as_ref().map(|receipt| receipt.success);
+        info!("Kalypso ask {}: {} in {} ({})", ask_id, outcome, submission.tx_hash, message);
+        self.audit.record(
+            "kalypso_ask",
+            json!({ "ask_id": ask_id.to_string(), "outcome": outcome, "tx_hash": submission.tx_hash, "success": success, "message": message }),
+        );
+        Ok(())
+    }
+
+    async fn prove(&self, state: &AppState, inputs: &[u8]) -> Result<ProofResponse> {
+        let envelope: Envelope = serde_json::from_slice(inputs).context("Ask inputs are not a sealed envelope")?;
+        let plaintext = zeroize::Zeroizing::new(state.keys.open_envelope(&envelope)?);
+        let request: ProofRequest = serde_json::from_slice(&plaintext).context("Sealed ask inputs are not a ProofRequest")?;
+        state.pool.submit(request).await
+    }
+}
+
+fn calldata(signature: &str, arguments: &[Token]) -> Vec<u8> {
+    [evm::selector(signature).as_slice(), &abi::encode(arguments)].concat()
+}
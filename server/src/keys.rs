@@ -0,0 +1,304 @@
+use crate::encryption;
+use crate::envelope::Envelope;
+use crate::signing::EnclaveSigner;
+use aes_gcm::aead::{KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use k256::ecdsa::SigningKey;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const KEYRING_FILE: &str = "keyring.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyKind {
+    /// secp256k1 key signing proofs, bundles and exports
+    Signing,
+    /// X25519 key clients encrypt data for the enclave to
+    Encryption,
+}
+
+impl fmt::Display for KeyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KeyKind::Signing => "signing",
+            KeyKind::Encryption => "encryption",
+        })
+    }
+}
+
+/// A key as kept in the keyring: public half in the clear, secret sealed under the master key.
+/// Keys derived from the Oyster KMS keep only their derivation path; the secret is derived again
+/// on every start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredKey {
+    id: String,
+    kind: KeyKind,
+    public_key: String,
+    created_at: DateTime<Utc>,
+    retired_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kms_path: Option<String>,
+}
+
+/// Public view of a managed key, as served by GET /keys.
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicKeyInfo {
+    pub id: String,
+    pub kind: KeyKind,
+    pub public_key: String,
+    /// Ethereum address, for signing keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// KMS derivation path, for keys derived from the Oyster KMS root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kms_path: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+/// Generates, persists and rotates the enclave's long-lived keys. Secrets are sealed with
+/// AES-256-GCM under the enclave master key (the one wrapping tenant data keys) and never
+/// written in the clear. Retired keys stay in the keyring so their signatures remain checkable.
+pub struct KeyManager {
+    path: PathBuf,
+    sealing: Aes256Gcm,
+    keys: Vec<StoredKey>,
+    /// Secret of the current signing key when it was derived from the KMS this run
+    derived_signing: Option<[u8; 32]>,
+}
+
+impl KeyManager {
+    /// Opens the keyring in `key_dir`, creating any key that does not exist yet.
+    pub fn open(key_dir: &Path, master_key: &[u8]) -> Result<Self> {
+        let path = key_dir.join(KEYRING_FILE);
+        let keys = if path.exists() {
+            let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&content).with_context(|| format!("{} is not a valid keyring", path.display()))?
+        } else {
+            Vec::new()
+        };
+        let mut manager = Self {
+            path,
+            sealing: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(master_key)),
+            keys,
+            derived_signing: None,
+        };
+
+        let mut created = false;
+        for kind in [KeyKind::Signing, KeyKind::Encryption] {
+            if manager.current_key(kind).is_none() {
+                manager.generate(kind)?;
+                created = true;
+            }
+        }
+        if created {
+            manager.save()?;
+        }
+        Ok(manager)
+    }
+
+    /// The current signing key.
+    pub fn signer(&self) -> Result<EnclaveSigner> {
+        let key = self.current_key(KeyKind::Signing).expect("the keyring always holds a current key of each kind");
+        match (&key.kms_path, self.derived_signing) {
+            (Some(_), Some(secret)) => EnclaveSigner::from_bytes(&secret),
+            (Some(path), None) => bail!(
+                "Signing key {} is derived from the Oyster KMS at '{}'; start with --kms or run rotate-keys --kind signing",
+                key.id,
+                path
+            ),
+            (None, _) => EnclaveSigner::from_bytes(&self.unseal(key)?),
+        }
+    }
+
+    /// Secret of the current encryption key, the static key of the Noise transport.
+    pub fn encryption_secret(&self) -> Result<[u8; 32]> {
+        self.unseal(self.current_key(KeyKind::Encryption).expect("the keyring always holds a current key of each kind"))
+    }
+
+    /// Decrypts a request sealed to one of the enclave's encryption keys: the one named by
+    /// `key_id`, which may be retired so clients holding an older key keep working, or else the
+    /// current one.
+    pub fn open_envelope(&self, envelope: &Envelope) -> Result<Vec<u8>> {
+        let key = match &envelope.key_id {
+            Some(id) => self
+                .keys
+                .iter()
+                .find(|key| key.kind == KeyKind::Encryption && &key.id == id)
+                .with_context(|| format!("Unknown encryption key {}", id))?,
+            None => self.current_key(KeyKind::Encryption).expect("the keyring always holds a current key of each kind"),
+        };
+        envelope.open(&self.unseal(key)?)
+    }
+
+    /// Makes the key derived from the KMS root at `path` the current signing key. The first time a
+    /// path is used, the previous signing key is retired and the derived one recorded; afterwards
+    /// the recorded public key must match, so a different KMS root or image is noticed.
+    pub fn use_kms_signing_key(&mut self, path: &str, secret: [u8; 32]) -> Result<PublicKeyInfo> {
+        let public_key = EnclaveSigner::from_bytes(&secret)?.public_key();
+        let current = self.current_key(KeyKind::Signing).expect("the keyring always holds a current key of each kind");
+        if current.kms_path.as_deref() == Some(path) {
+            if current.public_key != public_key {
+                bail!(
+                    "KMS derived a different key at '{}' than the one recorded as {} (different KMS root or enclave image?)",
+                    path,
+                    current.id
+                );
+            }
+        } else {
+            let now = Utc::now();
+            for key in self.keys.iter_mut().filter(|key| key.kind == KeyKind::Signing && key.retired_at.is_none()) {
+                key.retired_at = Some(now);
+            }
+            self.keys.push(StoredKey {
+                id: self.next_id(KeyKind::Signing),
+                kind: KeyKind::Signing,
+                public_key,
+                created_at: now,
+                retired_at: None,
+                sealed_secret: None,
+                kms_path: Some(path.to_string()),
+            });
+            self.save()?;
+        }
+        self.derived_signing = Some(secret);
+        Ok(self.current(KeyKind::Signing))
+    }
+
+    pub fn current(&self, kind: KeyKind) -> PublicKeyInfo {
+        public_info(self.current_key(kind).expect("the keyring always holds a current key of each kind"))
+    }
+
+    /// Every key ever issued, newest first.
+    pub fn public_keys(&self) -> Vec<PublicKeyInfo> {
+        self.keys.iter().rev().map(public_info).collect()
+    }
+
+    /// Retires the current key of `kind` and replaces it with a sealed one. Running servers keep
+    /// using the old key until they restart.
+    pub fn rotate(&mut self, kind: KeyKind) -> Result<PublicKeyInfo> {
+        let now = Utc::now();
+        if kind == KeyKind::Signing {
+            self.derived_signing = None;
+        }
+        for key in self.keys.iter_mut().filter(|key| key.kind == kind && key.retired_at.is_none()) {
+            key.retired_at = Some(now);
+        }
+        self.generate(kind)?;
+        self.save()?;
+        Ok(self.current(kind))
+    }
+
+    /// Seals every stored secret under `master_key` instead of the current master key.
+    pub fn reseal(&mut self, master_key: &[u8]) -> Result<()> {
+        let sealing = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(master_key));
+        for index in 0..self.keys.len() {
+            if self.keys[index].sealed_secret.is_none() {
+                continue;
+            }
+            let secret = self.unseal(&self.keys[index])?;
+            let key = &mut self.keys[index];
+            key.sealed_secret = Some(hex::encode(encryption::encrypt(&sealing, key.id.as_bytes(), &secret)?));
+        }
+        self.sealing = sealing;
+        self.save()
+    }
+
+    /// sha256 binding the current public keys together, embedded as `user_data` in the
+    /// attestation document GET /keys returns.
+    pub fn binding(&self) -> String {
+        let message = format!(
+            "zk-insurance keys\nsigning: {}\nencryption: {}",
+            self.current(KeyKind::Signing).public_key,
+            self.current(KeyKind::Encryption).public_key
+        );
+        format!("0x{}", hex::encode(Sha256::digest(message.as_bytes())))
+    }
+
+    fn current_key(&self, kind: KeyKind) -> Option<&StoredKey> {
+        self.keys.iter().rev().find(|key| key.kind == kind && key.retired_at.is_none())
+    }
+
+    fn generate(&mut self, kind: KeyKind) -> Result<()> {
+        let (secret, public_key) = match kind {
+            KeyKind::Signing => {
+                let secret: [u8; 32] = SigningKey::random(&mut OsRng).to_bytes().into();
+                let public_key = EnclaveSigner::from_bytes(&secret)?.public_key();
+                (secret, public_key)
+            }
+            KeyKind::Encryption => {
+                let mut secret = [0u8; 32];
+                OsRng.fill_bytes(&mut secret);
+                let public_key = MontgomeryPoint::mul_base_clamped(secret).to_bytes();
+                (secret, format!("0x{}", hex::encode(public_key)))
+            }
+        };
+        let id = self.next_id(kind);
+        let sealed = encryption::encrypt(&self.sealing, id.as_bytes(), &secret)?;
+        self.keys.push(StoredKey {
+            id,
+            kind,
+            public_key,
+            created_at: Utc::now(),
+            retired_at: None,
+            sealed_secret: Some(hex::encode(sealed)),
+            kms_path: None,
+        });
+        Ok(())
+    }
+
+    fn next_id(&self, kind: KeyKind) -> String {
+        format!("{}-{}", kind, self.keys.iter().filter(|key| key.kind == kind).count() + 1)
+    }
+
+    fn unseal(&self, key: &StoredKey) -> Result<[u8; 32]> {
+        let sealed = key.sealed_secret.as_deref().with_context(|| format!("Key {} has no sealed secret", key.id))?;
+        let sealed = hex::decode(sealed).with_context(|| format!("Key {} is corrupted", key.id))?;
+        let secret = encryption::decrypt(&self.sealing, key.id.as_bytes(), &sealed)
+            .with_context(|| format!("Failed to unseal key {} (was the master key replaced?)", key.id))?;
+        match secret.try_into() {
+            Ok(secret) => Ok(secret),
+            Err(_) => bail!("Key {} has an invalid length", key.id),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&self.keys)?).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path).with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+fn public_info(key: &StoredKey) -> PublicKeyInfo {
+    let address = (key.kind == KeyKind::Signing)
+        .then(|| {
+            let bytes = hex::decode(key.public_key.trim_start_matches("0x")).ok()?;
+            let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(&bytes).ok()?;
+            Some(crate::signing::address_of(&key))
+        })
+        .flatten();
+    PublicKeyInfo {
+        id: key.id.clone(),
+        kind: key.kind,
+        public_key: key.public_key.clone(),
+        address,
+        kms_path: key.kms_path.clone(),
+        created_at: key.created_at,
+        retired_at: key.retired_at,
+    }
+}
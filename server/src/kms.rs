@@ -0,0 +1,55 @@
+use anyhow::{bail, Context, Result};
+
+/// Oyster's key derivation server inside the CVM. It derives keys from the KMS root granted to
+/// this image, so every deployment of the same image gets the same keys for the same path.
+pub const DEFAULT_KMS_URL: &str = "http://127.0.0.1:1100";
+
+/// Path the enclave signing key is derived at unless `--kms-derive-path` says otherwise.
+pub const DEFAULT_SIGNING_PATH: &str = "zk-insurance/signing";
+
+/// Path the master key is derived at with `--master-key-source kms`.
+pub const DEFAULT_MASTER_KEY_PATH: &str = "zk-insurance/storage";
+
+pub struct KmsClient {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl KmsClient {
+    pub fn new(http: reqwest::Client, url: String) -> Self {
+        Self {
+            http,
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The secp256k1 secret derived at `path`.
+    pub async fn derive_secp256k1(&self, path: &str) -> Result<[u8; 32]> {
+        self.fetch("derive/secp256k1", path).await
+    }
+
+    /// A 32-byte symmetric key derived at `path`.
+    pub async fn derive(&self, path: &str) -> Result<[u8; 32]> {
+        self.fetch("derive", path).await
+    }
+
+    async fn fetch(&self, endpoint: &str, path: &str) -> Result<[u8; 32]> {
+        let url = format!("{}/{}", self.url, endpoint);
+        let response = self
+            .http
+            .get(&url)
+            .query(&[("path", path)])
+            .send()
+            .await
+            .with_context(|| format!("KMS derive server at {} is unreachable (not running in an Oyster enclave?)", self.url))?;
+        let status = response.status();
+        let body = response.bytes().await.context("Failed to read the derived key")?;
+        if !status.is_success() {
+            bail!("KMS derive server returned {}: {}", status, String::from_utf8_lossy(&body).trim());
+        }
+        match body.as_ref().try_into() {
+            Ok(secret) => Ok(secret),
+            Err(_) => bail!("KMS derive server returned {} bytes for {}, expected 32", body.len(), endpoint),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::io;
+use std::process::Output;
+use tokio::process::Command;
+
+/// Per-process rlimits applied to every nargo/bb invocation, so one oversized job fails on its
+/// own instead of pushing the whole enclave into the OOM killer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceLimits {
+    /// Address-space limit in bytes (RLIMIT_AS)
+    pub memory_bytes: Option<u64>,
+    /// CPU time limit in seconds (RLIMIT_CPU); the process gets SIGXCPU when it runs out
+    pub cpu_secs: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Builds limits from CLI values where 0 means unlimited.
+    pub fn new(memory_mb: u64, cpu_secs: u64) -> Self {
+        Self {
+            memory_bytes: (memory_mb > 0).then(|| memory_mb * 1024 * 1024),
+            cpu_secs: (cpu_secs > 0).then_some(cpu_secs),
+        }
+    }
+
+    pub fn apply(&self, command: &mut Command) {
+        if self.memory_bytes.is_none() && self.cpu_secs.is_none() {
+            return;
+        }
+        let limits = *self;
+        // SAFETY: the closure only calls setrlimit, which is async-signal-safe
+        unsafe {
+            command.pre_exec(move || {
+                let set_limit = |resource, value: u64| {
+                    let limit = libc::rlimit {
+                        rlim_cur: value as libc::rlim_t,
+                        rlim_max: value as libc::rlim_t,
+                    };
+                    if libc::setrlimit(resource, &limit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                };
+                if let Some(bytes) = limits.memory_bytes {
+                    set_limit(libc::RLIMIT_AS, bytes)?;
+                }
+                if let Some(secs) = limits.cpu_secs {
+                    set_limit(libc::RLIMIT_CPU, secs)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Whether a failed run looks like it hit one of these limits.
+    pub fn exceeded(&self, output: &Output) -> bool {
+        use std::os::unix::process::ExitStatusExt;
+        if self.cpu_secs.is_some() && matches!(output.status.signal(), Some(libc::SIGXCPU) | Some(libc::SIGKILL)) {
+            return true;
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+        self.memory_bytes.is_some()
+            && ["bad_alloc", "out of memory", "cannot allocate memory"]
+                .iter()
+                .any(|marker| stderr.contains(marker))
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing_subscriber::EnvFilter;
+
+/// Header carrying the request ID on HTTP requests and responses.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request ID accepted from a client.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Whether applicants' health inputs may appear in logs; off unless --log-private-inputs.
+static LOG_PRIVATE_INPUTS: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per event, for log pipelines
+    Json,
+}
+
+/// Sends log events to stderr, keeping stdout for command output such as `--once` responses.
+/// `filter` uses the `RUST_LOG` directive syntax, e.g. `info` or `info,zk_insurance_server=debug`.
+pub fn init(format: LogFormat, filter: &str, log_private_inputs: bool) -> Result<()> {
+    LOG_PRIVATE_INPUTS.store(log_private_inputs, Ordering::Relaxed);
+    let filter = EnvFilter::try_new(filter).with_context(|| format!("Invalid log filter '{}'", filter))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(true).with_span_list(false).init(),
+    }
+    Ok(())
+}
+
+/// A fresh ID for a connection, HTTP request or job.
+pub fn request_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Whether a client-supplied request ID can be adopted: short and free of anything that could
+/// forge log lines.
+pub fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+/// An applicant's private input, logged as `[redacted]` unless --log-private-inputs is set.
+pub struct Private<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for Private<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if LOG_PRIVATE_INPUTS.load(Ordering::Relaxed) {
+            self.0.fmt(f)
+        } else {
+            f.write_str("[redacted]")
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Debug for Private<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::attestation::{self, AttestationClient};
+use crate::integrity;
+use anyhow::{Context, Result};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// What produced this server's proofs: the enclave image, the server binary and the circuit.
+/// Served signed by GET /measurements so auditors can pin all of it at once.
+#[derive(Debug, Clone, Serialize)]
+pub struct Measurements {
+    /// Server version from Cargo.toml
+    pub version: String,
+    /// sha256 of the running server binary
+    pub binary_sha256: String,
+    /// Non-zero Nitro PCRs of the enclave image by index (0-2 identify the image); empty when
+    /// not running in an enclave
+    pub pcrs: BTreeMap<u8, String>,
+    /// sha256 of the compiled circuit, the `circuit_hash` every proof response carries
+    pub circuit_hash: String,
+    /// sha256 of the verification key, as served by GET /vk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vk_hash: Option<String>,
+    /// sha256 over the enclave public keys, as served by GET /keys
+    pub keys_binding: String,
+}
+
+impl Measurements {
+    /// What the enclave signs for the manifest; the attestation document served with it embeds
+    /// the sha256 of this message as `user_data`.
+    pub fn signing_message(&self, signed_at: i64) -> String {
+        let pcrs: String = self.pcrs.iter().map(|(index, value)| format!("pcr{}: {}\n", index, value)).collect();
+        format!(
+            "zk-insurance measurements\nversion: {}\nbinary_sha256: {}\n{}circuit_hash: {}\nvk_hash: {}\nkeys: {}\nsigned_at: {}",
+            self.version,
+            self.binary_sha256,
+            pcrs,
+            self.circuit_hash,
+            self.vk_hash.as_deref().unwrap_or(""),
+            self.keys_binding,
+            signed_at
+        )
+    }
+}
+
+/// Collects measurements, caching the parts that cannot change while the server runs.
+#[derive(Default)]
+pub struct MeasurementReporter {
+    binary_sha256: OnceCell<String>,
+    pcrs: OnceCell<BTreeMap<u8, String>>,
+}
+
+impl MeasurementReporter {
+    pub async fn measure(
+        &self,
+        attestation: &AttestationClient,
+        circuit_path: &Path,
+        vk_hash: Option<String>,
+        keys_binding: String,
+    ) -> Result<Measurements> {
+        let binary_sha256 = self
+            .binary_sha256
+            .get_or_try_init(|| async {
+                let exe = std::env::current_exe().context("Failed to locate the server binary")?;
+                let hash = tokio::task::spawn_blocking(move || integrity::sha256_file(&exe)).await??;
+                Ok::<_, anyhow::Error>(format!("0x{}", hash))
+            })
+            .await?
+            .clone();
+        let circuit = fs::read(circuit_path.join("target/insurance_verifier.json")).context("Failed to read the compiled circuit")?;
+        Ok(Measurements {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            binary_sha256,
+            pcrs: self.pcrs(attestation).await,
+            circuit_hash: format!("0x{}", hex::encode(Sha256::digest(&circuit))),
+            vk_hash,
+            keys_binding,
+        })
+    }
+
+    /// PCRs from an attestation document, fetched once; empty (and retried next time) when the
+    /// attestation server is unavailable.
+    async fn pcrs(&self, attestation: &AttestationClient) -> BTreeMap<u8, String> {
+        let result = self
+            .pcrs
+            .get_or_try_init(|| async {
+                let mut nonce = [0u8; 32];
+                rand::thread_rng().fill_bytes(&mut nonce);
+                attestation::pcrs(&attestation.document(&nonce, &[]).await?)
+            })
+            .await;
+        match result {
+            Ok(pcrs) => pcrs.clone(),
+            Err(e) => {
+                warn!("Failed to read enclave PCRs: {:#}", e);
+                BTreeMap::new()
+            }
+        }
+    }
+}
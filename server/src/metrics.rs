@@ -0,0 +1,313 @@
+use crate::prover::{ErrorCode, ProofResponse};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Upper bounds (seconds) of the step duration histogram buckets.
+const DURATION_BUCKETS: [f64; 11] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Process-wide proving counters. Only `proofs_generated` is billable; jobs cancelled at their
+/// proving-time ceiling are counted under `budget_exceeded` instead.
+#[derive(Default)]
+pub struct Metrics {
+    proofs_requested: AtomicU64,
+    proofs_generated: AtomicU64,
+    ineligible: AtomicU64,
+    backend_errors: AtomicU64,
+    backend_unavailable: AtomicU64,
+    budget_exceeded: AtomicU64,
+    invalid_requests: AtomicU64,
+    server_busy: AtomicU64,
+    rate_limited: AtomicU64,
+    witness_seconds: Histogram,
+    prove_seconds: Histogram,
+    hex_seconds: Histogram,
+    tcp_connections: AtomicI64,
+    http_requests_in_flight: AtomicI64,
+    canary_successes: AtomicU64,
+    canary_failures: AtomicU64,
+    last_canary: Mutex<Option<CanaryRun>>,
+    /// Successful proofs by circuit hash, for GET /circuits/stats
+    circuits: Mutex<HashMap<String, ProvingStats>>,
+    /// Proof requests by tenant, for GET /admin/tenants; kept off GET /metrics so the public
+    /// port does not list the partners sharing the deployment
+    tenants: Mutex<BTreeMap<String, TenantStats>>,
+}
+
+/// One tenant's proof requests since the server started.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct TenantStats {
+    pub proofs_requested: u64,
+    pub proofs_generated: u64,
+    pub ineligible: u64,
+    /// Requests that failed for any other reason
+    pub failed: u64,
+}
+
+/// Measured cost of proving with one circuit since the server started.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ProvingStats {
+    /// Successful proofs the averages are taken over
+    pub proofs: u64,
+    pub mean_witness_secs: f64,
+    pub mean_prove_secs: f64,
+    /// Size of the latest proof; a circuit's proofs are all the same size for one scheme
+    pub proof_bytes: usize,
+}
+
+/// Outcome of the latest periodic canary proof.
+#[derive(Debug, Clone, Copy)]
+struct CanaryRun {
+    success: bool,
+    seconds: f64,
+    /// Unix time it finished
+    finished_at: i64,
+}
+
+/// Seconds one job spent in each proving step. The prover fills it in (possibly in an isolated
+/// worker process) and the pool records and strips it before the response goes out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepDurations {
+    /// nargo execute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub witness: Option<f64>,
+    /// bb prove
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prove: Option<f64>,
+    /// Proof to hex conversion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hex: Option<f64>,
+}
+
+/// Which gauge an `ActiveGuard` holds up.
+#[derive(Debug, Clone, Copy)]
+pub enum Activity {
+    TcpConnection,
+    HttpRequest,
+}
+
+/// Counts one open connection or request for as long as it is alive.
+pub struct ActiveGuard {
+    metrics: Arc<Metrics>,
+    activity: Activity,
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.metrics.gauge(self.activity).fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket (not cumulative); the last one is +Inf
+    buckets: [AtomicU64; DURATION_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, seconds: f64) {
+        let bucket = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound).unwrap_or(DURATION_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add((seconds * 1e6) as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            let bound = DURATION_BUCKETS.get(index).map_or("+Inf".to_string(), |bound| bound.to_string());
+            let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum_micros.load(Ordering::Relaxed) as f64 / 1e6);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, cumulative);
+    }
+}
+
+/// Point-in-time copy of the counters, as exported to cold storage.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub proofs_requested: u64,
+    pub proofs_generated: u64,
+    pub ineligible: u64,
+    pub backend_errors: u64,
+    pub backend_unavailable: u64,
+    pub budget_exceeded: u64,
+    pub invalid_requests: u64,
+    pub server_busy: u64,
+    pub rate_limited: u64,
+}
+
+impl Metrics {
+    /// Counts a finished proof job of `tenant` (None for the default tenant).
+    pub fn record(&self, result: &Result<ProofResponse>, tenant: Option<&str>) {
+        {
+            let mut tenants = self.tenants.lock().unwrap();
+            let stats = tenants.entry(tenant.unwrap_or(crate::DEFAULT_TENANT).to_string()).or_default();
+            stats.proofs_requested += 1;
+            match result {
+                Ok(response) if response.success => stats.proofs_generated += 1,
+                Ok(ProofResponse { error_code: Some(ErrorCode::Ineligible), .. }) => stats.ineligible += 1,
+                _ => stats.failed += 1,
+            }
+        }
+        self.proofs_requested.fetch_add(1, Ordering::Relaxed);
+        let counter = match result {
+            Ok(response) if response.success => &self.proofs_generated,
+            Ok(response) => match response.error_code {
+                Some(ErrorCode::Ineligible) => &self.ineligible,
+                Some(ErrorCode::BackendUnavailable) => &self.backend_unavailable,
+                Some(ErrorCode::BudgetExceeded) => &self.budget_exceeded,
+                Some(ErrorCode::InvalidRequest | ErrorCode::CircuitMismatch | ErrorCode::PaymentRequired) => &self.invalid_requests,
+                Some(ErrorCode::ServerBusy) => &self.server_busy,
+                Some(ErrorCode::RateLimited | ErrorCode::QuotaExceeded) => &self.rate_limited,
+                Some(ErrorCode::BackendError) | None => &self.backend_errors,
+            },
+            Err(_) => &self.backend_errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a request turned away by the rate limiter before it reached the pool.
+    pub fn record_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a periodic canary proof; failures are not proof failures and are counted apart.
+    pub fn record_canary(&self, success: bool, seconds: f64) {
+        let counter = if success { &self.canary_successes } else { &self.canary_failures };
+        counter.fetch_add(1, Ordering::Relaxed);
+        *self.last_canary.lock().unwrap() = Some(CanaryRun {
+            success,
+            seconds,
+            finished_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    pub fn record_steps(&self, steps: &StepDurations) {
+        for (histogram, seconds) in [
+            (&self.witness_seconds, steps.witness),
+            (&self.prove_seconds, steps.prove),
+            (&self.hex_seconds, steps.hex),
+        ] {
+            if let Some(seconds) = seconds {
+                histogram.observe(seconds);
+            }
+        }
+    }
+
+    /// Adds a successful proof made with the circuit `circuit_hash` to its running averages.
+    pub fn record_circuit(&self, circuit_hash: &str, steps: &StepDurations, proof_bytes: usize) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let stats = circuits.entry(circuit_hash.to_string()).or_default();
+        stats.proofs += 1;
+        let weight = 1.0 / stats.proofs as f64;
+        stats.mean_witness_secs += (steps.witness.unwrap_or_default() - stats.mean_witness_secs) * weight;
+        stats.mean_prove_secs += (steps.prove.unwrap_or_default() - stats.mean_prove_secs) * weight;
+        stats.proof_bytes = proof_bytes;
+    }
+
+    /// Proving statistics of the circuit `circuit_hash`; None until it has made a proof.
+    pub fn circuit_stats(&self, circuit_hash: &str) -> Option<ProvingStats> {
+        self.circuits.lock().unwrap().get(circuit_hash).copied()
+    }
+
+    /// Proof requests of every tenant that has made one.
+    pub fn tenant_stats(&self) -> BTreeMap<String, TenantStats> {
+        self.tenants.lock().unwrap().clone()
+    }
+
+    /// Counts `activity` until the returned guard is dropped.
+    pub fn track(self: &Arc<Self>, activity: Activity) -> ActiveGuard {
+        self.gauge(activity).fetch_add(1, Ordering::Relaxed);
+        ActiveGuard {
+            metrics: self.clone(),
+            activity,
+        }
+    }
+
+    /// Connections or requests of `activity` open right now.
+    pub fn active(&self, activity: Activity) -> i64 {
+        self.gauge(activity).load(Ordering::Relaxed)
+    }
+
+    fn gauge(&self, activity: Activity) -> &AtomicI64 {
+        match activity {
+            Activity::TcpConnection => &self.tcp_connections,
+            Activity::HttpRequest => &self.http_requests_in_flight,
+        }
+    }
+
+    /// Everything in the Prometheus text exposition format, as served by GET /metrics.
+    pub fn render_prometheus(&self, queue_depth: usize) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+        out.push_str("# HELP zk_insurance_proofs_requested_total Proof requests handled by the worker pool.\n");
+        out.push_str("# TYPE zk_insurance_proofs_requested_total counter\n");
+        let _ = writeln!(out, "zk_insurance_proofs_requested_total {}", snapshot.proofs_requested);
+        out.push_str("# HELP zk_insurance_proofs_generated_total Proofs generated successfully.\n");
+        out.push_str("# TYPE zk_insurance_proofs_generated_total counter\n");
+        let _ = writeln!(out, "zk_insurance_proofs_generated_total {}", snapshot.proofs_generated);
+        out.push_str("# HELP zk_insurance_proof_failures_total Requests that produced no proof, by error code.\n");
+        out.push_str("# TYPE zk_insurance_proof_failures_total counter\n");
+        for (code, count) in [
+            ("ineligible", snapshot.ineligible),
+            ("backend_error", snapshot.backend_errors),
+            ("backend_unavailable", snapshot.backend_unavailable),
+            ("budget_exceeded", snapshot.budget_exceeded),
+            ("invalid_request", snapshot.invalid_requests),
+            ("server_busy", snapshot.server_busy),
+            ("rate_limited", snapshot.rate_limited),
+        ] {
+            let _ = writeln!(out, "zk_insurance_proof_failures_total{{error_code=\"{}\"}} {}", code, count);
+        }
+        out.push_str("# HELP zk_insurance_queue_depth Jobs waiting for a proving worker.\n");
+        out.push_str("# TYPE zk_insurance_queue_depth gauge\n");
+        let _ = writeln!(out, "zk_insurance_queue_depth {}", queue_depth);
+        out.push_str("# HELP zk_insurance_step_duration_seconds Time spent in each proving step.\n");
+        out.push_str("# TYPE zk_insurance_step_duration_seconds histogram\n");
+        for (step, histogram) in [("witness", &self.witness_seconds), ("prove", &self.prove_seconds), ("hex", &self.hex_seconds)] {
+            histogram.render(&mut out, "zk_insurance_step_duration_seconds", &format!("step=\"{}\"", step));
+        }
+        out.push_str("# HELP zk_insurance_tcp_connections Open TCP client connections.\n");
+        out.push_str("# TYPE zk_insurance_tcp_connections gauge\n");
+        let _ = writeln!(out, "zk_insurance_tcp_connections {}", self.tcp_connections.load(Ordering::Relaxed));
+        out.push_str("# HELP zk_insurance_http_requests_in_flight HTTP API requests being served.\n");
+        out.push_str("# TYPE zk_insurance_http_requests_in_flight gauge\n");
+        let _ = writeln!(out, "zk_insurance_http_requests_in_flight {}", self.http_requests_in_flight.load(Ordering::Relaxed));
+        out.push_str("# HELP zk_insurance_canary_runs_total Periodic canary proofs, by result.\n");
+        out.push_str("# TYPE zk_insurance_canary_runs_total counter\n");
+        let _ = writeln!(out, "zk_insurance_canary_runs_total{{result=\"success\"}} {}", self.canary_successes.load(Ordering::Relaxed));
+        let _ = writeln!(out, "zk_insurance_canary_runs_total{{result=\"failure\"}} {}", self.canary_failures.load(Ordering::Relaxed));
+        // Gauges only once a canary has run, so a fresh node does not look like a failing one
+        if let Some(run) = *self.last_canary.lock().unwrap() {
+            out.push_str("# HELP zk_insurance_canary_success Whether the last canary proof was generated and verified.\n");
+            out.push_str("# TYPE zk_insurance_canary_success gauge\n");
+            let _ = writeln!(out, "zk_insurance_canary_success {}", run.success as u8);
+            out.push_str("# HELP zk_insurance_canary_duration_seconds How long the last canary proof took.\n");
+            out.push_str("# TYPE zk_insurance_canary_duration_seconds gauge\n");
+            let _ = writeln!(out, "zk_insurance_canary_duration_seconds {}", run.seconds);
+            out.push_str("# HELP zk_insurance_canary_last_run_timestamp_seconds When the last canary proof finished.\n");
+            out.push_str("# TYPE zk_insurance_canary_last_run_timestamp_seconds gauge\n");
+            let _ = writeln!(out, "zk_insurance_canary_last_run_timestamp_seconds {}", run.finished_at);
+        }
+        out
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            proofs_requested: self.proofs_requested.load(Ordering::Relaxed),
+            proofs_generated: self.proofs_generated.load(Ordering::Relaxed),
+            ineligible: self.ineligible.load(Ordering::Relaxed),
+            backend_errors: self.backend_errors.load(Ordering::Relaxed),
+            backend_unavailable: self.backend_unavailable.load(Ordering::Relaxed),
+            budget_exceeded: self.budget_exceeded.load(Ordering::Relaxed),
+            invalid_requests: self.invalid_requests.load(Ordering::Relaxed),
+            server_busy: self.server_busy.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use snow::Builder;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+
+/// Noise handshake and cipher suite spoken by the TCP listener with `--tcp-transport noise`.
+pub const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_SHA256";
+
+/// Mixed into the handshake hash, so a handshake for another protocol cannot be replayed here.
+const PROLOGUE: &[u8] = b"zk-insurance noise v1";
+
+/// Largest Noise message, payload plus 16-byte tag.
+const MAX_MESSAGE_LEN: usize = 65535;
+const TAG_LEN: usize = 16;
+
+/// How the TCP listener's byte stream is carried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TcpTransport {
+    /// The interactive prompt in clear text
+    Plain,
+    /// The same prompt inside a Noise_XX channel keyed to the enclave encryption key
+    Noise,
+}
+
+/// Runs the responder side of a Noise_XX handshake on `stream`, authenticating as `static_key`,
+/// and returns the decrypted byte stream together with the client's static public key.
+/// Every message on the wire is prefixed with its length as a big-endian u16.
+pub async fn accept<S>(stream: S, static_key: &[u8; 32]) -> Result<(DuplexStream, Vec<u8>)>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut handshake = Builder::new(NOISE_PARAMS.parse()?)
+        .local_private_key(static_key)
+        .prologue(PROLOGUE)
+        .build_responder()?;
+    let mut message = vec![0u8; MAX_MESSAGE_LEN];
+    let mut payload = vec![0u8; MAX_MESSAGE_LEN];
+
+    // -> e
+    let frame = read_frame(&mut reader).await?;
+    handshake.read_message(&frame, &mut payload).context("Invalid Noise handshake message 1")?;
+    // <- e, ee, s, es
+    let len = handshake.write_message(&[], &mut message)?;
+    write_frame(&mut writer, &message[..len]).await?;
+    // -> s, se
+    let frame = read_frame(&mut reader).await?;
+    handshake.read_message(&frame, &mut payload).context("Invalid Noise handshake message 3")?;
+
+    let remote_static = handshake.get_remote_static().context("Client sent no static key")?.to_vec();
+    let transport = Arc::new(handshake.into_stateless_transport_mode()?);
+    let (plain, channel) = tokio::io::duplex(MAX_MESSAGE_LEN);
+    let (mut channel_reader, mut channel_writer) = tokio::io::split(channel);
+
+    let inbound = transport.clone();
+    tokio::spawn(async move {
+        let mut payload = vec![0u8; MAX_MESSAGE_LEN];
+        for nonce in 0.. {
+            let Ok(frame) = read_frame(&mut reader).await else { break };
+            let Ok(len) = inbound.read_message(nonce, &frame, &mut payload) else { break };
+            if channel_writer.write_all(&payload[..len]).await.is_err() {
+                break;
+            }
+        }
+    });
+    tokio::spawn(async move {
+        let mut plaintext = vec![0u8; MAX_MESSAGE_LEN - TAG_LEN];
+        let mut message = vec![0u8; MAX_MESSAGE_LEN];
+        for nonce in 0.. {
+            let len = match channel_reader.read(&mut plaintext).await {
+                Ok(0) | Err(_) => break,
+                Ok(len) => len,
+            };
+            let Ok(len) = transport.write_message(nonce, &plaintext[..len], &mut message) else { break };
+            if write_frame(&mut writer, &message[..len]).await.is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown().await;
+    });
+
+    Ok((plain, remote_static))
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Vec<u8>> {
+    let len = reader.read_u16().await.context("Noise peer closed the connection")? as usize;
+    if len == 0 {
+        bail!("Empty Noise message");
+    }
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &[u8]) -> Result<()> {
+    writer.write_u16(frame.len() as u16).await?;
+    writer.write_all(frame).await?;
+    writer.flush().await?;
+    Ok(())
+}
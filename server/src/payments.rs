@@ -0,0 +1,281 @@
+use crate::audit::AuditLog;
+use crate::eip712::{self, Domain, ProofPayment};
+use crate::evm;
+use crate::jobs::JobStore;
+use crate::signing::{self, EnclaveSigner};
+use crate::state::AppState;
+use crate::submitter;
+use anyhow::{bail, Context, Result};
+use ethers::abi::{self, ParamType, Token};
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Eip1559TransactionRequest, U256};
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Escrow: deposit `id` as (payer, payee, amount, expiresAt, settled). A deposit that was never
+/// made has a zero payer.
+const DEPOSITS: &str = "deposits(bytes32)";
+/// Escrow: pays `amount` of deposit `id` to its payee, refunds the rest to its payer and marks
+/// the deposit settled. Only the payee may call it.
+const RELEASE: &str = "release(bytes32,uint256)";
+
+/// Least time a deposit must have left before it expires to pay for a request, so the release
+/// lands before the payer can take the deposit back.
+const MIN_DEPOSIT_LIFETIME_SECS: u64 = 600;
+
+/// Why a request's payment was not accepted.
+pub enum PaymentError {
+    /// The deposit does not pay for the request; the client has to fix it
+    Refused(String),
+    /// The escrow could not be read
+    Unavailable(anyhow::Error),
+}
+
+/// Charges proofs to deposits in an escrow contract on a configured chain, for servers that are
+/// paid per proof as on Marlin's compute marketplace. A request names a deposit payable to the
+/// submitter key, signed for by its payer. The deposit is checked on-chain and held before
+/// proving; every successful proof gets a receipt signed by the enclave, and once the request
+/// is done the proofs it was charged for are released from escrow in one transaction. Charges
+/// are recorded in the job store before their receipt is handed out, and those not yet released
+/// are released again after a restart.
+pub struct PaymentGate {
+    chain: String,
+    escrow: Address,
+    /// Price of one proof in the escrow token's smallest unit
+    price: U256,
+    signer: Arc<EnclaveSigner>,
+    audit: Arc<AuditLog>,
+    jobs: Arc<JobStore>,
+    /// Deposits paying for a request in progress or already released, which cannot pay again
+    spent: Mutex<HashSet<[u8; 32]>>,
+    releases: mpsc::UnboundedSender<Release>,
+    pending: tokio::sync::Mutex<mpsc::UnboundedReceiver<Release>>,
+}
+
+/// A deposit's charge, to be released from escrow.
+struct Release {
+    deposit_id: [u8; 32],
+    proofs: u64,
+    amount: U256,
+}
+
+/// A deposit held for one request. When the request's last permit is gone, the proofs charged
+/// are released; a deposit nothing was charged to is let go for another request.
+struct Deposit {
+    gate: Arc<PaymentGate>,
+    id: [u8; 32],
+    domain: Domain,
+    charged: AtomicU64,
+}
+
+impl Drop for Deposit {
+    fn drop(&mut self) {
+        let proofs = self.charged.load(Ordering::Relaxed);
+        if proofs == 0 {
+            self.gate.spent.lock().unwrap().remove(&self.id);
+            return;
+        }
+        let release = Release {
+            deposit_id: self.id,
+            proofs,
+            amount: self.gate.price * proofs,
+        };
+        if self.gate.releases.send(release).is_err() {
+            warn!("Payment releases have stopped; deposit 0x{} is not released", hex::encode(self.id));
+        }
+    }
+}
+
+/// A deposit accepted to pay for a request's proofs. Each proof takes a permit from it.
+pub struct Payment(Arc<Deposit>);
+
+impl Payment {
+    /// A permit for `proofs` proofs charged together, such as the members of an aggregation.
+    pub fn permit(&self, proofs: u64) -> PaymentPermit {
+        PaymentPermit {
+            deposit: self.0.clone(),
+            proofs,
+        }
+    }
+}
+
+/// Proofs paid for from a held deposit; nothing is charged unless the permit is settled.
+pub struct PaymentPermit {
+    deposit: Arc<Deposit>,
+    proofs: u64,
+}
+
+impl PaymentPermit {
+    /// Charges the permit's proofs to the deposit and returns the receipt for the proof `proof_id`.
+    /// The charge is recorded before the receipt exists, so no receipt is ever handed out for a
+    /// charge a restart could lose.
+    pub async fn settle(self, proof_id: &str) -> Result<ProofPayment> {
+        let gate = &self.deposit.gate;
+        let proof_id = eip712::parse_bytes32(proof_id).context("Invalid proof ID")?;
+        let deposit_id = format!("0x{}", hex::encode(self.deposit.id));
+        gate.jobs.charge(&deposit_id, self.proofs, &gate.price.to_string()).await?;
+        self.deposit.charged.fetch_add(self.proofs, Ordering::Relaxed);
+        ProofPayment::sign(&self.deposit.domain, &gate.signer, self.deposit.id, proof_id, gate.price * self.proofs)
+    }
+}
+
+impl PaymentGate {
+    pub fn new(chain: String, escrow: Address, price: u128, signer: Arc<EnclaveSigner>, audit: Arc<AuditLog>, jobs: Arc<JobStore>) -> Self {
+        let (releases, pending) = mpsc::unbounded_channel();
+        Self {
+            chain,
+            escrow,
+            price: price.into(),
+            signer,
+            audit,
+            jobs,
+            spent: Mutex::new(HashSet::new()),
+            releases,
+            pending: tokio::sync::Mutex::new(pending),
+        }
+    }
+
+    /// Price of one proof in the escrow token's smallest unit.
+    pub fn price(&self) -> U256 {
+        self.price
+    }
+
+    /// Checks that deposit `deposit_id` covers `proofs` proofs, is payable to the submitter key
+    /// and was signed for by its payer (an EIP-191 signature over the 32 deposit ID bytes), and
+    /// holds it for the request.
+    pub async fn hold(self: &Arc<Self>, state: &AppState, deposit_id: &str, signature: &str, proofs: u64) -> Result<Payment, PaymentError> {
+        let id = eip712::parse_bytes32(deposit_id)
+            .map_err(|e| PaymentError::Refused(format!("Invalid deposit ID: {:#}", e)))?;
+        let signed_by: Address = signing::recover_address(&id, signature)
+            .and_then(|address| Ok(address.parse()?))
+            .map_err(|e| PaymentError::Refused(format!("Invalid payment signature: {:#}", e)))?;
+        if !self.spent.lock().unwrap().insert(id) {
+            return Err(PaymentError::Refused(format!("Deposit {} has already paid for a request", deposit_id)));
+        }
+        // Built before any check can fail, so a refusal or a dropped request lets the deposit go again
+        let mut deposit = Deposit {
+            gate: self.clone(),
+            id,
+            domain: Domain::new(None, Some(self.escrow)),
+            charged: AtomicU64::new(0),
+        };
+        let (chain_id, payer, payee, amount, expires_at, settled) = self.deposit(state, id).await.map_err(PaymentError::Unavailable)?;
+        let cost = self.price * proofs;
+        let payee_wanted = state.submitter.as_ref().map(|submitter| submitter.address());
+        let refusal = if payer.is_zero() {
+            Some(format!("No deposit {} in the escrow", deposit_id))
+        } else if settled {
+            Some(format!("Deposit {} has already been settled", deposit_id))
+        } else if Some(payee) != payee_wanted {
+            Some(format!("Deposit {} is payable to {:?}, not to this server", deposit_id, payee))
+        } else if expires_at < U256::from(chrono::Utc::now().timestamp().max(0) as u64 + MIN_DEPOSIT_LIFETIME_SECS) {
+            Some(format!("Deposit {} expires in less than {}s", deposit_id, MIN_DEPOSIT_LIFETIME_SECS))
+        } else if amount < cost {
+            Some(format!("Deposit {} holds {}, but {} proofs cost {}", deposit_id, amount, proofs, cost))
+        } else if signed_by != payer {
+            Some(format!("The payment signature is not by the payer of deposit {}", deposit_id))
+        } else {
+            None
+        };
+        if let Some(refusal) = refusal {
+            return Err(PaymentError::Refused(refusal));
+        }
+        deposit.domain = Domain::new(Some(chain_id), Some(self.escrow));
+        Ok(Payment(Arc::new(deposit)))
+    }
+
+    /// Reads deposit `id` from the escrow: the chain ID and the deposit's payer, payee, amount,
+    /// expiry and whether it is settled.
+    async fn deposit(&self, state: &AppState, id: [u8; 32]) -> Result<(u64, Address, Address, U256, U256, bool)> {
+        let (name, chain) = state.config.read().unwrap().chain(Some(&self.chain))?;
+        let provider = submitter::provider(&state.http, &name, &chain)?;
+        let tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .to(self.escrow)
+            .data(calldata(DEPOSITS, &[Token::FixedBytes(id.to_vec())]))
+            .chain_id(chain.chain_id)
+            .into();
+        let output = provider
+            .call(&tx, None)
+            .await
+            .with_context(|| format!("Failed to read the escrow on chain '{}'", name))?;
+        let types = [ParamType::Address, ParamType::Address, ParamType::Uint(256), ParamType::Uint(256), ParamType::Bool];
+        match abi::decode(&types, &output).context("Unexpected reply from the escrow's deposits()")?.as_slice() {
+            [Token::Address(payer), Token::Address(payee), Token::Uint(amount), Token::Uint(expires_at), Token::Bool(settled)] => {
+                Ok((chain.chain_id, *payer, *payee, *amount, *expires_at, *settled))
+            }
+            _ => bail!("Unexpected reply from the escrow's deposits()"),
+        }
+    }
+
+    /// Releases the proofs charged to deposits as their requests finish, each in its own
+    /// transaction, starting with those charged before the last restart. A failed release is
+    /// logged and audited; the deposit stays spent on this server and its charge stays recorded,
+    /// so the release is tried again after the next restart.
+    pub async fn run(self: Arc<Self>, state: Arc<AppState>) {
+        match self.jobs.pending_releases().await {
+            Ok(unreleased) => {
+                for (deposit_id, proofs, price) in unreleased {
+                    let (Ok(id), Ok(price)) = (eip712::parse_bytes32(&deposit_id), U256::from_dec_str(&price)) else {
+                        warn!("Pending release of deposit {} is not valid and is skipped", deposit_id);
+                        continue;
+                    };
+                    info!("Releasing deposit {}, charged before the last restart", deposit_id);
+                    self.spent.lock().unwrap().insert(id);
+                    let release = Release {
+                        deposit_id: id,
+                        proofs,
+                        amount: price * proofs,
+                    };
+                    if self.releases.send(release).is_err() {
+                        break;
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to read the pending payment releases: {:#}", e),
+        }
+        let mut pending = self.pending.lock().await;
+        while let Some(release) = pending.recv().await {
+            let gate = self.clone();
+            let state = state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = gate.release(&state, &release).await {
+                    let deposit_id = format!("0x{}", hex::encode(release.deposit_id));
+                    warn!("Failed to release deposit {}: {:#}", deposit_id, e);
+                    gate.audit.record(
+                        "payment_release_failed",
+                        json!({ "deposit_id": deposit_id, "proofs": release.proofs, "amount": release.amount.to_string(), "error": format!("{:#}", e) }),
+                    );
+                }
+            });
+        }
+    }
+
+    async fn release(&self, state: &AppState, release: &Release) -> Result<()> {
+        let submitter = state.submitter.as_ref().context("Payments need a submitter key")?;
+        let (name, chain) = state.config.read().unwrap().chain(Some(&self.chain))?;
+        let data = calldata(RELEASE, &[Token::FixedBytes(release.deposit_id.to_vec()), Token::Uint(release.amount)]);
+        let submission = submitter.transact(&state.http, &name, &chain, self.escrow, data, None).await?;
+        match &submission.receipt {
+            Some(receipt) if receipt.success => {}
+            Some(_) => bail!("Release transaction {} reverted", submission.tx_hash),
+            None => bail!("Release transaction {} has no receipt", submission.tx_hash),
+        }
+        let deposit_id = format!("0x{}", hex::encode(release.deposit_id));
+        info!("Released {} for {} proofs from deposit {} in {}", release.amount, release.proofs, deposit_id, submission.tx_hash);
+        self.audit.record(
+            "payment_released",
+            json!({ "deposit_id": deposit_id, "proofs": release.proofs, "amount": release.amount.to_string(), "tx_hash": submission.tx_hash }),
+        );
+        self.jobs.released(&deposit_id).await.context("Released, but failed to clear the recorded charge")?;
+        Ok(())
+    }
+}
+
+fn calldata(signature: &str, arguments: &[Token]) -> Vec<u8> {
+    [evm::selector(signature).as_slice(), &abi::encode(arguments)].concat()
+}
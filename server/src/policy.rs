@@ -0,0 +1,418 @@
+use crate::prover::{MAX_AGE, MAX_BMI, MIN_AGE, MIN_BMI};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Most alternatives a policy may expand to; each one is a separate set of circuit bounds.
+const MAX_VARIANTS: usize = 16;
+
+/// Inclusive ranges the eligibility circuit checks, i.e. its public inputs. BMI is in tenths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min_age: u32,
+    pub max_age: u32,
+    pub min_bmi: u32,
+    pub max_bmi: u32,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Bounds {
+    /// The product the circuit was written for, used for requests without a configured policy.
+    pub const DEFAULT: Bounds = Bounds {
+        min_age: MIN_AGE,
+        max_age: MAX_AGE,
+        min_bmi: MIN_BMI,
+        max_bmi: MAX_BMI,
+    };
+
+    const ANY: Bounds = Bounds {
+        min_age: 0,
+        max_age: u32::MAX,
+        min_bmi: 0,
+        max_bmi: u32::MAX,
+    };
+
+    /// Rejects ranges no applicant can satisfy.
+    pub fn check(&self) -> Result<()> {
+        if self.min_age > self.max_age || self.min_bmi > self.max_bmi {
+            bail!("Empty range: age {}-{}, bmi_multiplied {}-{}", self.min_age, self.max_age, self.min_bmi, self.max_bmi);
+        }
+        Ok(())
+    }
+
+    /// Public inputs in circuit order.
+    pub fn public_inputs(&self) -> [u32; 4] {
+        [self.min_age, self.max_age, self.min_bmi, self.max_bmi]
+    }
+
+    pub fn contains(&self, age: u32, bmi_multiplied: u32) -> bool {
+        (self.min_age..=self.max_age).contains(&age) && (self.min_bmi..=self.max_bmi).contains(&bmi_multiplied)
+    }
+
+    /// The ranges `age` and `bmi_multiplied` fall outside of, described for the applicant.
+    pub fn violations(&self, age: u32, bmi_multiplied: u32) -> Vec<String> {
+        let mut violations = Vec::new();
+        if age < self.min_age {
+            violations.push(format!("age must be at least {}", self.min_age));
+        } else if age > self.max_age {
+            violations.push(format!("age must be at most {}", self.max_age));
+        }
+        if bmi_multiplied < self.min_bmi {
+            violations.push(format!("bmi_multiplied must be at least {}", self.min_bmi));
+        } else if bmi_multiplied > self.max_bmi {
+            violations.push(format!("bmi_multiplied must be at most {}", self.max_bmi));
+        }
+        violations
+    }
+
+    fn intersect(&self, other: &Bounds) -> Option<Bounds> {
+        let bounds = Bounds {
+            min_age: self.min_age.max(other.min_age),
+            max_age: self.max_age.min(other.max_age),
+            min_bmi: self.min_bmi.max(other.min_bmi),
+            max_bmi: self.max_bmi.min(other.max_bmi),
+        };
+        (bounds.min_age <= bounds.max_age && bounds.min_bmi <= bounds.max_bmi).then_some(bounds)
+    }
+}
+
+/// Bounds a request is proven against, resolved from its policy when a worker picks it up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Eligibility {
+    /// Tier of a tiered policy the applicant qualified for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    pub bounds: Bounds,
+}
+
+/// Policy as written in configuration: a single rule, or tiers from best to worst.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PolicyDefinition {
+    Rule(String),
+    Tiered { tiers: Vec<TierDefinition> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TierDefinition {
+    pub name: String,
+    pub rule: String,
+}
+
+/// An eligibility policy compiled to circuit bounds. Rules combine ranges over the named health
+/// fields `age` (years) and `bmi` (one decimal) with `and`, `or` and parentheses:
+///
+/// ```text
+/// age in 18..=25 and (bmi in 18.5..=24.9 or bmi < 27)
+/// ```
+///
+/// The circuit checks one range per field, so a rule is expanded into alternatives of that shape
+/// and the first one the applicant satisfies is proven. A request may instead name one tier of a
+/// tiered policy, which limits the alternatives to that tier's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PolicyDefinition", into = "PolicyDefinition")]
+pub struct Policy {
+    definition: PolicyDefinition,
+    tiers: Vec<(Option<String>, Vec<Bounds>)>,
+}
+
+impl TryFrom<PolicyDefinition> for Policy {
+    type Error = String;
+
+    fn try_from(definition: PolicyDefinition) -> Result<Self, String> {
+        let tiers = match &definition {
+            PolicyDefinition::Rule(rule) => vec![(None, compile(rule).map_err(|e| format!("{:#}", e))?)],
+            PolicyDefinition::Tiered { tiers } => {
+                if tiers.is_empty() {
+                    return Err("a tiered policy needs at least one tier".to_string());
+                }
+                tiers
+                    .iter()
+                    .map(|tier| {
+                        compile(&tier.rule)
+                            .map(|variants| (Some(tier.name.clone()), variants))
+                            .map_err(|e| format!("tier '{}': {:#}", tier.name, e))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        Ok(Self { definition, tiers })
+    }
+}
+
+impl From<Policy> for PolicyDefinition {
+    fn from(policy: Policy) -> Self {
+        policy.definition
+    }
+}
+
+impl Policy {
+    /// The best tier and alternative the applicant satisfies, within `tier` if one is named. If
+    /// there is none, the first one is returned so the circuit rejects the inputs like any other
+    /// ineligible applicant.
+    pub fn resolve(&self, tier: Option<&str>, age: u32, bmi_multiplied: u32) -> Eligibility {
+        let candidates: Vec<_> = self
+            .tiers
+            .iter()
+            .filter(|(name, _)| tier.is_none() || name.as_deref() == tier)
+            .flat_map(|(tier, variants)| variants.iter().map(move |bounds| (tier, bounds)))
+            .collect();
+        let (tier, bounds) = candidates
+            .iter()
+            .find(|(_, bounds)| bounds.contains(age, bmi_multiplied))
+            .or(candidates.first())
+            .copied()
+            .unwrap_or((&self.tiers[0].0, &self.tiers[0].1[0]));
+        Eligibility {
+            tier: tier.clone(),
+            bounds: *bounds,
+        }
+    }
+
+    /// Named tiers, best first, with the alternatives each may be proven with.
+    pub fn tiers(&self) -> impl Iterator<Item = (&str, &[Bounds])> {
+        self.tiers.iter().filter_map(|(name, variants)| Some((name.as_deref()?, variants.as_slice())))
+    }
+
+    /// Every set of bounds a proof under this policy may carry.
+    pub fn variants(&self) -> impl Iterator<Item = &Bounds> {
+        self.tiers.iter().flat_map(|(_, variants)| variants)
+    }
+}
+
+/// Reads a policies table (JSON object of policy id to rule or tiers), compiling every rule.
+pub fn load(path: &Path) -> Result<BTreeMap<String, Policy>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("{} is not a valid policies table", path.display()))
+}
+
+/// Parses a rule and expands it into the circuit bounds of each alternative.
+pub fn compile(rule: &str) -> Result<Vec<Bounds>> {
+    let mut parser = Parser {
+        tokens: tokenize(rule)?,
+        position: 0,
+    };
+    let expr = parser.or()?;
+    if let Some(token) = parser.tokens.get(parser.position) {
+        bail!("unexpected '{}'", token);
+    }
+    let variants = expand(&expr)?;
+    if variants.is_empty() {
+        bail!("'{}' can never be satisfied", rule);
+    }
+    Ok(variants)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Range(Bounds),
+    All(Box<Expr>, Box<Expr>),
+    Any(Box<Expr>, Box<Expr>),
+}
+
+/// Disjunctive normal form: each alternative is a conjunction of ranges, i.e. one set of bounds.
+/// Alternatives that cannot be satisfied are dropped.
+fn expand(expr: &Expr) -> Result<Vec<Bounds>> {
+    let variants = match expr {
+        Expr::Range(bounds) => vec![*bounds],
+        Expr::Any(left, right) => {
+            let mut variants = expand(left)?;
+            for bounds in expand(right)? {
+                if !variants.contains(&bounds) {
+                    variants.push(bounds);
+                }
+            }
+            variants
+        }
+        Expr::All(left, right) => {
+            let right = expand(right)?;
+            let mut variants = Vec::new();
+            for left in expand(left)? {
+                for bounds in right.iter().filter_map(|right| left.intersect(right)) {
+                    if !variants.contains(&bounds) {
+                        variants.push(bounds);
+                    }
+                }
+            }
+            variants
+        }
+    };
+    if variants.len() > MAX_VARIANTS {
+        bail!("the rule expands to more than {} alternatives", MAX_VARIANTS);
+    }
+    Ok(variants)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) | Token::Number(word) => f.write_str(word),
+            Token::Op(op) => f.write_str(op),
+        }
+    }
+}
+
+const OPS: [&str; 8] = ["..=", ">=", "<=", "==", ">", "<", "(", ")"];
+
+fn tokenize(rule: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = rule.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+            // A range's ".." is not part of the number
+            let len = rest[..len].find("..").unwrap_or(len);
+            tokens.push(Token::Number(rest[..len].to_string()));
+            len
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..len].to_string()));
+            len
+        } else {
+            bail!("unexpected character '{}'", c);
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Age,
+    Bmi,
+}
+
+impl Field {
+    /// Parses a value in the field's unit into the integer the circuit compares.
+    fn value(self, number: &str) -> Result<u32> {
+        let (whole, tenths) = match number.split_once('.') {
+            Some((whole, tenths)) => (whole, Some(tenths)),
+            None => (number, None),
+        };
+        let whole: u32 = whole.parse().map_err(|_| anyhow!("invalid number '{}'", number))?;
+        match (self, tenths) {
+            (Field::Age, None) => Ok(whole),
+            (Field::Age, Some(_)) => bail!("age is in whole years, found '{}'", number),
+            (Field::Bmi, tenths) => {
+                let tenths: u32 = match tenths {
+                    None => 0,
+                    Some(tenths) if tenths.len() == 1 => tenths.parse().map_err(|_| anyhow!("invalid number '{}'", number))?,
+                    Some(_) => bail!("bmi has one decimal, found '{}'", number),
+                };
+                whole
+                    .checked_mul(10)
+                    .and_then(|value| value.checked_add(tenths))
+                    .ok_or_else(|| anyhow!("'{}' is out of range", number))
+            }
+        }
+    }
+
+    fn range(self, min: u32, max: u32) -> Bounds {
+        match self {
+            Field::Age => Bounds { min_age: min, max_age: max, ..Bounds::ANY },
+            Field::Bmi => Bounds { min_bmi: min, max_bmi: max, ..Bounds::ANY },
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token> {
+        let token = self.tokens.get(self.position).cloned().ok_or_else(|| anyhow!("unexpected end of rule"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let matches = self.tokens.get(self.position) == Some(&Token::Word(word.to_string()));
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat_word("or") {
+            expr = Expr::Any(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.atom()?;
+        while self.eat_word("and") {
+            expr = Expr::All(Box::new(expr), Box::new(self.atom()?));
+        }
+        Ok(expr)
+    }
+
+    fn atom(&mut self) -> Result<Expr> {
+        let field = match self.next()? {
+            Token::Op("(") => {
+                let expr = self.or()?;
+                return match self.next()? {
+                    Token::Op(")") => Ok(expr),
+                    token => bail!("expected ')', found '{}'", token),
+                };
+            }
+            Token::Word(word) => match word.as_str() {
+                "age" => Field::Age,
+                "bmi" => Field::Bmi,
+                _ => bail!("unknown field '{}' (expected age or bmi)", word),
+            },
+            token => bail!("expected a field, found '{}'", token),
+        };
+        let op = self.next()?;
+        let value = match self.next()? {
+            Token::Number(number) => field.value(&number)?,
+            token => bail!("expected a number, found '{}'", token),
+        };
+        let (min, max) = match op {
+            Token::Word(word) if word == "in" => {
+                match self.next()? {
+                    Token::Op("..=") => {}
+                    token => bail!("expected '..=', found '{}'", token),
+                }
+                let max = match self.next()? {
+                    Token::Number(number) => field.value(&number)?,
+                    token => bail!("expected a number, found '{}'", token),
+                };
+                if value > max {
+                    bail!("empty range {}..={}", value, max);
+                }
+                (value, max)
+            }
+            Token::Op(">=") => (value, u32::MAX),
+            Token::Op("<=") => (0, value),
+            Token::Op("==") => (value, value),
+            Token::Op(">") => (value.checked_add(1).ok_or_else(|| anyhow!("nothing is greater than {}", value))?, u32::MAX),
+            Token::Op("<") => (0, value.checked_sub(1).ok_or_else(|| anyhow!("nothing is less than 0"))?),
+            token => bail!("expected 'in' or a comparison, found '{}'", token),
+        };
+        Ok(Expr::Range(field.range(min, max)))
+    }
+}
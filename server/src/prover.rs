@@ -0,0 +1,1335 @@
+use crate::abi::{CircuitAbi, Inputs};
+use crate::backpressure;
+use crate::control::ReleaseHistory;
+use crate::eip712::{EligibilityAttestation, ProofPayment};
+use crate::evm;
+use crate::honk::{self, Native, NativeVerifier};
+use crate::limits::ResourceLimits;
+use crate::logging::Private;
+use crate::metrics::StepDurations;
+use crate::payments::PaymentPermit;
+use crate::policy::{Bounds, Eligibility};
+use crate::registry::CircuitRegistry;
+use crate::retry::{self, RetryPolicy};
+use crate::scratch::{self, JobWorkspace};
+use crate::signing::EnclaveSignature;
+use crate::telemetry::{Span, TraceContext};
+use crate::usage::UsagePermit;
+use crate::watermark::Watermark;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::process::Command;
+use tracing::Instrument;
+use zeroize::Zeroizing;
+
+/// Barretenberg proving system. Verifier contracts are generated for exactly one scheme,
+/// so the proof has to be produced with the scheme the relying party's verifier accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvingScheme {
+    UltraHonk,
+    UltraPlonk,
+}
+
+impl ProvingScheme {
+    /// bb arguments that prove `witness` against `bytecode`, writing the proof to `output`.
+    pub fn prove_args<'a>(&self, bytecode: &'a str, witness: &'a str, output: &'a str) -> Vec<&'a str> {
+        match self {
+            ProvingScheme::UltraHonk => vec![
+                "prove",
+                "--scheme", "ultra_honk",
+                "-b", bytecode,
+                "-w", witness,
+                "-o", output,
+                "--oracle_hash", "keccak",
+                "--output_format", "bytes_and_fields",
+            ],
+            // UltraPlonk is only reachable through bb's legacy command set
+            ProvingScheme::UltraPlonk => vec![
+                "OLD_API", "prove",
+                "-b", bytecode,
+                "-w", witness,
+                "-o", output,
+            ],
+        }
+    }
+}
+
+/// Where the verification key is cached inside a circuit package. Release directories are never
+/// modified after activation, so a key written there stays valid for that release.
+pub const VK_DIR: &str = "target/verifier_vk";
+
+/// Eligibility bounds for requests without a configured policy, in circuit order
+pub const MIN_AGE: u32 = 10;
+pub const MAX_AGE: u32 = 25;
+pub const MIN_BMI: u32 = 185;
+pub const MAX_BMI: u32 = 249;
+
+/// Circuit parameters filled from `age`, `bmi_multiplied`, the policy's bounds, `date_of_birth`,
+/// the underwriting fields, `nonce` and the proof's expiry, which `inputs` may therefore not set.
+pub const SERVER_INPUTS: [&str; 14] = [
+    "age", "bmi", "min_age", "max_age", "min_bmi", "max_bmi",
+    "dob", "as_of_date",
+    "smoker", "systolic_bp", "diastolic_bp", "conditions",
+    "nonce", "expires_at",
+];
+
+/// How long proofs stay valid without --proof-validity-secs: 30 days.
+pub const DEFAULT_PROOF_VALIDITY_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Longest --proof-validity-secs accepted: 10 years.
+pub const MAX_PROOF_VALIDITY_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+
+/// Unix time `validity_secs` from now.
+pub fn expiry_from_now(validity_secs: u64) -> u64 {
+    (Utc::now().timestamp() as u64).saturating_add(validity_secs)
+}
+
+/// `age` or `bmi_multiplied` of a request that left it out, which only requests for registered
+/// circuits may do.
+pub const UNSET: u32 = u32::MAX;
+
+fn unset() -> u32 {
+    UNSET
+}
+
+/// A date as the circuits take it: the integer YYYYMMDD.
+pub fn date_number(date: NaiveDate) -> u32 {
+    date.year() as u32 * 10_000 + date.month() * 100 + date.day()
+}
+
+/// Whole years from `date_of_birth` to `date`, computed as the DOB circuit does.
+pub fn age_on(date_of_birth: NaiveDate, date: NaiveDate) -> u32 {
+    date_number(date).saturating_sub(date_number(date_of_birth)) / 10_000
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ProofRequest {
+    #[serde(default = "unset")]
+    pub age: u32,
+    #[serde(default = "unset")]
+    pub bmi_multiplied: u32, // BMI * 10 to avoid decimals
+    /// With `weight_kg`, instead of `bmi_multiplied`, which intake computes from them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height_cm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight_kg: Option<f64>,
+    /// Instead of `age`: circuits taking `dob` prove the age band as of the current date, a
+    /// public input (`as_of_date`); for others intake computes `age` from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_of_birth: Option<NaiveDate>,
+    /// Underwriting fields, passed to circuits with parameters of the same names. The
+    /// eligibility circuit takes none of them (age and BMI only); a request sending one to a
+    /// circuit without it fails rather than prove less than was asked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smoker: Option<bool>,
+    /// Blood pressure in mmHg, sent together with `diastolic_bp`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub systolic_bp: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diastolic_bp: Option<u32>,
+    /// Pre-existing conditions as a bitmap; the insurer's circuit defines the bits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<u64>,
+    /// Values for any other parameters of the circuit's `main`, by name, checked against the
+    /// compiled circuit's ABI. Sealed with the health fields while the job waits.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: Inputs,
+    /// Challenge from the relying party's verifier session (hex, up to 31 bytes), proven as the
+    /// public input `nonce` so the proof cannot be replayed to another session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Registered circuit to prove with (see --circuits-file) instead of the eligibility circuit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_id: Option<String>,
+    /// sha256 of the compiled circuit the client expects, as in `circuit_hash`; the job fails
+    /// with `circuit_mismatch` if the server would prove with another
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_circuit_hash: Option<String>,
+    /// Overrides the server's default proving scheme
+    #[serde(default)]
+    pub scheme: Option<ProvingScheme>,
+    /// Queue priority; defaults depend on the transport (see `Priority`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// Insurance policy the proof is for; selects the eligibility rules and proving-time ceiling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Tier of `policy` to prove against instead of the best one the applicant satisfies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    /// Resolved from `policy` when the request is admitted; whatever a client sends is replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eligibility: Option<Eligibility>,
+    /// Unix time the proof expires at, proven as the public input `expires_at`; set from
+    /// --proof-validity-secs when the request is admitted, whatever a client sends is replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Also produce the recursive (Poseidon2) variant from the same witness (UltraHonk only)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub recursive: bool,
+    /// Prove against the circuit release that was active at this time instead of the current one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<DateTime<Utc>>,
+    /// The applicant agreed to have these inputs proven (required with --require-consent)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub consent: bool,
+    /// Extra encodings of the proof to include in the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<OutputFormat>,
+    /// bytes32 the insurer ties to the applicant (e.g. keccak256 of their address and a salt),
+    /// carried into `eligibility_attestation`; defaults to the proof_id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applicant_commitment: Option<String>,
+    /// W3C trace context the proof's spans are parented to; set by the server, not clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+    /// Correlates the job's log lines and is echoed in the response; assigned by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Tenant the request is proven for, from the caller's API key or X-Tenant-Id; None for the
+    /// default tenant. Set by the server, whatever a client sends is replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Quota permit of the API key the request came with; the proof is charged to the key when
+    /// the job finishes
+    #[serde(skip)]
+    pub usage: Option<UsagePermit>,
+    /// The escrow deposit paying for the request, when proofs are paid for; charged only if the
+    /// proof succeeds
+    #[serde(skip)]
+    pub payment: Option<PaymentPermit>,
+}
+
+/// Like a derived Debug, with the health inputs redacted unless --log-private-inputs is set.
+impl fmt::Debug for ProofRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProofRequest")
+            .field("age", &Private(self.age))
+            .field("bmi_multiplied", &Private(self.bmi_multiplied))
+            .field("height_cm", &self.height_cm.map(Private))
+            .field("weight_kg", &self.weight_kg.map(Private))
+            .field("date_of_birth", &self.date_of_birth.map(Private))
+            .field("smoker", &self.smoker.map(Private))
+            .field("systolic_bp", &self.systolic_bp.map(Private))
+            .field("diastolic_bp", &self.diastolic_bp.map(Private))
+            .field("conditions", &self.conditions.map(Private))
+            .field("inputs", &Private(serde_json::Value::from_iter(self.inputs.clone())))
+            .field("nonce", &self.nonce)
+            .field("circuit_id", &self.circuit_id)
+            .field("expected_circuit_hash", &self.expected_circuit_hash)
+            .field("scheme", &self.scheme)
+            .field("priority", &self.priority)
+            .field("policy", &self.policy)
+            .field("tier", &self.tier)
+            .field("eligibility", &self.eligibility)
+            .field("expires_at", &self.expires_at)
+            .field("recursive", &self.recursive)
+            .field("as_of", &self.as_of)
+            .field("consent", &self.consent)
+            .field("output_format", &self.output_format)
+            .field("applicant_commitment", &self.applicant_commitment)
+            .field("traceparent", &self.traceparent)
+            .field("request_id", &self.request_id)
+            .field("tenant", &self.tenant)
+            .field("usage", &self.usage.is_some())
+            .field("payment", &self.payment.is_some())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// `proof_hex` and `public_inputs` only
+    Hex,
+    /// Also ABI-encode the proof as a call to the generated Solidity verifier (ultra_honk only)
+    EvmCalldata,
+}
+
+/// Order in which queued jobs are picked up. Interactive flows default to `high`, single
+/// API calls to `normal` and batch items to `low`, so quotes are not stuck behind bulk work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+impl ProofRequest {
+    /// Synthetic applicant in the middle of `bounds`, the configured default ranges, used to
+    /// probe backend health. Its proof carries those bounds, so verifiers accept it.
+    pub fn canary(bounds: Bounds) -> Self {
+        Self {
+            age: bounds.min_age + (bounds.max_age - bounds.min_age) / 2,
+            bmi_multiplied: bounds.min_bmi + (bounds.max_bmi - bounds.min_bmi) / 2,
+            eligibility: Some(Eligibility { tier: None, bounds }),
+            priority: Some(Priority::High),
+            consent: true,
+            ..Default::default()
+        }
+    }
+
+    /// Bounds the circuit is run with: those resolved at admission, or the circuit's defaults
+    /// for requests that were never admitted.
+    pub fn bounds(&self) -> Bounds {
+        self.eligibility.as_ref().map(|eligibility| eligibility.bounds).unwrap_or(Bounds::DEFAULT)
+    }
+
+    /// Everything the circuit is run with: the health fields, the eligibility bounds, the
+    /// circuit's fixed `parameters` and the request's other `inputs`. Only the parameters `abi`
+    /// declares are kept from the first two, so circuits without them need no special casing.
+    /// Fails on a nonce that is not a field element, rather than proving another one.
+    pub fn circuit_inputs(&self, abi: &CircuitAbi, parameters: Inputs) -> Result<Inputs> {
+        let bounds = self.bounds();
+        let mut inputs: Inputs = SERVER_INPUTS[..6]
+            .iter()
+            .copied()
+            .zip([self.age, self.bmi_multiplied, bounds.min_age, bounds.max_age, bounds.min_bmi, bounds.max_bmi])
+        .filter(|(name, value)| *value != UNSET && abi.parameter(name).is_some())
+        .map(|(name, value)| (name.to_string(), value.into()))
+        .collect();
+        if let Some(date_of_birth) = self.date_of_birth.filter(|_| abi.parameter("dob").is_some()) {
+            inputs.insert("dob".to_string(), date_number(date_of_birth).into());
+            if abi.parameter("as_of_date").is_some() {
+                inputs.insert("as_of_date".to_string(), date_number(Utc::now().date_naive()).into());
+            }
+        }
+        inputs.extend(self.underwriting().into_iter().map(|(name, value)| (name.to_string(), value)));
+        // Circuits that take a nonce get 0 when the request has none, i.e. no verifier session
+        if abi.parameter("nonce").is_some() {
+            let nonce = match self.nonce.as_deref() {
+                Some(nonce) => honk::nonce_word(nonce)?,
+                None => [0; 32],
+            };
+            inputs.insert("nonce".to_string(), format!("0x{}", hex::encode(nonce)).into());
+        }
+        if let Some(expires_at) = self.expires_at.filter(|_| abi.parameter("expires_at").is_some()) {
+            inputs.insert("expires_at".to_string(), expires_at.into());
+        }
+        inputs.extend(parameters);
+        inputs.extend(self.inputs.clone());
+        Ok(inputs)
+    }
+
+    /// The underwriting fields the request carries, by circuit parameter name.
+    pub fn underwriting(&self) -> Vec<(&'static str, serde_json::Value)> {
+        [
+            ("smoker", self.smoker.map(serde_json::Value::from)),
+            ("systolic_bp", self.systolic_bp.map(serde_json::Value::from)),
+            ("diastolic_bp", self.diastolic_bp.map(serde_json::Value::from)),
+            ("conditions", self.conditions.map(serde_json::Value::from)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+    }
+
+    /// Whether the circuit constraints are satisfiable for these inputs.
+    pub fn is_eligible(&self) -> bool {
+        self.bounds().contains(self.age, self.bmi_multiplied)
+    }
+}
+
+/// Machine-readable failure class, so callers can tell a genuinely ineligible applicant
+/// apart from infrastructure trouble without parsing `message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The inputs do not satisfy the circuit constraints
+    Ineligible,
+    /// nargo/bb failed or produced unusable artifacts
+    BackendError,
+    /// The proving backend is failing repeatedly and is temporarily not being invoked
+    BackendUnavailable,
+    /// Proving ran past the ceiling for the request's policy and was cancelled; not billed
+    BudgetExceeded,
+    /// The request asks for something the server cannot do with these settings
+    InvalidRequest,
+    /// The server runs a different circuit than the request's `expected_circuit_hash`
+    CircuitMismatch,
+    /// The job queue is full; retry after `retry_after_secs`
+    ServerBusy,
+    /// The client is over its rate limit; retry after `retry_after_secs`
+    RateLimited,
+    /// The API key is over one of its quotas (see `quota`); retry after `retry_after_secs`
+    QuotaExceeded,
+    /// Proofs are paid for and the request's escrow deposit was missing or does not cover it
+    PaymentRequired,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofResponse {
+    pub proof_hex: String,
+    pub public_inputs: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// Poseidon2 variant of the same proof, for later recursive aggregation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recursive: Option<RecursiveProof>,
+    /// Transaction data for the Solidity verifier's `verify(bytes,bytes32[])`, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calldata: Option<String>,
+    /// keccak256 of the proof bytes; the same ID can be recovered from on-chain verify calls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof_id: Option<String>,
+    /// UUID the proof can be downloaded again by at GET /proofs/{job_id}; set when the server
+    /// records jobs (--database-url)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// CID of the proof's bundle on IPFS, for relying parties to cite; set when the server
+    /// publishes bundles (--ipfs-publish-api)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipfs_cid: Option<String>,
+    /// Tier of a tiered policy the proof qualifies for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    /// Ranges the proof was generated against, as carried in its public inputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<Bounds>,
+    /// Unix time the proof expires at, as carried in its public inputs; verifiers reject it after
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Signed tag identifying the tenant the proof was issued to, when watermarking is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<Watermark>,
+    /// sha256 of the compiled circuit the proof was generated with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_hash: Option<String>,
+    /// sha256 of the verification key the proof checks against, as served by GET /vk;
+    /// ultra_honk proofs only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vk_hash: Option<String>,
+    /// Enclave signature over `signing_message`, added to every successful proof
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enclave_signature: Option<EnclaveSignature>,
+    /// EIP-712 endorsement of the applicant's eligibility, for contracts; successful proofs only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eligibility_attestation: Option<EligibilityAttestation>,
+    /// Enclave-signed receipt for the proof's price, charged to the request's escrow deposit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment: Option<ProofPayment>,
+    /// Time spent in each step; recorded in the metrics and removed before the response is sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step_durations: Option<StepDurations>,
+    /// How `bmi_multiplied` was computed, for requests that sent height and weight
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bmi_derivation: Option<BmiDerivation>,
+    /// ID of the request in the server's logs, for support tickets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Seconds to wait before retrying a `server_busy` request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    pub message: String,
+}
+
+/// A proof in field form with the SNARK-friendly Poseidon2 transcript, verifiable inside a
+/// Noir circuit (see aggregation-circuit) rather than on-chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecursiveProof {
+    pub proof: Vec<String>,
+    pub public_inputs: Vec<String>,
+}
+
+/// The BMI intake computed from a request's height and weight: `bmi_multiplied` is
+/// `round(10 * weight_kg / (height_cm / 100)^2)`.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct BmiDerivation {
+    pub height_cm: f64,
+    pub weight_kg: f64,
+    pub bmi_multiplied: u32,
+}
+
+impl BmiDerivation {
+    pub fn new(height_cm: f64, weight_kg: f64) -> Self {
+        Self {
+            height_cm,
+            weight_kg,
+            bmi_multiplied: (weight_kg * 100_000.0 / (height_cm * height_cm)).round() as u32,
+        }
+    }
+
+    /// The derivation behind an opened request's BMI, if it sent height and weight.
+    fn of(request: &ProofRequest) -> Option<Self> {
+        Some(Self::new(request.height_cm?, request.weight_kg?))
+    }
+}
+
+impl fmt::Debug for BmiDerivation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BmiDerivation")
+            .field("height_cm", &Private(self.height_cm))
+            .field("weight_kg", &Private(self.weight_kg))
+            .field("bmi_multiplied", &Private(self.bmi_multiplied))
+            .finish()
+    }
+}
+
+/// bb flags producing a `RecursiveProof`.
+const RECURSIVE_PROOF_FLAGS: [&str; 5] = [
+    "--oracle_hash", "poseidon2",
+    "--output_format", "fields",
+    "--init_kzg_accumulator",
+];
+
+impl ProofResponse {
+    pub fn failed(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            proof_hex: String::new(),
+            public_inputs: String::new(),
+            success: false,
+            error_code: Some(code),
+            recursive: None,
+            calldata: None,
+            proof_id: None,
+            job_id: None,
+            ipfs_cid: None,
+            tier: None,
+            bounds: None,
+            expires_at: None,
+            watermark: None,
+            circuit_hash: None,
+            vk_hash: None,
+            enclave_signature: None,
+            eligibility_attestation: None,
+            payment: None,
+            step_durations: None,
+            bmi_derivation: None,
+            request_id: None,
+            retry_after_secs: None,
+            message: message.into(),
+        }
+    }
+
+    /// Turns a request away because the server is at capacity.
+    pub fn busy(retry_after_secs: u64) -> Self {
+        Self {
+            retry_after_secs: Some(retry_after_secs),
+            ..Self::failed(ErrorCode::ServerBusy, backpressure::busy_message(retry_after_secs))
+        }
+    }
+
+    /// What the enclave signs for a proof: the proof (by its `proof_id`, the keccak256 of its
+    /// bytes), the public inputs as returned, the circuit and the signing time. Anyone holding
+    /// the response can rebuild it and check `enclave_signature` with ecrecover.
+    pub fn signing_message(&self, signed_at: i64) -> String {
+        signing_message(self.proof_id.as_deref(), &self.public_inputs, self.circuit_hash.as_deref(), signed_at)
+    }
+}
+
+/// See `ProofResponse::signing_message`.
+pub fn signing_message(proof_id: Option<&str>, public_inputs: &str, circuit_hash: Option<&str>, signed_at: i64) -> String {
+    format!(
+        "zk-insurance proof response\nproof_id: {}\npublic_inputs: {}\ncircuit_hash: {}\nsigned_at: {}",
+        proof_id.unwrap_or(""),
+        public_inputs,
+        circuit_hash.unwrap_or(""),
+        signed_at
+    )
+}
+
+/// Zeroes and removes a file holding private inputs when dropped.
+struct WipeOnDrop(PathBuf);
+
+impl Drop for WipeOnDrop {
+    fn drop(&mut self) {
+        scratch::wipe_file(&self.0);
+    }
+}
+
+pub struct NoirProver {
+    circuit_path: String,
+    default_scheme: ProvingScheme,
+    retry_policy: RetryPolicy,
+    limits: ResourceLimits,
+    history: Option<ReleaseHistory>,
+    /// Memory-backed directory jobs run in instead of the circuit directory
+    scratch: Option<PathBuf>,
+    /// Circuits requests may select with `circuit_id`
+    registry: Arc<CircuitRegistry>,
+    /// In-process UltraHonk verification, shared by every prover checking the same keys
+    native: Arc<NativeVerifier>,
+}
+
+/// Where the eligibility circuit is looked for without --circuit-path: the Docker image layout
+/// (/app/noir-circuit), then a checkout next to the server crate.
+pub const DEFAULT_CIRCUIT_PATHS: [&str; 2] = ["/app/noir-circuit", "../noir-circuit"];
+
+/// Compiled artifact of the circuit package in `circuit_path`, relative to it. nargo names it
+/// after the package, so the eligibility circuit's is ./target/insurance_verifier.json.
+pub fn compiled_circuit(circuit_path: &Path) -> String {
+    let name = fs::read_to_string(circuit_path.join("Nargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.parse::<toml::Table>().ok())
+        .and_then(|manifest| Some(manifest.get("package")?.get("name")?.as_str()?.to_string()))
+        .unwrap_or_else(|| "insurance_verifier".to_string());
+    format!("./target/{}.json", name)
+}
+
+/// The first of `candidates` that holds a circuit package (a Nargo.toml).
+pub fn locate_circuit(candidates: &[PathBuf]) -> Option<&Path> {
+    candidates
+        .iter()
+        .map(PathBuf::as_path)
+        .find(|candidate| candidate.join("Nargo.toml").is_file())
+}
+
+impl NoirProver {
+    pub fn with_circuit_path(
+        circuit_path: String,
+        default_scheme: ProvingScheme,
+        retry_policy: RetryPolicy,
+        limits: ResourceLimits,
+    ) -> Self {
+        Self {
+            circuit_path,
+            default_scheme,
+            retry_policy,
+            limits,
+            history: None,
+            scratch: None,
+            registry: Arc::default(),
+            native: Arc::default(),
+        }
+    }
+
+    /// Serves `circuit_id` requests from `registry`.
+    pub fn with_registry(mut self, registry: Arc<CircuitRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn registry(&self) -> &CircuitRegistry {
+        &self.registry
+    }
+
+    /// Shares `native`'s calibrated keys, so a key trusted by one prover is trusted by all.
+    pub fn with_native_verifier(mut self, native: Arc<NativeVerifier>) -> Self {
+        self.native = native;
+        self
+    }
+
+    /// Runs every job in its own directory under `scratch` (see `scratch::prepare`), so private
+    /// inputs and witnesses never touch the circuit's filesystem.
+    pub fn with_scratch(mut self, scratch: Option<PathBuf>) -> Self {
+        self.scratch = scratch;
+        self
+    }
+
+    /// Where a job for `circuit_path` writes its inputs, witness and proof.
+    pub fn workspace(&self, circuit_path: &Path, job_id: &str) -> Result<JobWorkspace> {
+        match &self.scratch {
+            Some(scratch) => JobWorkspace::create(scratch, circuit_path, job_id).context("Failed to create the in-memory job directory"),
+            None => Ok(JobWorkspace::shared(circuit_path)),
+        }
+    }
+
+    /// Enables `as_of` requests against past circuit releases.
+    pub fn with_history(mut self, history: Option<ReleaseHistory>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Circuit directory a request is proven in: its registered circuit, the release active at
+    /// its `as_of`, or the active eligibility circuit.
+    fn request_circuit_path(&self, request: &ProofRequest) -> Result<PathBuf> {
+        match (&request.circuit_id, request.as_of) {
+            (Some(_), Some(_)) => bail!("as_of only applies to the eligibility circuit"),
+            (Some(id), None) => self.registry.circuit_path(id).with_context(|| format!("Unknown circuit_id {:?}", id)),
+            (None, Some(as_of)) => self.historical_circuit_path(as_of),
+            (None, None) => Ok(self.circuit_path()),
+        }
+    }
+
+    /// Circuit directory of the release active at `as_of`.
+    fn historical_circuit_path(&self, as_of: DateTime<Utc>) -> Result<PathBuf> {
+        let history = self
+            .history
+            .as_ref()
+            .context("Proving against past releases requires the fleet control channel")?;
+        Ok(history.resolve(as_of)?.1)
+    }
+
+    /// Runs a nargo/bb invocation under the prover's resource limits and retry policy. A run
+    /// that hit its limits would hit them again, so it is not retried.
+    pub async fn run_tool<F>(&self, build: F) -> io::Result<Output>
+    where
+        F: Fn() -> Command,
+    {
+        let limited = || {
+            let mut command = build();
+            self.limits.apply(&mut command);
+            // A job dropped mid-run (e.g. past its time ceiling) must not leave the tool running
+            command.kill_on_drop(true);
+            command
+        };
+        self.retry_policy.run(limited, |output| self.limits.exceeded(output)).await
+    }
+
+    /// Failure message for a tool run, calling out jobs stopped by their resource limits.
+    fn failure_detail(&self, output: &Output) -> String {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if self.limits.exceeded(output) {
+            format!("job exceeded its resource limits ({}): {}", output.status, stderr)
+        } else {
+            stderr.into_owned()
+        }
+    }
+
+    /// The circuit directory with symlinks resolved. A job resolves it once up front so that
+    /// a release being activated mid-job cannot mix artifacts from two circuit versions.
+    pub fn circuit_path(&self) -> PathBuf {
+        fs::canonicalize(&self.circuit_path).unwrap_or_else(|_| PathBuf::from(&self.circuit_path))
+    }
+
+    /// Directory holding the circuit's UltraHonk (keccak) verification key, generating it on first
+    /// use. The key is written to a scratch directory and renamed into place, so concurrent
+    /// generators, isolated prover workers included, never see a partial key.
+    pub async fn verification_key_dir(&self, circuit_path: &Path) -> Result<PathBuf> {
+        let vk_dir = circuit_path.join(VK_DIR);
+        if vk_dir.join("vk").exists() && vk_dir.join("vk_fields.json").exists() {
+            return Ok(vk_dir);
+        }
+
+        let scratch = format!("./target/vk_{}", uuid::Uuid::new_v4().simple());
+        let artifact = compiled_circuit(circuit_path);
+        let output = self
+            .run_tool(|| {
+                let mut command = Command::new("bb");
+                command
+                    .args([
+                        "write_vk",
+                        "--scheme", "ultra_honk",
+                        "-b", &artifact,
+                        "-o", &scratch,
+                        "--oracle_hash", "keccak",
+                        "--output_format", "bytes_and_fields",
+                    ])
+                    .current_dir(circuit_path);
+                command
+            })
+            .await
+            .context("Failed to run bb write_vk");
+        let scratch = circuit_path.join(&scratch);
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                let _ = fs::remove_dir_all(&scratch);
+                bail!("bb write_vk failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+            Err(e) => {
+                let _ = fs::remove_dir_all(&scratch);
+                return Err(e);
+            }
+        };
+        drop(output);
+
+        // A directory left incomplete by an older version or a crash is replaced
+        if vk_dir.exists() && !vk_dir.join("vk_fields.json").exists() {
+            let _ = fs::remove_dir_all(&vk_dir);
+        }
+        if let Err(e) = fs::rename(&scratch, &vk_dir) {
+            let _ = fs::remove_dir_all(&scratch);
+            // Losing the race to another generator is fine; its key is the same
+            if !vk_dir.join("vk_fields.json").exists() {
+                return Err(e).with_context(|| format!("Failed to move the verification key to {}", vk_dir.display()));
+            }
+        }
+        Ok(vk_dir)
+    }
+
+    /// Checks an UltraHonk (keccak) proof against the key in `vk_dir`: natively once the key is
+    /// calibrated (see `NativeVerifier`), with `bb verify` until then. The inner error is why the
+    /// proof was rejected.
+    pub async fn verify_ultra_honk(
+        &self,
+        circuit_path: &Path,
+        vk_dir: &Path,
+        proof: &[u8],
+        public_inputs: &[[u8; 32]],
+    ) -> Result<std::result::Result<(), String>> {
+        let (digest, native) = self.native.state(vk_dir)?;
+        match native {
+            Native::Trusted(key) => Ok(key.verify(proof, public_inputs).map_err(|e| format!("{:#}", e))),
+            Native::Uncalibrated(key) => {
+                let verdict = self.bb_verify(circuit_path, vk_dir, proof, public_inputs).await?;
+                self.native.calibrate(&digest, verdict.is_ok(), proof, public_inputs, &key.verify(proof, public_inputs));
+                Ok(verdict)
+            }
+            Native::Unavailable => self.bb_verify(circuit_path, vk_dir, proof, public_inputs).await,
+        }
+    }
+
+    /// Runs `bb verify` on a proof in a scratch directory under the circuit's target.
+    async fn bb_verify(
+        &self,
+        circuit_path: &Path,
+        vk_dir: &Path,
+        proof: &[u8],
+        public_inputs: &[[u8; 32]],
+    ) -> Result<std::result::Result<(), String>> {
+        let job_dir = PathBuf::from(format!("./target/verify_{}", uuid::Uuid::new_v4().simple()));
+        let work_dir = circuit_path.join(&job_dir);
+        fs::create_dir_all(&work_dir)?;
+        fs::write(work_dir.join("proof"), proof)?;
+        fs::write(work_dir.join("public_inputs"), public_inputs.concat())?;
+
+        let output = self
+            .run_tool(|| {
+                let mut command = Command::new("bb");
+                command
+                    .args(["verify", "--scheme", "ultra_honk", "--oracle_hash", "keccak", "-k"])
+                    .arg(vk_dir.join("vk"))
+                    .arg("-p")
+                    .arg(job_dir.join("proof"))
+                    .arg("-i")
+                    .arg(job_dir.join("public_inputs"))
+                    .current_dir(circuit_path);
+                command
+            })
+            .await
+            .context("Failed to run bb verify");
+        let _ = fs::remove_dir_all(&work_dir);
+        let output = output?;
+        if output.status.success() {
+            Ok(Ok(()))
+        } else {
+            Ok(Err(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+        }
+    }
+
+    /// sha256 of the compiled circuit and, for UltraHonk, of its verification key: the values
+    /// GET /vk and the release manifests use to identify a circuit version.
+    async fn artifact_hashes(&self, circuit_path: &Path, scheme: ProvingScheme) -> Result<(String, Option<String>)> {
+        let circuit = fs::read(circuit_path.join(compiled_circuit(circuit_path))).context("Failed to read the compiled circuit")?;
+        let vk_hash = if scheme == ProvingScheme::UltraHonk {
+            let vk = fs::read(self.verification_key_dir(circuit_path).await?.join("vk")).context("Failed to read verification key")?;
+            Some(sha256_hex(&vk))
+        } else {
+            None
+        };
+        Ok((sha256_hex(&circuit), vk_hash))
+    }
+
+    /// Steps 1-2 of the pipeline: write the job's Prover.toml and run `nargo execute`.
+    /// Returns the witness path relative to the circuit directory, or the response to send
+    /// back if the inputs could not be turned into a witness.
+    pub async fn generate_witness(
+        &self,
+        circuit_path: &Path,
+        request: &ProofRequest,
+        job_id: &str,
+    ) -> Result<std::result::Result<String, ProofResponse>> {
+        let prover_name = format!("Prover_{}", job_id);
+        let witness_name = format!("witness_{}", job_id);
+
+        // Step 1: Write private inputs to a job-specific Prover.toml, laid out by the circuit's ABI
+        let abi = CircuitAbi::load(&circuit_path.join(compiled_circuit(circuit_path)))?;
+        let parameters = self.registry.parameters(request.circuit_id.as_deref());
+        if let Some(name) = parameters.keys().find(|name| request.inputs.contains_key(*name)) {
+            return Ok(Err(ProofResponse::failed(
+                ErrorCode::InvalidRequest,
+                format!("inputs cannot set {:?}; the circuit fixes it", name),
+            )));
+        }
+        let mut requested = request.underwriting().into_iter().map(|(name, _)| name).chain(request.nonce.as_ref().map(|_| "nonce"));
+        if let Some(name) = requested.find(|name| abi.parameter(name).is_none()) {
+            return Ok(Err(ProofResponse::failed(
+                ErrorCode::InvalidRequest,
+                format!("The circuit does not take {}; choose a circuit_id whose circuit does (see GET /circuits)", name),
+            )));
+        }
+        let inputs = match request.circuit_inputs(&abi, parameters) {
+            Ok(inputs) => inputs,
+            Err(e) => return Ok(Err(ProofResponse::failed(ErrorCode::InvalidRequest, format!("{:#}", e)))),
+        };
+        if let Err(message) = abi.check(&inputs) {
+            return Ok(Err(ProofResponse::failed(ErrorCode::InvalidRequest, message)));
+        }
+        let prover_toml_content = Zeroizing::new(abi.prover_toml(&inputs)?);
+
+        let prover_path = circuit_path.join(format!("{}.toml", prover_name));
+        fs::write(&prover_path, prover_toml_content.as_bytes())?;
+        // Removes the private inputs even if the job is cancelled while nargo runs
+        let prover_file = WipeOnDrop(prover_path);
+
+        // Step 2: Execute to generate witness (this will create target/witness_<job>.gz)
+        let execute_output = self
+            .run_tool(|| {
+                let mut command = Command::new("nargo");
+                command
+                    .args(["execute", &witness_name, "--prover-name", &prover_name])
+                    .current_dir(circuit_path);
+                command
+            })
+            .await;
+        // The private inputs are only needed for witness generation
+        drop(prover_file);
+        let execute_output = execute_output.context("Failed to execute circuit")?;
+
+        if self.limits.exceeded(&execute_output) {
+            return Ok(Err(ProofResponse::failed(
+                ErrorCode::BackendError,
+                format!("Circuit execution failed: {}", self.failure_detail(&execute_output)),
+            )));
+        }
+
+        if retry::is_transient(&execute_output) {
+            return Ok(Err(ProofResponse::failed(
+                ErrorCode::BackendError,
+                format!(
+                    "Circuit execution kept failing for a transient reason ({}): {}",
+                    execute_output.status,
+                    String::from_utf8_lossy(&execute_output.stderr)
+                ),
+            )));
+        }
+
+        if !execute_output.status.success() {
+            return Ok(Err(ProofResponse::failed(
+                ErrorCode::Ineligible,
+                format!(
+                    "Circuit execution failed. The inputs don't satisfy the constraints: {}",
+                    String::from_utf8_lossy(&execute_output.stderr)
+                ),
+            )));
+        }
+
+        // Check if witness file was generated (witness_<job>.gz)
+        let witness_file = format!("./target/{}.gz", witness_name);
+        if !circuit_path.join(&witness_file).exists() {
+            return Ok(Err(ProofResponse::failed(
+                ErrorCode::BackendError,
+                "Witness file was not generated after circuit execution".to_string(),
+            )));
+        }
+
+        Ok(Ok(witness_file))
+    }
+
+    /// Proves an existing witness with the recursion-friendly transcript, as field elements.
+    pub async fn prove_recursive(
+        &self,
+        circuit_path: &Path,
+        witness_file: &str,
+        job_id: &str,
+    ) -> Result<std::result::Result<RecursiveProof, ProofResponse>> {
+        let output_dir = format!("./target/recursive_{}", job_id);
+        let artifact = compiled_circuit(circuit_path);
+        let output = self
+            .run_tool(|| {
+                let mut command = Command::new("bb");
+                command
+                    .args([
+                        "prove",
+                        "--scheme", "ultra_honk",
+                        "-b", &artifact,
+                        "-w", witness_file,
+                        "-o", &output_dir,
+                    ])
+                    .args(RECURSIVE_PROOF_FLAGS)
+                    .current_dir(circuit_path);
+                command
+            })
+            .await
+            .context("Failed to generate recursive proof with bb")?;
+
+        if !output.status.success() {
+            return Ok(Err(ProofResponse::failed(
+                ErrorCode::BackendError,
+                format!("Recursive proof generation failed: {}", self.failure_detail(&output)),
+            )));
+        }
+
+        let output_dir = circuit_path.join(&output_dir);
+        let proof = RecursiveProof {
+            proof: read_fields(&output_dir.join("proof_fields.json"))?,
+            public_inputs: read_fields(&output_dir.join("public_inputs_fields.json"))?,
+        };
+        let _ = fs::remove_dir_all(&output_dir);
+        Ok(Ok(proof))
+    }
+
+    pub async fn generate_proof(&self, request: ProofRequest) -> Result<ProofResponse> {
+        let job = tracing::info_span!("job", request_id = request.request_id.as_deref().unwrap_or_default());
+        self.traced_job(request).instrument(job).await
+    }
+
+    async fn traced_job(&self, request: ProofRequest) -> Result<ProofResponse> {
+        let parent = request.traceparent.as_deref().and_then(TraceContext::parse);
+        let mut span = Span::start("generate_proof", parent);
+        if let Some(scheme) = request.scheme.unwrap_or(self.default_scheme).to_possible_value() {
+            span.set("scheme", scheme.get_name());
+        }
+        if let Some(policy) = &request.policy {
+            span.set("policy", policy.as_str());
+        }
+        let mut steps = StepDurations::default();
+        let derivation = BmiDerivation::of(&request);
+        let result = self.run_job(request, &mut steps, &span).await;
+        match &result {
+            Ok(response) => {
+                span.set("success", response.success);
+                if let Some(code) = response.error_code {
+                    if let Ok(serde_json::Value::String(code)) = serde_json::to_value(code) {
+                        span.set("error_code", code);
+                    }
+                    span.fail(response.message.clone());
+                }
+            }
+            Err(e) => span.fail(format!("{:#}", e)),
+        }
+        let mut response = result?;
+        response.step_durations = Some(steps);
+        response.bmi_derivation = derivation;
+        Ok(response)
+    }
+
+    async fn run_job(&self, mut request: ProofRequest, steps: &mut StepDurations, span: &Span) -> Result<ProofResponse> {
+        // Canary and doctor proofs are not admitted through a worker, so get the default validity
+        request.expires_at.get_or_insert_with(|| expiry_from_now(DEFAULT_PROOF_VALIDITY_SECS));
+        let circuit_dir = match self.request_circuit_path(&request) {
+            Ok(circuit_dir) => circuit_dir,
+            Err(e) => return Ok(ProofResponse::failed(ErrorCode::InvalidRequest, format!("{:#}", e))),
+        };
+        let circuit_path = circuit_dir.as_path();
+        if let Some(expected) = &request.expected_circuit_hash {
+            let compiled = fs::read(circuit_path.join(compiled_circuit(circuit_path))).context("Failed to read the compiled circuit")?;
+            let running = sha256_hex(&compiled);
+            if !running.eq_ignore_ascii_case(&format!("0x{}", expected.trim().trim_start_matches("0x"))) {
+                return Ok(ProofResponse::failed(
+                    ErrorCode::CircuitMismatch,
+                    format!("The request expects circuit {} but the server runs {} (see GET /version)", expected, running),
+                ));
+            }
+        }
+
+        // Every file a job touches is named after its ID so that concurrent workers
+        // sharing the circuit directory never overwrite each other's inputs or outputs
+        let job_id = uuid::Uuid::new_v4().simple().to_string();
+        let scheme = request.scheme.unwrap_or(self.default_scheme);
+        if request.recursive && scheme != ProvingScheme::UltraHonk {
+            return Ok(ProofResponse::failed(
+                ErrorCode::InvalidRequest,
+                "Recursive proofs can only be produced with the ultra_honk scheme",
+            ));
+        }
+        let evm_calldata = request.output_format == Some(OutputFormat::EvmCalldata);
+        if evm_calldata && scheme != ProvingScheme::UltraHonk {
+            return Ok(ProofResponse::failed(
+                ErrorCode::InvalidRequest,
+                "EVM calldata is only available for ultra_honk proofs",
+            ));
+        }
+
+        // Inputs, witness and proof live in the job's workspace; the vk and hashes stay with the circuit
+        let workspace = self.workspace(circuit_path, &job_id)?;
+        let work_dir = workspace.path();
+
+        let witness_span = span.child("witness");
+        let started = Instant::now();
+        let witness = self.generate_witness(work_dir, &request, &job_id).await?;
+        steps.witness = Some(started.elapsed().as_secs_f64());
+        drop(witness_span);
+        let witness_file = match witness {
+            Ok(witness_file) => witness_file,
+            Err(response) => return Ok(response),
+        };
+        // The witness contains the private inputs, so it goes as soon as the last proof is made,
+        // or when the job is cancelled
+        let witness = WipeOnDrop(work_dir.join(&witness_file));
+
+        let proof_filename = format!("./target/proof_{}", job_id);
+        let artifact = compiled_circuit(work_dir);
+
+        // Step 3: Generate proof using bb (Barretenberg) with unique filename
+        let mut prove_span = span.child("bb prove");
+        let started = Instant::now();
+        let prove_output = self
+            .run_tool(|| {
+                let mut command = Command::new("bb");
+                command
+                    .args(scheme.prove_args(
+                        &artifact,
+                        &witness_file,
+                        &proof_filename,
+                    ))
+                    .current_dir(work_dir);
+                command
+            })
+            .await
+            .context("Failed to generate proof with bb")?;
+        steps.prove = Some(started.elapsed().as_secs_f64());
+        if !prove_output.status.success() {
+            prove_span.fail(format!("bb exited with {}", prove_output.status));
+        }
+        drop(prove_span);
+
+        if !prove_output.status.success() {
+            return Ok(ProofResponse::failed(
+                ErrorCode::BackendError,
+                format!(
+                    "Proof generation failed ({:?}): {}",
+                    scheme,
+                    self.failure_detail(&prove_output)
+                ),
+            ));
+        }
+
+        // bb might create either a file directly or a subdirectory with files
+        let proof_dir = work_dir.join(&proof_filename);
+        let proof_path_in_subdir = proof_dir.join("proof");
+        let direct_proof_path = work_dir.join(&proof_filename);
+
+        let (proof_path, public_inputs_path) = if proof_path_in_subdir.exists() {
+            // Case 1: bb created a subdirectory with proof files inside
+            (proof_path_in_subdir, proof_dir.join("public_inputs"))
+        } else if direct_proof_path.exists() {
+            // Case 2: bb created the proof file directly
+            (direct_proof_path, work_dir.join("target").join("public_inputs"))
+        } else {
+            // Neither case worked, check for default locations
+            let fallback_proof = work_dir.join("target").join("proof");
+            if fallback_proof.exists() {
+                (fallback_proof, work_dir.join("target").join("public_inputs"))
+            } else {
+                return Ok(ProofResponse::failed(
+                    ErrorCode::BackendError,
+                    format!(
+                        "Proof file was not generated. Checked paths: {}, {}, {}",
+                        proof_path_in_subdir.display(),
+                        direct_proof_path.display(),
+                        fallback_proof.display()
+                    ),
+                ));
+            }
+        };
+        
+        // Step 4: Read the proof back and hex-encode it
+        if !proof_path.exists() {
+            return Ok(ProofResponse::failed(
+                ErrorCode::BackendError,
+                format!("Proof file was not generated at path: {}", proof_path.display()),
+            ));
+        }
+
+        // Everything from here reads bb's output back: the proof, public inputs and artifact hashes
+        let _artifact_span = span.child("artifact read");
+        let started = Instant::now();
+        let proof = fs::read(&proof_path).context("Failed to read proof")?;
+        let proof_hex = format!("0x{}", hex::encode(&proof));
+        steps.hex = Some(started.elapsed().as_secs_f64());
+
+        // Step 5: Read public inputs from the correct location
+        // First try to read the formatted JSON version
+        let proof_dir = proof_path.parent().unwrap();
+        let public_inputs_fields_path = proof_dir.join("public_inputs_fields.json");
+        let fallback_public_inputs_fields_path = work_dir.join("target").join("public_inputs_fields.json");
+        let fallback_public_inputs_path = work_dir.join("target").join("public_inputs");
+        
+        let public_inputs = if public_inputs_fields_path.exists() {
+            // Prefer the JSON formatted version from the proof directory
+            match fs::read_to_string(&public_inputs_fields_path) {
+                Ok(content) => content.trim().to_string(),
+                Err(e) => {
+                    return Ok(ProofResponse {
+                        proof_hex,
+                        ..ProofResponse::failed(
+                            ErrorCode::BackendError,
+                            format!("Failed to read public inputs fields JSON at {}: {}", public_inputs_fields_path.display(), e),
+                        )
+                    });
+                }
+            }
+        } else if fallback_public_inputs_fields_path.exists() {
+            // Fallback to the target directory JSON version
+            match fs::read_to_string(&fallback_public_inputs_fields_path) {
+                Ok(content) => content.trim().to_string(),
+                Err(e) => {
+                    return Ok(ProofResponse {
+                        proof_hex,
+                        ..ProofResponse::failed(
+                            ErrorCode::BackendError,
+                            format!("Failed to read public inputs fields JSON at {}: {}", fallback_public_inputs_fields_path.display(), e),
+                        )
+                    });
+                }
+            }
+        } else if public_inputs_path.exists() {
+            // Use the public_inputs file from the proof directory
+            match fs::read_to_string(&public_inputs_path) {
+                Ok(text) => {
+                    text.trim().to_string()
+                },
+                Err(_) => {
+                    // If reading as text fails, read as binary and format as individual field elements
+                    match fs::read(&public_inputs_path) {
+                        Ok(bytes) => {
+                            // Each field element is 32 bytes (64 hex characters)
+                            let hex_string = hex::encode(bytes);
+                            if hex_string.len() % 64 == 0 && !hex_string.is_empty() {
+                                let mut field_elements = Vec::new();
+                                for i in (0..hex_string.len()).step_by(64) {
+                                    let end = std::cmp::min(i + 64, hex_string.len());
+                                    field_elements.push(format!("\"0x{}\"", &hex_string[i..end]));
+                                }
+                                format!("[{}]", field_elements.join(","))
+                            } else {
+                                format!("0x{}", hex_string)
+                            }
+                        },
+                        Err(e) => {
+                            return Ok(ProofResponse {
+                                proof_hex,
+                                ..ProofResponse::failed(
+                                    ErrorCode::BackendError,
+                                    format!("Failed to read public inputs file at {}: {}", public_inputs_path.display(), e),
+                                )
+                            });
+                        }
+                    }
+                }
+            }
+        } else if fallback_public_inputs_path.exists() {
+            // Final fallback to raw public_inputs in target directory and format it properly
+            match fs::read_to_string(&fallback_public_inputs_path) {
+                Ok(text) => {
+                    text.trim().to_string()
+                },
+                Err(_) => {
+                    // If reading as text fails, read as binary and format as individual field elements
+                    match fs::read(&fallback_public_inputs_path) {
+                        Ok(bytes) => {
+                            // Each field element is 32 bytes (64 hex characters)
+                            let hex_string = hex::encode(bytes);
+                            if hex_string.len() % 64 == 0 && !hex_string.is_empty() {
+                                let mut field_elements = Vec::new();
+                                for i in (0..hex_string.len()).step_by(64) {
+                                    let end = std::cmp::min(i + 64, hex_string.len());
+                                    field_elements.push(format!("\"0x{}\"", &hex_string[i..end]));
+                                }
+                                format!("[{}]", field_elements.join(","))
+                            } else {
+                                format!("0x{}", hex_string)
+                            }
+                        },
+                        Err(e) => {
+                            return Ok(ProofResponse {
+                                proof_hex,
+                                ..ProofResponse::failed(
+                                    ErrorCode::BackendError,
+                                    format!("Failed to read fallback public inputs file at {}: {}", fallback_public_inputs_path.display(), e),
+                                )
+                            });
+                        }
+                    }
+                }
+            }
+        } else {
+            return Ok(ProofResponse {
+                proof_hex,
+                ..ProofResponse::failed(
+                    ErrorCode::BackendError,
+                    format!("Neither public_inputs_fields.json nor public_inputs file was generated at {}", work_dir.join("target").display()),
+                )
+            });
+        };
+
+        // Step 6: Check that the proof commits to the bounds we proved against, then verify it
+        if scheme == ProvingScheme::UltraHonk {
+            let checked = evm::parse_public_inputs(&public_inputs).and_then(|inputs| {
+                if request.circuit_id.is_none() {
+                    honk::check_public_inputs(&inputs, &[request.bounds()])?;
+                }
+                Ok(inputs)
+            });
+            let inputs = match checked {
+                Ok(inputs) => inputs,
+                Err(e) => {
+                    return Ok(ProofResponse::failed(
+                        ErrorCode::BackendError,
+                        format!("Proof failed the public input check: {:#}", e),
+                    ))
+                }
+            };
+            let vk_dir = self.verification_key_dir(circuit_path).await?;
+            if let Err(e) = self.verify_ultra_honk(circuit_path, &vk_dir, &proof, &inputs).await? {
+                return Ok(ProofResponse::failed(ErrorCode::BackendError, format!("Proof failed verification: {}", e)));
+            }
+        }
+
+        // Step 7: Optionally prove the same witness again for recursion, reusing witness generation
+        let recursive = if request.recursive {
+            match self.prove_recursive(work_dir, &witness_file, &job_id).await? {
+                Ok(proof) => Some(proof),
+                Err(mut response) => {
+                    response.proof_hex = proof_hex;
+                    response.public_inputs = public_inputs;
+                    return Ok(response);
+                }
+            }
+        } else {
+            None
+        };
+        drop(witness);
+
+        let (circuit_hash, vk_hash) = self.artifact_hashes(circuit_path, scheme).await?;
+        let calldata = if evm_calldata {
+            let inputs = evm::parse_public_inputs(&public_inputs)?;
+            Some(format!("0x{}", hex::encode(evm::verify_calldata(&proof, &inputs))))
+        } else {
+            None
+        };
+
+        let bounds = request.circuit_id.is_none().then(|| request.bounds());
+        let takes_expiry = CircuitAbi::load(&circuit_path.join(compiled_circuit(circuit_path)))?.parameter("expires_at").is_some();
+        Ok(ProofResponse {
+            proof_hex,
+            public_inputs,
+            success: true,
+            error_code: None,
+            recursive,
+            calldata,
+            proof_id: Some(evm::proof_id(&proof)),
+            job_id: None,
+            ipfs_cid: None,
+            tier: request.eligibility.and_then(|eligibility| eligibility.tier),
+            bounds,
+            expires_at: request.expires_at.filter(|_| takes_expiry),
+            watermark: None,
+            circuit_hash: Some(circuit_hash),
+            vk_hash,
+            enclave_signature: None,
+            eligibility_attestation: None,
+            payment: None,
+            step_durations: None,
+            bmi_derivation: None,
+            request_id: None,
+            retry_after_secs: None,
+            message: "Proof generated successfully! The user is eligible for insurance discount.".to_string(),
+        })
+    }
+}
+
+pub fn read_fields(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("{} is not a JSON array of fields", path.display()))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(Sha256::digest(bytes)))
+}
@@ -0,0 +1,160 @@
+use crate::abi::{CircuitAbi, Inputs, Visibility};
+use crate::prover;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// A circuit served next to the eligibility circuit, as listed in --circuits-file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredCircuit {
+    /// Compiled Nargo package directory
+    pub path: PathBuf,
+    /// Inputs the server fixes for every request, typically public thresholds; requests
+    /// cannot set them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: Inputs,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A registered circuit as served by GET /circuits.
+#[derive(Debug, Serialize)]
+pub struct CircuitInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// sha256 of the compiled circuit, as in proof responses
+    pub circuit_hash: String,
+    /// sha256 of the UltraHonk verification key, as served by GET /vk?circuit_id=
+    pub vk_hash: Option<String>,
+    /// Parameters requests supply in `inputs`
+    pub inputs: Vec<InputInfo>,
+    pub parameters: Inputs,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InputInfo {
+    pub name: String,
+    pub visibility: Visibility,
+}
+
+/// Circuits requests can select with `circuit_id`, each with its own package, compiled
+/// artifact, verification key and fixed parameters. Requests without one go to the eligibility
+/// circuit (--circuit-path or the active release).
+#[derive(Debug, Default)]
+pub struct CircuitRegistry {
+    circuits: RwLock<BTreeMap<String, RegisteredCircuit>>,
+    /// File the registry was loaded from and circuits registered at runtime are saved to
+    source: Option<PathBuf>,
+}
+
+impl CircuitRegistry {
+    /// Reads a JSON object of circuit IDs to circuits and checks that each is compiled and that
+    /// its parameters match its ABI.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let circuits: BTreeMap<String, RegisteredCircuit> =
+            serde_json::from_str(&content).with_context(|| format!("{} is not a valid circuits table", path.display()))?;
+        for (id, circuit) in &circuits {
+            check_id(id)?;
+            if prover::locate_circuit(std::slice::from_ref(&circuit.path)).is_none() {
+                bail!("Circuit {}: no Nargo.toml in {}", id, circuit.path.display());
+            }
+            let abi = CircuitAbi::load(&circuit.path.join(prover::compiled_circuit(&circuit.path)))
+                .with_context(|| format!("Circuit {} is not compiled (run nargo compile in {})", id, circuit.path.display()))?;
+            if let Err(e) = abi.check_values(&circuit.parameters) {
+                bail!("Circuit {} parameters: {}", id, e);
+            }
+        }
+        Ok(Self {
+            circuits: RwLock::new(circuits),
+            source: Some(path.to_path_buf()),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.circuits.read().unwrap().is_empty()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.circuits.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.circuits.read().unwrap().contains_key(id)
+    }
+
+    /// Whether circuits can be added at runtime, which needs a file to record them in.
+    pub fn is_writable(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Adds a circuit and rewrites the registry file, so it survives restarts and isolated
+    /// prover workers, which load the file for every job, see it.
+    pub fn register(&self, id: &str, circuit: RegisteredCircuit) -> Result<()> {
+        check_id(id)?;
+        let source = self.source.as_ref().context("Circuits can only be registered with --circuits-file")?;
+        let mut circuits = self.circuits.write().unwrap();
+        if circuits.contains_key(id) {
+            bail!("Circuit {} is already registered", id);
+        }
+        let mut updated = circuits.clone();
+        updated.insert(id.to_string(), circuit);
+        let staged = source.with_extension("tmp");
+        fs::write(&staged, serde_json::to_vec_pretty(&updated)?).with_context(|| format!("Failed to write {}", staged.display()))?;
+        fs::rename(&staged, source).with_context(|| format!("Failed to replace {}", source.display()))?;
+        *circuits = updated;
+        Ok(())
+    }
+
+    /// The circuit's package directory with symlinks resolved, like `NoirProver::circuit_path`.
+    pub fn circuit_path(&self, id: &str) -> Option<PathBuf> {
+        let path = self.circuits.read().unwrap().get(id)?.path.clone();
+        Some(fs::canonicalize(&path).unwrap_or(path))
+    }
+
+    /// Package directories of every registered circuit.
+    pub fn circuit_dirs(&self) -> Vec<PathBuf> {
+        self.circuits.read().unwrap().values().map(|circuit| circuit.path.clone()).collect()
+    }
+
+    /// Inputs fixed for requests to `id`; none for the eligibility circuit.
+    pub fn parameters(&self, id: Option<&str>) -> Inputs {
+        let circuits = self.circuits.read().unwrap();
+        id.and_then(|id| circuits.get(id)).map(|circuit| circuit.parameters.clone()).unwrap_or_default()
+    }
+
+    /// What a client needs to call circuit `id`: its hashes and the inputs left to supply.
+    pub fn describe(&self, id: &str, vk_hash: Option<String>) -> Result<CircuitInfo> {
+        let circuit = self.circuits.read().unwrap().get(id).cloned().with_context(|| format!("Unknown circuit {}", id))?;
+        let artifact = circuit.path.join(prover::compiled_circuit(&circuit.path));
+        let compiled = fs::read(&artifact).with_context(|| format!("Failed to read {}", artifact.display()))?;
+        let abi = CircuitAbi::load(&artifact)?;
+        Ok(CircuitInfo {
+            description: circuit.description.clone(),
+            circuit_hash: format!("0x{}", hex::encode(Sha256::digest(&compiled))),
+            vk_hash,
+            inputs: abi
+                .parameters
+                .into_iter()
+                .filter(|parameter| !circuit.parameters.contains_key(&parameter.name))
+                .map(|parameter| InputInfo {
+                    name: parameter.name,
+                    visibility: parameter.visibility,
+                })
+                .collect(),
+            parameters: circuit.parameters,
+        })
+    }
+}
+
+/// Circuit IDs end up in URLs and directory names.
+pub fn check_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.len() > 64 || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("Circuit ID {:?} must be 1-64 letters, digits, '-' or '_'", id);
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use crate::audit::AuditLog;
+use crate::config::RuntimeConfig;
+use crate::state::AppState;
+use crate::systemd;
+use anyhow::Result;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info};
+
+/// Builds the runtime configuration afresh from the flags, the config file and the active release.
+pub type ConfigLoader = Box<dyn Fn() -> Result<RuntimeConfig> + Send + Sync>;
+
+/// Swaps in a freshly loaded runtime configuration: ranges, policies, rate limits, TCP timeouts,
+/// queue limits and the number of proving workers. Restarting instead would change the
+/// enclave's attestation state and drop in-flight jobs.
+pub struct Reloader {
+    state: Arc<AppState>,
+    audit: Arc<AuditLog>,
+    load: ConfigLoader,
+}
+
+impl Reloader {
+    pub fn new(state: Arc<AppState>, audit: Arc<AuditLog>, load: ConfigLoader) -> Self {
+        Self { state, audit, load }
+    }
+
+    /// Loads the configuration and applies it, returning the settings that changed. On any
+    /// error the running configuration is kept. `trigger` says what asked, for the audit log.
+    pub fn reload(&self, trigger: &str) -> Result<Vec<String>> {
+        systemd::notify_reloading();
+        let result = self.apply(trigger);
+        systemd::notify("READY=1");
+        result
+    }
+
+    fn apply(&self, trigger: &str) -> Result<Vec<String>> {
+        let loaded = (self.load)()?;
+        let changed = {
+            let mut config = self.state.config.write().unwrap();
+            let changed = changed_settings(&config, &loaded)?;
+            *config = loaded;
+            changed
+        };
+        if changed.iter().any(|setting| setting == "workers") {
+            self.state.pool.resize();
+        }
+        info!("Configuration reloaded on {} ({} settings changed)", trigger, changed.len());
+        self.audit.record("config_reloaded", json!({ "trigger": trigger, "changed": changed }));
+        Ok(changed)
+    }
+
+    /// Reloads on every SIGHUP.
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let mut hangups = signal(SignalKind::hangup())?;
+        while hangups.recv().await.is_some() {
+            if let Err(e) = self.reload("sighup") {
+                error!("Configuration not reloaded: {:#}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Top-level settings whose values differ between `old` and `new`.
+fn changed_settings(old: &RuntimeConfig, new: &RuntimeConfig) -> Result<Vec<String>> {
+    let (serde_json::Value::Object(old), serde_json::Value::Object(new)) = (serde_json::to_value(old)?, serde_json::to_value(new)?) else {
+        return Ok(Vec::new());
+    };
+    Ok(new.iter().filter(|(key, value)| old.get(*key) != Some(value)).map(|(key, _)| key.clone()).collect())
+}
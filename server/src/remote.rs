@@ -0,0 +1,85 @@
+use crate::abi::CircuitAbi;
+use crate::prover;
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Downloads tried before startup fails.
+const FETCH_ATTEMPTS: u32 = 3;
+
+/// Wait between download attempts, doubled after each.
+const FETCH_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Verification keys derived from the compiled circuit, stale once it is replaced.
+const DERIVED_DIRS: &[&str] = &[prover::VK_DIR, "target/recursive_vk"];
+
+/// Makes the compiled circuit of `package` the one pinned by `sha256`. A local artifact that
+/// already matches is used as is, so restarts need no network; otherwise it is downloaded from
+/// `url`, checked against the pin and moved into place, and the keys derived from the old one are
+/// dropped. Without a URL, a mismatch fails startup.
+pub async fn pin_circuit(http: &reqwest::Client, package: &Path, url: Option<&str>, sha256: &str) -> Result<()> {
+    let pinned = sha256.trim_start_matches("0x").to_ascii_lowercase();
+    if pinned.len() != 64 || !pinned.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("--circuit-sha256 must be 32 bytes of hex");
+    }
+    let artifact = package.join(prover::compiled_circuit(package));
+    let local = fs::read(&artifact).ok().map(|bytes| hex::encode(Sha256::digest(bytes)));
+    if local.as_deref() == Some(pinned.as_str()) {
+        info!("Compiled circuit {} matches the pinned hash 0x{}", artifact.display(), pinned);
+        return Ok(());
+    }
+    let Some(url) = url else {
+        bail!(
+            "Compiled circuit {} is {}, not the pinned 0x{}; set --circuit-url to fetch it",
+            artifact.display(),
+            local.map_or("missing".to_string(), |hash| format!("0x{}", hash)),
+            pinned
+        );
+    };
+
+    let bytes = fetch(http, url).await?;
+    let fetched = hex::encode(Sha256::digest(&bytes));
+    if fetched != pinned {
+        bail!("Circuit fetched from {} is 0x{}, not the pinned 0x{}", url, fetched, pinned);
+    }
+    let staged = artifact.with_extension("download");
+    if let Some(parent) = artifact.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&staged, &bytes).with_context(|| format!("Failed to write {}", staged.display()))?;
+    if let Err(e) = CircuitAbi::load(&staged) {
+        let _ = fs::remove_file(&staged);
+        return Err(e.context(format!("Circuit fetched from {} is not a compiled Noir circuit", url)));
+    }
+    fs::rename(&staged, &artifact).with_context(|| format!("Failed to replace {}", artifact.display()))?;
+    for dir in DERIVED_DIRS {
+        let _ = fs::remove_dir_all(package.join(dir));
+    }
+    info!("Fetched compiled circuit 0x{} from {} into {}", pinned, url, artifact.display());
+    Ok(())
+}
+
+async fn fetch(http: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let mut backoff = FETCH_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let result = async {
+            let response = http.get(url).send().await?.error_for_status()?;
+            anyhow::Ok(response.bytes().await?.to_vec())
+        }
+        .await;
+        match result {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) if attempt < FETCH_ATTEMPTS => {
+                warn!("Fetching the circuit from {} failed (attempt {}): {:#}", url, attempt, e);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to fetch the circuit from {}", url)),
+        }
+    }
+}
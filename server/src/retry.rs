@@ -0,0 +1,98 @@
+use std::io;
+use std::process::Output;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::warn;
+
+/// stderr fragments (lowercased) of failures that are worth another attempt: SRS/CRS downloads
+/// that hiccuped and allocation failures under memory pressure.
+const TRANSIENT_MARKERS: &[&str] = &[
+    "out of memory",
+    "bad_alloc",
+    "cannot allocate memory",
+    "failed to download",
+    "download failed",
+    "could not resolve host",
+    "temporary failure in name resolution",
+    "connection reset",
+    "connection refused",
+    "connection timed out",
+    "unable to load crs",
+];
+
+/// Deterministic failures. These are never retried even if a transient marker also appears.
+const PERMANENT_MARKERS: &[&str] = &["failed constraint", "assertion failed", "cannot satisfy constraint"];
+
+/// How often a nargo/bb invocation is retried when it fails for a transient reason.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Additional attempts after the first one
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Runs the command built by `build` until it succeeds, fails deterministically, or the
+    /// retries are used up, backing off exponentially between attempts. Failing to spawn the
+    /// binary at all is never retried, nor is any failure `permanent` recognises.
+    pub async fn run<F, P>(&self, build: F, permanent: P) -> io::Result<Output>
+    where
+        F: Fn() -> Command,
+        P: Fn(&Output) -> bool,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            let output = build().output().await?;
+            if output.status.success()
+                || attempt >= self.max_retries
+                || !is_transient(&output)
+                || permanent(&output)
+            {
+                return Ok(output);
+            }
+
+            attempt += 1;
+            warn!(
+                "Transient prover failure ({}), retrying in {:?} (attempt {}/{})",
+                output.status, backoff, attempt, self.max_retries
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+}
+
+/// Whether a failed run looks like infrastructure trouble rather than a property of the inputs.
+pub fn is_transient(output: &Output) -> bool {
+    if output.status.success() {
+        return false;
+    }
+
+    // Killed by a signal (typically SIGKILL from the OOM killer), or a shell reporting one
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if output.status.signal().is_some() {
+            return true;
+        }
+    }
+    if output.status.code() == Some(137) {
+        return true;
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+    if PERMANENT_MARKERS.iter().any(|marker| stderr.contains(marker)) {
+        return false;
+    }
+    TRANSIENT_MARKERS.iter().any(|marker| stderr.contains(marker))
+}
@@ -0,0 +1,158 @@
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Minimal S3-compatible client (AWS, MinIO, R2, ...) using path-style requests signed with
+/// SigV4. Credentials come from AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY.
+pub struct S3Client {
+    http: reqwest::Client,
+    endpoint: String,
+    region: String,
+    bucket: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Client {
+    pub fn from_env(http: reqwest::Client, endpoint: Option<String>, region: String, bucket: String) -> Result<Self> {
+        let access_key = std::env::var("AWS_ACCESS_KEY_ID").context("AWS_ACCESS_KEY_ID is not set")?;
+        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").context("AWS_SECRET_ACCESS_KEY is not set")?;
+        let endpoint = endpoint.unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+
+        Ok(Self {
+            http,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region,
+            bucket,
+            access_key,
+            secret_key,
+        })
+    }
+
+    /// Uploads `body` under `key`. Keys are expected to use URL-safe characters only.
+    pub async fn put_object(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        let response = self.send(reqwest::Method::PUT, key, &[], body).await?;
+        if !response.status().is_success() {
+            bail!("S3 upload of {} failed: {} {}", key, response.status(), response.text().await.unwrap_or_default());
+        }
+        Ok(())
+    }
+
+    /// The object under `key`; None if there is none.
+    pub async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let response = self.send(reqwest::Method::GET, key, &[], Vec::new()).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("S3 download of {} failed: {} {}", key, response.status(), response.text().await.unwrap_or_default());
+        }
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    /// Keys of every object starting with `prefix` (ListObjectsV2, following continuation tokens).
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut continuation = None;
+        loop {
+            let mut query = vec![("list-type", "2".to_string()), ("prefix", prefix.to_string())];
+            if let Some(token) = continuation.take() {
+                query.push(("continuation-token", token));
+            }
+            let response = self.send(reqwest::Method::GET, "", &query, Vec::new()).await?;
+            if !response.status().is_success() {
+                bail!("S3 listing of {} failed: {} {}", prefix, response.status(), response.text().await.unwrap_or_default());
+            }
+            let body = response.text().await?;
+            keys.extend(xml_values(&body, "Key"));
+            match xml_values(&body, "NextContinuationToken").pop() {
+                Some(token) if xml_values(&body, "IsTruncated").first().map(String::as_str) == Some("true") => continuation = Some(token),
+                _ => return Ok(keys),
+            }
+        }
+    }
+
+    /// Sends a path-style request for `key` (the bucket itself if empty), signed with SigV4.
+    async fn send(&self, method: reqwest::Method, key: &str, query: &[(&str, String)], body: Vec<u8>) -> Result<reqwest::Response> {
+        let path = format!("/{}/{}", self.bucket, key);
+        let mut query: Vec<_> = query.iter().map(|(name, value)| format!("{}={}", uri_encode(name), uri_encode(value))).collect();
+        query.sort();
+        let query = query.join("&");
+        let url = format!("{}{}{}{}", self.endpoint, path, if query.is_empty() { "" } else { "?" }, query);
+        let host = reqwest::Url::parse(&url)?
+            .host_str()
+            .context("S3 endpoint has no host")?
+            .to_string();
+        let host = match reqwest::Url::parse(&self.endpoint)?.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host,
+        };
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+            method, path, query, host, payload_hash, amz_date, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [date.as_str(), self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(format!("AWS4{}", self.secret_key).into_bytes(), |key, part| hmac(&key, part.as_bytes()));
+        let signature = hex::encode(hmac(&signing_key, string_to_sign.as_bytes()));
+
+        self.http
+            .request(method, &url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+                    self.access_key, scope, signature
+                ),
+            )
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", url))
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, as SigV4 query strings require.
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Text of every `<tag>` element in an S3 XML reply. Keys written by this server need no
+/// entity decoding.
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    xml.split(&open)
+        .skip(1)
+        .filter_map(|rest| rest.split_once(&close).map(|(value, _)| value.to_string()))
+        .collect()
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
@@ -0,0 +1,206 @@
+use crate::abi::{CircuitAbi, Inputs};
+use crate::intake;
+use crate::prover::{self, SERVER_INPUTS};
+use crate::verifier::Verifier;
+use anyhow::{anyhow, Result};
+use jsonschema::{Draft, JSONSchema};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// 32 bytes of hex, as intake accepts for hashes and commitments.
+const BYTES32: &str = "^(0x)?[0-9a-fA-F]{64}$";
+
+/// Compiled schemas kept before the cache is cleared; one per circuit compilation.
+const MAX_CACHED: usize = 64;
+
+/// A problem with one field of a request body.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// JSON pointer to the field, e.g. `/inputs/readings/1`; empty for the body as a whole
+    pub field: String,
+    pub message: String,
+}
+
+/// JSON Schema of a proof request body. The health and option fields are the same for every
+/// circuit; `inputs` lists the parameters of `main` the request has to supply, i.e. those the
+/// server neither derives from the request's own fields nor fixes for the circuit. Without an
+/// ABI, `inputs` is left to be checked when the job runs.
+pub fn request_schema(abi: Option<&CircuitAbi>, fixed: &Inputs, circuit_id: Option<&str>) -> Value {
+    let supplied: Vec<_> = abi
+        .map(|abi| {
+            abi.parameters
+                .iter()
+                .filter(|parameter| !SERVER_INPUTS.contains(&parameter.name.as_str()) && !fixed.contains_key(&parameter.name))
+                .collect()
+        })
+        .unwrap_or_default();
+    let inputs = match abi {
+        Some(_) => json!({
+            "type": "object",
+            "properties": supplied
+                .iter()
+                .map(|parameter| (parameter.name.clone(), parameter.typ.json_schema()))
+                .collect::<serde_json::Map<_, _>>(),
+            "required": supplied.iter().map(|parameter| parameter.name.as_str()).collect::<Vec<_>>(),
+            "additionalProperties": false,
+        }),
+        None => json!({ "type": "object" }),
+    };
+    let mut required = Vec::new();
+    if !supplied.is_empty() {
+        required.push("inputs");
+    }
+    let circuit_id = match circuit_id {
+        Some(id) => {
+            required.push("circuit_id");
+            json!({ "const": id })
+        }
+        None => json!({ "type": "null" }),
+    };
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Proof request",
+        "type": "object",
+        "properties": {
+            "age": { "type": "integer", "minimum": 0, "maximum": intake::MAX_PLAUSIBLE_AGE },
+            "bmi_multiplied": { "type": "integer", "minimum": 0, "maximum": intake::MAX_PLAUSIBLE_BMI },
+            "height_cm": {
+                "type": "number",
+                "minimum": intake::MIN_PLAUSIBLE_HEIGHT_CM,
+                "maximum": intake::MAX_PLAUSIBLE_HEIGHT_CM,
+            },
+            "weight_kg": {
+                "type": "number",
+                "minimum": intake::MIN_PLAUSIBLE_WEIGHT_KG,
+                "maximum": intake::MAX_PLAUSIBLE_WEIGHT_KG,
+            },
+            "date_of_birth": { "type": "string", "format": "date" },
+            "smoker": { "type": "boolean" },
+            "systolic_bp": {
+                "type": "integer",
+                "minimum": intake::PLAUSIBLE_SYSTOLIC_BP.start(),
+                "maximum": intake::PLAUSIBLE_SYSTOLIC_BP.end(),
+            },
+            "diastolic_bp": {
+                "type": "integer",
+                "minimum": intake::PLAUSIBLE_DIASTOLIC_BP.start(),
+                "maximum": intake::PLAUSIBLE_DIASTOLIC_BP.end(),
+            },
+            "conditions": { "type": "integer", "minimum": 0, "maximum": u64::MAX },
+            "inputs": inputs,
+            "nonce": { "type": "string", "pattern": "^(0x)?[0-9a-fA-F]{1,62}$" },
+            "circuit_id": circuit_id,
+            "expected_circuit_hash": { "type": "string", "pattern": BYTES32 },
+            "scheme": { "enum": ["ultra_honk", "ultra_plonk"] },
+            "priority": { "enum": ["high", "normal", "low"] },
+            "policy": { "type": "string" },
+            "tier": { "type": "string" },
+            "recursive": { "type": "boolean" },
+            "as_of": { "type": "string", "format": "date-time" },
+            "consent": { "type": "boolean" },
+            "output_format": { "enum": ["hex", "evm_calldata"] },
+            "applicant_commitment": { "type": "string", "pattern": BYTES32 },
+        },
+        "required": required,
+    })
+}
+
+/// Request schemas compiled for the circuits requests name, keyed by the compiled artifact and
+/// its modification time so a recompiled or swapped-in circuit gets a fresh schema.
+#[derive(Default)]
+pub struct RequestSchemas {
+    compiled: Mutex<HashMap<String, Arc<JSONSchema>>>,
+}
+
+impl RequestSchemas {
+    /// The schema a body for `circuit_id` (the eligibility circuit if None) is checked against.
+    pub fn document(&self, verifier: &Verifier, circuit_id: Option<&str>) -> Result<Value> {
+        let (abi, fixed) = match circuit_id {
+            Some(id) => {
+                let circuit_path = verifier.registry().circuit_path(id).ok_or_else(|| anyhow!("Unknown circuit_id {:?}", id))?;
+                (CircuitAbi::load(&artifact(circuit_path).0)?, verifier.registry().parameters(Some(id)))
+            }
+            None => (CircuitAbi::load(&artifact(verifier.circuit_path()).0)?, Inputs::new()),
+        };
+        Ok(request_schema(Some(&abi), &fixed, circuit_id))
+    }
+
+    /// Checks one request body, reporting every field that does not match the schema of the
+    /// circuit it names. Requests for past releases (`as_of`) have `inputs` checked when the
+    /// job runs, since their circuit may take different ones.
+    pub fn check(&self, verifier: &Verifier, body: &Value) -> Result<(), Vec<FieldError>> {
+        let circuit_id = body.get("circuit_id").and_then(Value::as_str);
+        let historical = body.get("as_of").is_some_and(|as_of| !as_of.is_null());
+        let schema = match circuit_id {
+            Some(id) => match verifier.registry().circuit_path(id) {
+                Some(circuit_path) => self.compiled(circuit_path, Some(id), verifier),
+                None => {
+                    return Err(vec![FieldError {
+                        field: "/circuit_id".to_string(),
+                        message: format!("Unknown circuit_id {:?} (see GET /circuits)", id),
+                    }])
+                }
+            },
+            None if historical => self.generic(),
+            None => self.compiled(verifier.circuit_path(), None, verifier),
+        };
+        // A circuit that cannot be read fails the job with a better message than a schema could
+        let Ok(schema) = schema else {
+            return Ok(());
+        };
+        schema.validate(body).map_err(|errors| {
+            errors
+                .map(|error| FieldError {
+                    field: error.instance_path.to_string(),
+                    message: error.to_string(),
+                })
+                .collect()
+        })
+    }
+
+    fn compiled(&self, circuit_path: PathBuf, circuit_id: Option<&str>, verifier: &Verifier) -> Result<Arc<JSONSchema>> {
+        let (artifact, version) = artifact(circuit_path);
+        let key = format!("{}:{}", circuit_id.unwrap_or_default(), version);
+        if let Some(schema) = self.compiled.lock().unwrap().get(&key) {
+            return Ok(schema.clone());
+        }
+        let abi = CircuitAbi::load(&artifact)?;
+        let fixed = verifier.registry().parameters(circuit_id);
+        self.insert(key, request_schema(Some(&abi), &fixed, circuit_id))
+    }
+
+    fn generic(&self) -> Result<Arc<JSONSchema>> {
+        if let Some(schema) = self.compiled.lock().unwrap().get("") {
+            return Ok(schema.clone());
+        }
+        self.insert(String::new(), request_schema(None, &Inputs::new(), None))
+    }
+
+    fn insert(&self, key: String, document: Value) -> Result<Arc<JSONSchema>> {
+        let schema = JSONSchema::options()
+            .with_draft(Draft::Draft202012)
+            .should_validate_formats(true)
+            .compile(&document)
+            .map(Arc::new)
+            .map_err(|e| anyhow!("Invalid request schema: {}", e))?;
+        let mut compiled = self.compiled.lock().unwrap();
+        if compiled.len() >= MAX_CACHED {
+            compiled.clear();
+        }
+        compiled.insert(key, schema.clone());
+        Ok(schema)
+    }
+}
+
+/// The circuit compiled in `circuit_path`, and a version that changes when it is recompiled.
+fn artifact(circuit_path: PathBuf) -> (PathBuf, String) {
+    let artifact = circuit_path.join(prover::compiled_circuit(&circuit_path));
+    let modified = fs::metadata(&artifact).and_then(|metadata| metadata.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+    let version = format!("{}@{:?}", artifact.display(), modified);
+    (artifact, version)
+}
@@ -0,0 +1,149 @@
+use crate::prover;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::ffi::CString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Where a job's private inputs (Prover.toml) and witness are written while it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InputStorage {
+    /// Next to the circuit, on whatever filesystem holds it
+    Disk,
+    /// In a per-job directory on a memory-backed filesystem (--scratch-dir), never on disk
+    Memory,
+}
+
+/// Default mount point of the in-memory scratch space.
+pub const DEFAULT_SCRATCH_DIR: &str = "/run/zk-insurance";
+
+/// Size of the tmpfs the server mounts itself; witnesses are a few KiB, the compiled circuit
+/// copied into each job a few MiB.
+const TMPFS_OPTIONS: &str = "mode=0700,size=256m";
+
+const TMPFS_MAGIC: i64 = 0x0102_1994;
+const RAMFS_MAGIC: i64 = 0x8584_58f6;
+
+/// Makes `dir` usable for in-memory jobs: mounts a tmpfs on it unless it already is memory-backed,
+/// and wipes whatever a previous run left behind. Fails rather than fall back to disk.
+pub fn prepare(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    if !is_memory_backed(dir)? {
+        mount_tmpfs(dir)?;
+        if !is_memory_backed(dir)? {
+            bail!("{} is still not memory-backed after mounting a tmpfs", dir.display());
+        }
+    }
+    // Only the server may read other jobs' inputs
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    for entry in fs::read_dir(dir)? {
+        wipe_tree(&entry?.path());
+    }
+    Ok(())
+}
+
+fn is_memory_backed(dir: &Path) -> Result<bool> {
+    let path = CString::new(dir.as_os_str().as_bytes())?;
+    // SAFETY: statfs only writes into the zeroed struct; the path is a valid C string
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error()).with_context(|| format!("Failed to stat {}", dir.display()));
+    }
+    let kind = stat.f_type as i64;
+    Ok(kind == TMPFS_MAGIC || kind == RAMFS_MAGIC)
+}
+
+fn mount_tmpfs(dir: &Path) -> Result<()> {
+    let target = CString::new(dir.as_os_str().as_bytes())?;
+    let fstype = CString::new("tmpfs")?;
+    let options = CString::new(TMPFS_OPTIONS)?;
+    let flags = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+    // SAFETY: every pointer is a valid C string that outlives the call
+    let result = unsafe { libc::mount(fstype.as_ptr(), target.as_ptr(), fstype.as_ptr(), flags, options.as_ptr().cast()) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error()).with_context(|| {
+            format!(
+                "{} is not memory-backed and a tmpfs could not be mounted on it; mount one there or run as root",
+                dir.display()
+            )
+        });
+    }
+    Ok(())
+}
+
+/// The directory a job runs nargo and bb in. For in-memory jobs this is a private copy of the
+/// circuit package on the scratch tmpfs, wiped and removed when the job ends (or is cancelled).
+pub struct JobWorkspace {
+    path: PathBuf,
+    owned: bool,
+}
+
+impl JobWorkspace {
+    /// Runs the job in the circuit directory itself.
+    pub fn shared(circuit_path: &Path) -> Self {
+        Self {
+            path: circuit_path.to_path_buf(),
+            owned: false,
+        }
+    }
+
+    /// A fresh package for `job_id` under `scratch`: Nargo.toml and the compiled circuit are
+    /// copied, since nargo rewrites its artifact; the sources are linked.
+    pub fn create(scratch: &Path, circuit_path: &Path, job_id: &str) -> Result<Self> {
+        let workspace = Self {
+            path: scratch.join(format!("job_{}", job_id)),
+            owned: true,
+        };
+        let circuit_path = fs::canonicalize(circuit_path)?;
+        fs::create_dir_all(workspace.path.join("target"))?;
+        fs::copy(circuit_path.join("Nargo.toml"), workspace.path.join("Nargo.toml"))?;
+        let artifact = prover::compiled_circuit(&circuit_path);
+        fs::copy(circuit_path.join(&artifact), workspace.path.join(&artifact))?;
+        std::os::unix::fs::symlink(circuit_path.join("src"), workspace.path.join("src"))?;
+        Ok(workspace)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for JobWorkspace {
+    fn drop(&mut self) {
+        if self.owned {
+            wipe_tree(&self.path);
+        }
+    }
+}
+
+/// Overwrites a file with zeros before unlinking it, so its contents do not linger in freed
+/// pages or disk blocks.
+pub fn wipe_file(path: &Path) {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.is_file() {
+            if let Ok(mut file) = fs::OpenOptions::new().write(true).open(path) {
+                let _ = file.write_all(&vec![0u8; metadata.len() as usize]);
+                let _ = file.sync_data();
+            }
+        }
+    }
+    let _ = fs::remove_file(path);
+}
+
+/// Wipes every file under `path` (symlinks are removed, never followed) and removes it.
+fn wipe_tree(path: &Path) {
+    let Ok(metadata) = fs::symlink_metadata(path) else { return };
+    if metadata.is_dir() {
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                wipe_tree(&entry.path());
+            }
+        }
+        let _ = fs::remove_dir(path);
+    } else {
+        wipe_file(path);
+    }
+}
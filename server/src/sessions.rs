@@ -0,0 +1,145 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Live sessions kept at once. Opening one is unauthenticated, so past this new ones are refused
+/// until old ones expire.
+const MAX_SESSIONS: usize = 10_000;
+
+/// An attestation session: opened with a client challenge, then referenced by the
+/// X-Attestation-Session header of every request sending data to the enclave.
+#[derive(Debug, Clone)]
+struct Session {
+    challenge: Vec<u8>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Answer to a session challenge, as served by POST /attestation/session.
+#[derive(Debug, Serialize)]
+pub struct SessionGrant {
+    pub session_id: String,
+    /// The client's challenge, echoed; the document's `nonce` field must equal it
+    pub challenge: String,
+    pub expires_at: DateTime<Utc>,
+    /// `binding` of the enclave keys (see GET /keys) the session was opened with
+    pub keys_binding: String,
+    /// `session_user_data(session_id, challenge, keys_binding)`, embedded as the document's `user_data`
+    pub user_data: String,
+    /// Hex-encoded Nitro attestation document
+    pub document: String,
+}
+
+/// Why a session was not opened.
+pub enum SessionError {
+    /// A live session already answered the challenge
+    Reused,
+    /// `MAX_SESSIONS` sessions are live
+    Full,
+}
+
+pub struct SessionStore {
+    ttl: Duration,
+    required: bool,
+    sessions: Mutex<Sessions>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    by_id: HashMap<String, Session>,
+    /// Session ID by challenge, to find reused challenges without a scan
+    by_challenge: HashMap<Vec<u8>, String>,
+}
+
+impl Sessions {
+    fn remove(&mut self, session_id: &str) {
+        if let Some(session) = self.by_id.remove(session_id) {
+            self.by_challenge.remove(&session.challenge);
+        }
+    }
+
+    fn remove_expired(&mut self, now: DateTime<Utc>) {
+        self.by_id.retain(|_, session| session.expires_at > now);
+        let by_id = &self.by_id;
+        self.by_challenge.retain(|_, session_id| by_id.contains_key(session_id));
+    }
+}
+
+impl SessionStore {
+    pub fn new(ttl_secs: u64, required: bool) -> Self {
+        Self {
+            ttl: Duration::seconds(ttl_secs as i64),
+            required,
+            sessions: Mutex::new(Sessions::default()),
+        }
+    }
+
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    /// Opens a session for `challenge`. Challenges of live sessions cannot be reused, so an
+    /// answer can never be handed out twice.
+    pub fn open(&self, challenge: &[u8]) -> Result<(String, DateTime<Utc>), SessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Utc::now();
+        if let Some(session_id) = sessions.by_challenge.get(challenge).cloned() {
+            match sessions.by_id.get(&session_id) {
+                Some(session) if session.expires_at > now => return Err(SessionError::Reused),
+                _ => sessions.remove(&session_id),
+            }
+        }
+        if sessions.by_id.len() >= MAX_SESSIONS {
+            sessions.remove_expired(now);
+            if sessions.by_id.len() >= MAX_SESSIONS {
+                return Err(SessionError::Full);
+            }
+        }
+        let session_id = uuid::Uuid::new_v4().simple().to_string();
+        let expires_at = now + self.ttl;
+        sessions.by_id.insert(
+            session_id.clone(),
+            Session {
+                challenge: challenge.to_vec(),
+                expires_at,
+            },
+        );
+        sessions.by_challenge.insert(challenge.to_vec(), session_id.clone());
+        Ok((session_id, expires_at))
+    }
+
+    /// Drops a session whose attestation could not be produced.
+    pub fn close(&self, session_id: &str) {
+        self.sessions.lock().unwrap().remove(session_id);
+    }
+
+    /// Checks the session a request references. Requests without one pass unless sessions are
+    /// required.
+    pub fn check(&self, session_id: Option<&str>) -> Result<()> {
+        let Some(session_id) = session_id else {
+            if self.required {
+                bail!("This server requires an attestation session (POST /attestation/session, then X-Attestation-Session)");
+            }
+            return Ok(());
+        };
+        match self.sessions.lock().unwrap().by_id.get(session_id) {
+            Some(session) if session.expires_at > Utc::now() => Ok(()),
+            Some(_) => bail!("Attestation session {} has expired; open a new one", session_id),
+            None => bail!("Unknown attestation session {}", session_id),
+        }
+    }
+}
+
+/// What the attestation document of a session binds: the session, the client's challenge and the
+/// enclave's current public keys.
+pub fn session_user_data(session_id: &str, challenge: &[u8], keys_binding: &str) -> [u8; 32] {
+    let message = format!(
+        "zk-insurance session\nsession_id: {}\nchallenge: 0x{}\nkeys: {}",
+        session_id,
+        hex::encode(challenge),
+        keys_binding
+    );
+    Sha256::digest(message.as_bytes()).into()
+}
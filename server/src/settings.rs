@@ -0,0 +1,96 @@
+use anyhow::{bail, Context, Result};
+use clap::{Command, CommandFactory, FromArgMatches};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prefix of the environment variables that set server flags: `--http-port` is read from
+/// `ZK_INSURANCE_HTTP_PORT`.
+pub const ENV_PREFIX: &str = "ZK_INSURANCE_";
+
+/// Flags that only make sense on the command line.
+const CLI_ONLY: [&str; 3] = ["config", "help", "version"];
+
+/// Parses the server flags from, in order of precedence, the command line, `ZK_INSURANCE_*`
+/// environment variables, the `--config` file and the built-in defaults.
+///
+/// The file is TOML (or JSON, by extension) keyed by flag name, e.g. `http_port = 8081` or
+/// `"job-memory-mb" = 2048`. Unknown keys are an error so typos do not go unnoticed.
+pub fn parse<T: CommandFactory + FromArgMatches>() -> Result<T> {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let matches = command::<T>(&argv)?.get_matches_from(argv);
+    Ok(T::from_arg_matches(&matches)?)
+}
+
+/// Parses the flags again as `parse` does, picking up edits to the `--config` file and the
+/// files it names. Errors are returned rather than ending the process, so a bad edit cannot
+/// take a running server down.
+pub fn reparse<T: CommandFactory + FromArgMatches>() -> Result<T> {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let matches = command::<T>(&argv)?.try_get_matches_from(argv)?;
+    Ok(T::from_arg_matches(&matches)?)
+}
+
+/// The command line parser with the `--config` file's values and the environment variables as
+/// defaults.
+fn command<T: CommandFactory>(argv: &[OsString]) -> Result<Command> {
+    let mut command = T::command();
+
+    // Find --config before the real parse; help, version and bad flags are reported by the latter
+    let config = T::command()
+        .ignore_errors(true)
+        .try_get_matches_from(argv)
+        .ok()
+        .and_then(|matches| matches.try_get_one::<PathBuf>("config").ok().flatten().cloned());
+    if let Some(path) = &config {
+        for (id, values) in load(path)? {
+            if CLI_ONLY.contains(&id.as_str()) || !command.get_arguments().any(|arg| arg.get_id() == id.as_str()) {
+                bail!("{}: unknown setting '{}'", path.display(), id);
+            }
+            command = command.mut_arg(id, |arg| arg.default_values(values));
+        }
+    }
+
+    let ids: Vec<String> = command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !CLI_ONLY.contains(&arg.get_id().as_str()))
+        .map(|arg| arg.get_id().to_string())
+        .collect();
+    for id in ids {
+        let name = format!("{}{}", ENV_PREFIX, id.to_uppercase());
+        command = command.mut_arg(id, |arg| arg.env(name));
+    }
+
+    Ok(command)
+}
+
+/// Reads a settings file into (flag id, values) pairs.
+fn load(path: &Path) -> Result<Vec<(String, Vec<String>)>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let table: serde_json::Map<String, serde_json::Value> = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&content).with_context(|| format!("{} is not a JSON object", path.display()))?,
+        _ => toml::from_str(&content).with_context(|| format!("{} is not valid TOML", path.display()))?,
+    };
+    table
+        .into_iter()
+        .map(|(key, value)| {
+            let values = match value {
+                serde_json::Value::Array(items) => items.into_iter().map(scalar).collect::<Option<Vec<_>>>(),
+                value => scalar(value).map(|value| vec![value]),
+            };
+            match values {
+                Some(values) => Ok((key.replace('-', "_"), values)),
+                None => bail!("{}: '{}' must be a string, number, boolean or a list of them", path.display(), key),
+            }
+        })
+        .collect()
+}
+
+fn scalar(value: serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(value) => Some(value),
+        serde_json::Value::Number(value) => Some(value.to_string()),
+        serde_json::Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
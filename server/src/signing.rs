@@ -0,0 +1,98 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+/// secp256k1 key held only inside the enclave. Signatures follow Ethereum conventions
+/// (keccak256 + recoverable 65-byte r||s||v) so relying parties and contracts can check
+/// them with standard tooling and `ecrecover`.
+pub struct EnclaveSigner {
+    key: SigningKey,
+}
+
+/// Signature by the enclave key over an artifact it produced, with everything needed to check it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnclaveSignature {
+    /// Ethereum address of the key; also embedded in the enclave's attestation document
+    pub signer: String,
+    /// Compressed secp256k1 public key
+    pub public_key: String,
+    /// Unix time of signing, part of the signed message
+    pub signed_at: i64,
+    /// EIP-191 signature, 65 bytes r||s||v
+    pub signature: String,
+}
+
+impl EnclaveSigner {
+    /// Signer with a caller-provided key, e.g. the published test key used for conformance vectors.
+    pub fn from_bytes(secret: &[u8; 32]) -> Result<Self> {
+        Ok(Self {
+            key: SigningKey::from_bytes(secret.into()).context("Invalid secp256k1 secret key")?,
+        })
+    }
+
+    /// Ethereum address derived from the public key, 0x-prefixed.
+    pub fn address(&self) -> String {
+        address_of(self.key.verifying_key())
+    }
+
+    /// Compressed public key, 0x-prefixed.
+    pub fn public_key(&self) -> String {
+        format!("0x{}", hex::encode(self.key.verifying_key().to_encoded_point(true).as_bytes()))
+    }
+
+    /// Signs the message `message_at` builds for the current time.
+    pub fn sign_at(&self, message_at: impl FnOnce(i64) -> String) -> Result<EnclaveSignature> {
+        let signed_at = Utc::now().timestamp();
+        Ok(EnclaveSignature {
+            signer: self.address(),
+            public_key: self.public_key(),
+            signed_at,
+            signature: self.sign_message(message_at(signed_at).as_bytes())?,
+        })
+    }
+
+    /// Signs `message` as an EIP-191 personal message and returns the 0x-prefixed signature.
+    pub fn sign_message(&self, message: &[u8]) -> Result<String> {
+        self.sign_digest(eip191_hash(message))
+    }
+
+    /// Signs a precomputed 32-byte keccak digest.
+    pub fn sign_digest(&self, digest: [u8; 32]) -> Result<String> {
+        let (signature, recovery_id) = self
+            .key
+            .sign_prehash_recoverable(&digest)
+            .context("Failed to sign digest")?;
+
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        Ok(format!("0x{}", hex::encode(bytes)))
+    }
+}
+
+pub fn eip191_hash(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+pub fn address_of(key: &VerifyingKey) -> String {
+    let point = key.to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+    format!("0x{}", hex::encode(&hash[12..]))
+}
+
+/// Address that produced an EIP-191 signature made by `EnclaveSigner::sign_message`.
+pub fn recover_address(message: &[u8], signature_hex: &str) -> Result<String> {
+    let bytes = hex::decode(signature_hex.trim().trim_start_matches("0x")).context("Signature is not valid hex")?;
+    if bytes.len() != 65 {
+        bail!("Signature must be 65 bytes, found {}", bytes.len());
+    }
+    let signature = Signature::from_slice(&bytes[..64]).context("Invalid signature")?;
+    let recovery_id = RecoveryId::from_byte(bytes[64].wrapping_sub(27)).ok_or_else(|| anyhow!("Invalid recovery id"))?;
+    let key = VerifyingKey::recover_from_prehash(&eip191_hash(message), &signature, recovery_id)
+        .context("Failed to recover the signing key")?;
+    Ok(address_of(&key))
+}
@@ -0,0 +1,239 @@
+use crate::abi::CircuitAbi;
+use crate::audit::AuditLog;
+use crate::control;
+use crate::prover;
+use crate::state::AppState;
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Link to the active snapshot, inside the snapshot directory.
+const CURRENT: &str = "current";
+
+/// Snapshots kept besides the active one, for jobs that started before a swap.
+const KEPT_SNAPSHOTS: usize = 3;
+
+/// A compiled circuit modified more recently than this may still be being written by nargo.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// A swap from one compiled eligibility circuit to another.
+#[derive(Debug)]
+pub struct Swap {
+    pub previous_hash: String,
+    pub circuit_hash: String,
+    pub vk_hash: String,
+    pub snapshot: PathBuf,
+}
+
+/// Serves the eligibility circuit from snapshots of --circuit-path, so it can be recompiled in
+/// place while the server runs. Each snapshot holds the package sources and compiled circuit in a
+/// directory named after the circuit hash, and `current` links to the active one. A swap copies
+/// the new circuit and generates its verification key before replacing the link; jobs resolve the
+/// link once when they start, so proofs in flight finish against the circuit they began with.
+pub struct CircuitSnapshots {
+    /// Package directory the operator recompiles
+    source: PathBuf,
+    dir: PathBuf,
+    /// Hashes of the snapshots activated by this process, the active one first
+    recent: Mutex<VecDeque<String>>,
+    /// Compiled circuit whose swap failed; the watcher skips it until it changes again
+    rejected: Mutex<Option<String>>,
+    swapping: tokio::sync::Mutex<()>,
+}
+
+impl CircuitSnapshots {
+    /// Snapshots the circuit as compiled now and makes it active. Snapshots left by earlier runs
+    /// are removed, since no job can still be using them.
+    pub fn init(source: &Path, dir: PathBuf) -> Result<Self> {
+        let source = fs::canonicalize(source).with_context(|| format!("Circuit not found at {}", source.display()))?;
+        check_manifest(&source)?;
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let (hash, compiled) = read_compiled(&source)?;
+        take_snapshot(&source, &dir, &hash, &compiled)?;
+        control::swap_symlink(Path::new(&hash), &dir.join(CURRENT))?;
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name != CURRENT && name != hash.as_str() {
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+        Ok(Self {
+            source,
+            dir,
+            recent: Mutex::new(VecDeque::from([hash])),
+            rejected: Mutex::new(None),
+            swapping: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// The path the server proves with: a link to the active snapshot.
+    pub fn current(&self) -> PathBuf {
+        self.dir.join(CURRENT)
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    fn active_hash(&self) -> String {
+        self.recent.lock().unwrap().front().cloned().unwrap_or_default()
+    }
+
+    /// Whether the source package holds a finished compilation that is neither active nor
+    /// known to fail.
+    fn has_pending(&self) -> bool {
+        let artifact = self.source.join(prover::compiled_circuit(&self.source));
+        let settled = fs::metadata(&artifact)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().unwrap_or_default() >= SETTLE_TIME);
+        if !settled {
+            return false;
+        }
+        match read_compiled(&self.source) {
+            Ok((hash, _)) => hash != self.active_hash() && self.rejected.lock().unwrap().as_ref() != Some(&hash),
+            Err(_) => false,
+        }
+    }
+
+    /// Swaps in the circuit currently compiled in the source package; None if it is already
+    /// active. The verification key is ready before the link moves, and the aggregator and
+    /// readiness checks pick up the new circuit afterwards.
+    pub async fn reload(&self, state: &AppState, audit: &AuditLog, trigger: &str) -> Result<Option<Swap>> {
+        let _swapping = self.swapping.lock().await;
+        let (hash, compiled) = read_compiled(&self.source)?;
+        let previous_hash = self.active_hash();
+        if hash == previous_hash {
+            return Ok(None);
+        }
+        let prepared = async {
+            check_manifest(&self.source)?;
+            let snapshot = take_snapshot(&self.source, &self.dir, &hash, &compiled)?;
+            let vk = state.verifier.key_at(&snapshot).await?;
+            anyhow::Ok((snapshot, vk))
+        }
+        .await;
+        let (snapshot, vk) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                *self.rejected.lock().unwrap() = Some(hash);
+                return Err(e);
+            }
+        };
+        control::swap_symlink(Path::new(&hash), &self.current())?;
+        self.prune(&hash);
+        state.aggregator.reload().await;
+        state.preflight.flush().await;
+
+        let swap = Swap {
+            previous_hash,
+            circuit_hash: hash,
+            vk_hash: vk.vk_hash,
+            snapshot,
+        };
+        info!(
+            "Circuit 0x{} swapped in from {} (vk {}, {})",
+            swap.circuit_hash,
+            self.source.display(),
+            swap.vk_hash,
+            trigger
+        );
+        audit.record(
+            "circuit_swapped",
+            json!({
+                "trigger": trigger,
+                "previous_hash": format!("0x{}", swap.previous_hash),
+                "circuit_hash": format!("0x{}", swap.circuit_hash),
+                "vk_hash": swap.vk_hash,
+            }),
+        );
+        Ok(Some(swap))
+    }
+
+    /// Records `hash` as active and removes snapshots too old for any job to still use.
+    fn prune(&self, hash: &str) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|kept| kept != hash);
+        recent.push_front(hash.to_string());
+        while recent.len() > KEPT_SNAPSHOTS + 1 {
+            if let Some(old) = recent.pop_back() {
+                let _ = fs::remove_dir_all(self.dir.join(old));
+            }
+        }
+    }
+
+    /// Polls the source package and swaps in each new compilation once nargo has finished it.
+    pub async fn run(self: Arc<Self>, state: Arc<AppState>, audit: Arc<AuditLog>, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            if !self.has_pending() {
+                continue;
+            }
+            if let Err(e) = self.reload(&state, &audit, "watch").await {
+                error!("Recompiled circuit in {} not swapped in: {:#}", self.source.display(), e);
+            }
+        }
+    }
+}
+
+/// The compiled circuit of the package at `source` and its hex sha256.
+fn read_compiled(source: &Path) -> Result<(String, Vec<u8>)> {
+    let artifact = source.join(prover::compiled_circuit(source));
+    let compiled = fs::read(&artifact).with_context(|| format!("Failed to read {}", artifact.display()))?;
+    Ok((hex::encode(Sha256::digest(&compiled)), compiled))
+}
+
+/// Snapshots are copies, so a package cannot reach files relative to its own directory.
+fn check_manifest(source: &Path) -> Result<()> {
+    let manifest: toml::Table = fs::read_to_string(source.join("Nargo.toml"))?
+        .parse()
+        .with_context(|| format!("{} has an invalid Nargo.toml", source.display()))?;
+    let local = manifest
+        .get("dependencies")
+        .and_then(|dependencies| dependencies.as_table())
+        .is_some_and(|dependencies| dependencies.values().any(|dependency| dependency.get("path").is_some()));
+    if local {
+        bail!("{} has path dependencies, which cannot be snapshotted for hot reloads", source.display());
+    }
+    Ok(())
+}
+
+/// Copies the package's manifest, sources and `compiled` circuit to `dir/<hash>`, through a
+/// staging directory so a snapshot is never seen half-written. An existing snapshot of the same
+/// circuit is reused, verification keys included.
+fn take_snapshot(source: &Path, dir: &Path, hash: &str, compiled: &[u8]) -> Result<PathBuf> {
+    let snapshot = dir.join(hash);
+    if snapshot.exists() {
+        return Ok(snapshot);
+    }
+    let staging = dir.join(format!(".{}.tmp", hash));
+    let _ = fs::remove_dir_all(&staging);
+    let artifact = staging.join(prover::compiled_circuit(source));
+    fs::create_dir_all(artifact.parent().unwrap_or(&staging))?;
+    fs::write(&artifact, compiled)?;
+    CircuitAbi::load(&artifact).context("The recompiled circuit cannot be loaded")?;
+    fs::copy(source.join("Nargo.toml"), staging.join("Nargo.toml"))?;
+    copy_tree(&source.join("src"), &staging.join("src"))?;
+    fs::rename(&staging, &snapshot).with_context(|| format!("Failed to move the snapshot to {}", snapshot.display()))?;
+    Ok(snapshot)
+}
+
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from).with_context(|| format!("Failed to read {}", from.display()))? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
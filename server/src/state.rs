@@ -0,0 +1,159 @@
+use crate::aggregation::Aggregator;
+use crate::apikeys;
+use crate::attestation::AttestationClient;
+use crate::backpressure::{ConnectionLimit, RateLimiter};
+use crate::config::SharedConfig;
+use crate::encryption::StorageEncryption;
+use crate::health::Preflight;
+use crate::indexer::OnchainIndexer;
+use crate::integrity::IntegrityMonitor;
+use crate::issuance::IssuanceLog;
+use crate::jobs::JobStore;
+use crate::jwt::JwtVerifier;
+use crate::keys::KeyManager;
+use crate::measurements::MeasurementReporter;
+use crate::metrics::Metrics;
+use crate::payments::PaymentGate;
+use crate::submitter::Submitter;
+use crate::prover::ProofResponse;
+use crate::schema::RequestSchemas;
+use crate::sessions::SessionStore;
+use crate::signing::EnclaveSigner;
+use crate::snapshots::CircuitSnapshots;
+use crate::storage::Storage;
+use crate::usage::UsageMeter;
+use crate::verifier::Verifier;
+use crate::watermark::Watermarker;
+use crate::worker::WorkerPool;
+use anyhow::{bail, Result};
+use std::sync::Arc;
+
+/// Everything a connection handler needs, shared between the TCP and HTTP front-ends.
+pub struct AppState {
+    pub pool: Arc<WorkerPool>,
+    pub aggregator: Aggregator,
+    pub verifier: Arc<Verifier>,
+    pub encryption: Arc<dyn StorageEncryption>,
+    pub config: SharedConfig,
+    pub integrity: Arc<IntegrityMonitor>,
+    /// Cap on TCP sessions and HTTP requests served at once
+    pub connections: ConnectionLimit,
+    /// Proofs per client, limited when `rate_limit` is configured
+    pub rate_limit: RateLimiter,
+    /// Proofs metered per API key, against each key's quotas
+    pub usage: Arc<UsageMeter>,
+    /// Whether the HTTP API identifies clients by X-Forwarded-For, for use behind a proxy
+    pub trust_forwarded_for: bool,
+    /// Set when on-chain submission is configured
+    pub submitter: Option<Submitter>,
+    /// Charges proofs to escrow deposits; set with --payment-chain
+    pub payments: Option<Arc<PaymentGate>>,
+    /// Set when on-chain indexing is enabled
+    pub indexer: Option<Arc<OnchainIndexer>>,
+    /// Source of Nitro attestation documents
+    pub attestation: AttestationClient,
+    /// Attestation sessions opened by clients
+    pub sessions: SessionStore,
+    /// Toolchain and circuit checks behind GET /readyz
+    pub preflight: Preflight,
+    /// Set when the eligibility circuit is served from snapshots that can be hot-swapped
+    pub snapshots: Option<Arc<CircuitSnapshots>>,
+    /// Request schemas compiled per circuit, checked before a body is deserialized
+    pub schemas: RequestSchemas,
+    /// Proving counters and timings, for GET /metrics
+    pub metrics: Arc<Metrics>,
+    /// Hash-chained record of proof requests, for GET /audit/issuance
+    pub issuance: Arc<IssuanceLog>,
+    /// Proof job history, for GET /admin/jobs; set with --database-url
+    pub jobs: Option<Arc<JobStore>>,
+    /// Where the TCP interface saves proof bundles (--artifact-storage)
+    pub artifacts: Arc<dyn Storage>,
+    /// Whether the TCP interface saves the proofs it returns (--save-proofs)
+    pub save_proofs: bool,
+    /// Image, binary and circuit measurements, for GET /measurements
+    pub measurements: MeasurementReporter,
+    /// Long-lived enclave keys, for GET /keys
+    pub keys: KeyManager,
+    /// The current enclave signing key
+    pub signer: Arc<EnclaveSigner>,
+    /// Set when watermarking is enabled
+    pub watermarker: Option<Watermarker>,
+    /// Checks bearer tokens on proof requests; set with --jwt-jwks-url
+    pub jwt: Option<JwtVerifier>,
+    /// Client for outbound requests such as chain RPC
+    pub http: reqwest::Client,
+}
+
+/// What a request authenticated with.
+pub enum Credential {
+    /// No credentials are configured, or none were sent to an open route
+    Anonymous,
+    /// A key from the API keys table, by name
+    ApiKey(String),
+    /// A bearer token from the identity provider, with the tenant its claim names
+    Bearer { tenant: Option<String> },
+}
+
+impl Credential {
+    /// Name of the API key, which quotas are kept by. Bearer tokens are not metered.
+    pub fn api_key(&self) -> Option<&str> {
+        match self {
+            Credential::ApiKey(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl AppState {
+    /// Checks the credentials of a proof request: a bearer token from the identity provider when
+    /// --jwt-jwks-url is set, otherwise a key from the API keys table. With neither configured,
+    /// anyone may prove.
+    pub async fn authenticate(&self, api_key: Option<&str>, bearer: Option<&str>) -> Result<Credential> {
+        if let (Some(jwt), Some(token)) = (&self.jwt, bearer) {
+            return Ok(Credential::Bearer { tenant: jwt.verify(token).await? });
+        }
+        let config = self.config.read().unwrap();
+        if config.api_keys.is_empty() {
+            if self.jwt.is_some() {
+                bail!("A bearer token is required");
+            }
+            return Ok(Credential::Anonymous);
+        }
+        if api_key.is_none() && self.jwt.is_some() {
+            bail!("An API key or bearer token is required");
+        }
+        Ok(match apikeys::authenticate(&config.api_keys, api_key)? {
+            Some(name) => Credential::ApiKey(name),
+            None => Credential::Anonymous,
+        })
+    }
+
+    /// Tenant a request is for: that of its API key, or the one its bearer token's tenant claim
+    /// names. The X-Tenant-Id header (`requested`) may repeat it, but a tenant the credential
+    /// does not back is refused, so naming a tenant is never enough to prove as it or read its
+    /// proofs. None is the default tenant.
+    pub fn tenant(&self, credential: &Credential, requested: Option<&str>) -> Result<Option<String>> {
+        let config = self.config.read().unwrap();
+        let bound = match credential {
+            Credential::ApiKey(name) => config.api_keys.get(name).and_then(|key| key.tenant.as_deref()),
+            Credential::Bearer { tenant } => tenant.as_deref(),
+            Credential::Anonymous => None,
+        };
+        let tenant = bound.unwrap_or(crate::DEFAULT_TENANT);
+        if let Some(requested) = requested.filter(|requested| *requested != tenant) {
+            match credential {
+                Credential::Anonymous => bail!("Tenant '{}' can only be reached with one of its credentials", requested),
+                _ => bail!("The credential belongs to tenant '{}', not '{}'", tenant, requested),
+            }
+        }
+        Ok(bound.filter(|tenant| *tenant != crate::DEFAULT_TENANT).map(str::to_string))
+    }
+
+    /// Watermarks a successful proof for `tenant` when watermarking is enabled.
+    pub fn watermark(&self, tenant: &str, response: &mut ProofResponse) -> Result<()> {
+        if let (Some(watermarker), Some(proof_id)) = (&self.watermarker, &response.proof_id) {
+            response.watermark = Some(watermarker.watermark(tenant, proof_id)?);
+        }
+        Ok(())
+    }
+}
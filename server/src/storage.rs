@@ -0,0 +1,133 @@
+use crate::s3::S3Client;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StorageBackend {
+    /// A directory on local (or mounted) disk
+    Local,
+    /// An S3-compatible bucket: AWS, MinIO, R2, or GCS through its XML API
+    S3,
+}
+
+/// Where artifacts that outlive a job are kept: the proof bundles saved by the TCP interface,
+/// their signature files and re-signing manifests. Enclave disk is gone with the enclave, so
+/// production deployments point this at an object store. Keys are flat file names.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Stores `body` under `key`, replacing what was there.
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<()>;
+
+    /// The object under `key`; None if there is none.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Keys starting with `prefix`, in no particular order.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Where `key` is stored, for logs and operator messages.
+    fn location(&self, key: &str) -> String;
+}
+
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        check_key(key)?;
+        fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        // Written aside and renamed, so a crash never leaves half an artifact
+        let path = self.dir.join(key);
+        let tmp = self.dir.join(format!(".{}.tmp", key));
+        fs::write(&tmp, body).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        check_key(key)?;
+        let path = self.dir.join(key);
+        match fs::read(&path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", self.dir.display())),
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(prefix) && entry.file_type()?.is_file() {
+                keys.push(name);
+            }
+        }
+        Ok(keys)
+    }
+
+    fn location(&self, key: &str) -> String {
+        self.dir.join(key).display().to_string()
+    }
+}
+
+pub struct ObjectStorage {
+    client: S3Client,
+    /// Prepended to every key, e.g. `artifacts/`
+    prefix: String,
+}
+
+impl ObjectStorage {
+    pub fn new(client: S3Client, prefix: String) -> Self {
+        Self { client, prefix }
+    }
+}
+
+#[async_trait]
+impl Storage for ObjectStorage {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        check_key(key)?;
+        self.client.put_object(&format!("{}{}", self.prefix, key), body).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        check_key(key)?;
+        self.client.get_object(&format!("{}{}", self.prefix, key)).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let keys = self.client.list_objects(&format!("{}{}", self.prefix, prefix)).await?;
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .filter(|key| !key.contains('/'))
+            .collect())
+    }
+
+    fn location(&self, key: &str) -> String {
+        format!("s3 key {}{}", self.prefix, key)
+    }
+}
+
+/// Keys name a single file, never a path.
+fn check_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.starts_with('.') || key.contains(['/', '\\']) {
+        bail!("Invalid artifact key {:?}", key);
+    }
+    Ok(())
+}
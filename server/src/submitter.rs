@@ -0,0 +1,260 @@
+use crate::config::ChainConfig;
+use crate::evm;
+use crate::http::RpcTransport;
+use crate::verifier::VerifyRequest;
+use anyhow::{anyhow, bail, Context, Result};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Middleware, Provider, RpcError};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Eip1559TransactionRequest, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// How long to wait for the verification transaction to be mined before returning only its hash
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+const GWEI: u64 = 1_000_000_000;
+
+/// Body of POST /submit: a proof plus the chain to verify it on.
+#[derive(Debug, Deserialize)]
+pub struct SubmitRequest {
+    #[serde(flatten)]
+    pub proof: VerifyRequest,
+    /// Name of a configured chain; may be omitted when only one is configured
+    #[serde(default)]
+    pub chain: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Submission {
+    pub chain: String,
+    pub tx_hash: String,
+    /// `None` if the transaction was not mined within the receipt timeout
+    pub receipt: Option<SubmissionReceipt>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubmissionReceipt {
+    pub block_number: Option<u64>,
+    pub gas_used: Option<String>,
+    /// Whether the verifier accepted the proof; it reverts on invalid proofs
+    pub success: bool,
+}
+
+/// Outcome of a dry run of the verifier call.
+#[derive(Debug, Serialize)]
+pub struct Simulation {
+    pub chain: String,
+    /// Whether the verifier returned true for this proof
+    pub would_pass: bool,
+    /// Gas a transaction would use; only estimated when the call passes
+    pub gas_estimate: Option<String>,
+    pub message: String,
+}
+
+/// Dry-runs `verify(bytes,bytes32[])` on `chain` with eth_call, so calldata or key mismatches
+/// show up before anyone pays for a transaction. Needs no key.
+pub async fn simulate(
+    http: &reqwest::Client,
+    name: &str,
+    chain: &ChainConfig,
+    from: Option<Address>,
+    proof: &[u8],
+    public_inputs: &[[u8; 32]],
+) -> Result<Simulation> {
+    let verifier = verifier_address(name, chain)?;
+    let provider = provider(http, name, chain)?;
+    let mut tx = Eip1559TransactionRequest::new()
+        .to(verifier)
+        .data(evm::verify_calldata(proof, public_inputs))
+        .chain_id(chain.chain_id);
+    if let Some(from) = from {
+        tx = tx.from(from);
+    }
+    let tx: TypedTransaction = tx.into();
+
+    let (would_pass, message) = match provider.call(&tx, None).await {
+        // verify returns a single ABI-encoded bool
+        Ok(output) if output.len() == 32 && output[31] == 1 && output[..31].iter().all(|b| *b == 0) => {
+            (true, "The verifier accepts this proof".to_string())
+        }
+        Ok(output) => (false, format!("The verifier returned 0x{} instead of true", hex::encode(&output))),
+        // A JSON-RPC error response is the node reporting the revert; anything else means the
+        // node could not be asked
+        Err(e) => match e.as_error_response() {
+            Some(error) => (false, format!("The verifier call reverted: {}", error.message)),
+            None => return Err(e).with_context(|| format!("Failed to simulate on chain '{}'", name)),
+        },
+    };
+    let gas_estimate = if would_pass {
+        Some(provider.estimate_gas(&tx, None).await.context("Failed to estimate gas")?.to_string())
+    } else {
+        None
+    };
+
+    Ok(Simulation {
+        chain: name.to_string(),
+        would_pass,
+        gas_estimate,
+        message,
+    })
+}
+
+/// Sends `verify(bytes,bytes32[])` transactions to deployed verifier contracts, so relying
+/// parties get an on-chain record without running their own relayer. The same key pays on
+/// every configured chain.
+pub struct Submitter {
+    wallet: LocalWallet,
+    // Transactions are sent one at a time so concurrent submissions never reuse a nonce
+    send_lock: Mutex<()>,
+}
+
+impl Submitter {
+    pub fn new(key_file: &Path) -> Result<Self> {
+        let key = fs::read_to_string(key_file)
+            .with_context(|| format!("Failed to read submitter key {}", key_file.display()))?;
+        let key = hex::decode(key.trim().trim_start_matches("0x")).context("Submitter key is not valid hex")?;
+        let wallet = LocalWallet::from_bytes(&key).context("Submitter key is not a valid secp256k1 key")?;
+
+        Ok(Self {
+            wallet,
+            send_lock: Mutex::new(()),
+        })
+    }
+
+    /// Address paying for submissions; it has to be funded on every target chain.
+    pub fn address(&self) -> Address {
+        self.wallet.address()
+    }
+
+    pub async fn submit(
+        &self,
+        http: &reqwest::Client,
+        name: &str,
+        chain: &ChainConfig,
+        proof: &[u8],
+        public_inputs: &[[u8; 32]],
+    ) -> Result<Submission> {
+        let verifier = verifier_address(name, chain)?;
+        self.transact(http, name, chain, verifier, evm::verify_calldata(proof, public_inputs), chain.gas_limit)
+            .await
+    }
+
+    /// Sends a transaction calling `to` with `data` and waits up to the receipt timeout for it
+    /// to be mined. Gas is estimated unless `gas_limit` is given.
+    pub async fn transact(
+        &self,
+        http: &reqwest::Client,
+        name: &str,
+        chain: &ChainConfig,
+        to: Address,
+        data: Vec<u8>,
+        gas_limit: Option<u64>,
+    ) -> Result<Submission> {
+        let provider = provider(http, name, chain)?;
+        // Signing with the configured chain id means a misconfigured RPC rejects the transaction
+        // instead of it landing on the wrong chain
+        let client = SignerMiddleware::new(provider, self.wallet.clone().with_chain_id(chain.chain_id));
+
+        let mut tx = Eip1559TransactionRequest::new().to(to).data(data).chain_id(chain.chain_id);
+        if let Some(gas_limit) = gas_limit {
+            tx = tx.gas(gas_limit);
+        }
+        let tx = with_fee_caps(tx, chain);
+
+        let pending = {
+            let _guard = self.send_lock.lock().await;
+            client
+                .send_transaction(tx, None)
+                .await
+                .with_context(|| format!("Failed to send transaction to {:?} on chain '{}'", to, name))?
+        };
+        let tx_hash = format!("{:?}", pending.tx_hash());
+
+        let receipt = match tokio::time::timeout(RECEIPT_TIMEOUT, pending).await {
+            Ok(receipt) => receipt.context("Failed to fetch transaction receipt")?,
+            Err(_) => None,
+        };
+        Ok(Submission {
+            chain: name.to_string(),
+            tx_hash,
+            receipt: receipt.map(|receipt| SubmissionReceipt {
+                block_number: receipt.block_number.map(|n| n.as_u64()),
+                gas_used: receipt.gas_used.map(|g| g.to_string()),
+                success: receipt.status.map(|s| s.as_u64() == 1).unwrap_or(false),
+            }),
+        })
+    }
+
+    /// Deploys a contract from its creation bytecode and returns its address and the
+    /// transaction hash once it is mined.
+    pub async fn deploy(&self, http: &reqwest::Client, name: &str, chain: &ChainConfig, bytecode: Vec<u8>) -> Result<(Address, String)> {
+        let provider = provider(http, name, chain)?;
+        let client = SignerMiddleware::new(provider, self.wallet.clone().with_chain_id(chain.chain_id));
+        // The chain's gas limit is sized for verify calls, so deployment gas is always estimated
+        let tx = with_fee_caps(Eip1559TransactionRequest::new().data(bytecode).chain_id(chain.chain_id), chain);
+
+        let pending = {
+            let _guard = self.send_lock.lock().await;
+            client
+                .send_transaction(tx, None)
+                .await
+                .with_context(|| format!("Failed to send deployment transaction on chain '{}'", name))?
+        };
+        let tx_hash = format!("{:?}", pending.tx_hash());
+
+        let receipt = tokio::time::timeout(RECEIPT_TIMEOUT, pending)
+            .await
+            .map_err(|_| anyhow!("Deployment {} was not mined within {}s", tx_hash, RECEIPT_TIMEOUT.as_secs()))?
+            .context("Failed to fetch transaction receipt")?
+            .with_context(|| format!("Deployment {} was dropped from the mempool", tx_hash))?;
+        if receipt.status.map(|s| s.as_u64()) != Some(1) {
+            bail!("Deployment {} reverted", tx_hash);
+        }
+        let address = receipt
+            .contract_address
+            .with_context(|| format!("Receipt of {} has no contract address", tx_hash))?;
+        Ok((address, tx_hash))
+    }
+}
+
+fn with_fee_caps(mut tx: Eip1559TransactionRequest, chain: &ChainConfig) -> Eip1559TransactionRequest {
+    if let Some(max_fee) = chain.max_fee_per_gas_gwei {
+        tx = tx.max_fee_per_gas(U256::from(max_fee) * GWEI);
+    }
+    if let Some(priority_fee) = chain.max_priority_fee_per_gas_gwei {
+        tx = tx.max_priority_fee_per_gas(U256::from(priority_fee) * GWEI);
+    }
+    tx
+}
+
+pub fn verifier_address(name: &str, chain: &ChainConfig) -> Result<Address> {
+    if chain.verifier_address.is_empty() {
+        bail!("No verifier is deployed on chain '{}'", name);
+    }
+    chain
+        .verifier_address
+        .parse()
+        .with_context(|| format!("Invalid verifier address for chain '{}'", name))
+}
+
+pub fn provider(http: &reqwest::Client, name: &str, chain: &ChainConfig) -> Result<Provider<RpcTransport>> {
+    let transport = RpcTransport::new(http.clone(), &chain.rpc_url).with_context(|| format!("Invalid RPC URL for chain '{}'", name))?;
+    Ok(Provider::new(transport))
+}
+
+/// Reads a chains table (JSON object of chain name to `ChainConfig`), checking every address.
+pub fn load_chains(path: &Path) -> Result<BTreeMap<String, ChainConfig>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let chains: BTreeMap<String, ChainConfig> =
+        serde_json::from_str(&content).with_context(|| format!("{} is not a valid chains table", path.display()))?;
+    for (name, chain) in chains.iter().filter(|(_, chain)| !chain.verifier_address.is_empty()) {
+        verifier_address(name, chain)?;
+    }
+    Ok(chains)
+}
@@ -0,0 +1,136 @@
+use anyhow::{bail, Context, Result};
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// First file descriptor systemd passes (SD_LISTEN_FDS_START).
+const LISTEN_FDS_START: RawFd = 3;
+
+/// `FileDescriptorName=`s that pick a listener; sockets named otherwise are handed out in
+/// order (TCP interface, HTTP API, admin API).
+pub const LISTENER_NAMES: [&str; 3] = ["tcp", "http", "admin"];
+
+/// Listening sockets inherited through systemd socket activation.
+#[derive(Default)]
+pub struct Activation {
+    listeners: Vec<(String, std::net::TcpListener)>,
+}
+
+impl Activation {
+    /// Takes the sockets systemd passed (LISTEN_PID, LISTEN_FDS, LISTEN_FDNAMES) and clears
+    /// those variables so child processes do not take them too. Empty when the server was not
+    /// socket activated.
+    pub fn from_env() -> Result<Self> {
+        let pid = std::env::var("LISTEN_PID").ok();
+        let count = std::env::var("LISTEN_FDS").ok();
+        let names = std::env::var("LISTEN_FDNAMES").ok();
+        for variable in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+            std::env::remove_var(variable);
+        }
+        let (Some(pid), Some(count)) = (pid, count) else {
+            return Ok(Self::default());
+        };
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return Ok(Self::default());
+        }
+        let count: RawFd = count.parse().context("LISTEN_FDS is not a number")?;
+        let names: Vec<&str> = names.as_deref().map(|names| names.split(':').collect()).unwrap_or_default();
+
+        let mut listeners = Vec::new();
+        for (index, fd) in (LISTEN_FDS_START..LISTEN_FDS_START + count).enumerate() {
+            // SAFETY: systemd passes these descriptors to this process alone, and each is
+            // wrapped exactly once
+            let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+            listener
+                .local_addr()
+                .with_context(|| format!("Inherited file descriptor {} is not a TCP listening socket", fd))?;
+            listener.set_nonblocking(true)?;
+            // SAFETY: fcntl on a descriptor this process owns
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+                bail!("Failed to set close-on-exec on file descriptor {}", fd);
+            }
+            listeners.push((names.get(index).copied().unwrap_or_default().to_string(), listener));
+        }
+        Ok(Self { listeners })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    /// The socket named `name`, or else the first one not named after any listener.
+    pub fn take(&mut self, name: &str) -> Option<tokio::net::TcpListener> {
+        let index = self
+            .listeners
+            .iter()
+            .position(|(socket, _)| socket == name)
+            .or_else(|| self.listeners.iter().position(|(socket, _)| !LISTENER_NAMES.contains(&socket.as_str())))?;
+        let (_, listener) = self.listeners.remove(index);
+        match tokio::net::TcpListener::from_std(listener) {
+            Ok(listener) => Some(listener),
+            Err(e) => {
+                warn!("Inherited socket for {} is unusable: {}", name, e);
+                None
+            }
+        }
+    }
+
+    /// The inherited socket for `name`, or a new one bound to `addr`.
+    pub async fn listener(&mut self, name: &str, addr: std::net::SocketAddr) -> Result<tokio::net::TcpListener> {
+        match self.take(name) {
+            Some(listener) => Ok(listener),
+            None => tokio::net::TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Failed to listen on {}", addr)),
+        }
+    }
+}
+
+/// Sends `state` (e.g. `READY=1`) to the service manager; a no-op unless NOTIFY_SOCKET is set.
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let path = path.to_string_lossy();
+    let result = (|| {
+        let address = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(path.as_ref())?,
+        };
+        UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address)
+    })();
+    match result {
+        Ok(_) => debug!("Notified the service manager: {}", state.replace('\n', " ")),
+        Err(e) => warn!("Failed to notify the service manager at {}: {}", path, e),
+    }
+}
+
+/// `RELOADING=1` with the timestamp systemd's Type=notify-reload expects.
+pub fn notify_reloading() {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: clock_gettime only writes to `now`
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let micros = now.tv_sec as u64 * 1_000_000 + now.tv_nsec as u64 / 1_000;
+    notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", micros));
+}
+
+/// Interval at which the service manager expects `WATCHDOG=1`, when WatchdogSec= is set.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Pings the watchdog at half its interval for as long as the runtime keeps scheduling tasks.
+pub async fn run_watchdog(interval: Duration) {
+    loop {
+        notify("WATCHDOG=1");
+        tokio::time::sleep(interval / 2).await;
+    }
+}
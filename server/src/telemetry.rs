@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// W3C trace context header carrying the caller's trace and span.
+pub const TRACEPARENT: &str = "traceparent";
+
+/// How often buffered spans are sent to the collector.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Spans kept while the collector is unreachable; newer ones are dropped beyond this.
+const MAX_BUFFERED_SPANS: usize = 10_000;
+
+/// Finished spans waiting to be exported (or, in a prover worker, handed to the parent). Unset
+/// when tracing is off, which makes every span a no-op.
+static FINISHED: OnceLock<Mutex<Vec<SpanData>>> = OnceLock::new();
+
+/// The trace and span a new span is parented to.
+#[derive(Debug, Clone, Copy)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+}
+
+impl TraceContext {
+    /// Parses a `traceparent` value (`00-<trace id>-<span id>-<flags>`).
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let (version, trace_id, span_id) = (parts.next()?, parts.next()?, parts.next()?);
+        if version != "00" || parts.next().is_none() {
+            return None;
+        }
+        let trace_id: [u8; 16] = hex::decode(trace_id).ok()?.try_into().ok()?;
+        let span_id: [u8; 8] = hex::decode(span_id).ok()?.try_into().ok()?;
+        // All-zero IDs are invalid per the spec
+        (trace_id != [0; 16] && span_id != [0; 8]).then_some(Self { trace_id, span_id })
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", hex::encode(self.trace_id), hex::encode(self.span_id))
+    }
+}
+
+/// A span in OTLP/JSON form, ready to export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanData {
+    trace_id: String,
+    span_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_span_id: Option<String>,
+    name: String,
+    kind: u8,
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<Attribute>,
+    status: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attribute {
+    key: String,
+    value: AnyValue,
+}
+
+/// OTLP `AnyValue`, serialized as e.g. `{"stringValue": ".."}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AnyValue {
+    #[serde(rename = "stringValue")]
+    String(String),
+    #[serde(rename = "intValue")]
+    Int(i64),
+    #[serde(rename = "boolValue")]
+    Bool(bool),
+}
+
+impl From<&str> for AnyValue {
+    fn from(value: &str) -> Self {
+        AnyValue::String(value.to_string())
+    }
+}
+
+impl From<String> for AnyValue {
+    fn from(value: String) -> Self {
+        AnyValue::String(value)
+    }
+}
+
+impl From<i64> for AnyValue {
+    fn from(value: i64) -> Self {
+        AnyValue::Int(value)
+    }
+}
+
+impl From<bool> for AnyValue {
+    fn from(value: bool) -> Self {
+        AnyValue::Bool(value)
+    }
+}
+
+/// OTLP span kinds used here.
+const KIND_INTERNAL: u8 = 1;
+const KIND_SERVER: u8 = 2;
+
+/// A timed operation. It is recorded when dropped, so early returns still close it. Never
+/// attach private inputs as attributes.
+pub struct Span {
+    context: TraceContext,
+    parent: Option<[u8; 8]>,
+    name: String,
+    kind: u8,
+    start: u128,
+    attributes: Vec<Attribute>,
+    error: Option<String>,
+}
+
+impl Span {
+    /// An internal operation, continuing `parent`'s trace or starting a new one.
+    pub fn start(name: &str, parent: Option<TraceContext>) -> Self {
+        Self::new(name, KIND_INTERNAL, parent)
+    }
+
+    /// The server side of a request from a client, which may have sent its trace context.
+    pub fn server(name: &str, parent: Option<TraceContext>) -> Self {
+        Self::new(name, KIND_SERVER, parent)
+    }
+
+    fn new(name: &str, kind: u8, parent: Option<TraceContext>) -> Self {
+        Self {
+            context: TraceContext {
+                trace_id: parent.map_or_else(rand::random, |parent| parent.trace_id),
+                span_id: rand::random(),
+            },
+            parent: parent.map(|parent| parent.span_id),
+            name: name.to_string(),
+            kind,
+            start: now_nanos(),
+            attributes: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn child(&self, name: &str) -> Span {
+        Span::start(name, Some(self.context))
+    }
+
+    pub fn context(&self) -> TraceContext {
+        self.context
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<AnyValue>) {
+        self.attributes.push(Attribute {
+            key: key.to_string(),
+            value: value.into(),
+        });
+    }
+
+    /// Marks the span as failed.
+    pub fn fail(&mut self, message: impl Into<String>) {
+        self.error = Some(message.into());
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(finished) = FINISHED.get() else { return };
+        let status = match self.error.take() {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 1 }),
+        };
+        let span = SpanData {
+            trace_id: hex::encode(self.context.trace_id),
+            span_id: hex::encode(self.context.span_id),
+            parent_span_id: self.parent.map(hex::encode),
+            name: std::mem::take(&mut self.name),
+            kind: self.kind,
+            start_time_unix_nano: self.start.to_string(),
+            end_time_unix_nano: now_nanos().to_string(),
+            attributes: std::mem::take(&mut self.attributes),
+            status,
+        };
+        let mut finished = finished.lock().unwrap();
+        if finished.len() < MAX_BUFFERED_SPANS {
+            finished.push(span);
+        }
+    }
+}
+
+/// Whether spans are being recorded.
+pub fn enabled() -> bool {
+    FINISHED.get().is_some()
+}
+
+/// Turns tracing on and sends spans to the OTLP/HTTP collector at `endpoint` (e.g.
+/// `http://collector:4318`) every few seconds.
+pub fn init_export(http: reqwest::Client, endpoint: &str, service_name: String) {
+    if FINISHED.set(Mutex::new(Vec::new())).is_err() {
+        return;
+    }
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(EXPORT_INTERVAL).await;
+            let spans = take_finished();
+            if spans.is_empty() {
+                continue;
+            }
+            let body = json!({
+                "resourceSpans": [{
+                    "resource": { "attributes": [{ "key": "service.name", "value": { "stringValue": service_name } }] },
+                    "scopeSpans": [{
+                        "scope": { "name": "zk-insurance-server", "version": env!("CARGO_PKG_VERSION") },
+                        "spans": spans,
+                    }],
+                }],
+            });
+            match http.post(&url).json(&body).send().await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => warn!("OTLP export of {} spans rejected: {}", spans.len(), response.status()),
+                Err(e) => warn!("OTLP export of {} spans failed: {}", spans.len(), e),
+            }
+        }
+    });
+}
+
+/// Turns tracing on without exporting; the spans are collected with `take_finished`. Used by
+/// prover workers, which hand their spans to the parent process.
+pub fn init_collect() {
+    let _ = FINISHED.set(Mutex::new(Vec::new()));
+}
+
+/// Queues spans recorded elsewhere, such as in a prover worker, for export.
+pub fn submit(spans: Vec<SpanData>) {
+    if let Some(finished) = FINISHED.get() {
+        let mut finished = finished.lock().unwrap();
+        let room = MAX_BUFFERED_SPANS.saturating_sub(finished.len());
+        finished.extend(spans.into_iter().take(room));
+    }
+}
+
+pub fn take_finished() -> Vec<SpanData> {
+    FINISHED.get().map(|finished| std::mem::take(&mut *finished.lock().unwrap())).unwrap_or_default()
+}
+
+fn now_nanos() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos()
+}
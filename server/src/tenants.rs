@@ -0,0 +1,70 @@
+use crate::apikeys::ApiKey;
+use crate::encryption;
+use crate::policy::Bounds;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// What one insurance partner sharing the deployment may prove. Requests are tied to a tenant by
+/// the API key they carry (see `ApiKey::tenant`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tenant {
+    /// Registered circuits (`circuit_id`) the tenant may prove with; all of them when absent.
+    /// The eligibility circuit is always available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuits: Option<BTreeSet<String>>,
+    /// Policies the tenant may request; all of them when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policies: Option<BTreeSet<String>>,
+    /// Ranges proven for the tenant's requests without a policy, instead of the server's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_bounds: Option<Bounds>,
+}
+
+impl Tenant {
+    /// Rejects a request for a circuit or policy outside the tenant's sets.
+    pub fn check(&self, circuit_id: Option<&str>, policy: Option<&str>) -> Result<(), String> {
+        if let (Some(circuits), Some(circuit_id)) = (&self.circuits, circuit_id) {
+            if !circuits.contains(circuit_id) {
+                return Err(format!("Circuit '{}' is not available to this tenant", circuit_id));
+            }
+        }
+        if let (Some(policies), Some(policy)) = (&self.policies, policy) {
+            if !policies.contains(policy) {
+                return Err(format!("Policy '{}' is not available to this tenant", policy));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads the tenants table: a JSON object mapping each tenant ID to its settings.
+pub fn load(path: &Path) -> Result<BTreeMap<String, Tenant>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let tenants: BTreeMap<String, Tenant> =
+        serde_json::from_str(&content).with_context(|| format!("{} is not a valid tenants table", path.display()))?;
+    for (id, tenant) in &tenants {
+        encryption::validate_tenant_id(id)?;
+        // The default tenant is whoever comes without a tenant, so it cannot be kept to keys
+        if id == crate::DEFAULT_TENANT {
+            bail!("'{}' in {} is the tenant of callers without one and cannot be configured", id, path.display());
+        }
+        if let Some(bounds) = &tenant.default_bounds {
+            bounds.check().with_context(|| format!("Invalid default_bounds of tenant '{}'", id))?;
+        }
+    }
+    Ok(tenants)
+}
+
+/// Fails if an API key names a tenant the table does not have.
+pub fn check_keys(tenants: &BTreeMap<String, Tenant>, api_keys: &BTreeMap<String, ApiKey>) -> Result<()> {
+    for (name, key) in api_keys {
+        if let Some(tenant) = key.tenant.as_deref().filter(|tenant| !tenants.contains_key(*tenant)) {
+            bail!("API key '{}' belongs to tenant '{}', which is not in --tenants-file", name, tenant);
+        }
+    }
+    Ok(())
+}
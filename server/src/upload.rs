@@ -0,0 +1,161 @@
+use crate::abi::{CircuitAbi, Inputs};
+use crate::limits::ResourceLimits;
+use crate::prover;
+use crate::registry;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+/// Largest package accepted, summed over its files.
+const MAX_PACKAGE_BYTES: usize = 1024 * 1024;
+
+/// Most files a package may contain.
+const MAX_PACKAGE_FILES: usize = 256;
+
+/// A Noir package as uploaded through POST /admin/circuits.
+#[derive(Deserialize)]
+pub struct CircuitPackage {
+    pub circuit_id: String,
+    /// File contents by path: Nargo.toml and the sources under src/
+    pub files: BTreeMap<String, String>,
+    /// Inputs the server fixes for every request, as in --circuits-file
+    #[serde(default)]
+    pub parameters: Inputs,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Compiles uploaded packages into --circuit-upload-dir. Each package is written to a fresh
+/// staging directory and `nargo compile` runs there with a cleared environment, resource limits
+/// and a deadline. Packages may not declare dependencies, so the compiler never fetches code or
+/// reads outside the package.
+pub struct CircuitCompiler {
+    dir: PathBuf,
+    limits: ResourceLimits,
+    timeout: Duration,
+    /// One compilation at a time; they are heavy and two uploads of one ID must not interleave
+    busy: Mutex<()>,
+}
+
+impl CircuitCompiler {
+    pub fn new(dir: PathBuf, limits: ResourceLimits, timeout: Duration) -> Self {
+        Self {
+            dir,
+            limits,
+            timeout,
+            busy: Mutex::new(()),
+        }
+    }
+
+    /// Compiles `package` and moves it to its final directory, which is returned. Problems with
+    /// the package itself, compile errors included, come back as the inner error, for the client.
+    pub async fn compile(&self, package: &CircuitPackage) -> Result<std::result::Result<PathBuf, String>> {
+        if let Err(e) = registry::check_id(&package.circuit_id) {
+            return Ok(Err(e.to_string()));
+        }
+        if let Err(e) = check_package(&package.files) {
+            return Ok(Err(e));
+        }
+        let _busy = self.busy.lock().await;
+        let target = self.dir.join(&package.circuit_id);
+        if target.exists() {
+            return Ok(Err(format!("{} already exists", target.display())));
+        }
+
+        fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let staging = Staging(self.dir.join(format!(".staging-{}", uuid::Uuid::new_v4().simple())));
+        for (name, content) in &package.files {
+            let path = staging.0.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, content)?;
+        }
+
+        let mut command = Command::new("nargo");
+        command
+            .arg("compile")
+            .current_dir(&staging.0)
+            .env_clear()
+            .envs(std::env::var_os("PATH").map(|path| ("PATH", path)))
+            .envs(std::env::var_os("HOME").map(|home| ("HOME", home)))
+            .kill_on_drop(true);
+        self.limits.apply(&mut command);
+        let output = match tokio::time::timeout(self.timeout, command.output()).await {
+            Ok(output) => output.context("Failed to run nargo compile")?,
+            Err(_) => return Ok(Err(format!("nargo compile did not finish within {}s", self.timeout.as_secs()))),
+        };
+        if !output.status.success() {
+            let detail = if self.limits.exceeded(&output) { " (resource limits exceeded)" } else { "" };
+            return Ok(Err(format!(
+                "nargo compile failed{}: {}",
+                detail,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let abi = match CircuitAbi::load(&staging.0.join(prover::compiled_circuit(&staging.0))) {
+            Ok(abi) => abi,
+            Err(e) => return Ok(Err(format!("{:#}", e))),
+        };
+        if let Err(e) = abi.check_values(&package.parameters) {
+            return Ok(Err(format!("parameters: {}", e)));
+        }
+        // Once moved, the staging directory is gone and dropping it removes nothing
+        fs::rename(&staging.0, &target).with_context(|| format!("Failed to move the package to {}", target.display()))?;
+        Ok(Ok(fs::canonicalize(&target)?))
+    }
+}
+
+/// Removes a staging directory that did not make it into place.
+struct Staging(PathBuf);
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Accepts a Nargo.toml for a binary package without dependencies, plus .nr sources under src/.
+fn check_package(files: &BTreeMap<String, String>) -> std::result::Result<(), String> {
+    if files.len() > MAX_PACKAGE_FILES {
+        return Err(format!("A package may contain at most {} files", MAX_PACKAGE_FILES));
+    }
+    if files.values().map(String::len).sum::<usize>() > MAX_PACKAGE_BYTES {
+        return Err(format!("A package may be at most {} bytes", MAX_PACKAGE_BYTES));
+    }
+    for name in files.keys() {
+        let path = Path::new(name);
+        let plain = path.components().all(|component| matches!(component, Component::Normal(_)));
+        let allowed = name == "Nargo.toml" || (path.starts_with("src") && path.extension().is_some_and(|extension| extension == "nr"));
+        if !plain || !allowed {
+            return Err(format!("Unexpected file {:?}: only Nargo.toml and src/**/*.nr are accepted", name));
+        }
+    }
+    if !files.contains_key("src/main.nr") {
+        return Err("The package has no src/main.nr".to_string());
+    }
+    let manifest: toml::Table = files
+        .get("Nargo.toml")
+        .ok_or("The package has no Nargo.toml")?
+        .parse()
+        .map_err(|e| format!("Nargo.toml is not valid TOML: {}", e))?;
+    let package = manifest.get("package").and_then(|package| package.as_table()).ok_or("Nargo.toml has no [package]")?;
+    if package.get("type").and_then(|kind| kind.as_str()) != Some("bin") {
+        return Err("Only binary packages (type = \"bin\") can be proven".to_string());
+    }
+    if package.get("name").and_then(|name| name.as_str()).is_none_or(|name| name.contains(['/', '.'])) {
+        return Err("Nargo.toml needs a plain package name".to_string());
+    }
+    if manifest.get("dependencies").and_then(|dependencies| dependencies.as_table()).is_some_and(|dependencies| !dependencies.is_empty())
+        || manifest.contains_key("workspace")
+    {
+        return Err("Uploaded packages cannot have dependencies or workspaces".to_string());
+    }
+    Ok(())
+}
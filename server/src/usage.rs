@@ -0,0 +1,232 @@
+use crate::config::SharedConfig;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// A quota of an API key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quota {
+    /// Proofs per UTC day (`proofs_per_day`)
+    ProofsPerDay,
+    /// Proof jobs queued or running at once (`max_concurrent`)
+    ConcurrentJobs,
+}
+
+/// Why a request was turned away, as sent in the 429 body.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaExceeded {
+    pub quota: Quota,
+    pub limit: u64,
+    /// Proofs charged today (including jobs still running), or jobs running, when the request
+    /// came in
+    pub used: u64,
+    /// Proofs the request asked for
+    pub requested: u64,
+    #[serde(skip)]
+    pub retry_after_secs: u64,
+}
+
+impl QuotaExceeded {
+    pub fn message(&self) -> String {
+        match self.quota {
+            Quota::ProofsPerDay => format!(
+                "Daily quota of {} proofs exceeded ({} used); it resets at 00:00 UTC",
+                self.limit, self.used
+            ),
+            Quota::ConcurrentJobs => format!(
+                "Quota of {} concurrent proof jobs exceeded ({} running)",
+                self.limit, self.used
+            ),
+        }
+    }
+}
+
+/// One key's usage, as served by GET /admin/usage.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyUsage {
+    /// UTC day the `_today` counters are for
+    pub day: NaiveDate,
+    /// Proofs charged: jobs that finished, unless they were turned away as server_busy
+    pub proofs_today: u64,
+    /// Charged proofs that succeeded
+    pub succeeded_today: u64,
+    /// Requests turned away for being over a quota
+    pub rejected_today: u64,
+    pub proofs_total: u64,
+    pub succeeded_total: u64,
+    /// Proof jobs queued or running now
+    pub active_jobs: u64,
+    /// The key's limits; 0 means none
+    pub proofs_per_day: u64,
+    pub max_concurrent: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    /// When counting started; counters are kept in memory and start over on a restart
+    pub since: DateTime<Utc>,
+    pub keys: BTreeMap<String, KeyUsage>,
+}
+
+#[derive(Default)]
+struct Counters {
+    day: NaiveDate,
+    proofs_today: u64,
+    succeeded_today: u64,
+    rejected_today: u64,
+    proofs_total: u64,
+    succeeded_total: u64,
+    active: u64,
+}
+
+impl Counters {
+    fn roll(&mut self, today: NaiveDate) {
+        if self.day != today {
+            self.day = today;
+            self.proofs_today = 0;
+            self.succeeded_today = 0;
+            self.rejected_today = 0;
+        }
+    }
+}
+
+/// Meters proofs per API key and enforces each key's quotas. Limits are read from the runtime
+/// configuration on every call, so a reload applies to keys already in use. A request is
+/// admitted with a permit per proof; the proof is charged when the permit is settled with the
+/// job's outcome, and a permit dropped unsettled (the job never ran) costs nothing.
+pub struct UsageMeter {
+    config: SharedConfig,
+    since: DateTime<Utc>,
+    keys: Mutex<HashMap<String, Counters>>,
+}
+
+/// A proof admitted under an API key's quotas; counts as a running job until dropped.
+pub struct UsagePermit {
+    meter: Arc<UsageMeter>,
+    key: String,
+    /// Whether the proof succeeded, once the job has finished
+    outcome: Option<bool>,
+}
+
+impl UsagePermit {
+    /// Charges the proof to the key when the permit is dropped.
+    pub fn settle(&mut self, succeeded: bool) {
+        self.outcome = Some(succeeded);
+    }
+}
+
+impl Drop for UsagePermit {
+    fn drop(&mut self) {
+        self.meter.release(&self.key, self.outcome);
+    }
+}
+
+impl UsageMeter {
+    pub fn new(config: SharedConfig) -> Self {
+        Self {
+            config,
+            since: Utc::now(),
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Admits `proofs` proofs for `key`, returning a permit for each, or the quota they would
+    /// exceed. Requests over a quota are turned away whole.
+    pub fn admit(self: &Arc<Self>, key: &str, proofs: usize) -> Result<Vec<UsagePermit>, QuotaExceeded> {
+        let (proofs_per_day, max_concurrent, retry_after_secs) = {
+            let config = self.config.read().unwrap();
+            let limits = config.api_keys.get(key);
+            (
+                limits.map_or(0, |limits| limits.proofs_per_day),
+                limits.map_or(0, |limits| limits.max_concurrent),
+                config.retry_after_secs,
+            )
+        };
+        let now = Utc::now();
+        let requested = proofs as u64;
+        let mut keys = self.keys.lock().unwrap();
+        let counters = keys.entry(key.to_string()).or_default();
+        counters.roll(now.date_naive());
+        let exceeded = if max_concurrent > 0 && counters.active + requested > max_concurrent {
+            Some(QuotaExceeded {
+                quota: Quota::ConcurrentJobs,
+                limit: max_concurrent,
+                used: counters.active,
+                requested,
+                retry_after_secs,
+            })
+        } else if proofs_per_day > 0 && counters.proofs_today + counters.active + requested > proofs_per_day {
+            let midnight = (now.date_naive() + chrono::Days::new(1)).and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+            Some(QuotaExceeded {
+                quota: Quota::ProofsPerDay,
+                limit: proofs_per_day,
+                used: counters.proofs_today + counters.active,
+                requested,
+                retry_after_secs: (midnight - now).num_seconds().max(1) as u64,
+            })
+        } else {
+            None
+        };
+        if let Some(exceeded) = exceeded {
+            counters.rejected_today += 1;
+            return Err(exceeded);
+        }
+        counters.active += requested;
+        Ok((0..proofs)
+            .map(|_| UsagePermit {
+                meter: self.clone(),
+                key: key.to_string(),
+                outcome: None,
+            })
+            .collect())
+    }
+
+    fn release(&self, key: &str, outcome: Option<bool>) {
+        let mut keys = self.keys.lock().unwrap();
+        let Some(counters) = keys.get_mut(key) else {
+            return;
+        };
+        counters.roll(Utc::now().date_naive());
+        counters.active = counters.active.saturating_sub(1);
+        if let Some(succeeded) = outcome {
+            counters.proofs_today += 1;
+            counters.proofs_total += 1;
+            if succeeded {
+                counters.succeeded_today += 1;
+                counters.succeeded_total += 1;
+            }
+        }
+    }
+
+    /// Usage of every configured key and of keys since removed that were used.
+    pub fn report(&self) -> UsageReport {
+        let today = Utc::now().date_naive();
+        let config = self.config.read().unwrap();
+        let mut keys = self.keys.lock().unwrap();
+        for name in config.api_keys.keys() {
+            keys.entry(name.clone()).or_default();
+        }
+        let keys = keys
+            .iter_mut()
+            .map(|(name, counters)| {
+                counters.roll(today);
+                let limits = config.api_keys.get(name);
+                let usage = KeyUsage {
+                    day: counters.day,
+                    proofs_today: counters.proofs_today,
+                    succeeded_today: counters.succeeded_today,
+                    rejected_today: counters.rejected_today,
+                    proofs_total: counters.proofs_total,
+                    succeeded_total: counters.succeeded_total,
+                    active_jobs: counters.active,
+                    proofs_per_day: limits.map_or(0, |limits| limits.proofs_per_day),
+                    max_concurrent: limits.map_or(0, |limits| limits.max_concurrent),
+                };
+                (name.clone(), usage)
+            })
+            .collect();
+        UsageReport { since: self.since, keys }
+    }
+}
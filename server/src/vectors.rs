@@ -0,0 +1,156 @@
+use crate::encryption;
+use crate::export;
+use crate::metrics::MetricsSnapshot;
+use crate::prover::{ErrorCode, ProofRequest, ProvingScheme, DEFAULT_PROOF_VALIDITY_SECS, MAX_AGE, MAX_BMI, MIN_AGE, MIN_BMI};
+use crate::signing::{self, EnclaveSigner};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+/// Published secp256k1 test key. Never use it for anything but these vectors.
+const TEST_SIGNING_KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const TEST_MASTER_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+const TEST_DATA_KEY: &str = "202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f";
+
+/// Bumped whenever the layout or meaning of a vector changes
+const FORMAT_VERSION: u32 = 3;
+
+#[derive(Serialize)]
+struct RequestVector {
+    description: &'static str,
+    request: ProofRequest,
+    expected: ExpectedResult,
+}
+
+#[derive(Serialize)]
+struct ExpectedResult {
+    success: bool,
+    error_code: Option<ErrorCode>,
+    /// Public inputs of a successful proof, as 32-byte big-endian field elements, up to the
+    /// expiry, which depends on when the proof is made
+    public_inputs: Option<Vec<String>>,
+    /// The last public input of a successful proof is its expiry: the unix time it was made
+    /// plus this many seconds, with the default --proof-validity-secs
+    expires_after_secs: Option<u64>,
+}
+
+/// Writes the conformance vectors to `out`. Everything is derived from fixed inputs and known
+/// keys so third-party SDKs can check their encoding, signature and decryption code against it;
+/// AES-GCM nonces are random, so sealed data differs between runs but always opens the same way.
+pub fn generate(out: &Path) -> Result<()> {
+    let mut files = Vec::new();
+
+    let cases: [(&str, &str, u32, u32, Option<ProvingScheme>); 8] = [
+        ("eligible_typical", "Applicant well inside both ranges", 20, 220, None),
+        ("eligible_lower_bounds", "Both values on the inclusive lower bound", MIN_AGE, MIN_BMI, None),
+        ("eligible_upper_bounds", "Both values on the inclusive upper bound", MAX_AGE, MAX_BMI, None),
+        ("eligible_ultra_plonk", "Scheme override on an eligible request", 20, 220, Some(ProvingScheme::UltraPlonk)),
+        ("ineligible_age_low", "Age one below the minimum", MIN_AGE - 1, 220, None),
+        ("ineligible_age_high", "Age one above the maximum", MAX_AGE + 1, 220, None),
+        ("ineligible_bmi_low", "BMI one below the minimum", 20, MIN_BMI - 1, None),
+        ("ineligible_bmi_high", "BMI one above the maximum", 20, MAX_BMI + 1, None),
+    ];
+    for (name, description, age, bmi_multiplied, scheme) in cases {
+        let request = ProofRequest { age, bmi_multiplied, scheme, ..Default::default() };
+        let eligible = request.is_eligible();
+        let vector = RequestVector {
+            description,
+            expected: ExpectedResult {
+                success: eligible,
+                error_code: (!eligible).then_some(ErrorCode::Ineligible),
+                // The bounds, then the nonce, 0 for requests without one
+                public_inputs: eligible.then(|| [MIN_AGE, MAX_AGE, MIN_BMI, MAX_BMI, 0].map(field_hex).to_vec()),
+                expires_after_secs: eligible.then_some(DEFAULT_PROOF_VALIDITY_SECS),
+            },
+            request,
+        };
+        files.push(write_json(out, &format!("requests/{}.json", name), &vector)?);
+    }
+
+    let signer = EnclaveSigner::from_bytes(&decode_key(TEST_SIGNING_KEY)?)?;
+    let message = b"zk-insurance conformance vector";
+    files.push(write_json(
+        out,
+        "signatures/eip191.json",
+        &json!({
+            "private_key": format!("0x{}", TEST_SIGNING_KEY),
+            "address": signer.address(),
+            "message_utf8": String::from_utf8_lossy(message),
+            "eip191_hash": format!("0x{}", hex::encode(signing::eip191_hash(message))),
+            "signature": signer.sign_message(message)?,
+        }),
+    )?);
+
+    let snapshot = export::signed_snapshot(
+        &signer,
+        "2024-01-01T00:00:00+00:00",
+        MetricsSnapshot {
+            proofs_requested: 10,
+            proofs_generated: 7,
+            ineligible: 2,
+            backend_errors: 1,
+            backend_unavailable: 0,
+            budget_exceeded: 0,
+            invalid_requests: 0,
+            server_busy: 0,
+            rate_limited: 0,
+        },
+    )?;
+    let path = "snapshots/signed_snapshot.json";
+    write_file(out, path, &snapshot)?;
+    files.push(path.to_string());
+
+    let tenant = "conformance";
+    let plaintext = b"0xdeadbeef";
+    let (wrapped_key, sealed) =
+        encryption::envelope_vector(&decode_key(TEST_MASTER_KEY)?, &decode_key(TEST_DATA_KEY)?, tenant, plaintext)?;
+    files.push(write_json(
+        out,
+        "encryption/envelope.json",
+        &json!({
+            "algorithm": "AES-256-GCM, nonce (12 bytes) || ciphertext || tag, tenant id as AAD",
+            "tenant": tenant,
+            "master_key": TEST_MASTER_KEY,
+            "data_key": TEST_DATA_KEY,
+            "wrapped_data_key": hex::encode(wrapped_key),
+            "sealed_bundle": hex::encode(sealed),
+            "plaintext_utf8": String::from_utf8_lossy(plaintext),
+        }),
+    )?);
+
+    write_json(
+        out,
+        "manifest.json",
+        &json!({
+            "format_version": FORMAT_VERSION,
+            "server_version": env!("CARGO_PKG_VERSION"),
+            "files": files,
+        }),
+    )?;
+    Ok(())
+}
+
+fn field_hex(value: u32) -> String {
+    format!("0x{:064x}", value)
+}
+
+fn decode_key(key: &str) -> Result<[u8; 32]> {
+    hex::decode(key)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Test key must be 32 bytes"))
+}
+
+fn write_json(out: &Path, relative: &str, value: &impl Serialize) -> Result<String> {
+    write_file(out, relative, &serde_json::to_vec_pretty(value)?)?;
+    Ok(relative.to_string())
+}
+
+fn write_file(out: &Path, relative: &str, contents: &[u8]) -> Result<()> {
+    let path = out.join(relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
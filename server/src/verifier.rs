@@ -0,0 +1,347 @@
+use crate::abi::CircuitAbi;
+use crate::config::{EligibilityParameters, SharedConfig};
+use crate::evm;
+use crate::honk;
+use crate::prover::{self, read_fields, NoirProver, ProvingScheme, VK_DIR};
+use crate::registry::{CircuitInfo, CircuitRegistry};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+/// A proof as returned by the prover: hex proof bytes plus the public inputs as a JSON array of
+/// field elements.
+#[derive(Debug, Deserialize)]
+pub struct VerifyRequest {
+    pub proof_hex: String,
+    pub public_inputs: String,
+    /// Registered circuit the proof was made with; the eligibility circuit when absent
+    #[serde(default)]
+    pub circuit_id: Option<String>,
+    /// The verifier session's nonce; the proof must carry it as its `nonce` public input
+    #[serde(default)]
+    pub nonce: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyResponse {
+    pub valid: bool,
+    pub message: String,
+}
+
+/// The verification key matching the server's active circuit, as served by GET /vk.
+#[derive(Debug, Serialize)]
+pub struct VerificationKey {
+    /// Key bytes as consumed by `bb verify` and the Solidity verifier generator
+    pub vk_hex: String,
+    pub vk_fields: Vec<String>,
+    /// sha256 of the key bytes, for pinning
+    pub vk_hash: String,
+}
+
+/// On-chain verifier for the active circuit, as generated by `bb write_solidity_verifier`.
+#[derive(Debug, Serialize)]
+pub struct SolidityVerifier {
+    pub source: String,
+    /// keccak256 of the source, so deployments can be matched to the circuit they verify
+    pub keccak256: String,
+}
+
+impl VerifyRequest {
+    /// Decodes the request into the proof bytes and its public inputs as bytes32 words.
+    pub fn decode(&self) -> Result<(Vec<u8>, Vec<[u8; 32]>)> {
+        let proof = hex::decode(self.proof_hex.trim().trim_start_matches("0x")).context("proof_hex is not valid hex")?;
+        if proof.is_empty() {
+            bail!("proof_hex is empty");
+        }
+        Ok((proof, evm::parse_public_inputs(&self.public_inputs)?))
+    }
+}
+
+/// Checks UltraHonk (keccak) proofs, natively or with `bb verify` (see `NativeVerifier`), so
+/// relying parties do not need a local Barretenberg install.
+pub struct Verifier {
+    prover: NoirProver,
+    /// Source of the policies whose bounds a proof may carry
+    config: SharedConfig,
+    // Serialises verification key generation so concurrent requests do not race on the file
+    vk_lock: Mutex<()>,
+    /// `bb gates` results by circuit hash; a compiled circuit's size never changes
+    sizes: std::sync::Mutex<HashMap<String, CircuitSize>>,
+}
+
+/// Size of a compiled circuit as `bb gates` reports it, which proving time and memory grow with.
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitSize {
+    /// sha256 of the compiled circuit, as in proof responses
+    pub circuit_hash: String,
+    pub acir_opcodes: u64,
+    /// Gates of the UltraHonk circuit
+    pub circuit_size: u64,
+    /// Field elements each proof carries as public inputs
+    pub public_inputs: usize,
+}
+
+impl Verifier {
+    pub fn new(prover: NoirProver, config: SharedConfig) -> Self {
+        Self {
+            prover,
+            config,
+            vk_lock: Mutex::new(()),
+            sizes: Default::default(),
+        }
+    }
+
+    /// Scheme requests are proven with unless they select one.
+    pub fn default_scheme(&self) -> ProvingScheme {
+        self.config.read().unwrap().default_scheme
+    }
+
+    /// Ranges the active configuration proves and accepts.
+    pub fn parameters(&self) -> EligibilityParameters {
+        self.config.read().unwrap().parameters()
+    }
+
+    /// Checks that a proof for `circuit_id` (the eligibility circuit if None) carries `nonce`, so
+    /// one made for another verifier session is turned away. The position of the nonce among the
+    /// public inputs comes from the circuit's ABI.
+    pub fn check_nonce(&self, circuit_id: Option<&str>, public_inputs: &[[u8; 32]], nonce: &[u8; 32]) -> Result<()> {
+        let circuit_path = match circuit_id {
+            Some(id) => self.prover.registry().circuit_path(id).with_context(|| format!("Unknown circuit_id {:?}", id))?,
+            None => self.prover.circuit_path(),
+        };
+        let abi = load_abi(&circuit_path)?;
+        let offset = abi.public_input_offset("nonce").context("The circuit takes no nonce")?;
+        match public_inputs.get(offset) {
+            Some(word) if word == nonce => Ok(()),
+            Some(_) => bail!("The proof was made for another nonce"),
+            None => bail!("The proof carries no nonce"),
+        }
+    }
+
+    /// Checks a proof against the eligibility circuit, or the registered circuit `circuit_id`.
+    pub async fn verify(&self, circuit_id: Option<&str>, proof: &[u8], public_inputs: &[[u8; 32]]) -> Result<VerifyResponse> {
+        let circuit_path = match circuit_id {
+            Some(id) => match self.prover.registry().circuit_path(id) {
+                Some(circuit_path) => circuit_path,
+                None => {
+                    return Ok(VerifyResponse {
+                        valid: false,
+                        message: format!("Unknown circuit_id {:?}", id),
+                    })
+                }
+            },
+            None => {
+                // Proofs that cannot be ours are rejected before the proof is checked
+                let accepted = self.config.read().unwrap().accepted_bounds();
+                if let Err(e) = honk::check_public_inputs(public_inputs, &accepted) {
+                    return Ok(VerifyResponse {
+                        valid: false,
+                        message: format!("Proof verification failed: {:#}", e),
+                    });
+                }
+                self.prover.circuit_path()
+            }
+        };
+        // Stale proofs are turned away like ones with foreign bounds, before the proof is checked
+        if let Err(e) = check_expiry(&circuit_path, public_inputs) {
+            return Ok(VerifyResponse {
+                valid: false,
+                message: format!("Proof verification failed: {:#}", e),
+            });
+        }
+        let vk_dir = self.vk_dir(&circuit_path).await?;
+        Ok(match self.prover.verify_ultra_honk(&circuit_path, &vk_dir, proof, public_inputs).await? {
+            Ok(()) => VerifyResponse {
+                valid: true,
+                message: "Proof is valid for the given public inputs".to_string(),
+            },
+            Err(e) => VerifyResponse {
+                valid: false,
+                message: format!("Proof verification failed: {}", e),
+            },
+        })
+    }
+
+    /// Verification key of the active circuit.
+    pub async fn verification_key(&self) -> Result<VerificationKey> {
+        self.key_at(&self.prover.circuit_path()).await
+    }
+
+    /// Verification key of registered circuit `id`; None if there is no such circuit.
+    pub async fn circuit_verification_key(&self, id: &str) -> Result<Option<VerificationKey>> {
+        match self.prover.registry().circuit_path(id) {
+            Some(circuit_path) => Ok(Some(self.key_at(&circuit_path).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The registered circuits by ID, with their hashes and the inputs requests supply.
+    pub async fn circuits(&self) -> Result<BTreeMap<String, CircuitInfo>> {
+        let registry = self.prover.registry();
+        let mut circuits = BTreeMap::new();
+        for id in registry.ids() {
+            let vk_hash = self.circuit_verification_key(&id).await.ok().flatten().map(|vk| vk.vk_hash);
+            let info = registry.describe(&id, vk_hash)?;
+            circuits.insert(id, info);
+        }
+        Ok(circuits)
+    }
+
+    /// Size of the eligibility circuit, or of registered circuit `circuit_id`; None if there is
+    /// no such circuit. `bb gates` runs once per compiled circuit.
+    pub async fn circuit_size(&self, circuit_id: Option<&str>) -> Result<Option<CircuitSize>> {
+        let circuit_path = match circuit_id {
+            Some(id) => match self.prover.registry().circuit_path(id) {
+                Some(circuit_path) => circuit_path,
+                None => return Ok(None),
+            },
+            None => self.prover.circuit_path(),
+        };
+        let artifact = prover::compiled_circuit(&circuit_path);
+        let compiled = fs::read(circuit_path.join(&artifact)).context("Failed to read the compiled circuit")?;
+        let circuit_hash = format!("0x{}", hex::encode(Sha256::digest(&compiled)));
+        if let Some(size) = self.sizes.lock().unwrap().get(&circuit_hash) {
+            return Ok(Some(size.clone()));
+        }
+
+        let output = self
+            .prover
+            .run_tool(|| {
+                let mut command = Command::new("bb");
+                command
+                    .args(["gates", "--scheme", "ultra_honk", "-b", &artifact])
+                    .current_dir(&circuit_path);
+                command
+            })
+            .await
+            .context("Failed to run bb gates")?;
+        if !output.status.success() {
+            bail!("bb gates failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).context("bb gates printed no JSON report")?;
+        let main = report["functions"].get(0).context("bb gates reported no functions")?;
+        let count = |key: &str| main[key].as_u64().with_context(|| format!("bb gates reported no {}", key));
+        let size = CircuitSize {
+            circuit_hash: circuit_hash.clone(),
+            acir_opcodes: count("acir_opcodes")?,
+            circuit_size: count("circuit_size")?,
+            public_inputs: load_abi(&circuit_path)?.public_input_count(),
+        };
+        self.sizes.lock().unwrap().insert(circuit_hash, size.clone());
+        Ok(Some(size))
+    }
+
+    pub fn registry(&self) -> &CircuitRegistry {
+        self.prover.registry()
+    }
+
+    /// Verification key of the circuit package at `circuit_path`, generating it on first use.
+    pub async fn key_at(&self, circuit_path: &Path) -> Result<VerificationKey> {
+        let vk_dir = self.vk_dir(circuit_path).await?;
+        let bytes = fs::read(vk_dir.join("vk")).context("Failed to read verification key")?;
+        Ok(VerificationKey {
+            vk_hex: format!("0x{}", hex::encode(&bytes)),
+            vk_fields: read_fields(&vk_dir.join("vk_fields.json"))?,
+            vk_hash: format!("0x{}", hex::encode(Sha256::digest(&bytes))),
+        })
+    }
+
+    /// Package directory of the active circuit.
+    pub fn circuit_path(&self) -> PathBuf {
+        self.prover.circuit_path()
+    }
+
+    /// Solidity verifier contract for the active circuit, generated once per release.
+    pub async fn solidity_verifier(&self) -> Result<SolidityVerifier> {
+        let circuit_path = self.prover.circuit_path();
+        let vk_dir = self.vk_dir(&circuit_path).await?;
+        let contract_path = vk_dir.join("Verifier.sol");
+
+        let _guard = self.vk_lock.lock().await;
+        if !contract_path.exists() {
+            let output = self
+                .prover
+                .run_tool(|| {
+                    let mut command = Command::new("bb");
+                    command
+                        .args(["write_solidity_verifier", "--scheme", "ultra_honk", "-k"])
+                        .arg(vk_dir.join("vk"))
+                        .arg("-o")
+                        .arg(&contract_path);
+                    command
+                })
+                .await
+                .context("Failed to run bb write_solidity_verifier")?;
+            if !output.status.success() {
+                bail!("bb write_solidity_verifier failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+        }
+
+        let source = fs::read_to_string(&contract_path).context("Failed to read generated verifier contract")?;
+        Ok(SolidityVerifier {
+            keccak256: format!("0x{}", hex::encode(Keccak256::digest(source.as_bytes()))),
+            source,
+        })
+    }
+
+    /// Generates the active circuit's verification key unless it is already cached. Run at
+    /// startup and whenever a new circuit release is activated, so requests never wait for it.
+    pub async fn prepare(&self) {
+        let registry = self.prover.registry();
+        let registered = registry.ids().into_iter().filter_map(|id| registry.circuit_path(&id));
+        for circuit_path in std::iter::once(self.prover.circuit_path()).chain(registered) {
+            match self.vk_dir(&circuit_path).await {
+                Ok(_) => info!("Verification key ready for {}", circuit_path.display()),
+                Err(e) => error!("Failed to generate verification key for {}: {:#}", circuit_path.display(), e),
+            }
+        }
+    }
+
+    /// Discards the cached verification key and Solidity verifier and generates the key again,
+    /// for a circuit recompiled in place. Releases from the control channel arrive in fresh
+    /// directories and never need this.
+    pub async fn reload(&self) -> Result<VerificationKey> {
+        let circuit_path = self.prover.circuit_path();
+        {
+            let _guard = self.vk_lock.lock().await;
+            let vk_dir = circuit_path.join(VK_DIR);
+            if vk_dir.exists() {
+                fs::remove_dir_all(&vk_dir).with_context(|| format!("Failed to remove {}", vk_dir.display()))?;
+            }
+            self.prover.verification_key_dir(&circuit_path).await?;
+        }
+        self.verification_key().await
+    }
+
+    /// Directory holding the circuit's verification key, generating it on first use.
+    async fn vk_dir(&self, circuit_path: &Path) -> Result<PathBuf> {
+        let _guard = self.vk_lock.lock().await;
+        self.prover.verification_key_dir(circuit_path).await
+    }
+}
+
+fn load_abi(circuit_path: &Path) -> Result<CircuitAbi> {
+    CircuitAbi::load(&circuit_path.join(prover::compiled_circuit(circuit_path)))
+}
+
+/// Rejects a proof whose `expires_at` public input has passed. Circuits without one make proofs
+/// that never expire.
+fn check_expiry(circuit_path: &Path, public_inputs: &[[u8; 32]]) -> Result<()> {
+    let Some(offset) = load_abi(circuit_path)?.public_input_offset("expires_at") else {
+        return Ok(());
+    };
+    let expires_at = honk::expiry(public_inputs.get(offset).context("The proof carries no expiry")?)?;
+    let now = Utc::now().timestamp() as u64;
+    if now > expires_at {
+        bail!("The proof expired at {}", DateTime::from_timestamp(expires_at as i64, 0).unwrap_or_default().to_rfc3339());
+    }
+    Ok(())
+}
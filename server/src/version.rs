@@ -0,0 +1,64 @@
+use crate::health;
+use crate::prover::{self, ProvingScheme};
+use crate::verifier::Verifier;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+
+/// What this node runs, as served by GET /version, so operators can spot fleets whose
+/// toolchains or circuits have drifted apart.
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    /// Version of the server binary
+    pub server: String,
+    /// First line of `nargo --version`; null when nargo is not installed
+    pub nargo: Option<String>,
+    /// `bb --version`; null when bb is not installed
+    pub bb: Option<String>,
+    pub circuit: CircuitVersion,
+    /// Proving schemes requests may select
+    pub schemes: Vec<ProvingScheme>,
+    pub default_scheme: ProvingScheme,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CircuitVersion {
+    /// Package name from Nargo.toml
+    pub name: String,
+    /// Noir compiler the circuit was compiled with
+    pub noir_version: Option<String>,
+    /// sha256 of the compiled circuit, as in proof responses
+    pub circuit_hash: String,
+    /// sha256 of the UltraHonk verification key, as served by GET /vk
+    pub vk_hash: Option<String>,
+}
+
+pub async fn report(verifier: &Verifier) -> Result<VersionInfo> {
+    let circuit_path = verifier.circuit_path();
+    let manifest = fs::read_to_string(circuit_path.join("Nargo.toml")).context("Failed to read Nargo.toml")?;
+    let manifest: toml::Table = toml::from_str(&manifest).context("Nargo.toml is not valid TOML")?;
+    let name = manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(|name| name.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let compiled = fs::read(circuit_path.join(prover::compiled_circuit(&circuit_path))).context("Failed to read the compiled circuit")?;
+    let artifact: serde_json::Value = serde_json::from_slice(&compiled).context("The compiled circuit is not valid JSON")?;
+
+    Ok(VersionInfo {
+        server: env!("CARGO_PKG_VERSION").to_string(),
+        nargo: health::tool_version("nargo").await.ok(),
+        bb: health::tool_version("bb").await.ok(),
+        circuit: CircuitVersion {
+            name,
+            noir_version: artifact.get("noir_version").and_then(|version| version.as_str()).map(str::to_string),
+            circuit_hash: format!("0x{}", hex::encode(Sha256::digest(&compiled))),
+            vk_hash: verifier.verification_key().await.ok().map(|vk| vk.vk_hash),
+        },
+        schemes: ProvingScheme::value_variants().to_vec(),
+        default_scheme: verifier.default_scheme(),
+    })
+}
@@ -0,0 +1,82 @@
+use crate::signing::{self, EnclaveSigner};
+use anyhow::{bail, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+
+/// Bytes of the HMAC kept in a tag; enough to tell tenants apart, short enough to carry around.
+const TAG_LEN: usize = 16;
+
+/// Per-tenant mark attached to a proof response. The tag is an HMAC of the tenant ID, so it
+/// does not reveal the tenant to whoever holds the proof, and the enclave signature binds it to
+/// the proof so it cannot be moved onto another one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Watermark {
+    pub tag: String,
+    /// Enclave address that signed the watermark
+    pub signer: String,
+    /// EIP-191 signature over `watermark_message(proof_id, tag)`
+    pub signature: String,
+}
+
+pub struct Watermarker {
+    key: [u8; 32],
+    signer: Arc<EnclaveSigner>,
+}
+
+impl Watermarker {
+    /// Derives the tagging key from the enclave master key, so tags stay stable across restarts
+    /// and only the operator can map them back to tenants.
+    pub fn new(master_key: &[u8], signer: Arc<EnclaveSigner>) -> Result<Self> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(master_key)?;
+        mac.update(b"zk-insurance watermark key");
+        Ok(Self {
+            key: mac.finalize().into_bytes().into(),
+            signer,
+        })
+    }
+
+    pub fn tag(&self, tenant: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(tenant.as_bytes());
+        format!("0x{}", hex::encode(&mac.finalize().into_bytes()[..TAG_LEN]))
+    }
+
+    pub fn watermark(&self, tenant: &str, proof_id: &str) -> Result<Watermark> {
+        let tag = self.tag(tenant);
+        Ok(Watermark {
+            signature: self.signer.sign_message(watermark_message(proof_id, &tag).as_bytes())?,
+            signer: self.signer.address(),
+            tag,
+        })
+    }
+
+    /// The tenant among `candidates` a watermark was issued to. Fails unless the watermark was
+    /// signed for this proof by one of `enclave_signers` (the addresses of the keyring's signing
+    /// keys, retired ones included), i.e. if it was forged or copied from another proof.
+    pub fn trace<'a>(
+        &self,
+        watermark: &Watermark,
+        proof_id: &str,
+        enclave_signers: &[String],
+        candidates: &'a [String],
+    ) -> Result<Option<&'a str>> {
+        let signer = signing::recover_address(watermark_message(proof_id, &watermark.tag).as_bytes(), &watermark.signature)?;
+        if !signer.eq_ignore_ascii_case(&watermark.signer) {
+            bail!("Watermark signature does not match proof {} (recovered signer {})", proof_id, signer);
+        }
+        // The signer named in the watermark is the forger's to choose, so only the keyring counts
+        if !enclave_signers.iter().any(|address| address.eq_ignore_ascii_case(&signer)) {
+            bail!("Watermark was signed by {}, which is not one of the enclave's signing keys", signer);
+        }
+        Ok(candidates
+            .iter()
+            .find(|tenant| self.tag(tenant).eq_ignore_ascii_case(&watermark.tag))
+            .map(String::as_str))
+    }
+}
+
+fn watermark_message(proof_id: &str, tag: &str) -> String {
+    format!("zk-insurance watermark\nproof_id: {}\ntag: {}", proof_id, tag)
+}
@@ -0,0 +1,83 @@
+use crate::abi::{CircuitAbi, Visibility};
+use crate::policy::Bounds;
+use crate::verifier::Verifier;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+/// @aztec/bb.js release built from the same Barretenberg as the `bb` in the Docker image, so
+/// browsers derive byte-identical verification keys.
+pub const BB_JS_VERSION: &str = "0.87.0";
+
+/// JS glue shipped with every bundle.
+const VERIFY_JS: &str = include_str!("web/verify.js");
+
+/// Describes a browser verification bundle; written to its `manifest.json`.
+#[derive(Debug, Serialize)]
+pub struct WebManifest {
+    pub scheme: &'static str,
+    pub oracle_hash: &'static str,
+    pub bb_js_version: &'static str,
+    /// sha256 of the verification key, as served by GET /vk
+    pub vk_hash: String,
+    /// sha256 of the compiled circuit the key was derived from
+    pub circuit_hash: String,
+    /// Order of the public inputs in a proof: the bounds, then `nonce` if the circuit takes one
+    pub public_inputs: Vec<String>,
+    /// Bounds a proof may carry: the circuit's defaults plus every configured policy
+    pub accepted_bounds: Vec<Bounds>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Writes everything a front-end needs to verify proofs for the active circuit without calling
+/// the server: the verification key, the compiled circuit, `verify.js` and a manifest. The
+/// Barretenberg WASM itself comes from the pinned @aztec/bb.js package when the bundle is
+/// installed or bundled.
+pub async fn export_bundle(verifier: &Verifier, accepted_bounds: Vec<Bounds>, out: &Path) -> Result<WebManifest> {
+    let vk = verifier.verification_key().await?;
+    let circuit_file = verifier.circuit_path().join("target/insurance_verifier.json");
+    let circuit = fs::read(&circuit_file).with_context(|| format!("Failed to read {}", circuit_file.display()))?;
+    let abi = CircuitAbi::load(&circuit_file)?;
+
+    let manifest = WebManifest {
+        scheme: "ultra_honk",
+        oracle_hash: "keccak",
+        bb_js_version: BB_JS_VERSION,
+        vk_hash: vk.vk_hash.clone(),
+        circuit_hash: format!("0x{}", hex::encode(Sha256::digest(&circuit))),
+        public_inputs: abi
+            .parameters
+            .into_iter()
+            .filter(|parameter| parameter.visibility == Visibility::Public)
+            .map(|parameter| parameter.name)
+            .collect(),
+        accepted_bounds,
+        created_at: Utc::now(),
+    };
+    let package = json!({
+        "name": "zk-insurance-web-verifier",
+        "private": true,
+        "type": "module",
+        "main": "verify.js",
+        "dependencies": { "@aztec/bb.js": BB_JS_VERSION },
+    });
+
+    fs::create_dir_all(out).with_context(|| format!("Failed to create {}", out.display()))?;
+    let files: [(&str, Vec<u8>); 6] = [
+        ("vk", hex::decode(vk.vk_hex.trim_start_matches("0x"))?),
+        ("vk_fields.json", serde_json::to_vec_pretty(&vk.vk_fields)?),
+        ("circuit.json", circuit),
+        ("manifest.json", serde_json::to_vec_pretty(&manifest)?),
+        ("package.json", serde_json::to_vec_pretty(&package)?),
+        ("verify.js", VERIFY_JS.as_bytes().to_vec()),
+    ];
+    for (name, content) in files {
+        let path = out.join(name);
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(manifest)
+}
@@ -0,0 +1,117 @@
+// Client-side verifier for insurance eligibility proofs, written by
+// `zk-insurance-server export-web-bundle`. Proofs are checked in the browser with
+// Barretenberg's WASM build (@aztec/bb.js, pinned in package.json to the bb version the
+// server proves with); no call to the proving server is made.
+//
+//   import { verifyProof } from "./verify.js";
+//   const { valid, message } = await verifyProof(response.proof_hex, response.public_inputs, nonce);
+import { UltraHonkBackend } from "@aztec/bb.js";
+
+let loaded;
+
+async function fetchJson(name) {
+  const response = await fetch(new URL(name, import.meta.url));
+  if (!response.ok) {
+    throw new Error(`Failed to load ${name}: HTTP ${response.status}`);
+  }
+  return response.json();
+}
+
+function hexToBytes(hex) {
+  const digits = hex.trim().replace(/^0x/, "");
+  if (digits.length === 0 || digits.length % 2 !== 0 || /[^0-9a-fA-F]/.test(digits)) {
+    throw new Error("proof_hex is not valid hex");
+  }
+  const bytes = new Uint8Array(digits.length / 2);
+  for (let i = 0; i < bytes.length; i++) {
+    bytes[i] = parseInt(digits.slice(2 * i, 2 * i + 2), 16);
+  }
+  return bytes;
+}
+
+async function sha256Hex(bytes) {
+  const digest = new Uint8Array(await crypto.subtle.digest("SHA-256", bytes));
+  return "0x" + Array.from(digest, (b) => b.toString(16).padStart(2, "0")).join("");
+}
+
+// Loads the circuit once and checks that the key bb.js derives from it is the one the
+// server pinned, so a stale or tampered bundle fails loudly instead of verifying wrongly.
+async function load() {
+  if (!loaded) {
+    loaded = (async () => {
+      const [manifest, circuit] = await Promise.all([fetchJson("manifest.json"), fetchJson("circuit.json")]);
+      const backend = new UltraHonkBackend(circuit.bytecode);
+      const vk = await backend.getVerificationKey({ keccak: true });
+      const vkHash = await sha256Hex(vk);
+      if (vkHash !== manifest.vk_hash) {
+        throw new Error(`Verification key ${vkHash} does not match the bundle's ${manifest.vk_hash}`);
+      }
+      return { manifest, backend };
+    })();
+    loaded.catch(() => (loaded = undefined));
+  }
+  return loaded;
+}
+
+// The circuit only proves that the inputs lie within its public bounds, so the bounds must be
+// ones an insurer actually accepts.
+function checkBounds(manifest, fields) {
+  if (fields.length !== manifest.public_inputs.length) {
+    return `expected ${manifest.public_inputs.length} public inputs, got ${fields.length}`;
+  }
+  const values = fields.map((field) => BigInt(field));
+  const accepted = manifest.accepted_bounds.some((bounds) =>
+    manifest.public_inputs.every((name, i) => !(name in bounds) || values[i] === BigInt(bounds[name])),
+  );
+  return accepted ? null : "public inputs do not match any accepted eligibility bounds";
+}
+
+// A proof made for another verifier session carries another nonce.
+function checkNonce(manifest, fields, nonce) {
+  const index = manifest.public_inputs.indexOf("nonce");
+  if (index < 0) {
+    return "the circuit takes no nonce";
+  }
+  return BigInt(fields[index]) === BigInt(nonce) ? null : "the proof was made for another nonce";
+}
+
+// Proofs carry the unix time they expire at; circuits without one make proofs that never do.
+function checkExpiry(manifest, fields) {
+  const index = manifest.public_inputs.indexOf("expires_at");
+  if (index < 0) {
+    return null;
+  }
+  const expiresAt = Number(BigInt(fields[index]));
+  return Date.now() / 1000 > expiresAt ? `the proof expired at ${new Date(expiresAt * 1000).toISOString()}` : null;
+}
+
+/**
+ * Verifies a proof returned by the server.
+ *
+ * @param {string} proofHex `proof_hex` from the proving response
+ * @param {string | string[]} publicInputs `public_inputs` from the response (a JSON array of
+ *   field elements), or the array itself
+ * @param {string} [nonce] the 0x-prefixed nonce of this verifier session, if it sent one
+ * @returns {Promise<{valid: boolean, message: string}>}
+ */
+export async function verifyProof(proofHex, publicInputs, nonce) {
+  const { manifest, backend } = await load();
+  const fields = typeof publicInputs === "string" ? JSON.parse(publicInputs) : publicInputs;
+  const problem =
+    checkBounds(manifest, fields) ??
+    checkExpiry(manifest, fields) ??
+    (nonce === undefined ? null : checkNonce(manifest, fields, nonce));
+  if (problem) {
+    return { valid: false, message: `Proof verification failed: ${problem}` };
+  }
+  const padded = fields.map((field) => "0x" + BigInt(field).toString(16).padStart(64, "0"));
+  const valid = await backend.verifyProof({ proof: hexToBytes(proofHex), publicInputs: padded }, { keccak: true });
+  return valid
+    ? { valid: true, message: "Proof is valid for the given public inputs" }
+    : { valid: false, message: "Proof verification failed" };
+}
+
+/** The bundle's manifest: circuit, key hash and the eligibility bounds it accepts. */
+export async function manifest() {
+  return (await load()).manifest;
+}
@@ -0,0 +1,673 @@
+use crate::audit::AuditLog;
+use crate::backend::ProverBackend;
+use crate::breaker::{Admission, CircuitBreaker};
+use crate::bundles::BundlePublisher;
+use crate::config::SharedConfig;
+use crate::eip712::Attester;
+use crate::indexer::IssuedProofs;
+use crate::intake::{Intake, SealedRequest};
+use crate::issuance::IssuanceLog;
+use crate::jobs::{self, JobStore, QueuedJob, StoredProof};
+use crate::logging::{self, Private};
+use crate::metrics::Metrics;
+use crate::policy::Bounds;
+use crate::prover::{ErrorCode, Priority, ProofRequest, ProofResponse};
+use crate::signing::EnclaveSigner;
+use crate::telemetry::{Span, TraceContext};
+use anyhow::{anyhow, Result};
+use futures::stream::FuturesUnordered;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{oneshot, Notify};
+use tracing::{debug, error, info, warn, Instrument};
+
+struct Job {
+    request: SealedRequest,
+    reply: oneshot::Sender<Result<ProofResponse>>,
+    /// Synthetic job probing whether a tripped backend has recovered
+    canary: bool,
+    /// Proving-time ceiling, resolved at submission
+    ceiling: Option<Duration>,
+    /// Row in the job store, when persistence is enabled
+    job_id: Option<String>,
+}
+
+/// One FIFO lane per priority; the highest non-empty lane is always served first.
+#[derive(Default)]
+struct JobQueue {
+    lanes: [VecDeque<Job>; 3],
+}
+
+impl JobQueue {
+    fn lane(priority: Priority) -> usize {
+        match priority {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+
+    fn push(&mut self, job: Job) {
+        let priority = job.request.request.priority.unwrap_or(Priority::Normal);
+        self.lanes[Self::lane(priority)].push_back(job);
+    }
+
+    fn pop(&mut self) -> Option<Job> {
+        self.lanes.iter_mut().find_map(|lane| lane.pop_front())
+    }
+
+    fn len(&self) -> usize {
+        self.lanes.iter().map(|lane| lane.len()).sum()
+    }
+}
+
+/// Fixed set of workers pulling proving jobs off a shared queue. Every transport submits
+/// through the pool so the number of concurrent nargo/bb processes stays bounded.
+pub struct WorkerPool {
+    queue: Mutex<JobQueue>,
+    notify: Notify,
+    intake: Intake,
+    backend: ProverBackend,
+    breaker: CircuitBreaker,
+    config: SharedConfig,
+    metrics: Arc<Metrics>,
+    audit: Arc<AuditLog>,
+    /// Signs every proof before it leaves the pool
+    signer: Arc<EnclaveSigner>,
+    /// Endorses successful proofs as EIP-712 typed data
+    attester: Attester,
+    /// Every proof handed out, for correlation with on-chain verifications
+    issued: Arc<IssuedProofs>,
+    /// Hash-chained record of every request's outcome
+    issuance: Arc<IssuanceLog>,
+    /// Job state and outcomes kept across restarts, when --database-url is set
+    jobs: Option<Arc<JobStore>>,
+    /// Publishes successful proofs' bundles to IPFS, when --ipfs-publish-api is set
+    publisher: Option<BundlePublisher>,
+    /// Cleared once the backend has warmed up and the workers are running
+    warming_up: AtomicBool,
+    /// Set by the admin API; new jobs are turned away as server_busy while queued ones finish
+    draining: AtomicBool,
+    /// Jobs a worker is proving right now
+    active: AtomicUsize,
+    /// Workers running; moved towards the configured `workers` by `resize`
+    workers: AtomicUsize,
+}
+
+impl WorkerPool {
+    /// Starts the pool. With `warm_up` set, a throwaway proof is run first (loading the SRS and
+    /// circuit artifacts); jobs submitted meanwhile are queued and picked up once it finishes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        intake: Intake,
+        backend: ProverBackend,
+        config: SharedConfig,
+        metrics: Arc<Metrics>,
+        audit: Arc<AuditLog>,
+        signer: Arc<EnclaveSigner>,
+        attester: Attester,
+        issuance: Arc<IssuanceLog>,
+        jobs: Option<Arc<JobStore>>,
+        publisher: Option<BundlePublisher>,
+        warm_up: bool,
+    ) -> Arc<Self> {
+        let pool = Arc::new(Self {
+            queue: Mutex::new(JobQueue::default()),
+            notify: Notify::new(),
+            intake,
+            backend,
+            breaker: CircuitBreaker::new(config.clone()),
+            config,
+            metrics,
+            audit,
+            signer,
+            attester,
+            issued: Arc::new(IssuedProofs::default()),
+            issuance,
+            jobs,
+            publisher,
+            warming_up: AtomicBool::new(warm_up),
+            draining: AtomicBool::new(false),
+            active: AtomicUsize::new(0),
+            workers: AtomicUsize::new(0),
+        });
+
+        let starter = pool.clone();
+        tokio::spawn(async move {
+            if warm_up {
+                starter.warm_up().await;
+            }
+            starter.resize();
+        });
+
+        pool
+    }
+
+    /// Starts or retires workers until the configured number are running. A retired worker
+    /// finishes its current job first. Until the warm-up is done this is left to `start`.
+    pub fn resize(self: &Arc<Self>) {
+        if self.is_warming_up() {
+            return;
+        }
+        let target = self.config.read().unwrap().workers.max(1);
+        let mut running = self.workers.load(Ordering::Acquire);
+        while running < target {
+            match self.workers.compare_exchange(running, running + 1, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => {
+                    let pool = self.clone();
+                    tokio::spawn(async move { pool.run_worker().await });
+                    running += 1;
+                }
+                Err(actual) => running = actual,
+            }
+        }
+        if running > target {
+            // Idle workers only notice they are surplus once woken
+            self.notify.notify_waiters();
+        }
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.workers.load(Ordering::Acquire)
+    }
+
+    /// Takes this worker out of the pool if more are running than configured.
+    fn retire(&self) -> bool {
+        let target = self.config.read().unwrap().workers.max(1);
+        self.workers
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| (running > target).then(|| running - 1))
+            .is_ok()
+    }
+
+    async fn warm_up(&self) {
+        let started = std::time::Instant::now();
+        match self.backend.generate_proof(self.canary()).await {
+            Ok(response) if response.success => info!("Prover warmed up in {:.1?}", started.elapsed()),
+            // Keep going: real jobs will surface the problem and trip the breaker if it persists
+            Ok(response) => warn!("Warm-up proof failed: {}", response.message),
+            Err(e) => warn!("Warm-up proof failed: {}", e),
+        }
+        self.warming_up.store(false, Ordering::Release);
+    }
+
+    /// Proves the synthetic canary applicant directly on the backend. It skips the queue and the
+    /// counters, so health checks are neither billed nor held up behind real jobs.
+    pub async fn prove_canary(&self) -> Result<ProofResponse> {
+        self.backend.generate_proof(self.canary()).await
+    }
+
+    fn canary(&self) -> ProofRequest {
+        ProofRequest::canary(self.config.read().unwrap().default_bounds)
+    }
+
+    pub fn is_warming_up(&self) -> bool {
+        self.warming_up.load(Ordering::Acquire)
+    }
+
+    pub fn queued_jobs(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    pub fn active_jobs(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Stops admitting jobs; those already queued or proving still complete.
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.draining.store(false, Ordering::Release);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// Whether no job is queued or being proven.
+    pub fn is_idle(&self) -> bool {
+        self.queued_jobs() == 0 && self.active_jobs() == 0
+    }
+
+    /// Whether `jobs` more jobs fit in the queue under `max_queue`; never while draining.
+    pub fn has_room(&self, jobs: usize) -> bool {
+        if self.is_draining() {
+            return false;
+        }
+        let max_queue = self.config.read().unwrap().max_queue;
+        max_queue == 0 || self.queued_jobs() + jobs <= max_queue
+    }
+
+    async fn run_worker(&self) {
+        loop {
+            if self.retire() {
+                // The wake-up may have been meant for a queued job; pass it on
+                self.notify.notify_one();
+                debug!("Proving worker retired");
+                return;
+            }
+            let job = {
+                let mut queue = self.queue.lock().unwrap();
+                let job = queue.pop();
+                // Counted before the queue is released, so a drain never sees the job in neither
+                if job.is_some() {
+                    self.active.fetch_add(1, Ordering::AcqRel);
+                }
+                job
+            };
+            match job {
+                Some(job) => {
+                    if let (Some(jobs), Some(job_id)) = (&self.jobs, &job.job_id) {
+                        jobs.started(job_id).await;
+                    }
+                    let result = self.prove(job.request, job.ceiling).await;
+                    if let Ok(response) = &result {
+                        if let Some(steps) = &response.step_durations {
+                            self.metrics.record_steps(steps);
+                            if let Some(circuit_hash) = response.circuit_hash.as_deref().filter(|_| response.success) {
+                                let proof_bytes = response.proof_hex.trim_start_matches("0x").len() / 2;
+                                self.metrics.record_circuit(circuit_hash, steps, proof_bytes);
+                            }
+                        }
+                    }
+                    if job.canary {
+                        self.breaker.record_probe(&result);
+                    } else {
+                        self.breaker.record(&result);
+                    }
+                    // The submitter may have gone away; nothing to do with the result then
+                    let _ = job.reply.send(result);
+                    self.active.fetch_sub(1, Ordering::AcqRel);
+                }
+                None => self.notify.notified().await,
+            }
+        }
+    }
+
+    async fn prove(&self, sealed: SealedRequest, ceiling: Option<Duration>) -> Result<ProofResponse> {
+        // Inputs are only decrypted once a worker is ready to generate the witness
+        let mut request = self.intake.open(sealed)?;
+        // The bounds depend on the inputs, so they are only resolved now that they are open
+        {
+            let config = self.config.read().unwrap();
+            request.eligibility = Some(config.eligibility(
+                request.tenant.as_deref(),
+                request.policy.as_deref(),
+                request.tier.as_deref(),
+                request.age,
+                request.bmi_multiplied,
+            ));
+            request.expires_at = Some(config.proof_expiry());
+        }
+        match ceiling {
+            // Dropping the proof future kills its nargo/bb processes
+            Some(ceiling) => tokio::time::timeout(ceiling, self.backend.generate_proof(request))
+                .await
+                .unwrap_or_else(|_| {
+                    Ok(ProofResponse::failed(
+                        ErrorCode::BudgetExceeded,
+                        format!("Proving exceeded the {}s ceiling for this policy and was cancelled; it is not billed", ceiling.as_secs()),
+                    ))
+                }),
+            None => self.backend.generate_proof(request).await,
+        }
+    }
+
+    pub fn intake(&self) -> &Intake {
+        &self.intake
+    }
+
+    pub fn issued_proofs(&self) -> Arc<IssuedProofs> {
+        self.issued.clone()
+    }
+
+    fn enqueue(&self, job: Job) {
+        self.queue.lock().unwrap().push(job);
+        self.notify.notify_one();
+    }
+
+    /// Queues a single request and waits for its result, which carries the request's ID.
+    pub async fn submit(&self, request: ProofRequest) -> Result<ProofResponse> {
+        self.submit_job(request, None).await
+    }
+
+    /// Records `request` as a queued job and proves it in the background, returning the job's
+    /// ID for the client to poll (GET /jobs/{job_id}). None if there is no room in the queue.
+    /// Until a worker picks it up, the job survives restarts.
+    pub async fn submit_detached(self: &Arc<Self>, mut request: ProofRequest) -> Result<Option<String>> {
+        let jobs = self.jobs.as_ref().ok_or_else(|| anyhow!("Jobs are not recorded (--database-url)"))?;
+        if !self.has_room(1) {
+            return Ok(None);
+        }
+        request.request_id.get_or_insert_with(logging::request_id);
+        let job = QueuedJob {
+            job_id: logging::request_id(),
+            created_at: jobs::now(),
+            request,
+        };
+        jobs.queued(&job.job_id, &job.created_at, &job.request, &self.issuance.request_hash(&job.request))
+            .await?;
+        let job_id = job.job_id.clone();
+        self.run_queued(job);
+        Ok(Some(job_id))
+    }
+
+    /// Proves a job already recorded as queued in the background; its outcome is recorded in
+    /// the job store. Used for detached jobs and those queued before a restart.
+    pub fn run_queued(self: &Arc<Self>, job: QueuedJob) {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let _ = pool.submit_job(job.request, Some((job.job_id, job.created_at))).await;
+        });
+    }
+
+    /// Proves `request`; `recorded` is the ID and creation time of a job already in the job
+    /// store, which has been admitted to the queue. The proof is charged to the request's API key
+    /// unless the queue turned it away.
+    async fn submit_job(&self, mut request: ProofRequest, recorded: Option<(String, String)>) -> Result<ProofResponse> {
+        let request_id = request.request_id.get_or_insert_with(logging::request_id).clone();
+        let mut usage = request.usage.take();
+        let job = tracing::info_span!("job", request_id = %request_id);
+        let mut result = self.process(request, recorded).instrument(job).await;
+        if let Ok(response) = &mut result {
+            response.request_id = Some(request_id);
+            if let Some(usage) = usage.as_mut().filter(|_| response.error_code != Some(ErrorCode::ServerBusy)) {
+                usage.settle(response.success);
+            }
+        }
+        result
+    }
+
+    async fn process(&self, mut request: ProofRequest, recorded: Option<(String, String)>) -> Result<ProofResponse> {
+        debug!(
+            age = %Private(request.age),
+            bmi_multiplied = %Private(request.bmi_multiplied),
+            policy = ?request.policy,
+            scheme = ?request.scheme,
+            "Proof requested"
+        );
+        let started = std::time::Instant::now();
+        // Covers queueing as well as proving, so time spent waiting for a worker shows up in traces
+        let mut span = Span::start("proof_job", request.traceparent.as_deref().and_then(TraceContext::parse));
+        request.traceparent = Some(span.context().traceparent());
+        let as_of = request.as_of;
+        let policy = request.policy.clone();
+        let commitment = request.applicant_commitment.clone();
+        let request_id = request.request_id.clone();
+        let circuit_id = request.circuit_id.clone();
+        let tenant = request.tenant.clone();
+        let payment = request.payment.take();
+        let scheme = jobs::scheme_name(&request);
+        let request_hash = self.issuance.request_hash(&request);
+        let admitted = recorded.is_some();
+        let (job_id, created_at) = match recorded {
+            Some((job_id, created_at)) => (Some(job_id), created_at),
+            None => {
+                let created_at = jobs::now();
+                let job_id = (self.jobs.is_some() || self.publisher.is_some()).then(logging::request_id);
+                if let (Some(jobs), Some(job_id)) = (&self.jobs, &job_id) {
+                    if let Err(e) = jobs.queued(job_id, &created_at, &request, &request_hash).await {
+                        warn!("Failed to record proof job {}: {:#}", job_id, e);
+                    }
+                }
+                (job_id, created_at)
+            }
+        };
+        let mut result = self
+            .admit_and_prove(request, job_id.clone(), admitted)
+            .await
+            .and_then(|response| self.sign(response, commitment.as_deref()));
+        if let (Some(publisher), Some(job_id), Ok(response)) = (&self.publisher, &job_id, &mut result) {
+            if response.success {
+                // The bundle packs the proof as the job store keeps it, so both serve the same bytes
+                let proof = StoredProof {
+                    job_id: job_id.clone(),
+                    proof_hex: response.proof_hex.clone(),
+                    public_inputs: response.public_inputs.clone(),
+                    proof_id: response.proof_id.clone(),
+                    circuit_hash: response.circuit_hash.clone(),
+                    vk_hash: response.vk_hash.clone(),
+                    expires_at: response.expires_at.map(|expires_at| expires_at as i64),
+                    enclave_signature: response.enclave_signature.clone(),
+                    ipfs_cid: None,
+                    request_id: request_id.clone(),
+                    policy: policy.clone(),
+                    circuit_id,
+                    scheme,
+                    node: self.signer.address(),
+                    created_at,
+                    finished_at: None,
+                    // The bundle is for relying parties; the payment receipt is the payer's alone
+                    payment: None,
+                };
+                match publisher.publish(&proof).await {
+                    Ok(cid) => response.ipfs_cid = Some(cid),
+                    Err(e) => warn!(job_id = %job_id, "Failed to publish the proof bundle to IPFS: {:#}", e),
+                }
+            }
+        }
+        // Charged before the job is recorded, so the job store keeps the receipt with the proof
+        if let (Some(payment), Ok(response)) = (payment, &mut result) {
+            if let (true, Some(proof_id)) = (response.success, response.proof_id.as_deref()) {
+                match payment.settle(proof_id).await {
+                    Ok(receipt) => response.payment = Some(receipt),
+                    Err(e) => warn!("Failed to charge the proof to its deposit: {:#}", e),
+                }
+            }
+        }
+        // Step timings are kept by the job store and metrics, not returned to clients
+        let steps = result.as_mut().ok().and_then(|response| response.step_durations.take());
+        if let (Some(jobs), Some(job_id)) = (&self.jobs, &job_id) {
+            jobs.finished(job_id, &result, steps.as_ref()).await;
+        }
+        if let Ok(response) = &mut result {
+            if response.success && self.jobs.is_some() {
+                response.job_id = job_id;
+            }
+        }
+        self.metrics.record(&result, tenant.as_deref());
+        self.issuance.record(request_id.as_deref(), request_hash, &result);
+        match &result {
+            Ok(response) => {
+                span.set("success", response.success);
+                info!(success = response.success, error_code = ?response.error_code, elapsed = ?started.elapsed(), "Proof job finished");
+            }
+            Err(e) => {
+                span.fail(format!("{:#}", e));
+                error!(elapsed = ?started.elapsed(), "Proof job failed: {:#}", e);
+            }
+        }
+        if let Ok(ProofResponse { proof_id: Some(proof_id), .. }) = &result {
+            self.issued.record(proof_id, tenant.as_deref());
+        }
+
+        // Re-proving under past rules is how disputes are settled, so keep a trail of it
+        if let Some(as_of) = as_of {
+            let (success, error_code) = match &result {
+                Ok(response) => (response.success, response.error_code),
+                Err(_) => (false, Some(ErrorCode::BackendError)),
+            };
+            self.audit.record(
+                "historical_proof",
+                json!({ "as_of": as_of, "policy": policy, "success": success, "error_code": error_code }),
+            );
+        }
+        result
+    }
+
+    /// Adds the enclave signature and the eligibility attestation to a successful proof. The
+    /// attestation commits to `applicant_commitment`, or to the proof itself without one.
+    fn sign(&self, mut response: ProofResponse, applicant_commitment: Option<&str>) -> Result<ProofResponse> {
+        if let Some(proof_id) = &response.proof_id {
+            response.enclave_signature = Some(self.signer.sign_at(|signed_at| response.signing_message(signed_at))?);
+            if let Some(circuit_hash) = &response.circuit_hash {
+                let commitment = applicant_commitment.unwrap_or(proof_id);
+                response.eligibility_attestation = Some(self.attester.attest(&self.signer, commitment, circuit_hash)?);
+            }
+        }
+        Ok(response)
+    }
+
+    /// Queues and proves `request`. An `admitted` job was given its place in the queue when it
+    /// was recorded, so it is not turned away for lack of room now.
+    async fn admit_and_prove(&self, mut request: ProofRequest, job_id: Option<String>, admitted: bool) -> Result<ProofResponse> {
+        match self.breaker.admit() {
+            Admission::Allow => {}
+            Admission::Reject => return Ok(self.breaker.rejection()),
+            Admission::Probe => {
+                // Nobody waits on the canary; its outcome is reported to the breaker by the worker
+                let (reply, _) = oneshot::channel();
+                if let Ok(Ok(request)) = self.intake.admit(self.canary()) {
+                    self.enqueue(Job { request, reply, canary: true, ceiling: None, job_id: None });
+                }
+                return Ok(self.breaker.rejection());
+            }
+        }
+
+        if !admitted && !self.has_room(1) {
+            return Ok(ProofResponse::busy(self.config.read().unwrap().retry_after_secs));
+        }
+
+        // Resolve the scheme and ceiling now so a config change cannot affect jobs already queued
+        let ceiling = {
+            let config = self.config.read().unwrap();
+            let checked = config
+                .check_tenant(request.tenant.as_deref(), request.circuit_id.as_deref(), request.policy.as_deref())
+                .and_then(|()| config.check_tier(request.policy.as_deref(), request.tier.as_deref()));
+            if let Err(message) = checked {
+                return Ok(ProofResponse::failed(ErrorCode::InvalidRequest, message));
+            }
+            request.scheme.get_or_insert(config.default_scheme);
+            config.proving_ceiling(request.policy.as_deref())
+        };
+
+        let request = match self.intake.admit(request)? {
+            Ok(sealed) => sealed,
+            Err(rejection) => return Ok(rejection),
+        };
+        let (reply, receiver) = oneshot::channel();
+        self.enqueue(Job { request, reply, canary: false, ceiling, job_id });
+
+        receiver
+            .await
+            .map_err(|_| anyhow!("Proving worker stopped before completing the job"))?
+    }
+
+    /// Queues every request at once so they are spread over all workers, returning results in
+    /// request order. A failing item never fails the batch; it is reported in its own slot.
+    pub async fn submit_batch(&self, requests: Vec<ProofRequest>) -> Vec<ProofResponse> {
+        let jobs = requests.into_iter().map(|request| self.submit_batch_item(request));
+        futures::future::join_all(jobs).await
+    }
+
+    /// Like `submit_batch`, but yields every item as soon as it completes, tagged with its
+    /// position in the request.
+    pub fn submit_batch_unordered(self: Arc<Self>, requests: Vec<ProofRequest>) -> FuturesUnordered<impl Future<Output = BatchItem>> {
+        requests
+            .into_iter()
+            .enumerate()
+            .map(|(index, request)| {
+                let pool = self.clone();
+                async move {
+                    BatchItem {
+                        index,
+                        response: pool.submit_batch_item(request).await,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    async fn submit_batch_item(&self, mut request: ProofRequest) -> ProofResponse {
+        request.priority.get_or_insert(Priority::Low);
+        self.submit(request).await.unwrap_or_else(|e| {
+            ProofResponse::failed(ErrorCode::BackendError, format!("Error generating proof: {}", e))
+        })
+    }
+
+    /// Runs the checks a request goes through before proving, and compares its inputs with the
+    /// bounds it would be proven against, without queueing it or running nargo or bb.
+    pub fn validate(&self, mut request: ProofRequest) -> Validation {
+        let config = self.config.read().unwrap();
+        let checked = self
+            .intake
+            .validate(&mut request)
+            .and_then(|()| config.check_tenant(request.tenant.as_deref(), request.circuit_id.as_deref(), request.policy.as_deref()))
+            .and_then(|()| config.check_tier(request.policy.as_deref(), request.tier.as_deref()));
+        if let Err(message) = checked {
+            return Validation {
+                valid: false,
+                eligible: None,
+                tier: None,
+                bounds: None,
+                violations: Vec::new(),
+                message,
+            };
+        }
+        // A registered circuit's constraints are only known by executing it
+        if request.circuit_id.is_some() {
+            return Validation {
+                valid: true,
+                eligible: None,
+                tier: None,
+                bounds: None,
+                violations: Vec::new(),
+                message: "The request is valid; a registered circuit's constraints are only checked when proving".to_string(),
+            };
+        }
+        let eligibility = config.eligibility(
+            request.tenant.as_deref(),
+            request.policy.as_deref(),
+            request.tier.as_deref(),
+            request.age,
+            request.bmi_multiplied,
+        );
+        let violations = eligibility.bounds.violations(request.age, request.bmi_multiplied);
+        let message = if violations.is_empty() {
+            "The inputs are within the eligibility ranges".to_string()
+        } else {
+            format!("The inputs are outside the eligibility ranges: {}", violations.join(", "))
+        };
+        Validation {
+            valid: true,
+            eligible: Some(violations.is_empty()),
+            tier: eligibility.tier,
+            bounds: Some(eligibility.bounds),
+            violations,
+            message,
+        }
+    }
+}
+
+/// Outcome of a dry run, as returned by POST /validate.
+#[derive(Debug, Serialize)]
+pub struct Validation {
+    /// The request would be accepted for proving
+    pub valid: bool,
+    /// The inputs satisfy the bounds they would be proven against; unknown for registered
+    /// circuits and invalid requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eligible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<Bounds>,
+    /// Each range the inputs fall outside of
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<String>,
+    pub message: String,
+}
+
+/// One result of a streamed batch.
+#[derive(Debug, Serialize)]
+pub struct BatchItem {
+    pub index: usize,
+    #[serde(flatten)]
+    pub response: ProofResponse,
+}